@@ -0,0 +1,70 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use fog_pack::{document::*, schema::*, validator::*};
+use rand::Rng;
+use serde::Serialize;
+use std::slice;
+
+#[derive(Serialize)]
+struct Post {
+    title: String,
+    score: u32,
+    tags: Vec<String>,
+}
+
+fn build_schema() -> Schema {
+    let tag = StrValidator::new().max_len(32).build();
+    let doc = MapValidator::new()
+        .req_add("title", StrValidator::new().max_len(256).build())
+        .req_add("score", IntValidator::new().max(1_000_000u32).build())
+        .req_add("tags", ArrayValidator::new().items(tag).max_len(16).build())
+        .build();
+    let schema = SchemaBuilder::new(doc).build().unwrap();
+    Schema::from_doc(&schema).unwrap()
+}
+
+fn build_docs<R: Rng>(rng: &mut R, schema: &Schema, count: usize) -> Vec<Document> {
+    (0..count)
+        .map(|i| {
+            let post = Post {
+                title: format!("Post number {}", i),
+                score: rng.gen_range(0, 1_000_000),
+                tags: (0..rng.gen_range(0, 16))
+                    .map(|t| format!("tag{}", t))
+                    .collect(),
+            };
+            let doc = NewDocument::new(post, Some(schema.hash())).unwrap();
+            schema.validate_new_doc(doc).unwrap()
+        })
+        .collect()
+}
+
+fn validate_sequential(schema: &Schema, docs: &[Document]) -> usize {
+    docs.iter()
+        .filter(|doc| schema.validate_batch(slice::from_ref(doc))[0].is_ok())
+        .count()
+}
+
+fn validate_batch(schema: &Schema, docs: &[Document]) -> usize {
+    schema
+        .validate_batch(docs)
+        .iter()
+        .filter(|res| res.is_ok())
+        .count()
+}
+
+fn criterion_benchmark(c: &mut Criterion) {
+    let mut rng = rand::thread_rng();
+    const DOCS: usize = 1000;
+    let schema = build_schema();
+    let docs = build_docs(&mut rng, &schema, DOCS);
+
+    c.bench_function("validate_sequential", |b| {
+        b.iter(|| validate_sequential(black_box(&schema), black_box(&docs)))
+    });
+    c.bench_function("validate_batch", |b| {
+        b.iter(|| validate_batch(black_box(&schema), black_box(&docs)))
+    });
+}
+
+criterion_group!(benches, criterion_benchmark);
+criterion_main!(benches);
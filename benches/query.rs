@@ -0,0 +1,87 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use fog_pack::{entry::*, query::*, schema::*, types::*, validator::*};
+use rand::Rng;
+use serde::Serialize;
+
+#[derive(Serialize)]
+struct Post {
+    tag: String,
+    score: u32,
+}
+
+fn build_schema() -> Schema {
+    let post = MapValidator::new()
+        .req_add("tag", StrValidator::new().query(true).build())
+        .req_add("score", IntValidator::new().query(true).ord(true).build())
+        .map_ok(true)
+        .build();
+    let schema = SchemaBuilder::new(Validator::Null)
+        .entry_add("post", post, None)
+        .build()
+        .unwrap();
+    Schema::from_doc(&schema).unwrap()
+}
+
+fn build_entries<R: Rng>(rng: &mut R, schema: &Schema, count: usize) -> Vec<Entry> {
+    let parent = Hash::new(b"Pretend I am a parent document");
+    (0..count)
+        .map(|_| {
+            let post = Post {
+                tag: format!("tag{}", rng.gen_range(0, 256)),
+                score: rng.gen_range(0, 1000),
+            };
+            let entry = NewEntry::new(post, "post", &parent).unwrap();
+            let (_, encoded) = schema.encode_new_entry(entry).unwrap().complete().unwrap();
+            schema
+                .decode_entry(encoded, "post", &parent)
+                .unwrap()
+                .complete()
+                .unwrap()
+        })
+        .collect()
+}
+
+/// A subscription assembled from several overlapping filters, each repeating the same tag list.
+fn build_query(schema: &Schema) -> Query {
+    let tags = |s: StrValidator| (0..64).fold(s, |s, i| s.in_add(format!("tag{}", i * 4)));
+    let query = QueryBuilder::new("post")
+        .schema(schema)
+        .where_str("tag", tags)
+        .where_str("tag", tags)
+        .where_str("tag", tags)
+        .where_int("score", |i| i.min(500))
+        .build()
+        .unwrap();
+    schema
+        .decode_query(schema.encode_query(query).unwrap())
+        .unwrap()
+}
+
+fn criterion_benchmark(c: &mut Criterion) {
+    let mut rng = rand::thread_rng();
+    const ENTRIES: usize = 1000;
+    let schema = build_schema();
+    let entries = build_entries(&mut rng, &schema, ENTRIES);
+    let query = build_query(&schema);
+    let compiled = query.compile();
+
+    c.bench_function("query_matches", |b| {
+        b.iter(|| {
+            black_box(&entries)
+                .iter()
+                .filter(|e| query.matches(e).unwrap().is_match())
+                .count()
+        })
+    });
+    c.bench_function("compiled_query_matches", |b| {
+        b.iter(|| {
+            black_box(&entries)
+                .iter()
+                .filter(|e| compiled.matches(e).unwrap().is_match())
+                .count()
+        })
+    });
+}
+
+criterion_group!(benches, criterion_benchmark);
+criterion_main!(benches);
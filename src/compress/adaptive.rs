@@ -0,0 +1,238 @@
+use super::CompressType;
+use crate::document::{Document, SplitDoc};
+use std::{collections::VecDeque, fmt, time::Instant};
+
+const DEFAULT_LEVEL: u8 = 3;
+
+/// Statistics from compressing a single document with an [`AdaptiveCompressor`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CompressionStats {
+    /// The zstd compression level that was used.
+    pub level: u8,
+    /// Length of the encoded document before compression.
+    pub raw_len: usize,
+    /// Length of the encoded document after compression.
+    pub encoded_len: usize,
+    /// Time spent compressing, in microseconds.
+    pub micros: u64,
+    /// True if the document was compressed. Documents that don't get smaller when compressed are
+    /// encoded without compression.
+    pub compressed: bool,
+}
+
+impl CompressionStats {
+    /// The compression ratio achieved, as the uncompressed length divided by the compressed
+    /// length.
+    pub fn ratio(&self) -> f32 {
+        self.raw_len as f32 / self.encoded_len.max(1) as f32
+    }
+
+    /// Time spent compressing, scaled to microseconds per MiB of input.
+    pub fn micros_per_mb(&self) -> u64 {
+        self.micros.saturating_mul(1 << 20) / self.raw_len.max(1) as u64
+    }
+}
+
+/// A document compressor that tunes its zstd compression level as it goes.
+///
+/// The compressor keeps the [`CompressionStats`] of the last `window` documents. Once the window
+/// is full, it compares the average compression ratio and CPU time against its targets:
+///
+/// - If compression is taking more than `max_micros_per_mb`, the level is lowered.
+/// - If compression is within the CPU budget but the ratio is below `target_ratio`, the level is
+///   raised.
+/// - Otherwise, the level is left alone.
+///
+/// The history is cleared whenever the level changes, so each level is judged only on documents
+/// it actually compressed. The level always stays between 1 and zstd's maximum level, and starts
+/// at the usual default of 3.
+///
+/// The compressor always uses general zstd compression at its current level, ignoring any
+/// compression setting on the documents themselves. Its output can be decoded like any other
+/// encoded document.
+pub struct AdaptiveCompressor {
+    ctx: zstd_safe::CCtx<'static>,
+    level: u8,
+    target_ratio: f32,
+    max_micros_per_mb: u64,
+    window: usize,
+    history: VecDeque<CompressionStats>,
+}
+
+impl AdaptiveCompressor {
+    /// Create a new compressor aiming for `target_ratio`, without spending more than
+    /// `max_micros_per_mb` microseconds per MiB of input, averaged over the last `window`
+    /// documents. A `window` of 0 is treated as 1.
+    pub fn new(target_ratio: f32, max_micros_per_mb: u64, window: usize) -> Self {
+        let window = window.max(1);
+        Self {
+            ctx: zstd_safe::CCtx::create(),
+            level: DEFAULT_LEVEL,
+            target_ratio,
+            max_micros_per_mb,
+            window,
+            history: VecDeque::with_capacity(window),
+        }
+    }
+
+    /// Get the compression level that will be used for the next document.
+    pub fn level(&self) -> u8 {
+        self.level
+    }
+
+    /// Encode a document, replacing the contents of `buf` with the fully encoded, compressed
+    /// document. Afterwards, the compression level may be adjusted for future documents.
+    pub fn compress(&mut self, doc: &Document, buf: &mut Vec<u8>) -> CompressionStats {
+        let raw = doc.raw();
+        let split = SplitDoc::split(raw).unwrap();
+        let header_len = raw.len() - split.data.len() - split.signature_raw.len();
+
+        // Compress the data portion, just like a Schema would
+        buf.clear();
+        buf.extend_from_slice(&raw[..header_len]);
+        let max_len = zstd_safe::compress_bound(split.data.len());
+        buf.resize(header_len + max_len, 0);
+        let start = Instant::now();
+        let result = self
+            .ctx
+            .compress(&mut buf[header_len..], split.data, self.level as i32);
+        let micros = start.elapsed().as_micros() as u64;
+
+        let compressed = match result {
+            Ok(len) if len < split.data.len() => {
+                let data_len = len.to_le_bytes();
+                buf.truncate(header_len + len);
+                buf[0] = CompressType::Compress.into();
+                buf[header_len - 3] = data_len[0];
+                buf[header_len - 2] = data_len[1];
+                buf[header_len - 1] = data_len[2];
+                buf.extend_from_slice(split.signature_raw);
+                true
+            }
+            _ => {
+                buf.clear();
+                buf.extend_from_slice(raw);
+                false
+            }
+        };
+
+        let stats = CompressionStats {
+            level: self.level,
+            raw_len: raw.len(),
+            encoded_len: buf.len(),
+            micros,
+            compressed,
+        };
+        self.record(stats);
+        stats
+    }
+
+    /// Add a result to the history, and adjust the level once the window is full.
+    fn record(&mut self, stats: CompressionStats) {
+        if self.history.len() == self.window {
+            self.history.pop_front();
+        }
+        self.history.push_back(stats);
+        if self.history.len() < self.window {
+            return;
+        }
+
+        let count = self.history.len() as f32;
+        let ratio = self.history.iter().map(|s| s.ratio()).sum::<f32>() / count;
+        let micros_per_mb = self
+            .history
+            .iter()
+            .map(|s| s.micros_per_mb() as f32)
+            .sum::<f32>()
+            / count;
+
+        let max_level = zstd_safe::max_c_level().clamp(1, u8::MAX as i32) as u8;
+        let new_level = if micros_per_mb > self.max_micros_per_mb as f32 {
+            self.level.saturating_sub(1).max(1)
+        } else if ratio < self.target_ratio {
+            (self.level + 1).min(max_level)
+        } else {
+            self.level
+        };
+        if new_level != self.level {
+            self.level = new_level;
+            self.history.clear();
+        }
+    }
+}
+
+impl fmt::Debug for AdaptiveCompressor {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("AdaptiveCompressor")
+            .field("level", &self.level)
+            .field("target_ratio", &self.target_ratio)
+            .field("max_micros_per_mb", &self.max_micros_per_mb)
+            .field("window", &self.window)
+            .field("history", &self.history)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::document::NewDocument;
+    use crate::schema::NoSchema;
+
+    fn make_doc(i: usize, len: usize) -> Document {
+        let text: Vec<String> = (0..len).map(|j| format!("entry {} of {}", j, i)).collect();
+        NoSchema::validate_new_doc(NewDocument::new(text, None).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut compressor = AdaptiveCompressor::new(2.0, u64::MAX, 4);
+        let mut buf = Vec::new();
+        for i in 0..10 {
+            let doc = make_doc(i, 200);
+            let stats = compressor.compress(&doc, &mut buf);
+            assert!(stats.compressed);
+            assert_eq!(stats.encoded_len, buf.len());
+            assert!(stats.ratio() > 1.0);
+            let decoded = NoSchema::decode_doc(buf.clone()).unwrap();
+            assert_eq!(decoded.hash(), doc.hash());
+            assert_eq!(decoded.data(), doc.data());
+        }
+    }
+
+    #[test]
+    fn incompressible() {
+        let mut compressor = AdaptiveCompressor::new(2.0, u64::MAX, 4);
+        let mut buf = vec![1, 2, 3];
+        let doc = NoSchema::validate_new_doc(NewDocument::new(1u8, None).unwrap()).unwrap();
+        let stats = compressor.compress(&doc, &mut buf);
+        assert!(!stats.compressed);
+        assert_eq!(buf, doc.raw());
+    }
+
+    #[test]
+    fn adjusts_level() {
+        let mut buf = Vec::new();
+
+        // An unreachable ratio with no CPU limit walks the level up
+        let mut compressor = AdaptiveCompressor::new(1000.0, u64::MAX, 2);
+        for i in 0..8 {
+            compressor.compress(&make_doc(i, 5000), &mut buf);
+        }
+        assert_eq!(compressor.level(), DEFAULT_LEVEL + 4);
+
+        // No CPU budget at all walks the level down, but never below 1
+        let mut compressor = AdaptiveCompressor::new(1000.0, 0, 1);
+        for i in 0..8 {
+            compressor.compress(&make_doc(i, 5000), &mut buf);
+        }
+        assert_eq!(compressor.level(), 1);
+
+        // A ratio that's already met leaves the level alone
+        let mut compressor = AdaptiveCompressor::new(1.0, u64::MAX, 1);
+        for i in 0..8 {
+            compressor.compress(&make_doc(i, 5000), &mut buf);
+        }
+        assert_eq!(compressor.level(), DEFAULT_LEVEL);
+    }
+}
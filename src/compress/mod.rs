@@ -1,8 +1,12 @@
+use crate::document::Document;
 use crate::error::{Error, Result};
 use serde::{Deserialize, Serialize};
 use serde_bytes::ByteBuf;
 use std::{convert::TryFrom, fmt};
 
+mod adaptive;
+pub use self::adaptive::*;
+
 pub const ALGORITHM_ZSTD: u8 = 0;
 
 /// Defines the compression types supported by documents & entries. Format when encoded is a single
@@ -261,6 +265,48 @@ impl Dictionary {
     }
 }
 
+/// Trains a ZStandard compression dictionary from sample documents.
+///
+/// Dictionaries work best when trained on a representative set of documents that adhere to the
+/// same schema, usually at least a few hundred of them. The resulting dictionary can be used with
+/// [`Compress::new_zstd_dict`].
+#[derive(Clone, Debug, Default)]
+pub struct DictBuilder {
+    samples: Vec<u8>,
+    sizes: Vec<usize>,
+}
+
+impl DictBuilder {
+    /// Start building a new dictionary with no samples.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a document's data as a training sample.
+    pub fn add_sample(mut self, doc: &Document) -> Self {
+        let data = doc.data();
+        self.samples.extend_from_slice(data);
+        self.sizes.push(data.len());
+        self
+    }
+
+    /// Train the dictionary, returning it as a byte vector no larger than `target_dict_size`.
+    /// Fails if zstd couldn't train a dictionary, which usually means there weren't enough
+    /// samples.
+    pub fn build(self, target_dict_size: usize) -> Result<Vec<u8>> {
+        let mut dict = vec![0u8; target_dict_size];
+        let len =
+            zstd_safe::train_from_buffer(&mut dict, &self.samples, &self.sizes).map_err(|e| {
+                Error::BadEncode(format!(
+                    "Failed to train dictionary, zstd error = {}",
+                    zstd_safe::get_error_name(e)
+                ))
+            })?;
+        dict.truncate(len);
+        Ok(dict)
+    }
+}
+
 #[derive(Serialize, Deserialize)]
 #[serde(from = "DictionarySerde", into = "DictionarySerde")]
 enum DictionaryPrivate {
@@ -7,6 +7,7 @@ use crate::{
 #[derive(Clone, Debug)]
 pub struct DepthTracker {
     tracking: Vec<u32>,
+    limit: usize,
 }
 
 impl DepthTracker {
@@ -14,9 +15,15 @@ impl DepthTracker {
     pub fn new() -> Self {
         Self {
             tracking: Vec::new(),
+            limit: MAX_DEPTH,
         }
     }
 
+    /// Lower the nesting limit below [`MAX_DEPTH`]. Limits above it are ignored.
+    pub fn set_limit(&mut self, limit: usize) {
+        self.limit = limit.min(MAX_DEPTH);
+    }
+
     /// Update the depth tracker on each new element to serialize.
     #[inline]
     pub fn update_elem(&mut self, elem: &Element) -> Result<()> {
@@ -33,7 +40,7 @@ impl DepthTracker {
         }
 
         // Check to see if we hit the nesting limit
-        if self.tracking.len() > MAX_DEPTH {
+        if self.tracking.len() > self.limit {
             return Err(Error::ParseLimit("Depth limit exceeded".to_string()));
         }
 
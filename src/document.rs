@@ -124,6 +124,11 @@ impl DocumentInner {
         self.signer.as_ref()
     }
 
+    /// Get the encoded size of the document, uncompressed and including any signature.
+    fn size(&self) -> usize {
+        self.buf.len()
+    }
+
     /// Get the hash of the schema this document adheres to.
     fn schema_hash(&self) -> Option<&Hash> {
         self.schema_hash.as_ref()
@@ -188,6 +193,10 @@ impl DocumentInner {
         self.split().data
     }
 
+    fn raw(&self) -> &[u8] {
+        &self.buf
+    }
+
     fn complete(self) -> (Hash, Vec<u8>, Option<Option<u8>>) {
         (self.hash_state.finalize(), self.buf, self.set_compress)
     }
@@ -617,6 +626,11 @@ impl NewDocument {
         Ok(Self(self.0.sign(key)?))
     }
 
+    /// Get the Identity of the signer of this document, if the document is signed.
+    pub fn signer(&self) -> Option<&Identity> {
+        self.0.signer()
+    }
+
     /// Get what the document's hash will be, given its current state
     pub fn hash(&self) -> Hash {
         self.0.hash()
@@ -625,6 +639,10 @@ impl NewDocument {
     pub(crate) fn data(&self) -> &[u8] {
         self.0.data()
     }
+
+    pub(crate) fn size(&self) -> usize {
+        self.0.size()
+    }
 }
 
 /// Holds serialized data optionally adhering to a schema.
@@ -689,6 +707,15 @@ impl Document {
         self.0.data()
     }
 
+    pub(crate) fn size(&self) -> usize {
+        self.0.size()
+    }
+
+    /// Get the complete, uncompressed encoding of the document.
+    pub(crate) fn raw(&self) -> &[u8] {
+        self.0.raw()
+    }
+
     /// Get the hash of the schema this document adheres to.
     pub fn schema_hash(&self) -> Option<&Hash> {
         self.0.schema_hash()
@@ -40,26 +40,31 @@ pub enum Element<'a> {
 
 impl<'a> Element<'a> {
     pub fn name(&self) -> &'static str {
+        self.element_type().name()
+    }
+
+    /// Get the type of this element, without its contents.
+    pub fn element_type(&self) -> ElementType {
         use self::Element::*;
         match self {
-            Null => "Null",
-            Bool(_) => "Bool",
-            Int(_) => "Int",
-            Str(_) => "Str",
-            F32(_) => "F32",
-            F64(_) => "F64",
-            Bin(_) => "Bin",
-            Array(_) => "Array",
-            Map(_) => "Map",
-            Timestamp(_) => "Time",
-            Hash(_) => "Hash",
-            Identity(_) => "Identity",
-            LockId(_) => "LockId",
-            StreamId(_) => "StreamId",
-            DataLockbox(_) => "DataLockbox",
-            IdentityLockbox(_) => "IdentityLockbox",
-            StreamLockbox(_) => "StreamLockbox",
-            LockLockbox(_) => "LockLockbox",
+            Null => ElementType::Null,
+            Bool(_) => ElementType::Bool,
+            Int(_) => ElementType::Int,
+            Str(_) => ElementType::Str,
+            F32(_) => ElementType::F32,
+            F64(_) => ElementType::F64,
+            Bin(_) => ElementType::Bin,
+            Array(_) => ElementType::Array,
+            Map(_) => ElementType::Map,
+            Timestamp(_) => ElementType::Timestamp,
+            Hash(_) => ElementType::Hash,
+            Identity(_) => ElementType::Identity,
+            LockId(_) => ElementType::LockId,
+            StreamId(_) => ElementType::StreamId,
+            DataLockbox(_) => ElementType::DataLockbox,
+            IdentityLockbox(_) => ElementType::IdentityLockbox,
+            StreamLockbox(_) => ElementType::StreamLockbox,
+            LockLockbox(_) => ElementType::LockLockbox,
         }
     }
 
@@ -91,6 +96,69 @@ impl<'a> Element<'a> {
     }
 }
 
+/// The type of an [`Element`], without any of its contents.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ElementType {
+    Null,
+    Bool,
+    Int,
+    Str,
+    F32,
+    F64,
+    Bin,
+    Array,
+    Map,
+    Timestamp,
+    Hash,
+    Identity,
+    LockId,
+    StreamId,
+    DataLockbox,
+    IdentityLockbox,
+    StreamLockbox,
+    LockLockbox,
+}
+
+impl ElementType {
+    pub fn name(&self) -> &'static str {
+        use self::ElementType::*;
+        match self {
+            Null => "Null",
+            Bool => "Bool",
+            Int => "Int",
+            Str => "Str",
+            F32 => "F32",
+            F64 => "F64",
+            Bin => "Bin",
+            Array => "Array",
+            Map => "Map",
+            Timestamp => "Time",
+            Hash => "Hash",
+            Identity => "Identity",
+            LockId => "LockId",
+            StreamId => "StreamId",
+            DataLockbox => "DataLockbox",
+            IdentityLockbox => "IdentityLockbox",
+            StreamLockbox => "StreamLockbox",
+            LockLockbox => "LockLockbox",
+        }
+    }
+
+    fn from_ext(ext_type: ExtType) -> Self {
+        match ext_type {
+            ExtType::Timestamp => ElementType::Timestamp,
+            ExtType::Hash => ElementType::Hash,
+            ExtType::Identity => ElementType::Identity,
+            ExtType::LockId => ElementType::LockId,
+            ExtType::StreamId => ElementType::StreamId,
+            ExtType::DataLockbox => ElementType::DataLockbox,
+            ExtType::IdentityLockbox => ElementType::IdentityLockbox,
+            ExtType::StreamLockbox => ElementType::StreamLockbox,
+            ExtType::LockLockbox => ElementType::LockLockbox,
+        }
+    }
+}
+
 /// Serialize an element onto a byte vector. Doesn't check if Array & Map structures make
 /// sense, just writes elements out.
 pub fn serialize_elem(buf: &mut Vec<u8>, elem: Element) {
@@ -424,6 +492,8 @@ pub struct Parser<'a> {
     data: &'a [u8],
     depth_tracking: DepthTracker,
     errored: bool,
+    now: Option<Timestamp>,
+    signer: Option<&'a Identity>,
 }
 
 impl<'a> Parser<'a> {
@@ -434,6 +504,8 @@ impl<'a> Parser<'a> {
             data,
             depth_tracking: DepthTracker::new(),
             errored: false,
+            now: None,
+            signer: None,
         }
     }
 
@@ -446,7 +518,41 @@ impl<'a> Parser<'a> {
             data,
             depth_tracking: DepthTracker::new(),
             errored: false,
+            now: None,
+            signer: None,
+        }
+    }
+
+    /// Set the reference time that validators use for bounds relative to the current time. If
+    /// not set, validators use the system time instead.
+    pub fn with_now(mut self, now: Option<Timestamp>) -> Parser<'a> {
+        self.now = now;
+        self
+    }
+
+    /// Get the reference time for validation, falling back to the system time if none was set.
+    pub fn now(&self) -> Option<Timestamp> {
+        self.now.or_else(Timestamp::now)
+    }
+
+    /// Lower the maximum nesting depth allowed while parsing. Does nothing if `None`, and can't
+    /// raise the depth above [`MAX_DEPTH`][crate::MAX_DEPTH].
+    pub fn with_max_depth(mut self, max_depth: Option<usize>) -> Parser<'a> {
+        if let Some(max_depth) = max_depth {
+            self.depth_tracking.set_limit(max_depth);
         }
+        self
+    }
+
+    /// Set the Identity that signed the Document or Entry being parsed, if it was signed.
+    pub fn with_signer(mut self, signer: Option<&'a Identity>) -> Parser<'a> {
+        self.signer = signer;
+        self
+    }
+
+    /// Get the Identity that signed the Document or Entry being parsed, if one was set.
+    pub fn signer(&self) -> Option<&'a Identity> {
+        self.signer
     }
 
     /// Look at what the next marker byte to be parsed will be.
@@ -454,6 +560,38 @@ impl<'a> Parser<'a> {
         self.data.first().map(|n| Marker::from_u8(*n))
     }
 
+    /// Look at the type of the next element to be parsed, without parsing it. This is much
+    /// cheaper than parsing a cloned parser, as no element contents are decoded. Returns `None`
+    /// if there's nothing left, or if the next element is obviously malformed; parsing will then
+    /// report the actual error. The element isn't checked at all past its type, so a peeked type
+    /// doesn't mean the element will parse successfully.
+    pub fn peek_type(&self) -> Option<ElementType> {
+        use self::Marker::*;
+        let ext_len_bytes = match self.peek_marker()? {
+            Null => return Some(ElementType::Null),
+            False | True => return Some(ElementType::Bool),
+            PosFixInt(_) | NegFixInt(_) | UInt8 | UInt16 | UInt32 | UInt64 | Int8 | Int16
+            | Int32 | Int64 => return Some(ElementType::Int),
+            FixStr(_) | Str8 | Str16 | Str24 => return Some(ElementType::Str),
+            F32 => return Some(ElementType::F32),
+            F64 => return Some(ElementType::F64),
+            Bin8 | Bin16 | Bin24 => return Some(ElementType::Bin),
+            FixArray(_) | Array8 | Array16 | Array24 => return Some(ElementType::Array),
+            FixMap(_) | Map8 | Map16 | Map24 => return Some(ElementType::Map),
+            Reserved => return None,
+            Ext8 => 1,
+            Ext16 => 2,
+            Ext24 => 3,
+        };
+        let ext_type = *self.data.get(1 + ext_len_bytes)?;
+        ExtType::from_u8(ext_type).map(ElementType::from_ext)
+    }
+
+    /// Get the bytes that have yet to be parsed.
+    pub fn remaining(&self) -> &'a [u8] {
+        self.data
+    }
+
     /// Call when parsing is expected to be complete. Fails if there are any bytes left inside the
     /// parser.
     pub fn finish(self) -> Result<()> {
@@ -1042,6 +1180,36 @@ mod test {
         }
     }
 
+    #[test]
+    fn peek_type() {
+        use crate::value::Value;
+        let values = vec![
+            Value::Null,
+            Value::from(true),
+            Value::from(-300i32),
+            Value::from("fog"),
+            Value::from(1.5f32),
+            Value::from(1.5f64),
+            Value::from(vec![0u8; 300]),
+            Value::from(vec![Value::Null]),
+            Value::from(std::collections::BTreeMap::<String, Value>::new()),
+            Value::from(Timestamp::from_utc(1, 0).unwrap()),
+        ];
+        for value in values {
+            let mut ser = crate::ser::FogSerializer::default();
+            serde::Serialize::serialize(&value, &mut ser).unwrap();
+            let enc = ser.finish();
+            let mut parser = Parser::new(&enc);
+            let peeked = parser.peek_type().unwrap();
+            assert_eq!(parser.remaining(), enc.as_slice());
+            let elem = parser.next().unwrap().unwrap();
+            assert_eq!(peeked, elem.element_type());
+        }
+        assert!(Parser::new(&[]).peek_type().is_none());
+        assert!(Parser::new(&[0xc1]).peek_type().is_none());
+        assert!(Parser::new(&[0xc7, 0x01, 0xff]).peek_type().is_none());
+    }
+
     mod null {
         use super::*;
 
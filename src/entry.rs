@@ -11,6 +11,7 @@ use crate::{
     de::FogDeserializer,
     element::{serialize_elem, Element},
     ser::FogSerializer,
+    value::Value,
     MAX_ENTRY_SIZE,
 };
 use byteorder::{LittleEndian, ReadBytesExt};
@@ -72,7 +73,7 @@ pub struct NewEntry {
     key: String,
     parent_hash: Hash,
     entry_hash: Hash,
-    has_signature: bool,
+    signer: Option<Identity>,
     set_compress: Option<Option<u8>>,
 }
 
@@ -111,7 +112,7 @@ impl NewEntry {
             key: key.to_owned(),
             parent_hash: parent.to_owned(),
             entry_hash,
-            has_signature: false,
+            signer: None,
             set_compress: None,
         })
     }
@@ -152,7 +153,7 @@ impl NewEntry {
     pub fn sign(mut self, key: &IdentityKey) -> Result<Self> {
         // Sign and check for size violation
         let signature = key.sign(&self.entry_hash);
-        let new_len = if self.has_signature {
+        let new_len = if self.signer.is_some() {
             self.buf.len() - self.split().signature_raw.len() + signature.size()
         } else {
             self.buf.len() + signature.size()
@@ -164,7 +165,7 @@ impl NewEntry {
             });
         }
 
-        if self.has_signature {
+        if self.signer.is_some() {
             let split = SplitEntry::split(&self.buf).unwrap();
             let new_len = split.data.len() + ENTRY_PREFIX_LEN;
             let mut hash_state = HashState::new();
@@ -181,10 +182,15 @@ impl NewEntry {
         let pre_len = self.buf.len();
         signature.encode_vec(&mut self.buf);
         self.hash_state.update(&self.buf[pre_len..]);
-        self.has_signature = true;
+        self.signer = Some(key.id().clone());
         Ok(self)
     }
 
+    /// Get the Identity of the signer of this entry, if the entry is signed.
+    pub fn signer(&self) -> Option<&Identity> {
+        self.signer.as_ref()
+    }
+
     /// Get what the document's hash will be, given its current state
     pub fn hash(&self) -> Hash {
         self.hash_state.hash()
@@ -213,6 +219,69 @@ impl NewEntry {
     }
 }
 
+/// A batch of new Entries that all share the same parent Document.
+///
+/// Each added Entry is created exactly as [`NewEntry::new`] would, and the whole batch can be
+/// signed with one call to [`sign_all`][EntryBatch::sign_all]. If any step fails, the batch is
+/// dropped, so either every Entry is produced or none are. The resulting [`NewEntry`] values must
+/// still pass through a [`Schema`][crate::schema::Schema] one at a time.
+pub struct EntryBatch {
+    parent_hash: Hash,
+    entries: Vec<NewEntry>,
+}
+
+impl EntryBatch {
+    /// Start a new, empty batch of Entries for the given parent Document.
+    pub fn new(parent: &Hash) -> Self {
+        Self {
+            parent_hash: parent.to_owned(),
+            entries: Vec::new(),
+        }
+    }
+
+    /// Create a new Entry from serializable data and a key, and add it to the batch. Fails if the
+    /// Entry couldn't be created.
+    pub fn add<S: Serialize>(mut self, data: S, key: &str) -> Result<Self> {
+        self.entries
+            .push(NewEntry::new(data, key, &self.parent_hash)?);
+        Ok(self)
+    }
+
+    /// Sign every Entry in the batch, replacing any existing signatures. Fails if any signature
+    /// would grow an Entry beyond the maximum allowed size.
+    pub fn sign_all(self, key: &IdentityKey) -> Result<Self> {
+        let entries = self
+            .entries
+            .into_iter()
+            .map(|entry| entry.sign(key))
+            .collect::<Result<Vec<NewEntry>>>()?;
+        Ok(Self {
+            parent_hash: self.parent_hash,
+            entries,
+        })
+    }
+
+    /// Get the hash of the parent [`Document`][crate::document::Document] shared by the batch.
+    pub fn parent(&self) -> &Hash {
+        &self.parent_hash
+    }
+
+    /// Get the number of Entries in the batch.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Check if the batch has no Entries in it.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Turn the batch into its Entries, in the order they were added.
+    pub fn into_vec(self) -> Vec<NewEntry> {
+        self.entries
+    }
+}
+
 /// Holds serialized data associated with a parent document and a key string.
 ///
 /// An Entry holds a piece of serialized data, which may be deserialized by calling
@@ -285,6 +354,24 @@ impl Entry {
         &self.key
     }
 
+    /// Get the hash of the Entry's parent document and the Entry's key at the same time.
+    pub fn parent_and_key(&self) -> (&Hash, &str) {
+        (&self.parent_hash, &self.key)
+    }
+
+    /// Take the hash of the Entry's parent document and the Entry's key, discarding the rest of
+    /// the Entry.
+    pub fn into_parent_and_key(self) -> (Hash, String) {
+        (self.parent_hash, self.key)
+    }
+
+    /// Break the Entry up into its parent document's hash, its key, its data, and its signer, if
+    /// it was signed. Fails if the data can't be deserialized into a [`Value`].
+    pub fn into_parts(self) -> Result<(Hash, String, Value, Option<Identity>)> {
+        let value = self.deserialize()?;
+        Ok((self.parent_hash, self.key, value, self.signer))
+    }
+
     /// Get the Identity of the signer of this document, if the document is signed.
     pub fn signer(&self) -> Option<&Identity> {
         self.signer.as_ref()
@@ -353,3 +440,68 @@ impl Entry {
         (self.hash_state.finalize(), self.buf, self.set_compress)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn batch_sign_all() {
+        let key = IdentityKey::new_temp(&mut rand::rngs::OsRng);
+        let parent = Hash::new(b"Pretend I am a parent document");
+        let batch = EntryBatch::new(&parent)
+            .add(1u8, "num")
+            .unwrap()
+            .add("text", "str")
+            .unwrap()
+            .sign_all(&key)
+            .unwrap();
+        assert_eq!(batch.len(), 2);
+        let entries = batch.into_vec();
+        let expected = NewEntry::new(1u8, "num", &parent)
+            .unwrap()
+            .sign(&key)
+            .unwrap();
+        assert_eq!(entries[0].hash(), expected.hash());
+        assert_eq!(entries[1].key(), "str");
+        assert!(entries.iter().all(|e| e.parent() == &parent));
+    }
+
+    #[test]
+    fn parts() {
+        use crate::{schema::*, validator::*};
+        let schema = SchemaBuilder::new(Validator::Null)
+            .entry_add("num", IntValidator::new().build(), None)
+            .build()
+            .unwrap();
+        let schema = Schema::from_doc(&schema).unwrap();
+        let key = IdentityKey::new_temp(&mut rand::rngs::OsRng);
+        let parent = Hash::new(b"Pretend I am a parent document");
+        let new = NewEntry::new(5u8, "num", &parent)
+            .unwrap()
+            .sign(&key)
+            .unwrap();
+        let (_, encoded) = schema.encode_new_entry(new).unwrap().complete().unwrap();
+        let decode = || {
+            schema
+                .decode_entry(encoded.clone(), "num", &parent)
+                .unwrap()
+                .complete()
+                .unwrap()
+        };
+
+        let entry = decode();
+        assert_eq!(entry.parent_and_key(), (entry.parent(), entry.key()));
+        let expected_signer = entry.signer().cloned();
+        assert_eq!(
+            entry.into_parent_and_key(),
+            (parent.clone(), "num".to_string())
+        );
+        let (entry_parent, entry_key, value, signer) = decode().into_parts().unwrap();
+        assert_eq!(entry_parent, parent);
+        assert_eq!(entry_key, "num");
+        assert_eq!(value, Value::from(5u8));
+        assert_eq!(signer, expected_signer);
+        assert_eq!(signer.as_ref(), Some(key.id()));
+    }
+}
@@ -1,5 +1,6 @@
 //! Library error types.
 //!
+use crate::types::{PathSegment, ValuePath};
 use fog_crypto::{hash::Hash, CryptoError};
 use std::fmt;
 
@@ -39,11 +40,14 @@ pub enum Error {
     /// Basic fog-pack encoding failure
     BadEncode(String),
     /// Schema validation failure.
-    FailValidate(String),
+    FailValidate(Box<ValidateError>),
     /// Failure within the cryptographic submodule.
     CryptoError(CryptoError),
     /// Schema or validation hit some parsing limit.
     ParseLimit(String),
+    /// A [`ValuePath`][crate::types::ValuePath] couldn't be parsed, or couldn't be followed when
+    /// setting a value.
+    BadPath(String),
 }
 
 impl fmt::Display for Error {
@@ -94,6 +98,34 @@ impl fmt::Display for Error {
             Error::FailValidate(ref err) => write!(f, "Failed validation: {}", err),
             Error::CryptoError(_) => write!(f, "Cryptographic Error"),
             Error::ParseLimit(ref err) => write!(f, "Hit parsing limit: {}", err),
+            Error::BadPath(ref err) => write!(f, "Bad value path: {}", err),
+        }
+    }
+}
+
+impl Error {
+    /// Make a new [`FailValidate`][Error::FailValidate] error with the given code and message.
+    pub(crate) fn validate(code: ValidateCode, message: impl Into<String>) -> Self {
+        Error::FailValidate(Box::new(ValidateError::new(code, message)))
+    }
+
+    /// Make a new [`WrongType`][ValidateCode::WrongType] validation error, for when a value isn't
+    /// the expected type.
+    pub(crate) fn wrong_type(expected: &str, actual: &str) -> Self {
+        ValidateError::new(
+            ValidateCode::WrongType,
+            format!("Expected {}, got {}", expected, actual),
+        )
+        .expected(expected)
+        .actual(actual)
+        .into()
+    }
+
+    /// Get the validation failure details, if this is a validation failure.
+    pub fn validate_error(&self) -> Option<&ValidateError> {
+        match self {
+            Error::FailValidate(err) => Some(err),
+            _ => None,
         }
     }
 }
@@ -124,3 +156,135 @@ impl de::Error for Error {
         Error::SerdeFail(msg.to_string())
     }
 }
+
+/// The kind of failure behind a [`ValidateError`], for handling validation failures without
+/// parsing the error message.
+///
+/// More codes may be added in the future, so matching on them should always have a catch-all
+/// arm.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ValidateCode {
+    /// The value wasn't the expected type, or a value was missing.
+    WrongType,
+    /// The value was outside the allowed range, like an integer above `max`.
+    OutOfRange,
+    /// The value was too long, like a string longer than `max_len`.
+    TooLong,
+    /// The value was too short, like a string shorter than `min_len`.
+    TooShort,
+    /// The value was nested too deeply.
+    TooDeep,
+    /// A timestamp was more precise than allowed.
+    TooPrecise,
+    /// The value wasn't on the `in` list.
+    NotInList,
+    /// The value was on the `nin` list.
+    InNinList,
+    /// The value didn't have the required bits set or cleared.
+    BitMismatch,
+    /// The value didn't match a regular expression or a constant.
+    NoMatch,
+    /// An array didn't have the items required by `contains`.
+    MissingContains,
+    /// An array's items or a map's values weren't unique.
+    NotUnique,
+    /// A map was missing a required key.
+    MissingKey,
+    /// A map had a banned key, or a key that no validator accepts.
+    BadKey,
+    /// An enum variant wasn't recognized, or had the wrong form.
+    UnknownVariant,
+    /// The value wasn't encoded in canonical form, like a map with unordered keys.
+    BadEncoding,
+    /// No validator in a Multi passed, some validator in an All failed, or the validator in a Not
+    /// passed.
+    NoBranch,
+    /// An Identity wasn't the signer of the containing Document or Entry.
+    NotSigner,
+    /// There was no current time to check a relative time bound against.
+    NoTime,
+    /// A Document didn't meet a Hash validator's `link` or `schema` requirements, or wasn't
+    /// expected by the checklist.
+    BadLink,
+    /// Not all of a checklist's Documents were checked.
+    Incomplete,
+    /// An immutable field was changed.
+    Immutable,
+    /// An Entry's key isn't in the schema.
+    UnknownEntry,
+    /// An Entry was past its schema's time-to-live.
+    Expired,
+    /// A query used a validator the schema doesn't allow.
+    QueryNotAllowed,
+    /// A validator, schema, or query was configured incorrectly.
+    BadValidator,
+}
+
+/// Details of a validation failure.
+///
+/// The error's `Display` output is the location of the failure within the value, if it's nested
+/// inside an array or map, followed by the message, like `[42].tags[3]: Expected Str, got Int`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ValidateError {
+    /// What kind of failure this was.
+    pub code: ValidateCode,
+    /// Where in the value the failure occurred. Empty if the failure was at the top level, or
+    /// wasn't caused by a specific value.
+    pub path: ValuePath,
+    /// A summary of what was expected, if available.
+    pub expected: Option<String>,
+    /// A summary of what was actually found, if available.
+    pub actual: Option<String>,
+    /// A human-readable description of the failure.
+    pub message: String,
+}
+
+impl ValidateError {
+    /// Make a new error with the given code and message, and no path.
+    pub fn new(code: ValidateCode, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            path: ValuePath::default(),
+            expected: None,
+            actual: None,
+            message: message.into(),
+        }
+    }
+
+    /// Set the summary of what was expected.
+    pub fn expected(mut self, expected: impl Into<String>) -> Self {
+        self.expected = Some(expected.into());
+        self
+    }
+
+    /// Set the summary of what was actually found.
+    pub fn actual(mut self, actual: impl Into<String>) -> Self {
+        self.actual = Some(actual.into());
+        self
+    }
+
+    /// Add a step to the front of the path, for when the failure occurred inside an array or map.
+    pub(crate) fn nest(&mut self, segment: PathSegment) {
+        self.path.push_front(segment);
+    }
+}
+
+impl fmt::Display for ValidateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.path.segments().is_empty() {
+            f.write_str(&self.message)
+        } else {
+            for segment in self.path.segments() {
+                write!(f, "{}", segment)?;
+            }
+            write!(f, ": {}", self.message)
+        }
+    }
+}
+
+impl From<ValidateError> for Error {
+    fn from(e: ValidateError) -> Self {
+        Error::FailValidate(Box::new(e))
+    }
+}
@@ -78,6 +78,67 @@ impl Integer {
             IntPriv::NegInt(n) => n as u64,
         }
     }
+
+    #[inline]
+    fn as_i128(&self) -> i128 {
+        match self.n {
+            IntPriv::PosInt(n) => n as i128,
+            IntPriv::NegInt(n) => n as i128,
+        }
+    }
+
+    #[inline]
+    fn from_i128(n: i128) -> Option<Integer> {
+        if let Ok(n) = u64::try_from(n) {
+            Some(Integer::from(n))
+        } else {
+            i64::try_from(n).ok().map(Integer::from)
+        }
+    }
+
+    /// Checked integer addition. Computes `self + rhs`, returning `None` if the result can't be
+    /// represented.
+    pub fn checked_add(&self, rhs: &Integer) -> Option<Integer> {
+        Self::from_i128(self.as_i128() + rhs.as_i128())
+    }
+
+    /// Checked integer subtraction. Computes `self - rhs`, returning `None` if the result can't be
+    /// represented.
+    pub fn checked_sub(&self, rhs: &Integer) -> Option<Integer> {
+        Self::from_i128(self.as_i128() - rhs.as_i128())
+    }
+
+    /// Checked integer multiplication. Computes `self * rhs`, returning `None` if the result can't
+    /// be represented.
+    pub fn checked_mul(&self, rhs: &Integer) -> Option<Integer> {
+        self.as_i128()
+            .checked_mul(rhs.as_i128())
+            .and_then(Self::from_i128)
+    }
+
+    /// Saturating integer addition. Computes `self + rhs`, saturating at
+    /// [`min_value`][Self::min_value] and [`max_value`][Self::max_value].
+    pub fn saturating_add(&self, rhs: &Integer) -> Integer {
+        self.checked_add(rhs).unwrap_or_else(|| {
+            if rhs.is_u64() {
+                Integer::max_value()
+            } else {
+                Integer::min_value()
+            }
+        })
+    }
+
+    /// Saturating integer subtraction. Computes `self - rhs`, saturating at
+    /// [`min_value`][Self::min_value] and [`max_value`][Self::max_value].
+    pub fn saturating_sub(&self, rhs: &Integer) -> Integer {
+        self.checked_sub(rhs).unwrap_or_else(|| {
+            if rhs.is_u64() {
+                Integer::min_value()
+            } else {
+                Integer::max_value()
+            }
+        })
+    }
 }
 
 pub(crate) fn get_int_internal(val: &Integer) -> IntPriv {
@@ -128,6 +189,17 @@ impl ops::Add<i64> for Integer {
     }
 }
 
+impl ops::Add for Integer {
+    type Output = Integer;
+
+    /// Add two integers together. Panics if the result can't be represented; use
+    /// [`checked_add`][Integer::checked_add] to handle overflow instead.
+    fn add(self, other: Integer) -> Integer {
+        self.checked_add(&other)
+            .expect("attempt to add with overflow")
+    }
+}
+
 impl ops::Sub<i64> for Integer {
     type Output = Integer;
 
@@ -331,4 +403,64 @@ mod tests {
         let x = Integer::from((1u64 << 63) - 1);
         assert_eq!(x - y, Integer::from((1u64 << 63) - 2));
     }
+
+    #[test]
+    fn checked() {
+        let max = Integer::max_value();
+        let min = Integer::min_value();
+        let one = Integer::from(1u8);
+        let neg_one = Integer::from(-1i8);
+        assert_eq!(max.checked_add(&one), None);
+        assert_eq!(max.checked_add(&neg_one), Some(Integer::from(u64::MAX - 1)));
+        assert_eq!(min.checked_sub(&one), None);
+        assert_eq!(min.checked_add(&max), Some(Integer::from(i64::MAX as u64)));
+        assert_eq!(
+            Integer::from(0u8).checked_sub(&max),
+            None,
+            "-u64::MAX is below i64::MIN"
+        );
+        assert_eq!(
+            Integer::from(i64::MAX).checked_sub(&min),
+            Some(Integer::from(u64::MAX))
+        );
+        assert_eq!(
+            Integer::from(1u64 << 32).checked_mul(&Integer::from(1u64 << 31)),
+            Some(Integer::from(1u64 << 63))
+        );
+        assert_eq!(
+            Integer::from(1u64 << 32).checked_mul(&Integer::from(1u64 << 32)),
+            None
+        );
+        assert_eq!(
+            Integer::from(-(1i64 << 32)).checked_mul(&Integer::from(1u64 << 31)),
+            Some(min)
+        );
+        assert_eq!(max.checked_mul(&max), None);
+    }
+
+    #[test]
+    fn saturating() {
+        let max = Integer::max_value();
+        let min = Integer::min_value();
+        let one = Integer::from(1u8);
+        assert_eq!(max.saturating_add(&one), max);
+        assert_eq!(min.saturating_add(&Integer::from(-1i8)), min);
+        assert_eq!(min.saturating_sub(&one), min);
+        assert_eq!(max.saturating_sub(&Integer::from(-1i8)), max);
+        assert_eq!(one.saturating_sub(&one), Integer::from(0u8));
+    }
+
+    #[test]
+    fn add_integer() {
+        assert_eq!(
+            Integer::from(-5i8) + Integer::from(u64::MAX - 1),
+            Integer::from(u64::MAX - 6)
+        );
+    }
+
+    #[test]
+    #[should_panic]
+    fn add_integer_overflow() {
+        let _ = Integer::max_value() + Integer::from(1u8);
+    }
 }
@@ -0,0 +1,484 @@
+//! Conversion between JSON text and [`Value`].
+//!
+//! JSON's null, booleans, numbers, strings, arrays, and objects map directly onto fog-pack values.
+//! The remaining fog-pack types are written as tagged objects, like
+//! `{"$type": "timestamp", "$value": "2021-01-01T00:00:00Z"}`. The recognized tags are:
+//!
+//! - `timestamp`: an RFC 3339 time in UTC, ending with `Z`
+//! - `bin`: hex-encoded bytes
+//! - `hash`, `identity`, `lock_id`, `stream_id`: the base58 text form used by fog-crypto
+//! - `data_lockbox`, `identity_lockbox`, `stream_lockbox`, `lock_lockbox`: hex-encoded bytes
+
+use std::{collections::BTreeMap, fmt};
+
+use crate::value::Value;
+use crate::*;
+use fog_crypto::CryptoError;
+use serde_json::{Map as JsonMap, Number, Value as Json};
+
+/// Options for converting JSON into a [`Value`]. See [`Value::from_json_str_with_options`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct JsonImportOptions {
+    /// Whether tagged objects are converted into the fog-pack type they name. If false, every
+    /// JSON object becomes a map.
+    pub tagged: bool,
+    /// The key holding the type name of a tagged object.
+    pub type_key: String,
+    /// The key holding the value of a tagged object.
+    pub value_key: String,
+}
+
+impl Default for JsonImportOptions {
+    fn default() -> Self {
+        Self {
+            tagged: true,
+            type_key: "$type".to_string(),
+            value_key: "$value".to_string(),
+        }
+    }
+}
+
+impl JsonImportOptions {
+    /// Make a new set of options with the default configuration.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set whether tagged objects are converted into the fog-pack type they name.
+    pub fn tagged(mut self, tagged: bool) -> Self {
+        self.tagged = tagged;
+        self
+    }
+
+    /// Set the keys used for the type name and value of tagged objects.
+    pub fn tag_keys(mut self, type_key: &str, value_key: &str) -> Self {
+        self.type_key = type_key.to_owned();
+        self.value_key = value_key.to_owned();
+        self
+    }
+}
+
+/// An error from converting JSON text into a [`Value`].
+#[derive(Debug)]
+pub enum JsonImportError {
+    /// The text wasn't valid JSON.
+    Parse(serde_json::Error),
+    /// A tagged object named an unknown type, or its value couldn't be converted to that type.
+    BadTag {
+        /// The type name of the tagged object.
+        tag: String,
+        /// What was wrong with it.
+        message: String,
+    },
+}
+
+impl fmt::Display for JsonImportError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            JsonImportError::Parse(err) => write!(f, "Invalid JSON: {}", err),
+            JsonImportError::BadTag { tag, message } => {
+                write!(f, "Bad tagged value of type {:?}: {}", tag, message)
+            }
+        }
+    }
+}
+
+impl std::error::Error for JsonImportError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            JsonImportError::Parse(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl Value {
+    /// Parse JSON text into a Value, converting tagged objects into the fog-pack types they name.
+    /// See [`from_json_str_with_options`][Self::from_json_str_with_options] for details.
+    pub fn from_json_str(s: &str) -> Result<Value, JsonImportError> {
+        Self::from_json_str_with_options(s, &JsonImportOptions::default())
+    }
+
+    /// Parse JSON text into a Value.
+    ///
+    /// JSON numbers become integers if they are whole and fit in an [`Integer`], and 64-bit floats
+    /// otherwise. Objects with exactly two keys, the type and value keys from `options`, are
+    /// converted into the fog-pack type they name, unless tagging is turned off.
+    pub fn from_json_str_with_options(
+        s: &str,
+        options: &JsonImportOptions,
+    ) -> Result<Value, JsonImportError> {
+        let json: Json = serde_json::from_str(s).map_err(JsonImportError::Parse)?;
+        from_json(json, options)
+    }
+
+    /// Write the Value out as JSON text. Types JSON lacks are written as tagged objects using
+    /// the default `$type` and `$value` keys, so [`from_json_str`][Self::from_json_str] can read
+    /// them back.
+    ///
+    /// Infinite and NaN floats have no JSON form, and are written as null. A map that happens to
+    /// look like a tagged object will be read back as the tagged type.
+    pub fn to_json_str(&self) -> String {
+        to_json(self).to_string()
+    }
+}
+
+fn from_json(json: Json, options: &JsonImportOptions) -> Result<Value, JsonImportError> {
+    Ok(match json {
+        Json::Null => Value::Null,
+        Json::Bool(v) => Value::Bool(v),
+        Json::Number(v) => {
+            if let Some(v) = v.as_u64() {
+                Value::Int(v.into())
+            } else if let Some(v) = v.as_i64() {
+                Value::Int(v.into())
+            } else {
+                Value::F64(v.as_f64().unwrap_or(f64::NAN))
+            }
+        }
+        Json::String(v) => Value::Str(v),
+        Json::Array(v) => Value::Array(
+            v.into_iter()
+                .map(|v| from_json(v, options))
+                .collect::<Result<_, _>>()?,
+        ),
+        Json::Object(map) => {
+            if options.tagged && map.len() == 2 {
+                if let (Some(Json::String(tag)), Some(value)) =
+                    (map.get(&options.type_key), map.get(&options.value_key))
+                {
+                    return from_tagged(tag, value);
+                }
+            }
+            Value::Map(
+                map.into_iter()
+                    .map(|(k, v)| Ok((k, from_json(v, options)?)))
+                    .collect::<Result<BTreeMap<_, _>, _>>()?,
+            )
+        }
+    })
+}
+
+fn from_tagged(tag: &str, value: &Json) -> Result<Value, JsonImportError> {
+    let bad = |message: String| JsonImportError::BadTag {
+        tag: tag.to_owned(),
+        message,
+    };
+    let text = value
+        .as_str()
+        .ok_or_else(|| bad("value must be a string".to_string()))?;
+    let crypto = |err: CryptoError| bad(err.to_string());
+    Ok(match tag {
+        "timestamp" => Value::Timestamp(
+            parse_time(text)
+                .ok_or_else(|| bad(format!("{:?} isn't an RFC 3339 time in UTC", text)))?,
+        ),
+        "bin" => Value::Bin(from_hex(text).ok_or_else(|| bad("invalid hex".to_string()))?),
+        "hash" => Value::Hash(Hash::from_base58(text).map_err(crypto)?),
+        "identity" => Value::Identity(Identity::from_base58(text).map_err(crypto)?),
+        "lock_id" => Value::LockId(LockId::from_base58(text).map_err(crypto)?),
+        "stream_id" => Value::StreamId(StreamId::from_base58(text).map_err(crypto)?),
+        "data_lockbox" | "identity_lockbox" | "stream_lockbox" | "lock_lockbox" => {
+            let bytes = from_hex(text).ok_or_else(|| bad("invalid hex".to_string()))?;
+            match tag {
+                "data_lockbox" => Value::DataLockbox(
+                    DataLockboxRef::from_bytes(&bytes)
+                        .map_err(crypto)?
+                        .to_owned(),
+                ),
+                "identity_lockbox" => Value::IdentityLockbox(
+                    IdentityLockboxRef::from_bytes(&bytes)
+                        .map_err(crypto)?
+                        .to_owned(),
+                ),
+                "stream_lockbox" => Value::StreamLockbox(
+                    StreamLockboxRef::from_bytes(&bytes)
+                        .map_err(crypto)?
+                        .to_owned(),
+                ),
+                _ => Value::LockLockbox(
+                    LockLockboxRef::from_bytes(&bytes)
+                        .map_err(crypto)?
+                        .to_owned(),
+                ),
+            }
+        }
+        _ => return Err(bad("unknown type".to_string())),
+    })
+}
+
+fn to_json(value: &Value) -> Json {
+    let tagged = |tag: &str, value: String| {
+        let mut map = JsonMap::new();
+        map.insert("$type".to_string(), Json::String(tag.to_string()));
+        map.insert("$value".to_string(), Json::String(value));
+        Json::Object(map)
+    };
+    let float = |v: f64| Number::from_f64(v).map_or(Json::Null, Json::Number);
+    match value {
+        Value::Null => Json::Null,
+        Value::Bool(v) => Json::Bool(*v),
+        Value::Int(v) => match v.as_u64() {
+            Some(v) => Json::Number(v.into()),
+            None => Json::Number(v.as_i64().unwrap().into()),
+        },
+        Value::Str(v) => Json::String(v.clone()),
+        // Go through the shortest text form, so 0.1f32 doesn't become 0.10000000149011612
+        Value::F32(v) => float(v.to_string().parse().unwrap_or(f64::NAN)),
+        Value::F64(v) => float(*v),
+        Value::Bin(v) => tagged("bin", to_hex(v)),
+        Value::Array(v) => Json::Array(v.iter().map(to_json).collect()),
+        Value::Map(v) => Json::Object(v.iter().map(|(k, v)| (k.clone(), to_json(v))).collect()),
+        Value::Timestamp(v) => tagged("timestamp", format_time(v)),
+        Value::Hash(v) => tagged("hash", v.to_base58()),
+        Value::Identity(v) => tagged("identity", v.to_base58()),
+        Value::LockId(v) => tagged("lock_id", v.to_base58()),
+        Value::StreamId(v) => tagged("stream_id", v.to_base58()),
+        Value::DataLockbox(v) => tagged("data_lockbox", to_hex(v.as_bytes())),
+        Value::IdentityLockbox(v) => tagged("identity_lockbox", to_hex(v.as_bytes())),
+        Value::StreamLockbox(v) => tagged("stream_lockbox", to_hex(v.as_bytes())),
+        Value::LockLockbox(v) => tagged("lock_lockbox", to_hex(v.as_bytes())),
+    }
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn from_hex(text: &str) -> Option<Vec<u8>> {
+    if text.len() & 1 != 0 || !text.is_ascii() {
+        return None;
+    }
+    (0..text.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&text[i..i + 2], 16).ok())
+        .collect()
+}
+
+/// Convert days since the Unix epoch into a (year, month, day) civil date.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+/// Convert a civil date into days since the Unix epoch.
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year.rem_euclid(400);
+    let mp = ((month + 9) % 12) as i64;
+    let doy = (153 * mp + 2) / 5 + day as i64 - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468
+}
+
+/// Write a timestamp as RFC 3339 text. Years outside 0000-9999 get a sign and extra digits, as
+/// in ISO 8601's expanded year format.
+fn format_time(time: &Timestamp) -> String {
+    let sec = time.timestamp_utc();
+    let (year, month, day) = civil_from_days(sec.div_euclid(86400));
+    let secs = sec.rem_euclid(86400);
+    let mut out = if (0..=9999).contains(&year) {
+        format!("{:04}", year)
+    } else {
+        format!("{:+05}", year)
+    };
+    out.push_str(&format!(
+        "-{:02}-{:02}T{:02}:{:02}:{:02}",
+        month,
+        day,
+        secs / 3600,
+        secs / 60 % 60,
+        secs % 60
+    ));
+    let nano = time.timestamp_subsec_nanos();
+    if nano != 0 {
+        out.push_str(format!(".{:09}", nano).trim_end_matches('0'));
+    }
+    out.push('Z');
+    out
+}
+
+/// The largest year, positive or negative, that a timestamp's seconds can reach. Years beyond
+/// this would overflow the calendar math.
+const MAX_YEAR: i64 = 292_277_026_596;
+
+/// Parse RFC 3339 text in UTC, as written by [`format_time`].
+fn parse_time(text: &str) -> Option<Timestamp> {
+    let text = text.strip_suffix('Z').or_else(|| text.strip_suffix('z'))?;
+    let (date, time) = text.split_at(text.find(['T', 't'])?);
+    let time = &time[1..];
+
+    // The year may have a sign, so split the month and day off the end
+    let mut parts = date.rsplitn(3, '-');
+    let day: u32 = parse_digits(parts.next()?, 2)?;
+    let month: u32 = parse_digits(parts.next()?, 2)?;
+    let year = parts.next()?;
+    let year: i64 = match year.strip_prefix('+') {
+        Some(year) => parse_digits(year, 4)?,
+        None => match year.strip_prefix('-') {
+            Some(year) => -parse_digits::<i64>(year, 4)?,
+            None => parse_digits(year, 4)?,
+        },
+    };
+    if year.abs() > MAX_YEAR {
+        return None;
+    }
+
+    let (time, fraction) = match time.find('.') {
+        Some(index) => (&time[..index], Some(&time[index + 1..])),
+        None => (time, None),
+    };
+    let mut parts = time.split(':');
+    let hour: i64 = parse_digits(parts.next()?, 2)?;
+    let minute: i64 = parse_digits(parts.next()?, 2)?;
+    let second: i64 = parse_digits(parts.next()?, 2)?;
+    if parts.next().is_some() {
+        return None;
+    }
+    let nano = match fraction {
+        Some(f) if !f.is_empty() && f.len() <= 9 => {
+            parse_digits::<u32>(f, f.len())? * 10u32.pow(9 - f.len() as u32)
+        }
+        Some(_) => return None,
+        None => 0,
+    };
+
+    if !(1..=12).contains(&month) || day == 0 || hour > 23 || minute > 59 || second > 59 {
+        return None;
+    }
+    // Reject days past the end of the month
+    let days = days_from_civil(year, month, day);
+    if civil_from_days(days) != (year, month, day) {
+        return None;
+    }
+    let sec = days
+        .checked_mul(86400)?
+        .checked_add(hour * 3600 + minute * 60 + second)?;
+    Timestamp::from_utc(sec, nano)
+}
+
+/// Parse a string of at least `min_len` ASCII digits.
+fn parse_digits<T: std::str::FromStr>(text: &str, min_len: usize) -> Option<T> {
+    if text.len() < min_len || !text.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    text.parse().ok()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn plain_json() {
+        let value = Value::from_json_str(
+            r#"{"a": [1, -2, 1.5, 18446744073709551615], "b": {"c": null, "d": true}, "e": "fog"}"#,
+        )
+        .unwrap();
+        let mut inner = BTreeMap::new();
+        inner.insert("c".to_string(), Value::Null);
+        inner.insert("d".to_string(), Value::Bool(true));
+        let mut expected = BTreeMap::new();
+        expected.insert(
+            "a".to_string(),
+            Value::Array(vec![
+                Value::from(1u8),
+                Value::from(-2i8),
+                Value::F64(1.5),
+                Value::from(u64::MAX),
+            ]),
+        );
+        expected.insert("b".to_string(), Value::Map(inner));
+        expected.insert("e".to_string(), Value::from("fog"));
+        let expected = Value::Map(expected);
+        assert_eq!(value, expected);
+        assert_eq!(
+            Value::from_json_str(&value.to_json_str()).unwrap(),
+            expected
+        );
+
+        assert!(matches!(
+            Value::from_json_str("{\"a\": "),
+            Err(JsonImportError::Parse(_))
+        ));
+    }
+
+    #[test]
+    fn tagged() {
+        let hash = Hash::new(b"fog-pack");
+        let values = vec![
+            Value::Timestamp(Timestamp::from_utc(1_600_000_000, 500_000_000).unwrap()),
+            Value::Timestamp(Timestamp::from_utc(-1, 0).unwrap()),
+            Value::Timestamp(Timestamp::from_utc(300_000_000_000, 0).unwrap()),
+            Value::Bin(vec![0x00, 0xab, 0xff]),
+            Value::Hash(hash.clone()),
+            Value::F32(0.1),
+        ];
+        for value in values {
+            let json = value.to_json_str();
+            let decoded = Value::from_json_str(&json).unwrap();
+            if let Value::F32(v) = value {
+                assert_eq!(decoded, Value::F64(v.to_string().parse().unwrap()));
+            } else {
+                assert_eq!(decoded, value, "{} didn't round trip", json);
+            }
+        }
+
+        let time =
+            Value::from_json_str(r#"{"$type": "timestamp", "$value": "2020-09-13T12:26:40.5Z"}"#)
+                .unwrap();
+        assert_eq!(
+            time,
+            Value::Timestamp(Timestamp::from_utc(1_600_000_000, 500_000_000).unwrap())
+        );
+        assert_eq!(
+            Value::Timestamp(Timestamp::from_utc(-1, 0).unwrap()).to_json_str(),
+            r#"{"$type":"timestamp","$value":"1969-12-31T23:59:59Z"}"#
+        );
+        let json = format!(r#"{{"$type": "hash", "$value": "{}"}}"#, hash.to_base58());
+        assert_eq!(Value::from_json_str(&json).unwrap(), Value::Hash(hash));
+
+        // Bad tagged values say what type they were for
+        for bad in [
+            r#"{"$type": "timestamp", "$value": "2020-02-30T00:00:00Z"}"#,
+            r#"{"$type": "timestamp", "$value": "2020-02-03 00:00:00"}"#,
+            r#"{"$type": "timestamp", "$value": "+99999999999999999-01-01T00:00:00Z"}"#,
+            r#"{"$type": "timestamp", "$value": "-99999999999999999-01-01T00:00:00Z"}"#,
+            r#"{"$type": "timestamp", "$value": "+292277026597-01-01T00:00:00Z"}"#,
+            r#"{"$type": "timestamp", "$value": "+292277026596-12-31T23:59:59Z"}"#,
+            r#"{"$type": "bin", "$value": "abc"}"#,
+            r#"{"$type": "hash", "$value": 1}"#,
+            r#"{"$type": "uuid", "$value": "1234"}"#,
+        ]
+        .iter()
+        {
+            match Value::from_json_str(bad) {
+                Err(JsonImportError::BadTag { .. }) => (),
+                other => panic!("{} should have failed, got {:?}", bad, other),
+            }
+        }
+
+        // Tags can be turned off or renamed
+        let json = r#"{"kind": "bin", "data": "00ff"}"#;
+        let options = JsonImportOptions::new().tag_keys("kind", "data");
+        assert_eq!(
+            Value::from_json_str_with_options(json, &options).unwrap(),
+            Value::Bin(vec![0x00, 0xff])
+        );
+        assert!(Value::from_json_str(json).unwrap().is_map());
+        let json = r#"{"$type": "bin", "$value": "00ff"}"#;
+        let options = JsonImportOptions::new().tagged(false);
+        assert!(Value::from_json_str_with_options(json, &options)
+            .unwrap()
+            .is_map());
+    }
+}
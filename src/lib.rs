@@ -187,12 +187,24 @@
 //! # }
 //! ```
 //!
+//! # Optional Features
+//!
+//! - `json`: Convert between JSON text and [`Value`][crate::types::Value] with
+//!   [`Value::from_json_str`][crate::types::Value::from_json_str] and
+//!   [`Value::to_json_str`][crate::types::Value::to_json_str].
+//! - `rayon`: Validate batches of documents in parallel with
+//!   [`Schema::validate_batch`][crate::schema::Schema::validate_batch].
+//! - `testing`: Enable the [`testing`] module, with random generators of validators and values
+//!   that conform to them, for use in property tests.
+//!
 
 mod compress;
 mod de;
 mod depth_tracking;
 mod element;
 mod integer;
+#[cfg(feature = "json")]
+mod json;
 mod marker;
 mod ser;
 mod timestamp;
@@ -204,6 +216,8 @@ pub mod entry;
 pub mod error;
 pub mod query;
 pub mod schema;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
 pub mod validator;
 
 use types::*;
@@ -238,8 +252,10 @@ pub use document::get_doc_schema;
 ///
 pub mod types {
     pub use crate::integer::*;
+    #[cfg(feature = "json")]
+    pub use crate::json::{JsonImportError, JsonImportOptions};
     pub use crate::timestamp::*;
-    pub use crate::value::Value;
+    pub use crate::value::{PathSegment, Value, ValuePath};
     pub use crate::value_ref::ValueRef;
     pub use fog_crypto::{
         hash::Hash,
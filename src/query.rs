@@ -5,21 +5,36 @@
 //! [`Validator`][crate::validator::Validator] to [`NewQuery::new`]. Queries must be validated by a
 //! [Schema][crate::schema::Schema] before they can be used.
 //!
+//! Queries made with [`NewQuery::doc`] check Documents instead, using the schema's document
+//! validator for permissions, and are run with [`Query::matches_doc`].
+//!
+//! A [`QueryBuilder`] can assemble the query validator from constraints on individual paths
+//! within an Entry, instead of building the nested validators by hand.
+//!
 
-use std::collections::BTreeMap;
+use std::{
+    cmp::Ordering,
+    collections::{BTreeMap, HashMap, HashSet},
+};
 
+use crate::document::Document;
 use crate::entry::Entry;
 use crate::validator::Validator;
 use crate::{
     de::FogDeserializer,
     element::Parser,
-    error::{Error, Result},
+    error::{Error, Result, ValidateCode, ValidateError},
+    schema::Schema,
     ser::FogSerializer,
-    validator::{Checklist, DataChecklist},
+    validator::{
+        AllValidator, ArrayValidator, Checklist, Complexity, DataChecklist, IntValidator,
+        MapValidator, NotValidator, QueryCaps, StrValidator,
+    },
+    value::{PathSegment, Value, ValuePath},
     value_ref::ValueRef,
     MAX_QUERY_SIZE,
 };
-use fog_crypto::hash::Hash;
+use fog_crypto::{hash::Hash, identity::Identity};
 use serde::{Deserialize, Serialize};
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -27,6 +42,252 @@ use serde::{Deserialize, Serialize};
 struct InnerQuery {
     key: String,
     query: Validator,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    doc: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    limit: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    order: Option<QueryOrder>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    clauses: Vec<QueryClause>,
+    #[serde(default, skip_serializing_if = "QueryCombinator::is_and")]
+    combine: QueryCombinator,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    signers: Vec<Identity>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    after: Option<QueryCursor>,
+}
+
+impl InnerQuery {
+    fn clauses(&self) -> impl Iterator<Item = (&str, &Validator)> {
+        std::iter::once((self.key.as_str(), &self.query))
+            .chain(self.clauses.iter().map(|c| (c.key.as_str(), &c.query)))
+    }
+
+    /// Hash the encoded query, leaving out any cursor, so that every page of the same query has
+    /// the same hash.
+    fn hash(&self) -> Result<Hash> {
+        let mut ser = FogSerializer::default();
+        if self.after.is_some() {
+            let inner = InnerQuery {
+                after: None,
+                ..self.clone()
+            };
+            inner.serialize(&mut ser)?;
+        } else {
+            self.serialize(&mut ser)?;
+        }
+        Ok(Hash::new(ser.finish()))
+    }
+
+    /// Get the value an Entry is sorted by, if the query has an ordering and the entry has a
+    /// value at the ordering's path.
+    fn order_key(&self, entry: &Entry) -> Result<Option<Value>> {
+        let order = match &self.order {
+            Some(order) => order,
+            None => return Ok(None),
+        };
+        let path = ValuePath::parse(&order.path)?;
+        let data: Value = entry.deserialize()?;
+        Ok(path.get(&data).cloned())
+    }
+
+    fn cursor(&self, entry: &Entry) -> Result<QueryCursor> {
+        Ok(QueryCursor {
+            query: self.hash()?,
+            entry: entry.hash(),
+            key: self.order_key(entry)?,
+        })
+    }
+
+    /// Check if an Entry sorts after the cursor, or if there's no cursor at all.
+    fn past_cursor(&self, entry: &Entry) -> Result<bool> {
+        let cursor = match &self.after {
+            Some(cursor) => cursor,
+            None => return Ok(true),
+        };
+        let key = self.order_key(entry)?;
+        let order = self.position_cmp((&key, &entry.hash()), (&cursor.key, &cursor.entry));
+        Ok(order == Ordering::Greater)
+    }
+
+    /// Compare two result positions, each given as an ordering value and an Entry hash.
+    fn position_cmp(&self, a: (&Option<Value>, &Hash), b: (&Option<Value>, &Hash)) -> Ordering {
+        let key_order = match self.order.as_ref().map(|o| o.dir) {
+            Some(OrderDirection::Descending) => order_cmp(b.0, a.0),
+            _ => order_cmp(a.0, b.0),
+        };
+        key_order.then_with(|| a.1.cmp(b.1))
+    }
+}
+
+/// Compare two ordering values. Missing values sort first, and values of differing types (which
+/// a schema with `ord` permissions shouldn't produce) are treated as equal.
+fn order_cmp(a: &Option<Value>, b: &Option<Value>) -> Ordering {
+    match (a, b) {
+        (None, None) => Ordering::Equal,
+        (None, Some(_)) => Ordering::Less,
+        (Some(_), None) => Ordering::Greater,
+        (Some(a), Some(b)) => match (a, b) {
+            (Value::Int(a), Value::Int(b)) => a.cmp(b),
+            (Value::F32(a), Value::F32(b)) => a.total_cmp(b),
+            (Value::F64(a), Value::F64(b)) => a.total_cmp(b),
+            (Value::Bin(a), Value::Bin(b)) => a.cmp(b),
+            (Value::Str(a), Value::Str(b)) => a.cmp(b),
+            (Value::Timestamp(a), Value::Timestamp(b)) => a.cmp(b),
+            _ => Ordering::Equal,
+        },
+    }
+}
+
+/// An additional clause in a compound query, constraining the Entries under another key.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct QueryClause {
+    key: String,
+    query: Validator,
+}
+
+/// How the clauses of a compound query are combined.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum QueryCombinator {
+    /// Every clause must match.
+    #[default]
+    And,
+    /// At least one clause must match.
+    Or,
+}
+
+impl QueryCombinator {
+    fn is_and(&self) -> bool {
+        *self == QueryCombinator::And
+    }
+}
+
+/// The direction query results should be sorted in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum OrderDirection {
+    /// Smallest values first.
+    Ascending,
+    /// Largest values first.
+    Descending,
+}
+
+/// How query results should be ordered: by the value at a path inside each Entry.
+///
+/// The path uses the same syntax as [`ValuePath::parse`][crate::types::ValuePath::parse], and
+/// must lead to a validator in the entry's schema that permits `ord` queries. See
+/// [`Validator::query_capabilities_at`] for how the path is followed.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct QueryOrder {
+    path: String,
+    dir: OrderDirection,
+}
+
+impl QueryOrder {
+    /// The path to the value results are sorted by.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// The direction results are sorted in.
+    pub fn direction(&self) -> OrderDirection {
+        self.dir
+    }
+}
+
+/// A position in a query's results, for resuming a query after the last Entry that was returned.
+///
+/// Results are ordered by the query's [`QueryOrder`], if it has one, with ties broken by the
+/// Entry hashes in ascending order. Queries without an ordering are ordered by Entry hash alone.
+/// A cursor holds the hash of the last Entry returned and the value it was sorted by, so any
+/// Entry at or before that position can be skipped.
+///
+/// Cursors are made with [`NewQuery::cursor`] or [`Query::cursor`] and attached to a query with
+/// [`NewQuery::after`]. Each cursor also holds the hash of the query it was made for, leaving out
+/// any cursor already attached to it, and [`Schema::decode_query`] rejects queries whose cursor
+/// was made for a different query. This only catches cursors reused by mistake: a cursor is an
+/// unauthenticated position marker, and nothing ties its Entry hash or sort value to a real
+/// Entry. Anyone can make a cursor for any position in any query's results, so a query executor
+/// shouldn't rely on cursors to limit what a query can see. Cursors serialize as a small
+/// fog-pack map.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct QueryCursor {
+    query: Hash,
+    entry: Hash,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    key: Option<Value>,
+}
+
+impl QueryCursor {
+    /// The hash of the query this cursor was made for.
+    pub fn query_hash(&self) -> &Hash {
+        &self.query
+    }
+
+    /// The hash of the last Entry returned.
+    pub fn entry_hash(&self) -> &Hash {
+        &self.entry
+    }
+
+    /// The value the last Entry returned was sorted by, if the query has an ordering and the
+    /// entry had a value to sort by.
+    pub fn key(&self) -> Option<&Value> {
+        self.key.as_ref()
+    }
+}
+
+/// Limits on how complex a decoded query may be, so that running a query from an untrusted
+/// source can't become arbitrarily expensive. Used by [`Schema::decode_query_with_limits`].
+///
+/// Each limit covers the whole query, summed across all of its clauses. Ref validators count as a
+/// single validator, as the types they refer to come from the schema, not the query.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct QueryLimits {
+    /// The maximum number of validators, including every nested validator.
+    pub max_validators: usize,
+    /// The maximum total number of values in all `in` and `nin` lists.
+    pub max_list_entries: usize,
+    /// The maximum nesting depth of validators. A validator with no nested validators has a depth
+    /// of 1.
+    pub max_depth: usize,
+}
+
+impl Default for QueryLimits {
+    fn default() -> Self {
+        Self {
+            max_validators: 1024,
+            max_list_entries: 4096,
+            max_depth: 32,
+        }
+    }
+}
+
+impl QueryLimits {
+    /// Make a new set of limits with the default configuration.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the maximum number of validators allowed.
+    pub fn max_validators(mut self, max: usize) -> Self {
+        self.max_validators = max;
+        self
+    }
+
+    /// Set the maximum total number of `in` and `nin` list values allowed.
+    pub fn max_list_entries(mut self, max: usize) -> Self {
+        self.max_list_entries = max;
+        self
+    }
+
+    /// Set the maximum validator nesting depth allowed.
+    pub fn max_depth(mut self, max: usize) -> Self {
+        self.max_depth = max;
+        self
+    }
 }
 
 /// A new Query, ready for encoding.
@@ -35,7 +296,9 @@ struct InnerQuery {
 /// schema into a proper [`Query`][Query].
 ///
 /// A Query contains a single validator and a key, which may be used for querying a set of Entries.
-/// Entries that pass the validator can be returned as the query results.
+/// Entries that pass the validator can be returned as the query results. More clauses, each with
+/// their own key and validator, can be added with [`clause_add`][Self::clause_add] to make a
+/// compound query.
 ///
 /// Queries are not meant to be used without associated context; they should be provided alongside
 /// information about what Document they are being used to query.
@@ -50,10 +313,90 @@ impl NewQuery {
             inner: InnerQuery {
                 key: key.to_owned(),
                 query,
+                doc: false,
+                limit: None,
+                order: None,
+                clauses: Vec::new(),
+                combine: QueryCombinator::And,
+                signers: Vec::new(),
+                after: None,
             },
         }
     }
 
+    /// Make a query for Documents instead of Entries, checking each Document's data with
+    /// `query`. The query must be allowed by the schema's document validator, and is run with
+    /// [`Query::matches_doc`]. Document queries have an empty [`key`][Self::key], and can't have
+    /// more clauses or signer requirements.
+    pub fn doc(query: Validator) -> Self {
+        let mut new = Self::new("", query);
+        new.inner.doc = true;
+        new
+    }
+
+    /// Exclude Entries that pass `validator`. The query's validator becomes an
+    /// [`All`][Validator::All] validator holding the original validator and a
+    /// [`Not`][Validator::Not] validator for each exclusion.
+    ///
+    /// An exclusion is allowed exactly when the schema would allow querying with `validator`
+    /// directly, and it can't contain any Hash validators with `link` or `schema` requirements.
+    pub fn exclude(mut self, validator: Validator) -> Self {
+        let not = NotValidator::new(validator).build();
+        self.inner.query = match std::mem::replace(&mut self.inner.query, Validator::Any) {
+            Validator::All(all) => all.push(not).build(),
+            query => AllValidator::new().push(query).push(not).build(),
+        };
+        self
+    }
+
+    /// Add another clause to the query, with a validator for the Entries under `key`. Each
+    /// clause's validator must be allowed by the schema for its key.
+    pub fn clause_add(mut self, key: &str, query: Validator) -> Self {
+        self.inner.clauses.push(QueryClause {
+            key: key.to_owned(),
+            query,
+        });
+        self
+    }
+
+    /// Only match entries signed by one of the given Identities. The schema must permit signer
+    /// queries for every key in the query; see
+    /// [`SchemaBuilder::entry_signer_ok`][crate::schema::SchemaBuilder::entry_signer_ok].
+    pub fn signed_by(mut self, identities: Vec<Identity>) -> Self {
+        self.inner.signers = identities;
+        self
+    }
+
+    /// Set how the query's clauses are combined. The default is [`QueryCombinator::And`].
+    pub fn combinator(mut self, combine: QueryCombinator) -> Self {
+        self.inner.combine = combine;
+        self
+    }
+
+    /// Ask for no more than `limit` results.
+    pub fn limit(mut self, limit: u32) -> Self {
+        self.inner.limit = Some(limit);
+        self
+    }
+
+    /// Ask for results sorted by the value at `path` within each Entry. The schema must permit
+    /// `ord` queries on that value, or encoding the query will fail.
+    pub fn order_by(mut self, path: &str, direction: OrderDirection) -> Self {
+        self.inner.order = Some(QueryOrder {
+            path: path.to_owned(),
+            dir: direction,
+        });
+        self
+    }
+
+    /// Only ask for results after the position given by `cursor`. The cursor must have been made
+    /// for this same query, or decoding the query will fail. The position itself isn't checked;
+    /// see [`QueryCursor`].
+    pub fn after(mut self, cursor: QueryCursor) -> Self {
+        self.inner.after = Some(cursor);
+        self
+    }
+
     pub fn validator(&self) -> &Validator {
         &self.inner.query
     }
@@ -62,6 +405,48 @@ impl NewQuery {
         &self.inner.key
     }
 
+    /// Check if this query is for Documents, as made by [`NewQuery::doc`], instead of Entries.
+    pub fn is_doc(&self) -> bool {
+        self.inner.doc
+    }
+
+    /// The maximum number of results requested, if any.
+    pub fn max_results(&self) -> Option<u32> {
+        self.inner.limit
+    }
+
+    /// How the results should be ordered, if requested.
+    pub fn order(&self) -> Option<&QueryOrder> {
+        self.inner.order.as_ref()
+    }
+
+    /// Iterate over the key and validator of every clause in the query, starting with the one
+    /// given to [`NewQuery::new`].
+    pub fn clauses(&self) -> impl Iterator<Item = (&str, &Validator)> {
+        self.inner.clauses()
+    }
+
+    /// How the query's clauses are combined.
+    pub fn combine(&self) -> QueryCombinator {
+        self.inner.combine
+    }
+
+    /// The Identities an entry must be signed by one of, if any.
+    pub fn signers(&self) -> &[Identity] {
+        &self.inner.signers
+    }
+
+    /// The cursor results must come after, if any.
+    pub fn cursor_after(&self) -> Option<&QueryCursor> {
+        self.inner.after.as_ref()
+    }
+
+    /// Make a cursor for resuming this query after `entry`, which should be the last Entry
+    /// returned.
+    pub fn cursor(&self, entry: &Entry) -> Result<QueryCursor> {
+        self.inner.cursor(entry)
+    }
+
     pub(crate) fn complete(self, max_regex: u8) -> Result<Vec<u8>> {
         fn parse_validator(v: &Validator) -> usize {
             match v {
@@ -77,6 +462,10 @@ impl NewQuery {
                             .values()
                             .fold(0, |acc, val| acc + parse_validator(val))
                         + val.values.as_ref().map_or(0, |val| parse_validator(val))
+                        + val
+                            .pattern_fields
+                            .iter()
+                            .fold(0, |acc, (_, val)| acc + 1 + parse_validator(val))
                 }
                 Validator::Array(val) => {
                     val.contains
@@ -90,18 +479,27 @@ impl NewQuery {
                 }
                 Validator::Hash(val) => val.link.as_ref().map_or(0, |val| parse_validator(val)),
                 Validator::Enum(val) => val
-                    .values()
-                    .fold(0, |acc, val| acc + val.as_ref().map_or(0, parse_validator)),
+                    .validators()
+                    .fold(0, |acc, val| acc + parse_validator(val)),
                 Validator::Multi(val) => val.iter().fold(0, |acc, val| acc + parse_validator(val)),
+                Validator::All(val) => val.iter().fold(0, |acc, val| acc + parse_validator(val)),
+                Validator::Not(val) => parse_validator(val.validator()),
                 _ => 0,
             }
         }
-        let regexes = parse_validator(&self.inner.query);
+        let mut regexes = 0;
+        for (_, query) in self.inner.clauses() {
+            query.check_build()?;
+            regexes += parse_validator(query);
+        }
         if regexes > (max_regex as usize) {
-            return Err(Error::FailValidate(format!(
-                "Found {} regexes in query, only {} allowed",
-                regexes, max_regex
-            )));
+            return Err(Error::validate(
+                ValidateCode::QueryNotAllowed,
+                format!(
+                    "Found {} regexes in query, only {} allowed",
+                    regexes, max_regex
+                ),
+            ));
         }
         let mut ser = FogSerializer::default();
         self.inner.serialize(&mut ser)?;
@@ -117,10 +515,224 @@ impl NewQuery {
     }
 }
 
+/// Builds a [`NewQuery`] from constraints on values at paths within an Entry.
+///
+/// Each constraint is given as a [`ValuePath`][crate::types::ValuePath] string and a validator
+/// for the value found there. The builder fills in the map and array validators needed to reach
+/// each path: map keys become `req` fields, and array indices become `prefix` validators, with
+/// any earlier prefix positions accepting anything. Multiple constraints on the same path are
+/// combined with an [`All`][AllValidator] validator.
+///
+/// If a [`Schema`] is provided, each constraint is checked against the schema's query
+/// permissions as it's added, so [`build`][Self::build] can fail with a reason like "range
+/// queries aren't allowed at `score`" instead of waiting for
+/// [`Schema::encode_query`]. Without a schema, nothing is checked until the query is encoded.
+///
+/// ```
+/// # use fog_pack::query::QueryBuilder;
+/// let query = QueryBuilder::new("post")
+///     .where_int("score", |i| i.min(5))
+///     .where_str("author", |s| s.in_add("alice"))
+///     .build()
+///     .unwrap();
+/// assert_eq!(query.key(), "post");
+/// ```
+#[derive(Clone, Debug)]
+pub struct QueryBuilder<'a> {
+    key: String,
+    schema: Option<&'a Schema>,
+    root: PathNode,
+    error: Option<Error>,
+}
+
+/// The validators being assembled by a [`QueryBuilder`].
+#[derive(Clone, Debug)]
+enum PathNode {
+    Empty,
+    Leaf(Vec<Validator>),
+    Map(BTreeMap<String, PathNode>),
+    Array(BTreeMap<usize, PathNode>),
+}
+
+impl PathNode {
+    fn insert(&mut self, path: &[PathSegment], validator: Validator) -> bool {
+        let (segment, rest) = match path.split_first() {
+            Some(split) => split,
+            None => {
+                return match self {
+                    PathNode::Empty => {
+                        *self = PathNode::Leaf(vec![validator]);
+                        true
+                    }
+                    PathNode::Leaf(list) => {
+                        list.push(validator);
+                        true
+                    }
+                    _ => false,
+                }
+            }
+        };
+        if let PathNode::Empty = self {
+            *self = match segment {
+                PathSegment::Key(_) => PathNode::Map(BTreeMap::new()),
+                PathSegment::Index(_) => PathNode::Array(BTreeMap::new()),
+            };
+        }
+        let next = match (self, segment) {
+            (PathNode::Map(map), PathSegment::Key(key)) => {
+                map.entry(key.clone()).or_insert(PathNode::Empty)
+            }
+            (PathNode::Array(array), PathSegment::Index(index)) => {
+                array.entry(*index).or_insert(PathNode::Empty)
+            }
+            _ => return false,
+        };
+        next.insert(rest, validator)
+    }
+
+    fn build(self) -> Validator {
+        match self {
+            PathNode::Empty => Validator::Any,
+            PathNode::Leaf(mut list) => {
+                if list.len() == 1 {
+                    list.pop().unwrap()
+                } else {
+                    Validator::All(AllValidator(list))
+                }
+            }
+            PathNode::Map(map) => map
+                .into_iter()
+                .fold(MapValidator::new(), |validator, (key, node)| {
+                    validator.req_add(key, node.build())
+                })
+                .build(),
+            PathNode::Array(array) => {
+                let mut prefix = Vec::new();
+                for (index, node) in array {
+                    prefix.resize(index, Validator::Any);
+                    prefix.push(node.build());
+                }
+                ArrayValidator::new().prefix(prefix).build()
+            }
+        }
+    }
+}
+
+impl<'a> QueryBuilder<'a> {
+    /// Start building a query for the Entries under `key`.
+    pub fn new(key: &str) -> Self {
+        Self {
+            key: key.to_owned(),
+            schema: None,
+            root: PathNode::Empty,
+            error: None,
+        }
+    }
+
+    /// Check each constraint against a schema's query permissions as it's added.
+    pub fn schema(mut self, schema: &'a Schema) -> Self {
+        self.schema = Some(schema);
+        self
+    }
+
+    /// Constrain the value at `path` with any validator.
+    pub fn where_validator(self, path: &str, validator: Validator) -> Self {
+        self.add(path, validator, |_, _| None)
+    }
+
+    /// Constrain the integer at `path`, using `f` to set up an [`IntValidator`].
+    pub fn where_int(self, path: &str, f: impl FnOnce(IntValidator) -> IntValidator) -> Self {
+        self.add(path, f(IntValidator::new()).build(), |caps, v| {
+            let perms = |query, bit, ord| IntValidator::new().query(query).bit(bit).ord(ord);
+            [
+                ("`in` and `nin` list", perms(caps.in_nin, true, true)),
+                ("bit", perms(true, caps.bit, true)),
+                ("range", perms(true, true, caps.ord)),
+            ]
+            .iter()
+            .find(|(_, perms)| !perms.query_check(v))
+            .map(|(kind, _)| *kind)
+        })
+    }
+
+    /// Constrain the string at `path`, using `f` to set up a [`StrValidator`].
+    pub fn where_str(self, path: &str, f: impl FnOnce(StrValidator) -> StrValidator) -> Self {
+        self.add(path, f(StrValidator::new()).build(), |caps, v| {
+            let perms =
+                |query, regex, size| StrValidator::new().query(query).regex(regex).size(size);
+            [
+                ("`in` and `nin` list", perms(caps.in_nin, true, true)),
+                ("regex", perms(true, caps.regex, true)),
+                ("length", perms(true, true, caps.size)),
+            ]
+            .iter()
+            .find(|(_, perms)| !perms.query_check(v))
+            .map(|(kind, _)| *kind)
+        })
+    }
+
+    /// Add a constraint, checking it against the schema if there is one. `denied` gives the kind
+    /// of query the validator makes that the capabilities don't allow, if any.
+    fn add(
+        mut self,
+        path: &str,
+        validator: Validator,
+        denied: impl FnOnce(&QueryCaps, &Validator) -> Option<&'static str>,
+    ) -> Self {
+        if self.error.is_some() {
+            return self;
+        }
+        let parsed = match ValuePath::parse(path) {
+            Ok(parsed) => parsed,
+            Err(err) => {
+                self.error = Some(err);
+                return self;
+            }
+        };
+        if let Some(schema) = self.schema {
+            let denied = match schema.query_capabilities(&self.key, &parsed) {
+                None => Some(format!(
+                    "`{}` isn't a field of entry {:?} in the schema",
+                    path, self.key
+                )),
+                Some(caps) if !caps.allowed => {
+                    Some(format!("queries aren't allowed at `{}`", path))
+                }
+                Some(caps) => denied(&caps, &validator)
+                    .map(|kind| format!("{} queries aren't allowed at `{}`", kind, path)),
+            };
+            if let Some(message) = denied {
+                let mut err = ValidateError::new(ValidateCode::QueryNotAllowed, message);
+                err.path = parsed;
+                self.error = Some(err.into());
+                return self;
+            }
+        }
+        if !self.root.insert(parsed.segments(), validator) {
+            self.error = Some(Error::BadPath(format!(
+                "`{}` conflicts with the other paths in the query",
+                path
+            )));
+        }
+        self
+    }
+
+    /// Build the [`NewQuery`]. Fails if a path couldn't be parsed, paths conflicted (like
+    /// treating the same value as both a map and an array), or the schema didn't allow one of
+    /// the constraints.
+    pub fn build(self) -> Result<NewQuery> {
+        match self.error {
+            Some(err) => Err(err),
+            None => Ok(NewQuery::new(&self.key, self.root.build())),
+        }
+    }
+}
+
 /// For querying Entries.
 ///
 /// A Query contains a single validator and a key, which may be used for querying a set of Entries.
-/// Entries that pass the validator can be returned as the query results.
+/// Entries that pass the validator can be returned as the query results. Compound queries have
+/// more clauses, which can be found with [`clauses`][Self::clauses].
 ///
 /// Queries are not meant to be used without associated context; they should be provided alongside
 /// information about what Document they are being used to query.
@@ -133,6 +745,13 @@ pub struct Query {
 
 impl Query {
     pub(crate) fn new(buf: Vec<u8>, max_regex: u8) -> Result<Self> {
+        if buf.len() > MAX_QUERY_SIZE {
+            return Err(Error::LengthTooLong {
+                max: MAX_QUERY_SIZE,
+                actual: buf.len(),
+            });
+        }
+
         // Check to see how many regexes are in the validator
         fn parse_validator(v: &ValueRef) -> usize {
             // First, unpack the validator enum
@@ -161,7 +780,13 @@ impl Query {
                             map.values().fold(0, |acc, val| acc + parse_validator(val))
                         });
                         let values_matches = parse_validator(&val["values"]);
-                        key_matches + req_matches + opt_matches + values_matches
+                        // Each pattern is a regex of its own, paired with a validator
+                        let pattern_matches = val["pattern_fields"].as_array().map_or(0, |array| {
+                            array
+                                .iter()
+                                .fold(0, |acc, pair| acc + 1 + parse_validator(&pair[1]))
+                        });
+                        key_matches + req_matches + opt_matches + values_matches + pattern_matches
                     }
                     // Array validator
                     Some((&"Array", val)) => {
@@ -172,7 +797,7 @@ impl Query {
                             array.iter().fold(0, |acc, val| acc + parse_validator(val))
                         });
                         let items_matches = parse_validator(&val["items"]);
-                        let prefix_matches = val["contains"].as_array().map_or(0, |array| {
+                        let prefix_matches = val["prefix"].as_array().map_or(0, |array| {
                             array.iter().fold(0, |acc, val| acc + parse_validator(val))
                         });
                         contains_matches + items_matches + prefix_matches
@@ -185,13 +810,30 @@ impl Query {
                         parse_validator(&val["link"])
                     }
                     // Enum validator
-                    Some((&"Enum", val)) => val.as_map().map_or(0, |map| {
-                        map.values().fold(0, |acc, val| acc + parse_validator(val))
-                    }),
-                    // Multi validator
-                    Some((&"Multi", val)) => val.as_array().map_or(0, |array| {
-                        array.iter().fold(0, |acc, val| acc + parse_validator(val))
-                    }),
+                    Some((&"Enum", val)) => {
+                        if let Some(map) = val.as_map() {
+                            return map.values().fold(0, |acc, val| acc + parse_validator(val));
+                        }
+                        // Anything beyond named variants is a map of fields inside an array
+                        let val = &val[0];
+                        let var_matches = val["var"].as_map().map_or(0, |map| {
+                            map.values().fold(0, |acc, val| acc + parse_validator(val))
+                        });
+                        let int_var_matches = val["int_var"].as_array().map_or(0, |array| {
+                            array
+                                .iter()
+                                .fold(0, |acc, val| acc + parse_validator(&val[1]))
+                        });
+                        var_matches + int_var_matches + parse_validator(&val["other"])
+                    }
+                    // Multi and All validators
+                    Some((&"Multi", val)) | Some((&"All", val)) => {
+                        val.as_array().map_or(0, |array| {
+                            array.iter().fold(0, |acc, val| acc + parse_validator(val))
+                        })
+                    }
+                    // Not validator
+                    Some((&"Not", val)) => parse_validator(val),
                     _ => 0,
                 }
             } else {
@@ -200,17 +842,39 @@ impl Query {
         }
         let mut de = FogDeserializer::new(&buf);
         let regex_check = ValueRef::deserialize(&mut de)?;
-        let regexes = parse_validator(&regex_check["query"]);
+        let regexes = parse_validator(&regex_check["query"])
+            + regex_check["clauses"].as_array().map_or(0, |array| {
+                array
+                    .iter()
+                    .fold(0, |acc, val| acc + parse_validator(&val["query"]))
+            });
         if regexes > (max_regex as usize) {
-            return Err(Error::FailValidate(format!(
-                "Found {} regexes in query, only {} allowed",
-                regexes, max_regex
-            )));
+            return Err(Error::validate(
+                ValidateCode::QueryNotAllowed,
+                format!(
+                    "Found {} regexes in query, only {} allowed",
+                    regexes, max_regex
+                ),
+            ));
         }
 
         // Parse into an actual validator
         let mut de = FogDeserializer::new(&buf);
-        let inner = InnerQuery::deserialize(&mut de)?;
+        let mut inner = InnerQuery::deserialize(&mut de)?;
+        if let Some(cursor) = &inner.after {
+            if cursor.query != inner.hash()? {
+                return Err(Error::validate(
+                    ValidateCode::QueryNotAllowed,
+                    "query cursor was made for a different query",
+                ));
+            }
+        }
+        inner.query.check_build()?;
+        inner.query.build_index();
+        for clause in inner.clauses.iter_mut() {
+            clause.query.check_build()?;
+            clause.query.build_index();
+        }
         Ok(Self {
             inner,
             schema: Hash::new(&[]),
@@ -218,6 +882,33 @@ impl Query {
         })
     }
 
+    /// Check that the query, across all its clauses, is within the given complexity limits.
+    pub(crate) fn check_limits(&self, limits: &QueryLimits) -> Result<()> {
+        let mut total = Complexity::default();
+        for (_, query) in self.clauses() {
+            let complexity = query.complexity();
+            total.validators += complexity.validators;
+            total.list_entries += complexity.list_entries;
+            total.depth = total.depth.max(complexity.depth);
+        }
+        let exceeded = [
+            ("validators", total.validators, limits.max_validators),
+            (
+                "`in`/`nin` values",
+                total.list_entries,
+                limits.max_list_entries,
+            ),
+            ("levels of validator nesting", total.depth, limits.max_depth),
+        ];
+        match exceeded.iter().find(|(_, actual, max)| actual > max) {
+            Some((what, actual, max)) => Err(Error::validate(
+                ValidateCode::QueryNotAllowed,
+                format!("Found {} {} in query, only {} allowed", actual, what, max),
+            )),
+            None => Ok(()),
+        }
+    }
+
     pub fn validator(&self) -> &Validator {
         &self.inner.query
     }
@@ -226,22 +917,427 @@ impl Query {
         &self.inner.key
     }
 
+    /// Check if this query is for Documents, as made by [`NewQuery::doc`], instead of Entries.
+    pub fn is_doc(&self) -> bool {
+        self.inner.doc
+    }
+
+    /// The maximum number of results requested, if any.
+    pub fn max_results(&self) -> Option<u32> {
+        self.inner.limit
+    }
+
+    /// How the results should be ordered, if requested.
+    pub fn order(&self) -> Option<&QueryOrder> {
+        self.inner.order.as_ref()
+    }
+
+    /// Iterate over the key and validator of every clause in the query, starting with the one
+    /// given to [`NewQuery::new`].
+    pub fn clauses(&self) -> impl Iterator<Item = (&str, &Validator)> {
+        self.inner.clauses()
+    }
+
+    /// How the query's clauses are combined.
+    pub fn combine(&self) -> QueryCombinator {
+        self.inner.combine
+    }
+
+    /// The Identities an entry must be signed by one of, if any.
+    pub fn signers(&self) -> &[Identity] {
+        &self.inner.signers
+    }
+
+    /// The cursor results must come after, if any.
+    pub fn cursor_after(&self) -> Option<&QueryCursor> {
+        self.inner.after.as_ref()
+    }
+
+    /// Make a cursor for resuming this query after `entry`, which should be the last Entry
+    /// returned.
+    pub fn cursor(&self, entry: &Entry) -> Result<QueryCursor> {
+        self.inner.cursor(entry)
+    }
+
+    /// Check if an Entry comes after the query's cursor, under the ordering described by
+    /// [`QueryCursor`]. Entries at or before the cursor should be skipped. Always true if the
+    /// query has no cursor. This doesn't check whether the entry matches the query.
+    pub fn past_cursor(&self, entry: &Entry) -> Result<bool> {
+        self.inner.past_cursor(entry)
+    }
+
+    /// Run the query over a collection of Entries, the same way a query executor should. Steps
+    /// are applied in this order:
+    ///
+    /// 1. Entries that don't [match][Self::matches] the query are dropped, including ones
+    ///    without an allowed signer. Entries whose match depends on linked Documents are also
+    ///    dropped, as those Documents can't be checked here.
+    /// 2. Entries at or before the query's cursor, if it has one, are dropped.
+    /// 3. The remaining Entries are sorted as described by [`QueryCursor`].
+    /// 4. Only the first [`max_results`][Self::max_results] Entries are kept.
+    pub fn filter<'a>(&self, entries: impl Iterator<Item = &'a Entry>) -> Result<Vec<&'a Entry>> {
+        let mut results = Vec::new();
+        for entry in entries {
+            if !self.matches(entry)?.is_match() || !self.past_cursor(entry)? {
+                continue;
+            }
+            results.push((self.inner.order_key(entry)?, entry.hash(), entry));
+        }
+        results.sort_by(|a, b| self.inner.position_cmp((&a.0, &a.1), (&b.0, &b.1)));
+        if let Some(limit) = self.inner.limit {
+            results.truncate(limit as usize);
+        }
+        Ok(results.into_iter().map(|(_, _, entry)| entry).collect())
+    }
+
+    /// Check an Entry against the query. For compound queries, the entry is checked with every
+    /// clause for the entry's key, and the results are combined as set by
+    /// [`combine`][Self::combine]. Validation fails if no clause is for the entry's key. If the
+    /// query has [`signers`][Self::signers], the entry must be signed by one of them. Fails if
+    /// this is a Document query.
     pub fn query(&self, entry: &Entry) -> Result<DataChecklist<()>> {
-        let parser = Parser::new(entry.data());
+        self.check_target(false)?;
+        let signed = matches!(entry.signer(), Some(signer) if self.inner.signers.contains(signer));
+        if !self.inner.signers.is_empty() && !signed {
+            return Err(Error::validate(
+                ValidateCode::NotSigner,
+                "entry isn't signed by any of the query's signers",
+            ));
+        }
+        let queries = self
+            .inner
+            .clauses()
+            .filter(|(key, _)| *key == entry.key())
+            .map(|(_, query)| query);
+        self.check_clauses(queries, entry.data(), entry.signer())
+    }
+
+    /// Run each of the given validators over some data, combining the results with the query's
+    /// combinator. Fails validation if there are no validators to run.
+    fn check_clauses<'a>(
+        &'a self,
+        queries: impl Iterator<Item = &'a Validator>,
+        data: &[u8],
+        signer: Option<&Identity>,
+    ) -> Result<DataChecklist<'a, ()>> {
+        let no_clause = || {
+            Error::validate(
+                ValidateCode::NoMatch,
+                "no query clause is for the entry's key",
+            )
+        };
+        match self.inner.combine {
+            QueryCombinator::And => {
+                // Every clause adds its linked Documents to the same checklist
+                let mut checklist = Some(Checklist::new(&self.schema, &self.types));
+                let mut any = false;
+                for query in queries {
+                    let parser = Parser::new(data).with_signer(signer);
+                    checklist = query.validate(&self.types, parser, checklist)?.1;
+                    any = true;
+                }
+                if !any {
+                    return Err(no_clause());
+                }
+                Ok(DataChecklist::from_checklist(checklist.unwrap(), ()))
+            }
+            QueryCombinator::Or => {
+                // Prefer a clause that fully matches over one waiting on linked Documents
+                let mut pending = None;
+                let mut failure = None;
+                for query in queries {
+                    match self.check_data(query, data, signer) {
+                        Ok(checklist) if checklist.is_complete() => return Ok(checklist),
+                        Ok(checklist) => {
+                            pending.get_or_insert(checklist);
+                        }
+                        Err(err @ Error::FailValidate(_)) => failure = Some(err),
+                        Err(err) => return Err(err),
+                    }
+                }
+                match (pending, failure) {
+                    (Some(checklist), _) => Ok(checklist),
+                    (None, Some(err)) => Err(err),
+                    (None, None) => Err(no_clause()),
+                }
+            }
+        }
+    }
+
+    /// Run one of the query's validators over some data.
+    fn check_data<'a>(
+        &'a self,
+        query: &'a Validator,
+        data: &[u8],
+        signer: Option<&Identity>,
+    ) -> Result<DataChecklist<'a, ()>> {
+        let parser = Parser::new(data).with_signer(signer);
         let checklist = Some(Checklist::new(&self.schema, &self.types));
-        let (_, checklist) = self.inner.query.validate(&self.types, parser, checklist)?;
+        let (_, checklist) = query.validate(&self.types, parser, checklist)?;
         Ok(DataChecklist::from_checklist(checklist.unwrap(), ()))
     }
+
+    /// Check if an Entry matches the query, the same way as [`query`][Self::query]. If the
+    /// entry matches but the query has requirements for the Documents it links to, a
+    /// [`PendingMatch`] is returned, which those Documents must be fed into.
+    pub fn matches(&self, entry: &Entry) -> Result<QueryMatch<'_>> {
+        self.check_target(false)?;
+        QueryMatch::from_result(self.query(entry))
+    }
+
+    /// Check if a Document matches a query made with [`NewQuery::doc`], the same way
+    /// [`matches`][Self::matches] checks Entries. Fails if this is an Entry query.
+    pub fn matches_doc(&self, doc: &Document) -> Result<QueryMatch<'_>> {
+        self.check_target(true)?;
+        QueryMatch::from_result(self.check_data(&self.inner.query, doc.data(), doc.signer()))
+    }
+
+    /// Prepare this query for checking many Entries or Documents. See [`CompiledQuery`].
+    pub fn compile(&self) -> CompiledQuery {
+        let mut query = self.clone();
+        for validator in std::iter::once(&mut query.inner.query)
+            .chain(query.inner.clauses.iter_mut().map(|c| &mut c.query))
+        {
+            *validator = validator.simplify(&self.types);
+            validator.build_index();
+        }
+        let mut clauses = HashMap::new();
+        for (index, (key, _)) in query.inner.clauses().enumerate() {
+            clauses
+                .entry(key.to_owned())
+                .or_insert_with(Vec::new)
+                .push(index);
+        }
+        let signers = query.inner.signers.iter().cloned().collect();
+        CompiledQuery {
+            query,
+            clauses,
+            signers,
+        }
+    }
+
+    /// Check that the query is for Documents if `doc` is true, or for Entries otherwise.
+    fn check_target(&self, doc: bool) -> Result<()> {
+        match (self.inner.doc, doc) {
+            (true, false) => Err(Error::validate(
+                ValidateCode::QueryNotAllowed,
+                "query is for Documents, not Entries",
+            )),
+            (false, true) => Err(Error::validate(
+                ValidateCode::QueryNotAllowed,
+                "query is for Entries, not Documents",
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Set the hash of the schema that decoded this query, which Hash validators with a `schema`
+    /// requirement of "same schema as the Entry's Document" are checked against.
+    pub(crate) fn set_schema(&mut self, schema: Hash) {
+        self.schema = schema;
+    }
+}
+
+/// A [`Query`] prepared for checking many Entries or Documents, made with [`Query::compile`].
+///
+/// A compiled query accepts and rejects exactly the same Entries and Documents as the query it
+/// was made from, but does less work for each one:
+///
+/// - Each validator is [simplified][Validator::simplify], dropping repeated and redundant
+///   branches of Multi and All validators, and unwrapping ones left with a single branch.
+/// - The clauses for each entry key, and the set of allowed signers, are looked up in hash
+///   tables instead of by scanning lists.
+///
+/// Query validators can't have named types of their own, so there are no Ref validators to
+/// resolve ahead of time. Compiled queries are `Send` and `Sync`, so one can be shared by
+/// every thread checking Entries against it.
+#[derive(Clone, Debug)]
+pub struct CompiledQuery {
+    query: Query,
+    clauses: HashMap<String, Vec<usize>>,
+    signers: HashSet<Identity>,
+}
+
+impl CompiledQuery {
+    /// The simplified query.
+    pub fn query(&self) -> &Query {
+        &self.query
+    }
+
+    /// Check if an Entry matches the query. Gives the same result as [`Query::matches`].
+    pub fn matches(&self, entry: &Entry) -> Result<QueryMatch<'_>> {
+        self.query.check_target(false)?;
+        let signed = matches!(entry.signer(), Some(signer) if self.signers.contains(signer));
+        if !self.signers.is_empty() && !signed {
+            return Ok(QueryMatch::NoMatch);
+        }
+        let inner = &self.query.inner;
+        let queries =
+            self.clauses
+                .get(entry.key())
+                .into_iter()
+                .flatten()
+                .map(|index| match index {
+                    0 => &inner.query,
+                    index => &inner.clauses[index - 1].query,
+                });
+        QueryMatch::from_result(
+            self.query
+                .check_clauses(queries, entry.data(), entry.signer()),
+        )
+    }
+
+    /// Check if a Document matches a Document query. Gives the same result as
+    /// [`Query::matches_doc`].
+    pub fn matches_doc(&self, doc: &Document) -> Result<QueryMatch<'_>> {
+        self.query.matches_doc(doc)
+    }
+}
+
+/// The result of checking an Entry with [`Query::matches`].
+#[derive(Clone, Debug)]
+pub enum QueryMatch<'a> {
+    /// The entry doesn't match the query.
+    NoMatch,
+    /// The entry matches the query.
+    Match,
+    /// The entry matches so far, but the Documents it links to must be checked to finish
+    /// matching.
+    Pending(PendingMatch<'a>),
+}
+
+impl<'a> QueryMatch<'a> {
+    /// Turn the result of running a query's validator into a match. Failing validation means
+    /// there's no match, while any other error is passed on.
+    fn from_result(result: Result<DataChecklist<'a, ()>>) -> Result<Self> {
+        match result {
+            Ok(checklist) if checklist.is_complete() => Ok(QueryMatch::Match),
+            Ok(checklist) => Ok(QueryMatch::Pending(PendingMatch { checklist })),
+            Err(Error::FailValidate(_)) => Ok(QueryMatch::NoMatch),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Check if the entry is known to match the query. A pending match doesn't count.
+    pub fn is_match(&self) -> bool {
+        matches!(self, QueryMatch::Match)
+    }
+}
+
+/// An Entry that matches a query, as long as the Documents it links to pass the query's `link`
+/// and `schema` requirements.
+///
+/// Each Document in [`pending_hashes`][Self::pending_hashes] should be looked up and passed to
+/// [`resolve`][Self::resolve]. If one of the Documents can't be found, the entry can't be said to
+/// match the query.
+#[derive(Clone, Debug)]
+pub struct PendingMatch<'a> {
+    checklist: DataChecklist<'a, ()>,
+}
+
+impl<'a> PendingMatch<'a> {
+    /// Iterate over the hashes of the Documents that still need to be checked.
+    pub fn pending_hashes(&self) -> impl Iterator<Item = &Hash> {
+        self.checklist.pending_hashes()
+    }
+
+    /// Check one of the linked Documents. Returns [`QueryMatch::NoMatch`] if the Document
+    /// doesn't meet the query's requirements, [`QueryMatch::Match`] if it was the last Document
+    /// needed, and otherwise the match with the remaining Documents still pending. Fails if the
+    /// Document isn't one of the pending ones.
+    pub fn resolve(mut self, doc: &Document) -> Result<QueryMatch<'a>> {
+        let hash = doc.hash();
+        if !self.checklist.pending_hashes().any(|h| *h == hash) {
+            return Err(Error::validate(
+                ValidateCode::BadLink,
+                "Document isn't one the query match is waiting on",
+            ));
+        }
+        match self.checklist.check(doc) {
+            Ok(()) if self.checklist.is_complete() => Ok(QueryMatch::Match),
+            Ok(()) => Ok(QueryMatch::Pending(self)),
+            Err(Error::FailValidate(_)) => Ok(QueryMatch::NoMatch),
+            Err(err) => Err(err),
+        }
+    }
 }
 
 #[cfg(test)]
 mod test {
     use regex::Regex;
 
-    use crate::validator::{KeyValidator, MapValidator, StrValidator};
+    use crate::validator::KeyValidator;
 
     use super::*;
 
+    #[test]
+    fn compiled_is_send_sync() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<CompiledQuery>();
+    }
+
+    #[test]
+    fn compiled_matches_generated_corpus() {
+        use crate::{
+            entry::NewEntry,
+            schema::{Schema, SchemaBuilder},
+            testing::generate_conforming,
+            validator::MultiValidator,
+        };
+        use rand::{rngs::StdRng, SeedableRng};
+        let mut rng = StdRng::seed_from_u64(394);
+        let schema = SchemaBuilder::new(Validator::Null)
+            .entry_add("post", Validator::Any, None)
+            .build()
+            .unwrap();
+        let schema = Schema::from_doc(&schema).unwrap();
+        let parent = Hash::new(b"Pretend I am a parent document");
+        let entry = |value: Value| {
+            let entry = NewEntry::new(value, "post", &parent).unwrap();
+            let (_, encoded) = schema.encode_new_entry(entry).unwrap().complete().unwrap();
+            schema
+                .decode_entry(encoded, "post", &parent)
+                .unwrap()
+                .complete()
+                .unwrap()
+        };
+
+        let (mut accepted, mut rejected) = (0, 0);
+        for round in 0..40 {
+            let validator = Validator::arbitrary(&mut rng, 3);
+            let other = Validator::arbitrary(&mut rng, 3);
+            // Compose the validator with redundant branches, as generated queries often are
+            let composed = if round % 2 == 0 {
+                AllValidator::new()
+                    .push(validator.clone())
+                    .push(Validator::Any)
+                    .push(validator.clone())
+                    .build()
+            } else {
+                MultiValidator::new()
+                    .push(validator.clone())
+                    .push(validator.clone())
+                    .push(other.clone())
+                    .build()
+            };
+            let buf = NewQuery::new("post", composed).complete(0).unwrap();
+            let query = Query::new(buf, 0).unwrap();
+            let compiled = query.compile();
+            for i in 0..20 {
+                let source = if i % 2 == 0 { &validator } else { &other };
+                let entry = entry(generate_conforming(source, &mut rng));
+                let expected = query.matches(&entry).unwrap().is_match();
+                assert_eq!(compiled.matches(&entry).unwrap().is_match(), expected);
+                if expected {
+                    accepted += 1;
+                } else {
+                    rejected += 1;
+                }
+            }
+        }
+        assert!(accepted > 0 && rejected > 0);
+    }
+
     #[test]
     fn max_regex_in_key() {
         let map = MapValidator {
@@ -277,4 +1373,173 @@ mod test {
         assert!(Query::new(enc_query.clone(), 1).is_ok());
         assert!(Query::new(enc_query, 2).is_ok());
     }
+
+    #[test]
+    fn max_regex_in_pattern_fields() {
+        let validator = MapValidator::new()
+            .pattern_fields_add("^x_", Validator::Null)
+            .pattern_fields_add("_at$", Validator::Null)
+            .build();
+        NewQuery::new("test", validator.clone())
+            .complete(1)
+            .unwrap_err();
+        let enc_query = NewQuery::new("test", validator).complete(2).unwrap();
+        assert!(Query::new(enc_query.clone(), 1).is_err());
+        assert!(Query::new(enc_query, 2).is_ok());
+    }
+
+    #[test]
+    fn max_regex_in_prefix() {
+        let validator = ArrayValidator::new()
+            .prefix_add(
+                StrValidator::new()
+                    .matches(Regex::new("[a-z]").unwrap())
+                    .build(),
+            )
+            .build();
+        let enc_query = NewQuery::new("test", validator).complete(1).unwrap();
+        assert!(Query::new(enc_query.clone(), 0).is_err());
+        assert!(Query::new(enc_query, 1).is_ok());
+    }
+
+    #[test]
+    fn max_regex_in_enum() {
+        use crate::validator::EnumValidator;
+        let pattern = || {
+            Some(Validator::Str(StrValidator {
+                matches: Some(Box::new(Regex::new("[a-z]").unwrap())),
+                ..Default::default()
+            }))
+        };
+        let enums = vec![
+            EnumValidator::new().insert("a", pattern()),
+            EnumValidator::new()
+                .insert("a", None)
+                .insert_int(1, pattern()),
+            EnumValidator::new().insert("a", None).other(pattern()),
+        ];
+        for validator in enums {
+            let validator = validator.build();
+            NewQuery::new("test", validator.clone())
+                .complete(0)
+                .unwrap_err();
+            let enc_query = NewQuery::new("test", validator).complete(1).unwrap();
+            assert!(Query::new(enc_query.clone(), 0).is_err());
+            assert!(Query::new(enc_query, 1).is_ok());
+        }
+    }
+
+    fn post_schema() -> Schema {
+        use crate::schema::SchemaBuilder;
+        let tags = ArrayValidator::new()
+            .items(StrValidator::new().query(true).build())
+            .array(true)
+            .build();
+        let post = MapValidator::new()
+            .req_add("author", StrValidator::new().query(true).build())
+            .opt_add("score", IntValidator::new().ord(true).build())
+            .opt_add(
+                "meta",
+                MapValidator::new()
+                    .opt_add("tags", tags)
+                    .map_ok(true)
+                    .build(),
+            )
+            .map_ok(true)
+            .build();
+        let schema = SchemaBuilder::new(Validator::Null)
+            .entry_add("post", post, None)
+            .build()
+            .unwrap();
+        Schema::from_doc(&schema).unwrap()
+    }
+
+    #[test]
+    fn builder_matches_hand_built() {
+        let schema = post_schema();
+        let built = QueryBuilder::new("post")
+            .schema(&schema)
+            .where_int("score", |i| i.min(5))
+            .where_str("author", |s| s.in_add("alice"))
+            .where_str("meta.tags[1]", |s| s.in_add("rust"))
+            .build()
+            .unwrap();
+        let by_hand = MapValidator::new()
+            .req_add("score", IntValidator::new().min(5).build())
+            .req_add("author", StrValidator::new().in_add("alice").build())
+            .req_add(
+                "meta",
+                MapValidator::new()
+                    .req_add(
+                        "tags",
+                        ArrayValidator::new()
+                            .prefix_add(Validator::Any)
+                            .prefix_add(StrValidator::new().in_add("rust").build())
+                            .build(),
+                    )
+                    .build(),
+            )
+            .build();
+        assert_eq!(built.validator(), &by_hand);
+        let by_hand = NewQuery::new("post", by_hand);
+        assert_eq!(
+            schema.encode_query(built).unwrap(),
+            schema.encode_query(by_hand).unwrap()
+        );
+
+        // Repeated paths are combined
+        let built = QueryBuilder::new("post")
+            .where_int("score", |i| i.min(5))
+            .where_int("score", |i| i.max(10))
+            .build()
+            .unwrap();
+        let by_hand = MapValidator::new()
+            .req_add(
+                "score",
+                AllValidator::new()
+                    .push(IntValidator::new().min(5).build())
+                    .push(IntValidator::new().max(10).build())
+                    .build(),
+            )
+            .build();
+        assert_eq!(built.validator(), &by_hand);
+    }
+
+    #[test]
+    fn builder_checks_schema() {
+        let schema = post_schema();
+        let message = |builder: QueryBuilder| match builder.build() {
+            Err(Error::FailValidate(err)) => err.message,
+            other => panic!("expected a validation failure, got {:?}", other),
+        };
+        let builder = || QueryBuilder::new("post").schema(&schema);
+        assert_eq!(
+            message(builder().where_str("author", |s| s.max_len(5))),
+            "length queries aren't allowed at `author`"
+        );
+        assert_eq!(
+            message(builder().where_int("score", |i| i.in_add(5))),
+            "`in` and `nin` list queries aren't allowed at `score`"
+        );
+        let msg = message(builder().where_int("likes", |i| i.min(5)));
+        assert!(msg.contains("isn't a field"), "{}", msg);
+
+        // Without a schema, only the encoding is rejected
+        let query = QueryBuilder::new("post")
+            .where_str("author", |s| s.max_len(5))
+            .build()
+            .unwrap();
+        assert!(schema.encode_query(query).is_err());
+
+        // Paths must parse and agree with each other
+        assert!(QueryBuilder::new("post")
+            .where_int("score[", |i| i)
+            .build()
+            .is_err());
+        assert!(QueryBuilder::new("post")
+            .where_int("meta.tags", |i| i)
+            .where_int("meta.tags[0]", |i| i)
+            .build()
+            .is_err());
+    }
 }
@@ -5,18 +5,23 @@
 //! be easily built from scratch using a [`SchemaBuilder`].
 //!
 use std::{
-    collections::BTreeMap,
+    collections::{BTreeMap, BTreeSet},
     convert::{TryFrom, TryInto},
 };
 
 use crate::document::*;
 use crate::entry::*;
 pub use compress::*;
+use de::FogDeserializer;
 use element::Parser;
-use query::{NewQuery, Query};
+use query::{NewQuery, Query, QueryLimits, QueryOrder};
+use ser::FogSerializer;
 
-use crate::error::{Error, Result};
-use crate::validator::{Checklist, DataChecklist, Validator};
+use crate::error::{Error, Result, ValidateCode, ValidateError};
+use crate::validator::{
+    check_type_cycles, Checklist, DataChecklist, FieldStripper, HashValidator, MapValidator,
+    PrettyPrinter, QueryCaps, SimplifyContext, Validator,
+};
 use crate::*;
 use serde::{Deserialize, Serialize};
 
@@ -39,16 +44,27 @@ fn u8_is_zero(v: &u8) -> bool {
     *v == 0
 }
 
+#[inline]
+fn is_false(v: &bool) -> bool {
+    !v
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(deny_unknown_fields)]
 struct InnerSchema {
     doc: Validator, // required
+    #[serde(skip_serializing_if = "is_false", default)]
+    allow_unknown_schemas: bool,
     #[serde(skip_serializing_if = "String::is_empty", default)]
     description: String,
     #[serde(skip_serializing_if = "compress_is_default", default)]
     doc_compress: Compress,
     #[serde(skip_serializing_if = "BTreeMap::is_empty", default)]
     entries: BTreeMap<String, EntrySchema>,
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    immutable_fields: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    max_doc_size: Option<usize>,
     #[serde(skip_serializing_if = "String::is_empty", default)]
     name: String,
     #[serde(skip_serializing_if = "BTreeMap::is_empty", default)]
@@ -59,12 +75,241 @@ struct InnerSchema {
     max_regex: u8,
 }
 
+impl InnerSchema {
+    /// Deserialize a schema document, removing any fields that aren't recognized first. Returns
+    /// the schema and the paths of the removed fields.
+    fn deserialize_lenient(doc: &Document) -> Result<(Self, Vec<ValuePath>)> {
+        let mut value: Value = doc.deserialize()?;
+        let mut stripper = FieldStripper::new();
+        stripper.strip_struct::<InnerSchema>(&mut value);
+        stripper.key(&mut value, "doc", FieldStripper::validator);
+        stripper.key(&mut value, "types", |s, v| {
+            s.items(v, FieldStripper::validator)
+        });
+        stripper.key(&mut value, "entries", |s, v| {
+            s.items(v, |s, entry| {
+                s.strip_struct::<EntrySchema>(entry);
+                s.key(entry, "entry", FieldStripper::validator);
+            })
+        });
+
+        let mut ser = FogSerializer::default();
+        value.serialize(&mut ser)?;
+        let buf = ser.finish();
+        let mut de = FogDeserializer::new(&buf);
+        let inner = InnerSchema::deserialize(&mut de)?;
+        Ok((inner, stripper.finish()))
+    }
+
+    /// Build the `in`/`nin` lookup indexes of every validator in the schema.
+    fn build_index(&mut self) {
+        self.doc.build_index();
+        self.entries
+            .values_mut()
+            .map(|e| &mut e.entry)
+            .chain(self.types.values_mut())
+            .for_each(|v| v.build_index())
+    }
+
+    /// Check the configuration of every validator in the schema.
+    fn check_build(&self) -> Result<()> {
+        for field in self.immutable_fields.iter() {
+            ValuePath::parse(field)?;
+        }
+        self.validators().try_for_each(|v| v.check_build())?;
+        let mut missing = None;
+        self.validators().for_each(|v| {
+            v.for_each_ref(&mut |name| {
+                if missing.is_none() && !self.types.contains_key(name) {
+                    missing = Some(name);
+                }
+            })
+        });
+        if let Some(name) = missing {
+            return Err(Error::validate(
+                ValidateCode::BadValidator,
+                format!("validator Ref({}) not in list of types", name),
+            ));
+        }
+        check_type_cycles(&self.types)
+    }
+
+    /// Add externally supplied named types. Fails if one is already defined differently.
+    fn add_types(&mut self, types: BTreeMap<String, Validator>) -> Result<()> {
+        for (name, validator) in types {
+            match self.types.get(&name) {
+                Some(existing) if *existing != validator => {
+                    return Err(Error::validate(
+                        ValidateCode::BadValidator,
+                        format!(
+                            "named type {:?} conflicts with a supplied type of the same name",
+                            name
+                        ),
+                    ));
+                }
+                Some(_) => (),
+                None => {
+                    self.types.insert(name, validator);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Find every named type reachable from the document validator or an entry validator.
+    fn used_types(&self) -> BTreeSet<&str> {
+        let mut used = BTreeSet::new();
+        let mut pending: Vec<&str> = Vec::new();
+        std::iter::once(&self.doc)
+            .chain(self.entries.values().map(|e| &e.entry))
+            .for_each(|v| v.for_each_ref(&mut |name| pending.push(name)));
+        while let Some(name) = pending.pop() {
+            if !used.insert(name) {
+                continue;
+            }
+            if let Some(validator) = self.types.get(name) {
+                validator.for_each_ref(&mut |name| pending.push(name));
+            }
+        }
+        used
+    }
+
+    /// Iterate over every top-level validator: the document validator, each entry validator, and
+    /// each named type.
+    fn validators(&self) -> impl Iterator<Item = &Validator> {
+        std::iter::once(&self.doc)
+            .chain(self.entries.values().map(|e| &e.entry))
+            .chain(self.types.values())
+    }
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(deny_unknown_fields)]
 struct EntrySchema {
     entry: Validator, // required
     #[serde(skip_serializing_if = "compress_is_default", default)]
     compress: Compress,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    ttl: Option<u64>,
+    #[serde(skip_serializing_if = "std::ops::Not::not", default)]
+    signer_ok: bool,
+}
+
+impl EntrySchema {
+    /// Check that an entry hasn't expired, if this entry type has a time-to-live. Entries expire
+    /// `ttl` seconds after their `created_at` field, or at their `expires_at` field, whichever
+    /// comes first. Entries without either timestamp field never expire.
+    fn check_expiry(&self, data: &[u8], now: Option<Timestamp>) -> Result<()> {
+        let ttl = match self.ttl {
+            Some(ttl) => i64::try_from(ttl).unwrap_or(i64::MAX),
+            None => return Ok(()),
+        };
+        #[derive(Deserialize)]
+        struct Lifetime {
+            created_at: Option<Timestamp>,
+            expires_at: Option<Timestamp>,
+        }
+        // Entries that aren't maps, or whose fields aren't timestamps, have no lifetime
+        let lifetime = match Lifetime::deserialize(&mut FogDeserializer::new(data)) {
+            Ok(lifetime) => lifetime,
+            Err(_) => return Ok(()),
+        };
+        let created_at = lifetime
+            .created_at
+            .filter(|t| t.timestamp_utc().checked_add(ttl).is_some())
+            .map(|t| t + ttl);
+        let expires_at = lifetime.expires_at;
+        let deadline = match (created_at, expires_at) {
+            (Some(a), Some(b)) => a.min(b),
+            (Some(t), None) | (None, Some(t)) => t,
+            (None, None) => return Ok(()),
+        };
+        let now = now.or_else(Timestamp::now).ok_or_else(|| {
+            Error::validate(
+                ValidateCode::NoTime,
+                "no current time available to check entry expiry",
+            )
+        })?;
+        if now > deadline {
+            Err(Error::validate(
+                ValidateCode::Expired,
+                format!("entry expired at {}", deadline),
+            ))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// How [`Schema::from_doc_with_mode`] handles fields it doesn't recognize.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SchemaParseMode {
+    /// Fail to load the schema if the schema or any of its validators have a field that isn't
+    /// recognized.
+    #[default]
+    Strict,
+    /// Skip over unrecognized fields in the schema and its validators. This lets a schema made
+    /// with a newer version of fog-pack be loaded, as long as it only adds new fields. Validation
+    /// then ignores whatever the unrecognized fields would have checked.
+    IgnoreUnknown,
+}
+
+/// Options that change how a [`Schema`] validates documents and entries.
+///
+/// The default options are used by the validation methods that don't take options.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ValidateOptions {
+    /// The reference time for validators with bounds relative to the current time. If `None`,
+    /// the system time at validation is used.
+    pub now: Option<Timestamp>,
+    /// If not empty, the Document or Entry must be signed by one of these Identities.
+    pub signers: Vec<Identity>,
+    /// A lower limit on nesting depth than [`MAX_DEPTH`][crate::MAX_DEPTH]. If `None`, only the
+    /// usual limit applies.
+    pub max_depth: Option<usize>,
+}
+
+impl ValidateOptions {
+    /// Make a new set of options with the default configuration.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the reference time used for relative time bounds. Useful for reproducible validation,
+    /// or for re-validating old data as of when it was received.
+    pub fn now(mut self, now: Timestamp) -> Self {
+        self.now = Some(now);
+        self
+    }
+
+    /// Only accept data signed by one of the given Identities.
+    pub fn signers(mut self, signers: Vec<Identity>) -> Self {
+        self.signers = signers;
+        self
+    }
+
+    /// Set a lower maximum nesting depth for the data. Limits above
+    /// [`MAX_DEPTH`][crate::MAX_DEPTH] have no effect.
+    pub fn max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = Some(max_depth);
+        self
+    }
+
+    /// Make a parser for validating data with these options, after checking that the data's
+    /// signer is allowed.
+    fn parser<'a>(&self, data: &'a [u8], signer: Option<&'a Identity>) -> Result<Parser<'a>> {
+        let allowed = matches!(signer, Some(signer) if self.signers.contains(signer));
+        if !self.signers.is_empty() && !allowed {
+            return Err(Error::validate(
+                ValidateCode::NotSigner,
+                "data isn't signed by any of the allowed signers",
+            ));
+        }
+        Ok(Parser::new(data)
+            .with_now(self.now)
+            .with_max_depth(self.max_depth)
+            .with_signer(signer))
+    }
 }
 
 /// Validation for documents without a schema.
@@ -127,8 +372,35 @@ impl NoSchema {
         Ok((hash, compress_doc(doc, &compression)))
     }
 
-    /// Decode a document that doesn't have a schema.
-    pub fn decode_doc(doc: Vec<u8>) -> Result<Document> {
+    /// Re-encode a validated [`Document`] using dictionary compression, returning the resulting
+    /// Document's hash and fully encoded format. `dict` must be a dictionary setting, like one
+    /// made with [`Compress::new_zstd_dict`]. If the document requested no compression, it is
+    /// left uncompressed.
+    pub fn compress_doc_with_dict(doc: Document, dict: &Compress) -> Result<(Hash, Vec<u8>)> {
+        if !matches!(dict, Compress::Dict(_)) {
+            return Err(Error::BadEncode(
+                "compress_doc_with_dict requires a dictionary compression setting".into(),
+            ));
+        }
+        // Check that this document doesn't have a schema
+        if let Some(schema) = doc.schema_hash() {
+            return Err(Error::SchemaMismatch {
+                actual: Some(schema.to_owned()),
+                expected: None,
+            });
+        }
+
+        let (hash, doc, compression) = doc.complete();
+        let doc = match compression {
+            Some(None) => doc,
+            _ => compress_doc(doc, dict),
+        };
+        Ok((hash, doc))
+    }
+
+    /// Decode a document that doesn't have a schema, and that may have been compressed with
+    /// [`compress_doc_with_dict`][Self::compress_doc_with_dict] using the same dictionary.
+    pub fn decompress_doc_with_dict(doc: Vec<u8>, dict: &Compress) -> Result<Document> {
         // Check for hash
         let split = SplitDoc::split(&doc)?;
         if !split.hash_raw.is_empty() {
@@ -138,16 +410,22 @@ impl NoSchema {
             });
         }
 
-        // Decompress
-        let doc = Document::new(decompress_doc(doc, &Compress::None)?)?;
+        let doc = decompress_doc(doc, dict)?;
+        decode_any_doc(doc)
+    }
 
-        // Validate
-        let types = BTreeMap::new();
-        let parser = Parser::new(doc.data());
-        let (parser, _) = Validator::Any.validate(&types, parser, None)?;
-        parser.finish()?;
+    /// Decode a document that doesn't have a schema.
+    pub fn decode_doc(doc: Vec<u8>) -> Result<Document> {
+        // Check for hash
+        let split = SplitDoc::split(&doc)?;
+        if !split.hash_raw.is_empty() {
+            return Err(Error::SchemaMismatch {
+                actual: split.hash_raw.try_into().ok(),
+                expected: None,
+            });
+        }
 
-        Ok(doc)
+        decode_any_doc(doc)
     }
 
     /// Decode a Document, skipping any checks of the data. This should only be run when the raw
@@ -169,6 +447,20 @@ impl NoSchema {
     }
 }
 
+/// Decompress a document and check that it is valid fog-pack, ignoring any attached schema.
+fn decode_any_doc(doc: Vec<u8>) -> Result<Document> {
+    // Decompress
+    let doc = Document::new(decompress_doc(doc, &Compress::None)?)?;
+
+    // Validate
+    let types = BTreeMap::new();
+    let parser = Parser::new(doc.data());
+    let (parser, _) = Validator::Any.validate(&types, parser, None)?;
+    parser.finish()?;
+
+    Ok(doc)
+}
+
 fn compress_doc(doc: Vec<u8>, compression: &Compress) -> Vec<u8> {
     // Skip if we aren't compressing
     if let Compress::None = compression {
@@ -287,6 +579,10 @@ fn decompress_entry(compress: Vec<u8>, compression: &Compress) -> Result<Vec<u8>
 #[derive(Clone, Debug)]
 pub struct SchemaBuilder {
     inner: InnerSchema,
+    entry_ttls: BTreeMap<String, u64>,
+    entry_signer_ok: BTreeMap<String, bool>,
+    prune_unused: bool,
+    simplify: bool,
 }
 
 impl SchemaBuilder {
@@ -296,17 +592,33 @@ impl SchemaBuilder {
         Self {
             inner: InnerSchema {
                 doc,
+                allow_unknown_schemas: false,
                 description: String::default(),
                 doc_compress: Compress::default(),
                 entries: BTreeMap::new(),
+                immutable_fields: Vec::new(),
+                max_doc_size: None,
                 name: String::default(),
                 types: BTreeMap::new(),
                 version: Integer::default(),
                 max_regex: 0,
             },
+            entry_ttls: BTreeMap::new(),
+            entry_signer_ok: BTreeMap::new(),
+            prune_unused: false,
+            simplify: false,
         }
     }
 
+    /// Set whether documents with an unknown schema should be accepted when decoding. If `true`,
+    /// [`Schema::decode_doc`] and [`Schema::trusted_decode_doc`] will decode documents that
+    /// use some other schema the same way [`NoSchema`] decodes schemaless documents, checking only
+    /// that they are valid fog-pack. Documents without any schema are still rejected.
+    pub fn allow_unknown_schemas(mut self, allow: bool) -> Self {
+        self.inner.allow_unknown_schemas = allow;
+        self
+    }
+
     /// Set the schema description. This is only used for documentation purposes.
     pub fn description(mut self, description: &str) -> Self {
         self.inner.description = description.to_owned();
@@ -334,36 +646,189 @@ impl SchemaBuilder {
             EntrySchema {
                 entry: validator,
                 compress,
+                ttl: None,
+                signer_ok: false,
             },
         );
         self
     }
 
+    /// Add a new entry type to the schema whose entries are each a single Hash, linking to a
+    /// Document that uses the `target` schema. This is a shortcut for [`entry_add`][Self::entry_add]
+    /// with a [`HashValidator`][crate::validator::HashValidator] that has `target` as its only
+    /// schema, so the linked Document must be checked through the
+    /// [`DataChecklist`][crate::validator::DataChecklist] returned when encoding or decoding the
+    /// entry. See [`Schema::link_target`].
+    pub fn entry_add_with_link(
+        self,
+        entry: &str,
+        target: &Hash,
+        compress: Option<Compress>,
+    ) -> Self {
+        let validator = HashValidator::new().schema_add(target.clone()).build();
+        self.entry_add(entry, validator, compress)
+    }
+
+    /// Mark an entry type as expiring `ttl_seconds` after the entry's `created_at` timestamp
+    /// field, or at its `expires_at` timestamp field if sooner. Expired entries fail validation,
+    /// using the current time from [`ValidateOptions`]. Building fails if the entry type wasn't
+    /// added with [`entry_add`][Self::entry_add].
+    pub fn entry_expires(mut self, entry: &str, ttl_seconds: u64) -> Self {
+        self.entry_ttls.insert(entry.to_owned(), ttl_seconds);
+        self
+    }
+
+    /// Set whether queries on an entry type may require the entries to be signed by particular
+    /// Identities, using [`NewQuery::signed_by`]. Building fails if the entry type wasn't added
+    /// with [`entry_add`][Self::entry_add].
+    pub fn entry_signer_ok(mut self, entry: &str, signer_ok: bool) -> Self {
+        self.entry_signer_ok.insert(entry.to_owned(), signer_ok);
+        self
+    }
+
+    /// Mark a document field as immutable. [`Schema::validate_update`] will reject any update that
+    /// changes the value found at this field, which is written as a
+    /// [`ValuePath`][crate::types::ValuePath] (e.g. `created_at` or `meta.owner`).
+    pub fn immutable_field(mut self, field: &str) -> Self {
+        self.inner.immutable_fields.push(field.to_owned());
+        self
+    }
+
+    /// Set the maximum encoded size of documents adhering to this schema. This can only tighten
+    /// the limit; documents can never be larger than [`MAX_DOC_SIZE`][crate::MAX_DOC_SIZE].
+    pub fn max_doc_size(mut self, max_doc_size: usize) -> Self {
+        self.inner.max_doc_size = Some(max_doc_size);
+        self
+    }
+
+    /// Set the maximum number of regular expressions a query may contain. Regexes are expensive
+    /// to check, so queries with any at all are rejected by default. Each query validator must
+    /// also permit regexes, through the `regex` or `match_keys` flags.
+    pub fn max_regex(mut self, max_regex: u8) -> Self {
+        self.inner.max_regex = max_regex;
+        self
+    }
+
     /// Set the schema name. This is only used for documentation purposes.
     pub fn name(mut self, name: &str) -> Self {
         self.inner.name = name.to_owned();
         self
     }
 
-    /// Add a new stored type to the schema.
+    /// Add a new stored type to the schema, which can be used anywhere in the schema with
+    /// [`Validator::Ref`]. A type may refer to itself, as long as some validator between each
+    /// reference reads a value: a tree type containing an array of itself is fine, and is bounded
+    /// by the [maximum nesting depth][crate::MAX_DEPTH]. Types that can reach themselves through
+    /// only Ref, Multi, or All validators would loop forever, so [`build`][Self::build] rejects
+    /// them.
     pub fn type_add(mut self, type_ref: &str, validator: Validator) -> Self {
         self.inner.types.insert(type_ref.to_owned(), validator);
         self
     }
 
+    /// Set whether named types that can't be reached from the document validator or any entry
+    /// validator should be dropped when building. See [`unused_types`][Self::unused_types].
+    pub fn prune_unused(mut self, prune: bool) -> Self {
+        self.prune_unused = prune;
+        self
+    }
+
+    /// Set whether every validator in the schema should be simplified when building, which can
+    /// shrink the schema document. Simplification never changes what passes or fails validation;
+    /// see [`Validator::simplify`] for the rewrites made.
+    pub fn simplify(mut self, simplify: bool) -> Self {
+        self.simplify = simplify;
+        self
+    }
+
     /// Set the schema version. This is only used for documentation purposes.
     pub fn version<T: Into<Integer>>(mut self, version: T) -> Self {
         self.inner.version = version.into();
         self
     }
 
+    /// Get the names of all named types that can't be reached from the document validator or any
+    /// entry validator, whether directly or through other named types.
+    pub fn unused_types(&self) -> Vec<String> {
+        let used = self.inner.used_types();
+        self.inner
+            .types
+            .keys()
+            .filter(|name| !used.contains(name.as_str()))
+            .cloned()
+            .collect()
+    }
+
     /// Build the Schema, compiling the result into a Document
     pub fn build(self) -> Result<Document> {
+        self.build_with_types(&BTreeMap::new())
+    }
+
+    /// Build the Schema, compiling the result into a Document, for use with
+    /// [`Schema::from_doc_with_types`]. Refs may refer to the named types in `types` as well as
+    /// the ones added with [`type_add`][Self::type_add]. The supplied types are used for checking
+    /// but aren't included in the resulting Document.
+    pub fn build_with_types(mut self, types: &BTreeMap<String, Validator>) -> Result<Document> {
+        fn entry<'a>(
+            entries: &'a mut BTreeMap<String, EntrySchema>,
+            name: &str,
+        ) -> Result<&'a mut EntrySchema> {
+            entries.get_mut(name).ok_or_else(|| {
+                Error::validate(
+                    ValidateCode::UnknownEntry,
+                    format!("configured entry key {:?} is not in schema", name),
+                )
+            })
+        }
+        for (name, ttl) in self.entry_ttls.iter() {
+            entry(&mut self.inner.entries, name)?.ttl = Some(*ttl);
+        }
+        for (name, signer_ok) in self.entry_signer_ok.iter() {
+            entry(&mut self.inner.entries, name)?.signer_ok = *signer_ok;
+        }
+        if self.prune_unused {
+            for name in self.unused_types() {
+                self.inner.types.remove(&name);
+            }
+        }
+        if self.simplify {
+            let mut merged = self.inner.clone();
+            merged.add_types(types.clone())?;
+            let types = merged.types;
+            self.inner.doc.simplify_in(&types, SimplifyContext::Other);
+            for entry in self.inner.entries.values_mut() {
+                entry.entry.simplify_in(&types, SimplifyContext::Other);
+            }
+            for validator in self.inner.types.values_mut() {
+                validator.simplify_in(&types, SimplifyContext::Named);
+            }
+        }
+        let mut check = self.inner.clone();
+        check.add_types(types.clone())?;
+        check.check_build()?;
         let doc = NewDocument::new(self.inner, None)?;
         NoSchema::validate_new_doc(doc)
     }
 }
 
+/// A location inside an entry type that queries may constrain, as listed by
+/// [`Schema::queryable_fields`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct QueryableField {
+    /// The entry type this field is in.
+    pub entry: String,
+    /// The path to the field within the entry, in the [`ValuePath`] syntax. An empty path is the
+    /// entry itself. `[*]` stands for any array index checked by the array's `items` validator,
+    /// and `*` stands for any map key checked by the map's `values` validator. Map keys are
+    /// written as-is, so a key containing `.` or `[`, or a key of `*`, gives an ambiguous path.
+    pub path: String,
+    /// The constraints a query may use at this field.
+    pub caps: QueryCaps,
+    /// Whether queries on this entry type may also limit which Identities signed the entry. See
+    /// [`SchemaBuilder::entry_signer_ok`].
+    pub signer: bool,
+}
+
 /// A Schema, which can be used to encode/decode a document or entry, while verifying its
 /// contents.
 ///
@@ -377,14 +842,55 @@ impl SchemaBuilder {
 pub struct Schema {
     hash: Hash,
     inner: InnerSchema,
+    unknown_fields: Vec<ValuePath>,
 }
 
 impl Schema {
     /// Attempt to create a schema from a given document. Fails if the document isn't a schema.
     pub fn from_doc(doc: &Document) -> Result<Self> {
-        let inner = doc.deserialize()?;
+        Self::from_doc_with_mode(doc, SchemaParseMode::Strict)
+    }
+
+    /// Attempt to create a schema from a given document, using `mode` to decide how to handle
+    /// unrecognized fields. Fails if the document isn't a schema.
+    ///
+    /// With [`SchemaParseMode::IgnoreUnknown`], any unrecognized fields are skipped, and their
+    /// locations within the schema document can be found with [`Schema::unknown_fields`]. The
+    /// schema's hash is always the hash of the original document.
+    pub fn from_doc_with_mode(doc: &Document, mode: SchemaParseMode) -> Result<Self> {
+        let (mut inner, unknown_fields) = match mode {
+            SchemaParseMode::Strict => (doc.deserialize::<InnerSchema>()?, Vec::new()),
+            SchemaParseMode::IgnoreUnknown => InnerSchema::deserialize_lenient(doc)?,
+        };
+        inner.check_build()?;
+        inner.build_index();
         let hash = doc.hash();
-        Ok(Self { hash, inner })
+        Ok(Self {
+            hash,
+            inner,
+            unknown_fields,
+        })
+    }
+
+    /// Attempt to create a schema from a given document, with the named types seeded from
+    /// `types`. Refs in the schema may then refer to the supplied types without the schema
+    /// document defining them, so a "base" set of shared types can be used by many schemas. Fails
+    /// if the document isn't a schema, or if it defines a named type differently from the one
+    /// supplied.
+    ///
+    /// The supplied types aren't part of the schema document, so they don't affect the schema's
+    /// hash. Anyone validating against the schema must be using the same types.
+    pub fn from_doc_with_types(doc: &Document, types: BTreeMap<String, Validator>) -> Result<Self> {
+        let mut inner: InnerSchema = doc.deserialize()?;
+        inner.add_types(types)?;
+        inner.check_build()?;
+        inner.build_index();
+        let hash = doc.hash();
+        Ok(Self {
+            hash,
+            inner,
+            unknown_fields: Vec::new(),
+        })
     }
 
     /// Get the hash of this schema.
@@ -392,10 +898,193 @@ impl Schema {
         &self.hash
     }
 
+    /// Get the locations of any fields that were skipped over when loading the schema, as paths
+    /// within the schema document. Always empty unless the schema was loaded with
+    /// [`SchemaParseMode::IgnoreUnknown`].
+    pub fn unknown_fields(&self) -> &[ValuePath] {
+        &self.unknown_fields
+    }
+
+    /// Iterate over the schema's named types, which are used through [`Validator::Ref`].
+    pub fn named_types(&self) -> impl Iterator<Item = (&str, &Validator)> {
+        self.inner.types.iter().map(|(name, v)| (name.as_str(), v))
+    }
+
+    /// Render the schema as indented, human-readable text, in a style similar to TypeScript type
+    /// declarations. The document validator, each entry type, and each named type are written
+    /// out in turn, with their comments on the lines above.
+    ///
+    /// Map fields are written one per line, with optional fields marked by `?` and any default
+    /// value after an `=`. The output is for display only, and can't be parsed back into a schema.
+    pub fn pretty_print(&self) -> String {
+        let inner = &self.inner;
+        let mut printer = PrettyPrinter::new();
+        printer.comment(&inner.description);
+        if !inner.name.is_empty() || !int_is_zero(&inner.version) {
+            let mut header = String::from("schema");
+            if !inner.name.is_empty() {
+                header.push_str(&format!(" {:?}", inner.name));
+            }
+            if !int_is_zero(&inner.version) {
+                header.push_str(&format!(" v{}", inner.version));
+            }
+            printer.line(&header);
+            printer.line("");
+        }
+        printer.item("doc", ": ", &inner.doc);
+        for (key, entry) in inner.entries.iter() {
+            printer.line("");
+            printer.item(&format!("entry {:?}", key), ": ", &entry.entry);
+        }
+        for (name, validator) in inner.types.iter() {
+            printer.line("");
+            printer.item(&format!("type {}", name), " = ", validator);
+        }
+        printer.finish()
+    }
+
+    /// Summarize which constraints a query for the entry type `field` may use at `path` inside
+    /// the entry. Returns `None` if there is no such entry type, or if the path doesn't lead to a
+    /// validator. See [`Validator::query_capabilities_at`] for how the path is followed.
+    pub fn query_capabilities(&self, field: &str, path: &ValuePath) -> Option<QueryCaps> {
+        self.inner
+            .entries
+            .get(field)?
+            .entry
+            .query_capabilities_at(&self.inner.types, path)
+    }
+
+    /// List every field that queries may constrain, across all entry types. The list is sorted
+    /// by entry type, then by path, and only includes fields where a query validator is allowed.
+    /// See [`Validator::queryable_paths`] for how fields are found.
+    pub fn queryable_fields(&self) -> Vec<QueryableField> {
+        let types = &self.inner.types;
+        self.inner
+            .entries
+            .iter()
+            .flat_map(|(entry, schema)| {
+                schema
+                    .entry
+                    .queryable_paths(types)
+                    .into_iter()
+                    .map(move |(path, caps)| QueryableField {
+                        entry: entry.clone(),
+                        path,
+                        caps,
+                        signer: schema.signer_ok,
+                    })
+            })
+            .collect()
+    }
+
+    /// Get the time-to-live, in seconds, of an entry type. Returns `None` if the entry doesn't
+    /// expire or isn't in the schema. See [`SchemaBuilder::entry_expires`].
+    pub fn entry_ttl(&self, entry: &str) -> Option<u64> {
+        self.inner.entries.get(entry)?.ttl
+    }
+
+    /// Get the schema that Documents linked to by an entry type must use, if the entry type is a
+    /// single Hash limited to exactly one schema, as made by
+    /// [`SchemaBuilder::entry_add_with_link`]. Returns `None` for any other entry type, or if the
+    /// entry isn't in the schema.
+    pub fn link_target(&self, entry: &str) -> Option<&Hash> {
+        match &self.inner.entries.get(entry)?.entry {
+            Validator::Hash(validator) => match validator.schema.as_slice() {
+                [Some(target)] => Some(target),
+                [None] => Some(&self.hash),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// Check if this schema accepts documents with an unknown schema when decoding. See
+    /// [`SchemaBuilder::allow_unknown_schemas`].
+    pub fn allows_unknown_schemas(&self) -> bool {
+        self.inner.allow_unknown_schemas
+    }
+
+    /// Get the document validator's map validator, if it is a `Map` (or a `Ref` to one).
+    fn doc_map(&self) -> Option<&MapValidator> {
+        let doc = match &self.inner.doc {
+            Validator::Ref(name) => self.inner.types.get(name),
+            doc => Some(doc),
+        };
+        match doc {
+            Some(Validator::Map(map)) => Some(map),
+            _ => None,
+        }
+    }
+
+    /// Iterate over the fields of the document validator that have a default value, as given by
+    /// [`Validator::apply_default`]. Only the top level of the document is checked, and only if
+    /// the document validator is a `Map` (or a `Ref` to one).
+    pub fn doc_default_fields(&self) -> impl Iterator<Item = (&str, Value)> {
+        self.doc_map()
+            .into_iter()
+            .flat_map(|map| map.req.iter().chain(map.opt.iter()))
+            .filter_map(|(key, v)| v.apply_default().map(|v| (key.as_str(), v)))
+    }
+
+    /// Iterate over the required fields of the document validator, in key order. These are the
+    /// keys in the `req` list of the document's `Map` validator (or the validator a `Ref` points
+    /// to). Nothing is returned if the document validator isn't a map.
+    pub fn required_doc_fields(&self) -> impl Iterator<Item = &str> {
+        self.doc_map()
+            .into_iter()
+            .flat_map(|map| map.req.keys())
+            .map(|key| key.as_str())
+    }
+
+    /// Iterate over the optional fields of the document validator, in key order. These are the
+    /// keys in the `opt` list of the document's `Map` validator (or the validator a `Ref` points
+    /// to). Nothing is returned if the document validator isn't a map.
+    pub fn optional_doc_fields(&self) -> impl Iterator<Item = &str> {
+        self.doc_map()
+            .into_iter()
+            .flat_map(|map| map.opt.keys())
+            .map(|key| key.as_str())
+    }
+
+    /// Fill in any missing fields of a document's value that have a default in this schema's
+    /// document validator. A field is filled if it is a required or optional field of a map, its
+    /// validator is a scalar validator with a `default` set, and it isn't already present. Nested
+    /// maps are filled if they're present, but maps inside arrays are not. Validation itself
+    /// never fills in defaults, so this must be called before creating the document.
+    pub fn fill_defaults(&self, value: &mut Value) {
+        self.inner.doc.fill_defaults(&self.inner.types, value)
+    }
+
+    /// Get the maximum encoded size allowed for documents adhering to this schema.
+    pub fn max_doc_size(&self) -> usize {
+        self.inner
+            .max_doc_size
+            .map_or(MAX_DOC_SIZE, |max| max.min(MAX_DOC_SIZE))
+    }
+
+    fn check_doc_size(&self, size: usize) -> Result<()> {
+        let max = self.max_doc_size();
+        if size > max {
+            Err(Error::LengthTooLong { max, actual: size })
+        } else {
+            Ok(())
+        }
+    }
+
     /// Validate a [`NewDocument`], turning it into a [`Document`]. Fails if the document doesn't
     /// use this schema, or if it doesn't meet this schema's
     /// requirements.
     pub fn validate_new_doc(&self, doc: NewDocument) -> Result<Document> {
+        self.validate_new_doc_with_options(doc, &ValidateOptions::default())
+    }
+
+    /// Validate a [`NewDocument`] using the given [`ValidateOptions`], turning it into a
+    /// [`Document`].
+    pub fn validate_new_doc_with_options(
+        &self,
+        doc: NewDocument,
+        options: &ValidateOptions,
+    ) -> Result<Document> {
         // Check that the document uses this schema
         match doc.schema_hash() {
             Some(hash) if hash == &self.hash => (),
@@ -407,14 +1096,124 @@ impl Schema {
             }
         }
 
+        self.check_doc_size(doc.size())?;
+
         // Validate the data
-        let parser = Parser::new(doc.data());
+        let parser = options.parser(doc.data(), doc.signer())?;
         let (parser, _) = self.inner.doc.validate(&self.inner.types, parser, None)?;
         parser.finish()?;
 
         Ok(Document::from_new(doc))
     }
 
+    /// Check that `new` is an allowed update of `old`. Fails if either document doesn't use this
+    /// schema, if `new` doesn't meet this schema's requirements, or if any of the schema's
+    /// immutable fields has a different value in `new` than in `old`. A field that is missing
+    /// from both documents is considered unchanged.
+    ///
+    /// As with [`validate_doc`][Self::validate_doc], the `link` and `schema` requirements of any
+    /// [`HashValidator`] aren't checked, so Documents linked to by `new` aren't verified.
+    pub fn validate_update(&self, old: &Document, new: &Document) -> Result<()> {
+        self.validate_update_with_options(old, new, &ValidateOptions::default())
+    }
+
+    /// Check that `new` is an allowed update of `old`, validating `new` using the given
+    /// [`ValidateOptions`].
+    pub fn validate_update_with_options(
+        &self,
+        old: &Document,
+        new: &Document,
+        options: &ValidateOptions,
+    ) -> Result<()> {
+        for doc in [old, new].iter() {
+            match doc.schema_hash() {
+                Some(hash) if hash == &self.hash => (),
+                actual => {
+                    return Err(Error::SchemaMismatch {
+                        actual: actual.cloned(),
+                        expected: Some(self.hash.clone()),
+                    })
+                }
+            }
+        }
+
+        self.check_doc_size(new.size())?;
+
+        // Validate the new data
+        let parser = options.parser(new.data(), new.signer())?;
+        let (parser, _) = self.inner.doc.validate(&self.inner.types, parser, None)?;
+        parser.finish()?;
+
+        if self.inner.immutable_fields.is_empty() {
+            return Ok(());
+        }
+        let old_val: Value = old.deserialize()?;
+        let new_val: Value = new.deserialize()?;
+        for field in self.inner.immutable_fields.iter() {
+            let path = ValuePath::parse(field)?;
+            // Compare encoded forms so that floating-point fields are compared bit-for-bit
+            let encode = |v: Option<&Value>| -> Result<Option<Vec<u8>>> {
+                v.map(|v| {
+                    let mut ser = FogSerializer::default();
+                    v.serialize(&mut ser)?;
+                    Ok(ser.finish())
+                })
+                .transpose()
+            };
+            if encode(path.get(&old_val))? != encode(path.get(&new_val))? {
+                return Err(Error::validate(
+                    ValidateCode::Immutable,
+                    format!("immutable field \"{}\" was changed", field),
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Validate a batch of [`Document`]s against this schema, returning one result per document,
+    /// in the same order as the input. A document fails if it doesn't use this schema or doesn't
+    /// meet the schema's requirements.
+    ///
+    /// With the `rayon` feature enabled, the documents are validated in parallel.
+    pub fn validate_batch(&self, docs: &[Document]) -> Vec<Result<()>> {
+        #[cfg(feature = "rayon")]
+        {
+            use rayon::prelude::*;
+            docs.par_iter().map(|doc| self.validate_doc(doc)).collect()
+        }
+        #[cfg(not(feature = "rayon"))]
+        {
+            docs.iter().map(|doc| self.validate_doc(doc)).collect()
+        }
+    }
+
+    /// Validate a [`Document`] against this schema. Fails if the document doesn't use this
+    /// schema or doesn't meet the schema's requirements.
+    pub fn validate_doc(&self, doc: &Document) -> Result<()> {
+        self.validate_doc_with_options(doc, &ValidateOptions::default())
+    }
+
+    /// Validate a [`Document`] against this schema using the given [`ValidateOptions`].
+    pub fn validate_doc_with_options(
+        &self,
+        doc: &Document,
+        options: &ValidateOptions,
+    ) -> Result<()> {
+        match doc.schema_hash() {
+            Some(hash) if hash == &self.hash => (),
+            actual => {
+                return Err(Error::SchemaMismatch {
+                    actual: actual.cloned(),
+                    expected: Some(self.hash.clone()),
+                })
+            }
+        }
+        self.check_doc_size(doc.size())?;
+        let parser = options.parser(doc.data(), doc.signer())?;
+        let (parser, _) = self.inner.doc.validate(&self.inner.types, parser, None)?;
+        parser.finish()
+    }
+
     /// Encode a [`Document`], returning the resulting Document's hash and fully encoded format.
     /// Fails if the document doesn't use this schema.
     pub fn encode_doc(&self, doc: Document) -> Result<(Hash, Vec<u8>)> {
@@ -429,6 +1228,8 @@ impl Schema {
             }
         }
 
+        self.check_doc_size(doc.size())?;
+
         // Compress the document
         let (hash, doc, compression) = doc.complete();
         let doc = match compression {
@@ -467,15 +1268,45 @@ impl Schema {
         }
     }
 
-    /// Decode a document that uses this schema.
+    /// Check if a document uses some other schema, and this schema allows decoding it anyway.
+    fn is_allowed_unknown(&self, doc: &[u8]) -> Result<bool> {
+        if !self.inner.allow_unknown_schemas {
+            return Ok(false);
+        }
+        let split = SplitDoc::split(doc)?;
+        if split.hash_raw.is_empty() {
+            return Ok(false);
+        }
+        let schema = Hash::try_from(split.hash_raw)
+            .map_err(|_| Error::BadHeader("Unable to decode schema hash".into()))?;
+        Ok(schema != self.hash)
+    }
+
+    /// Decode a document that uses this schema. If this schema
+    /// [allows unknown schemas][Self::allows_unknown_schemas], documents using any other schema are
+    /// only checked for being valid fog-pack.
     pub fn decode_doc(&self, doc: Vec<u8>) -> Result<Document> {
+        self.decode_doc_with_options(doc, &ValidateOptions::default())
+    }
+
+    /// Decode a document that uses this schema, validating it using the given
+    /// [`ValidateOptions`].
+    pub fn decode_doc_with_options(
+        &self,
+        doc: Vec<u8>,
+        options: &ValidateOptions,
+    ) -> Result<Document> {
+        if self.is_allowed_unknown(&doc)? {
+            return decode_any_doc(doc);
+        }
         self.check_schema(&doc)?;
 
         // Decompress
         let doc = Document::new(decompress_doc(doc, &self.inner.doc_compress)?)?;
+        self.check_doc_size(doc.size())?;
 
         // Validate
-        let parser = Parser::new(doc.data());
+        let parser = options.parser(doc.data(), doc.signer())?;
         let (parser, _) = self.inner.doc.validate(&self.inner.types, parser, None)?;
         parser.finish()?;
 
@@ -486,7 +1317,9 @@ impl Schema {
     /// document has definitely been passed through validation before, i.e. if it is stored in a
     /// local database after going through [`encode_doc`][Self::encode_doc].
     pub fn trusted_decode_doc(&self, doc: Vec<u8>) -> Result<Document> {
-        self.check_schema(&doc)?;
+        if !self.is_allowed_unknown(&doc)? {
+            self.check_schema(&doc)?;
+        }
 
         // Decompress
         let doc = Document::new(decompress_doc(doc, &Compress::None)?)?;
@@ -498,10 +1331,23 @@ impl Schema {
     /// The resulting entry is stored in a [`DataChecklist`] that must be iterated over in order to
     /// finish verification and get the resulting data.
     pub fn encode_new_entry(&self, entry: NewEntry) -> Result<DataChecklist<(Hash, Vec<u8>)>> {
+        self.encode_new_entry_with_options(entry, &ValidateOptions::default())
+    }
+
+    /// Encode a [`NewEntry`] using the given [`ValidateOptions`], returning the resulting Entry's
+    /// hash and fully encoded format.
+    pub fn encode_new_entry_with_options(
+        &self,
+        entry: NewEntry,
+        options: &ValidateOptions,
+    ) -> Result<DataChecklist<'_, (Hash, Vec<u8>)>> {
         // Validate the data, getting a checklist of any further validation needed
-        let parser = Parser::new(entry.data());
+        let parser = options.parser(entry.data(), entry.signer())?;
         let entry_schema = self.inner.entries.get(entry.key()).ok_or_else(|| {
-            Error::FailValidate(format!("entry key \"{:?}\" is not in schema", entry.key()))
+            Error::validate(
+                ValidateCode::UnknownEntry,
+                format!("entry key \"{:?}\" is not in schema", entry.key()),
+            )
         })?;
         let checklist = Some(Checklist::new(&self.hash, &self.inner.types));
         let (parser, checklist) =
@@ -509,6 +1355,7 @@ impl Schema {
                 .entry
                 .validate(&self.inner.types, parser, checklist)?;
         parser.finish()?;
+        entry_schema.check_expiry(entry.data(), options.now)?;
 
         // Compress the document
         let (hash, entry, compression) = entry.complete();
@@ -536,9 +1383,12 @@ impl Schema {
     /// finish verification and get the resulting data.
     pub fn encode_entry(&self, entry: Entry) -> Result<DataChecklist<(Hash, Vec<u8>)>> {
         // Validate the data, getting a checklist of any further validation needed
-        let parser = Parser::new(entry.data());
+        let parser = Parser::new(entry.data()).with_signer(entry.signer());
         let entry_schema = self.inner.entries.get(entry.key()).ok_or_else(|| {
-            Error::FailValidate(format!("entry key \"{:?}\" is not in schema", entry.key()))
+            Error::validate(
+                ValidateCode::UnknownEntry,
+                format!("entry key \"{:?}\" is not in schema", entry.key()),
+            )
         })?;
         let checklist = Some(Checklist::new(&self.hash, &self.inner.types));
         let (parser, checklist) =
@@ -546,6 +1396,7 @@ impl Schema {
                 .entry
                 .validate(&self.inner.types, parser, checklist)?;
         parser.finish()?;
+        entry_schema.check_expiry(entry.data(), None)?;
 
         // Compress the document
         let (hash, entry, compression) = entry.complete();
@@ -575,9 +1426,24 @@ impl Schema {
         key: &str,
         parent: &Hash,
     ) -> Result<DataChecklist<Entry>> {
+        self.decode_entry_with_options(entry, key, parent, &ValidateOptions::default())
+    }
+
+    /// Decode an entry, given the key and parent Hash, validating it using the given
+    /// [`ValidateOptions`].
+    pub fn decode_entry_with_options(
+        &self,
+        entry: Vec<u8>,
+        key: &str,
+        parent: &Hash,
+        options: &ValidateOptions,
+    ) -> Result<DataChecklist<'_, Entry>> {
         // Find the entry
         let entry_schema = self.inner.entries.get(key).ok_or_else(|| {
-            Error::FailValidate(format!("entry key \"{:?}\" is not in schema", key))
+            Error::validate(
+                ValidateCode::UnknownEntry,
+                format!("entry key \"{:?}\" is not in schema", key),
+            )
         })?;
 
         // Decompress
@@ -588,13 +1454,14 @@ impl Schema {
         )?;
 
         // Validate
-        let parser = Parser::new(entry.data());
+        let parser = options.parser(entry.data(), entry.signer())?;
         let checklist = Some(Checklist::new(&self.hash, &self.inner.types));
         let (parser, checklist) =
             entry_schema
                 .entry
                 .validate(&self.inner.types, parser, checklist)?;
         parser.finish()?;
+        entry_schema.check_expiry(entry.data(), options.now)?;
 
         Ok(DataChecklist::from_checklist(checklist.unwrap(), entry))
     }
@@ -606,7 +1473,10 @@ impl Schema {
     pub fn trusted_decode_entry(&self, entry: Vec<u8>, key: &str, parent: &Hash) -> Result<Entry> {
         // Find the entry
         let entry_schema = self.inner.entries.get(key).ok_or_else(|| {
-            Error::FailValidate(format!("entry key \"{:?}\" is not in schema", key))
+            Error::validate(
+                ValidateCode::UnknownEntry,
+                format!("entry key \"{:?}\" is not in schema", key),
+            )
         })?;
 
         // Decompress
@@ -619,33 +1489,2070 @@ impl Schema {
     }
 
     pub fn encode_query(&self, query: NewQuery) -> Result<Vec<u8>> {
-        let key = query.key();
-        let entry_schema = self.inner.entries.get(key).ok_or_else(|| {
-            Error::FailValidate(format!("entry key \"{:?}\" is not in schema", key))
-        })?;
-        if entry_schema
-            .entry
-            .query_check(&self.inner.types, query.validator())
-        {
-            query.complete(self.inner.max_regex)
-        } else {
-            Err(Error::FailValidate("Query is not allowed by schema".into()))
-        }
+        self.check_query(
+            query.is_doc(),
+            query.clauses(),
+            query.order(),
+            !query.signers().is_empty(),
+        )?;
+        query.complete(self.inner.max_regex)
     }
 
     pub fn decode_query(&self, query: Vec<u8>) -> Result<Query> {
-        let query = Query::new(query, self.inner.max_regex)?;
-        let key = query.key();
-        let entry_schema = self.inner.entries.get(key).ok_or_else(|| {
-            Error::FailValidate(format!("entry key \"{:?}\" is not in schema", key))
-        })?;
-        if entry_schema
-            .entry
-            .query_check(&self.inner.types, query.validator())
-        {
-            Ok(query)
+        self.decode_query_with_limits(query, &QueryLimits::default())
+    }
+
+    /// Decode a query, failing if it's more complex than the given [`QueryLimits`] allow. The
+    /// error message names whichever limit was exceeded.
+    pub fn decode_query_with_limits(&self, query: Vec<u8>, limits: &QueryLimits) -> Result<Query> {
+        let mut query = Query::new(query, self.inner.max_regex)?;
+        query.check_limits(limits)?;
+        self.check_query(
+            query.is_doc(),
+            query.clauses(),
+            query.order(),
+            !query.signers().is_empty(),
+        )?;
+        query.set_schema(self.hash.clone());
+        Ok(query)
+    }
+
+    /// Check that every clause of a query is allowed by the validator for its entry, and that
+    /// the ordering is allowed by the first clause's entry. If the query requires particular
+    /// signers, every clause's entry must permit it. Document queries are instead checked
+    /// against the document validator, and can only have one clause and no signers.
+    fn check_query<'a>(
+        &self,
+        doc: bool,
+        mut clauses: impl Iterator<Item = (&'a str, &'a Validator)>,
+        order: Option<&QueryOrder>,
+        signers: bool,
+    ) -> Result<()> {
+        if doc {
+            let (_, query) = clauses.next().unwrap();
+            if clauses.next().is_some() || signers {
+                return Err(Error::validate(
+                    ValidateCode::QueryNotAllowed,
+                    "Document queries can't have more clauses or signer requirements",
+                ));
+            }
+            if !self.inner.doc.query_check(&self.inner.types, query) {
+                return Err(self.query_denied(&self.inner.doc, query));
+            }
+            return self.check_query_order(&self.inner.doc, order);
+        }
+        for (index, (key, query)) in clauses.enumerate() {
+            let entry_schema = self.inner.entries.get(key).ok_or_else(|| {
+                Error::validate(
+                    ValidateCode::UnknownEntry,
+                    format!("entry key \"{:?}\" is not in schema", key),
+                )
+            })?;
+            if !entry_schema.entry.query_check(&self.inner.types, query) {
+                return Err(self.query_denied(&entry_schema.entry, query));
+            }
+            if signers && !entry_schema.signer_ok {
+                return Err(Error::validate(
+                    ValidateCode::QueryNotAllowed,
+                    format!(
+                        "Query is not allowed by schema: entry key {:?} doesn't permit signer queries",
+                        key
+                    ),
+                ));
+            }
+            if index == 0 {
+                self.check_query_order(&entry_schema.entry, order)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Check that a query's requested result ordering sorts by a value the entry validator
+    /// permits `ord` queries on. As with any other query, the value must be reachable with the
+    /// `map_ok` and `array` permissions.
+    fn check_query_order(&self, entry: &Validator, order: Option<&QueryOrder>) -> Result<()> {
+        let order = match order {
+            Some(order) => order,
+            None => return Ok(()),
+        };
+        let path = ValuePath::parse(order.path())?;
+        let caps = entry.query_capabilities_at(&self.inner.types, &path);
+        if matches!(caps, Some(caps) if caps.ord) {
+            Ok(())
         } else {
-            Err(Error::FailValidate("Query is not allowed by schema".into()))
+            let mut err = ValidateError::new(
+                ValidateCode::QueryNotAllowed,
+                "Query ordering is not allowed by schema: sorting needs the `ord` permission",
+            );
+            err.path = path;
+            Err(err.into())
+        }
+    }
+
+    /// Make the error for a query the entry validator rejected. If the query uses a pattern
+    /// match the schema doesn't permit, the error says where and which permission was missing.
+    fn query_denied(&self, entry: &Validator, query: &Validator) -> Error {
+        match entry.denied_pattern(&self.inner.types, query) {
+            Some((path, permission)) => {
+                let mut err = ValidateError::new(
+                    ValidateCode::QueryNotAllowed,
+                    format!(
+                        "Query is not allowed by schema: pattern matching needs the `{}` permission",
+                        permission
+                    ),
+                );
+                err.path = path;
+                err.into()
+            }
+            None => Error::validate(
+                ValidateCode::QueryNotAllowed,
+                "Query is not allowed by schema",
+            ),
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::query::OrderDirection;
+    use crate::validator::{IntValidator, MapValidator, StrValidator};
+
+    fn update_schema() -> Schema {
+        let doc = MapValidator::new()
+            .req_add("created_at", IntValidator::new().build())
+            .req_add("title", StrValidator::new().build())
+            .opt_add("meta", Validator::Any)
+            .build();
+        let schema_doc = SchemaBuilder::new(doc)
+            .immutable_field("created_at")
+            .immutable_field("meta.owner")
+            .build()
+            .unwrap();
+        Schema::from_doc(&schema_doc).unwrap()
+    }
+
+    fn make_doc(schema: &Schema, created_at: u32, title: &str, owner: Option<&str>) -> Document {
+        let mut data = BTreeMap::new();
+        data.insert("created_at".to_string(), Value::from(created_at));
+        data.insert("title".to_string(), Value::from(title));
+        if let Some(owner) = owner {
+            let mut meta = BTreeMap::new();
+            meta.insert("owner".to_string(), Value::from(owner));
+            data.insert("meta".to_string(), Value::from(meta));
+        }
+        let doc = NewDocument::new(data, Some(schema.hash())).unwrap();
+        schema.validate_new_doc(doc).unwrap()
+    }
+
+    #[test]
+    fn validate_update() {
+        let schema = update_schema();
+        let old = make_doc(&schema, 1, "first", Some("alice"));
+        let new = make_doc(&schema, 1, "second", Some("alice"));
+        schema.validate_update(&old, &new).unwrap();
+
+        let new = make_doc(&schema, 2, "second", Some("alice"));
+        let err = schema.validate_update(&old, &new).unwrap_err();
+        assert!(
+            matches!(err, Error::FailValidate(ref e) if e.code == ValidateCode::Immutable && e.message.contains("created_at"))
+        );
+
+        let new = make_doc(&schema, 1, "first", Some("bob"));
+        assert!(schema.validate_update(&old, &new).is_err());
+        let new = make_doc(&schema, 1, "first", None);
+        assert!(schema.validate_update(&old, &new).is_err());
+
+        // Missing in both counts as unchanged
+        let old = make_doc(&schema, 1, "first", None);
+        let new = make_doc(&schema, 1, "second", None);
+        schema.validate_update(&old, &new).unwrap();
+    }
+
+    #[test]
+    fn validate_update_schema_mismatch() {
+        let schema = update_schema();
+        let old = make_doc(&schema, 1, "first", None);
+        let other = NoSchema::validate_new_doc(NewDocument::new(1u8, None).unwrap()).unwrap();
+        assert!(matches!(
+            schema.validate_update(&old, &other),
+            Err(Error::SchemaMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn immutable_field_bad_path() {
+        let result = SchemaBuilder::new(Validator::Null)
+            .immutable_field("a..b")
+            .build();
+        assert!(matches!(result, Err(Error::BadPath(_))));
+    }
+
+    #[test]
+    fn max_doc_size() {
+        use serde_bytes::ByteBuf;
+        let schema_doc = SchemaBuilder::new(Validator::Bin(Default::default()))
+            .max_doc_size(100)
+            .build()
+            .unwrap();
+        let schema = Schema::from_doc(&schema_doc).unwrap();
+        assert_eq!(schema.max_doc_size(), 100);
+
+        let small = NewDocument::new(ByteBuf::from(vec![0u8; 10]), Some(schema.hash())).unwrap();
+        let small = schema.validate_new_doc(small).unwrap();
+        let (_, encoded) = schema.encode_doc(small).unwrap();
+        schema.decode_doc(encoded).unwrap();
+
+        let big = NewDocument::new(ByteBuf::from(vec![0u8; 100]), Some(schema.hash())).unwrap();
+        assert!(matches!(
+            schema.validate_new_doc(big.clone()),
+            Err(Error::LengthTooLong { max: 100, .. })
+        ));
+        let big = Document::from_new(big);
+        assert!(matches!(
+            schema.validate_doc(&big),
+            Err(Error::LengthTooLong { max: 100, .. })
+        ));
+        let old = NewDocument::new(ByteBuf::from(vec![0u8; 10]), Some(schema.hash())).unwrap();
+        let old = schema.validate_new_doc(old).unwrap();
+        assert!(matches!(
+            schema.validate_update(&old, &big),
+            Err(Error::LengthTooLong { max: 100, .. })
+        ));
+
+        // The global limit still applies when the schema's limit is looser
+        let schema_doc = SchemaBuilder::new(Validator::Null)
+            .max_doc_size(usize::MAX)
+            .build()
+            .unwrap();
+        let schema = Schema::from_doc(&schema_doc).unwrap();
+        assert_eq!(schema.max_doc_size(), MAX_DOC_SIZE);
+    }
+
+    #[test]
+    fn allow_unknown_schemas() {
+        let other = update_schema();
+        let (_, encoded) = other
+            .encode_doc(make_doc(&other, 1, "first", None))
+            .unwrap();
+        let (_, no_schema) = NoSchema::encode_doc(
+            NoSchema::validate_new_doc(NewDocument::new(1u8, None).unwrap()).unwrap(),
+        )
+        .unwrap();
+
+        let strict =
+            Schema::from_doc(&SchemaBuilder::new(Validator::Null).build().unwrap()).unwrap();
+        assert!(!strict.allows_unknown_schemas());
+        assert!(matches!(
+            strict.decode_doc(encoded.clone()),
+            Err(Error::SchemaMismatch { .. })
+        ));
+
+        let gateway = Schema::from_doc(
+            &SchemaBuilder::new(Validator::Null)
+                .allow_unknown_schemas(true)
+                .build()
+                .unwrap(),
+        )
+        .unwrap();
+        assert!(gateway.allows_unknown_schemas());
+        let doc = gateway.decode_doc(encoded.clone()).unwrap();
+        assert_eq!(doc.schema_hash(), Some(other.hash()));
+        gateway.trusted_decode_doc(encoded).unwrap();
+        assert!(gateway.decode_doc(no_schema).is_err());
+    }
+
+    #[test]
+    fn dict_compression() {
+        let docs: Vec<Document> = (0..200u32)
+            .map(|i| {
+                let mut data = BTreeMap::new();
+                data.insert("id".to_string(), Value::from(i));
+                data.insert(
+                    "description".to_string(),
+                    Value::from(format!("item number {} in the sample catalog", i * 7919)),
+                );
+                data.insert("tags".to_string(), Value::from(vec![Value::from("sample")]));
+                NoSchema::validate_new_doc(NewDocument::new(data, None).unwrap()).unwrap()
+            })
+            .collect();
+        let dict = docs
+            .iter()
+            .fold(DictBuilder::new(), |builder, doc| builder.add_sample(doc))
+            .build(4096)
+            .unwrap();
+        assert!(!dict.is_empty() && dict.len() <= 4096);
+        let dict = Compress::new_zstd_dict(3, dict);
+
+        let doc = docs[0].clone();
+        let hash = doc.hash();
+        let (_, encoded) = NoSchema::compress_doc_with_dict(doc, &dict).unwrap();
+        let decoded = NoSchema::decompress_doc_with_dict(encoded.clone(), &dict).unwrap();
+        assert_eq!(decoded.hash(), hash);
+        assert!(NoSchema::decode_doc(encoded).is_err());
+
+        assert!(NoSchema::compress_doc_with_dict(docs[1].clone(), &Compress::default()).is_err());
+        assert!(DictBuilder::new().build(4096).is_err());
+    }
+
+    #[test]
+    fn relative_time() {
+        use crate::validator::TimeValidator;
+        let doc = MapValidator::new()
+            .req_add(
+                "at",
+                TimeValidator::new()
+                    .max_from_now(300)
+                    .min_from_now(-30 * 24 * 3600)
+                    .build(),
+            )
+            .build();
+        let schema = Schema::from_doc(&SchemaBuilder::new(doc).build().unwrap()).unwrap();
+        let now = Timestamp::from_sec(1_600_000_000);
+        let options = ValidateOptions::new().now(now);
+        let make = |at: Timestamp| {
+            let mut data = BTreeMap::new();
+            data.insert("at".to_string(), Value::from(at));
+            NewDocument::new(data, Some(schema.hash())).unwrap()
+        };
+
+        let check = |at: Timestamp| schema.validate_new_doc_with_options(make(at), &options);
+        check(now).unwrap();
+        check(now + 300).unwrap();
+        assert!(check((now + 300).next()).is_err());
+        check(now - 30 * 24 * 3600).unwrap();
+        assert!(check((now - 30 * 24 * 3600).prev()).is_err());
+
+        // The reference time also applies on decode
+        let doc = check(now).unwrap();
+        let (_, encoded) = schema.encode_doc(doc).unwrap();
+        schema
+            .decode_doc_with_options(encoded.clone(), &options)
+            .unwrap();
+        let later = ValidateOptions::new().now(now + 31 * 24 * 3600);
+        assert!(schema.decode_doc_with_options(encoded, &later).is_err());
+    }
+
+    #[test]
+    fn entry_add_with_link() {
+        let target = SchemaBuilder::new(Validator::Null).build().unwrap();
+        let target = Schema::from_doc(&target).unwrap();
+        let schema = SchemaBuilder::new(Validator::Null)
+            .entry_add_with_link("ref", target.hash(), None)
+            .entry_add("note", Validator::Any, None)
+            .build()
+            .unwrap();
+        let schema = Schema::from_doc(&schema).unwrap();
+        assert_eq!(schema.link_target("ref"), Some(target.hash()));
+        assert_eq!(schema.link_target("note"), None);
+        assert_eq!(schema.link_target("missing"), None);
+
+        let linked = target
+            .validate_new_doc(NewDocument::new((), Some(target.hash())).unwrap())
+            .unwrap();
+        let other = NoSchema::validate_new_doc(NewDocument::new((), None).unwrap()).unwrap();
+        let parent = Hash::new(b"Pretend I am a parent document");
+        let encode = |doc: &Document| {
+            let entry = NewEntry::new(doc.hash(), "ref", &parent).unwrap();
+            let mut checklist = schema.encode_new_entry(entry).unwrap();
+            checklist.check(doc)?;
+            checklist.complete()
+        };
+        encode(&linked).unwrap();
+        assert!(encode(&other).is_err());
+        assert!(schema
+            .encode_new_entry(NewEntry::new("not a hash", "ref", &parent).unwrap())
+            .is_err());
+    }
+
+    #[test]
+    fn validate_options() {
+        use crate::validator::{ArrayValidator, MultiValidator};
+        use fog_crypto::identity::IdentityKey;
+        let nested = Validator::new_ref("nested");
+        let schema = SchemaBuilder::new(nested.clone())
+            .type_add(
+                "nested",
+                MultiValidator::new()
+                    .push(IntValidator::new().build())
+                    .push(ArrayValidator::new().items(nested).build())
+                    .build(),
+            )
+            .build()
+            .unwrap();
+        let schema = Schema::from_doc(&schema).unwrap();
+        let keys: Vec<IdentityKey> = (0..2)
+            .map(|_| IdentityKey::new_temp(&mut rand::rngs::OsRng))
+            .collect();
+        let make = |depth: usize, key: Option<&IdentityKey>| {
+            let value = (0..depth).fold(Value::from(1u8), |v, _| Value::from(vec![v]));
+            let doc = NewDocument::new(value, Some(schema.hash())).unwrap();
+            let doc = match key {
+                Some(key) => doc.sign(key).unwrap(),
+                None => doc,
+            };
+            schema.validate_new_doc(doc).unwrap()
+        };
+
+        // Only whitelisted signers are accepted
+        let options = ValidateOptions::new().signers(vec![keys[0].id().clone()]);
+        let signed = make(1, Some(&keys[0]));
+        schema.validate_doc(&signed).unwrap();
+        schema.validate_doc_with_options(&signed, &options).unwrap();
+        for doc in [make(1, Some(&keys[1])), make(1, None)].iter() {
+            schema.validate_doc(doc).unwrap();
+            let err = schema.validate_doc_with_options(doc, &options).unwrap_err();
+            assert_eq!(err.validate_error().unwrap().code, ValidateCode::NotSigner);
+        }
+
+        // Nesting depth can be limited below the usual maximum
+        let options = ValidateOptions::new().max_depth(3);
+        schema
+            .validate_doc_with_options(&make(3, None), &options)
+            .unwrap();
+        let deep = make(4, None);
+        schema.validate_doc(&deep).unwrap();
+        assert!(schema.validate_doc_with_options(&deep, &options).is_err());
+        assert!(schema
+            .validate_update_with_options(&make(1, None), &deep, &options)
+            .is_err());
+        schema
+            .validate_update_with_options(&deep, &make(3, None), &options)
+            .unwrap();
+        let (_, encoded) = schema.encode_doc(deep).unwrap();
+        assert!(schema.decode_doc_with_options(encoded, &options).is_err());
+
+        // Updates check the signer of the new document only
+        let options = ValidateOptions::new().signers(vec![keys[0].id().clone()]);
+        schema
+            .validate_update_with_options(&make(1, None), &signed, &options)
+            .unwrap();
+        let err = schema
+            .validate_update_with_options(&signed, &make(1, Some(&keys[1])), &options)
+            .unwrap_err();
+        assert_eq!(err.validate_error().unwrap().code, ValidateCode::NotSigner);
+    }
+
+    #[test]
+    fn entry_expires() {
+        use crate::validator::TimeValidator;
+        let post = MapValidator::new()
+            .opt_add("created_at", TimeValidator::new().build())
+            .opt_add("expires_at", TimeValidator::new().build())
+            .build();
+        let schema = SchemaBuilder::new(Validator::Null)
+            .entry_add("post", post.clone(), None)
+            .entry_add("note", post, None)
+            .entry_expires("post", 3600)
+            .build()
+            .unwrap();
+        let schema = Schema::from_doc(&schema).unwrap();
+        assert_eq!(schema.entry_ttl("post"), Some(3600));
+        assert_eq!(schema.entry_ttl("note"), None);
+        assert_eq!(schema.entry_ttl("reply"), None);
+
+        let now = Timestamp::from_sec(1_600_000_000);
+        let options = ValidateOptions::new().now(now);
+        let parent = Hash::new(b"Pretend I am a parent document");
+        let check = |key: &str, field: &str, at: Timestamp| {
+            let mut data = BTreeMap::new();
+            data.insert(field.to_string(), Value::from(at));
+            let entry = NewEntry::new(data, key, &parent).unwrap();
+            schema.encode_new_entry_with_options(entry, &options)
+        };
+        check("post", "created_at", now - 3600).unwrap();
+        let err = check("post", "created_at", (now - 3600).prev()).unwrap_err();
+        assert_eq!(err.validate_error().unwrap().code, ValidateCode::Expired);
+        check("post", "expires_at", now).unwrap();
+        assert!(check("post", "expires_at", now.prev()).is_err());
+        check("note", "created_at", now - 7200).unwrap();
+
+        // Expiry is also checked on decode, against the given time
+        let (_, encoded) = check("post", "created_at", now)
+            .unwrap()
+            .complete()
+            .unwrap();
+        schema
+            .decode_entry_with_options(encoded.clone(), "post", &parent, &options)
+            .unwrap();
+        let later = ValidateOptions::new().now(now + 3601);
+        assert!(schema
+            .decode_entry_with_options(encoded, "post", &parent, &later)
+            .is_err());
+
+        // Only entries in the schema can expire
+        assert!(SchemaBuilder::new(Validator::Null)
+            .entry_expires("post", 3600)
+            .build()
+            .is_err());
+    }
+
+    #[test]
+    fn doc_default_fields() {
+        use crate::validator::BoolValidator;
+        let doc = MapValidator::new()
+            .req_add("title", StrValidator::new().build())
+            .req_add("count", IntValidator::new().default_value(0u8).build())
+            .opt_add("draft", BoolValidator::new().default_value(true).build())
+            .build();
+        let schema_doc = SchemaBuilder::new(Validator::new_ref("Post"))
+            .type_add("Post", doc)
+            .build()
+            .unwrap();
+        let schema = Schema::from_doc(&schema_doc).unwrap();
+        let fields: Vec<(&str, Value)> = schema.doc_default_fields().collect();
+        assert_eq!(
+            fields,
+            vec![("count", Value::from(0u8)), ("draft", Value::from(true))]
+        );
+
+        let schema =
+            Schema::from_doc(&SchemaBuilder::new(Validator::Null).build().unwrap()).unwrap();
+        assert_eq!(schema.doc_default_fields().count(), 0);
+    }
+
+    #[test]
+    fn doc_fields() {
+        let doc = MapValidator::new()
+            .req_add("title", StrValidator::new().build())
+            .req_add("count", IntValidator::new().default_value(0u8).build())
+            .opt_add("tags", Validator::Any)
+            .build();
+        let schema_doc = SchemaBuilder::new(Validator::new_ref("Post"))
+            .type_add("Post", doc)
+            .build()
+            .unwrap();
+        let schema = Schema::from_doc(&schema_doc).unwrap();
+        let required: Vec<&str> = schema.required_doc_fields().collect();
+        assert_eq!(required, vec!["count", "title"]);
+        let optional: Vec<&str> = schema.optional_doc_fields().collect();
+        assert_eq!(optional, vec!["tags"]);
+
+        let schema =
+            Schema::from_doc(&SchemaBuilder::new(Validator::Null).build().unwrap()).unwrap();
+        assert_eq!(schema.required_doc_fields().count(), 0);
+        assert_eq!(schema.optional_doc_fields().count(), 0);
+    }
+
+    #[test]
+    fn fill_defaults() {
+        use crate::validator::{ArrayValidator, TimeValidator};
+        let meta = MapValidator::new()
+            .opt_add("lang", StrValidator::new().default_value("en").build())
+            .build();
+        let comment = MapValidator::new()
+            .opt_add("score", IntValidator::new().default_value(1u8).build())
+            .build();
+        let doc = MapValidator::new()
+            .req_add("title", StrValidator::new().build())
+            .opt_add(
+                "created",
+                TimeValidator::new()
+                    .default_value(Timestamp::from_sec(100))
+                    .build(),
+            )
+            .opt_add("meta", meta)
+            .opt_add("comments", ArrayValidator::new().items(comment).build())
+            .build();
+        let schema = Schema::from_doc(&SchemaBuilder::new(doc).build().unwrap()).unwrap();
+
+        let mut meta = BTreeMap::new();
+        meta.insert("author".to_string(), Value::from("a"));
+        let mut value = BTreeMap::new();
+        value.insert("title".to_string(), Value::from("t"));
+        value.insert("meta".to_string(), Value::from(meta));
+        value.insert(
+            "comments".to_string(),
+            Value::from(vec![Value::from(BTreeMap::<String, Value>::new())]),
+        );
+        let mut value = Value::from(value);
+        schema.fill_defaults(&mut value);
+
+        assert_eq!(
+            value.get_path("created"),
+            Some(&Value::from(Timestamp::from_sec(100)))
+        );
+        assert_eq!(value.get_path("meta.lang"), Some(&Value::from("en")));
+        assert_eq!(value.get_path("comments[0].score"), None);
+
+        // Present fields are left as-is
+        let mut value = BTreeMap::new();
+        value.insert("title".to_string(), Value::from("t"));
+        value.insert("created".to_string(), Value::from(Timestamp::from_sec(5)));
+        let mut value = Value::from(value);
+        schema.fill_defaults(&mut value);
+        assert_eq!(
+            value.get_path("created"),
+            Some(&Value::from(Timestamp::from_sec(5)))
+        );
+        assert_eq!(value.get_path("meta"), None);
+    }
+
+    #[test]
+    fn validate_batch() {
+        let schema = Schema::from_doc(
+            &SchemaBuilder::new(IntValidator::new().max(10u8).build())
+                .build()
+                .unwrap(),
+        )
+        .unwrap();
+        let good = schema
+            .validate_new_doc(NewDocument::new(5u8, Some(schema.hash())).unwrap())
+            .unwrap();
+        // Skip validation to get a document that breaks the schema
+        let bad = Document::from_new(NewDocument::new(20u8, Some(schema.hash())).unwrap());
+        let other = NoSchema::validate_new_doc(NewDocument::new(5u8, None).unwrap()).unwrap();
+
+        let results = schema.validate_batch(&[good.clone(), bad, other, good]);
+        assert_eq!(results.len(), 4);
+        assert!(results[0].is_ok());
+        assert!(matches!(results[1], Err(Error::FailValidate(_))));
+        assert!(matches!(results[2], Err(Error::SchemaMismatch { .. })));
+        assert!(results[3].is_ok());
+        assert!(schema.validate_batch(&[]).is_empty());
+    }
+
+    #[test]
+    fn type_cycles() {
+        use crate::validator::{AllValidator, ArrayValidator, MultiValidator};
+        let expect_cycle = |builder: SchemaBuilder, cycle: &str| {
+            // Caught when building...
+            match builder.clone().build() {
+                Err(Error::FailValidate(err)) => {
+                    assert!(err.message.ends_with(cycle), "got {}", err)
+                }
+                _ => panic!("expected a cycle error"),
+            }
+            // ...and when loading a schema document built without the check
+            let doc =
+                NoSchema::validate_new_doc(NewDocument::new(builder.inner, None).unwrap()).unwrap();
+            assert!(Schema::from_doc(&doc).is_err());
+        };
+
+        let builder =
+            SchemaBuilder::new(Validator::new_ref("a")).type_add("a", Validator::new_ref("a"));
+        expect_cycle(builder, "a -> a");
+
+        let builder = SchemaBuilder::new(Validator::Null)
+            .type_add(
+                "a",
+                MultiValidator::new()
+                    .push(Validator::Null)
+                    .push(Validator::new_ref("b"))
+                    .build(),
+            )
+            .type_add(
+                "b",
+                AllValidator::new().push(Validator::new_ref("c")).build(),
+            )
+            .type_add("c", Validator::new_ref("a"));
+        expect_cycle(builder, "a -> b -> c -> a");
+
+        // A tree that holds an array of itself is fine
+        let tree = MapValidator::new()
+            .req_add("name", StrValidator::new().build())
+            .opt_add(
+                "children",
+                ArrayValidator::new()
+                    .items(Validator::new_ref("tree"))
+                    .build(),
+            )
+            .build();
+        let schema = SchemaBuilder::new(Validator::new_ref("tree"))
+            .type_add("tree", tree)
+            .build()
+            .unwrap();
+        let schema = Schema::from_doc(&schema).unwrap();
+
+        let nest = |leaf: Value| {
+            (0..20).fold(leaf, |child, depth| {
+                let mut node = BTreeMap::new();
+                node.insert("name".to_string(), Value::from(format!("node{}", depth)));
+                node.insert("children".to_string(), Value::from(vec![child]));
+                Value::from(node)
+            })
+        };
+        let mut leaf = BTreeMap::new();
+        leaf.insert("name".to_string(), Value::from("leaf"));
+        let doc = NewDocument::new(nest(Value::from(leaf)), Some(schema.hash())).unwrap();
+        assert!(schema.validate_new_doc(doc).is_ok());
+        // The innermost node is missing its name
+        let leaf = Value::from(BTreeMap::<String, Value>::new());
+        let doc = NewDocument::new(nest(leaf), Some(schema.hash())).unwrap();
+        assert!(schema.validate_new_doc(doc).is_err());
+    }
+
+    #[test]
+    fn query_capabilities() {
+        let post = MapValidator::new()
+            .req_add("title", StrValidator::new().regex(true).build())
+            .opt_add("score", IntValidator::new().ord(true).build())
+            .map_ok(true)
+            .build();
+        let schema = SchemaBuilder::new(Validator::Null)
+            .type_add("post", post)
+            .entry_add("post", Validator::new_ref("post"), None)
+            .build()
+            .unwrap();
+        let schema = Schema::from_doc(&schema).unwrap();
+        let path = |p| ValuePath::parse(p).unwrap();
+        let caps = schema.query_capabilities("post", &path("")).unwrap();
+        assert!(caps.map && !caps.in_nin);
+        let caps = schema.query_capabilities("post", &path("title")).unwrap();
+        assert!(caps.regex && !caps.ord);
+        let caps = schema.query_capabilities("post", &path("score")).unwrap();
+        assert!(caps.ord && !caps.regex);
+        assert!(schema.query_capabilities("post", &path("body")).is_none());
+        assert!(schema.query_capabilities("reply", &path("")).is_none());
+    }
+
+    #[test]
+    fn queryable_fields() {
+        use crate::validator::{
+            ArrayValidator, BinValidator, BoolValidator, EnumValidator, F32Validator, F64Validator,
+            MultiValidator, TimeValidator,
+        };
+        let tag = StrValidator::new()
+            .query(true)
+            .regex(true)
+            .size(true)
+            .build();
+        let post = MapValidator::new()
+            .req_add("title", Validator::new_ref("tag"))
+            .opt_add("score", IntValidator::new().ord(true).bit(true).build())
+            .opt_add(
+                "tags",
+                ArrayValidator::new()
+                    .items(Validator::new_ref("tag"))
+                    .array(true)
+                    .contains_ok(true)
+                    .unique_ok(true)
+                    .build(),
+            )
+            .opt_add(
+                "extra",
+                MapValidator::new()
+                    .values(Validator::Null)
+                    .map_ok(true)
+                    .match_keys(true)
+                    .len_keys(true)
+                    .build(),
+            )
+            .opt_add(
+                "parent",
+                Validator::Multi(MultiValidator(vec![
+                    HashValidator::new().link_ok(true).build(),
+                    HashValidator::new().schema_ok(true).build(),
+                ])),
+            )
+            .opt_add("body", Validator::Any)
+            .map_ok(true)
+            .build();
+        let kind = EnumValidator::new()
+            .insert("Empty", None)
+            .insert("Num", Some(IntValidator::new().ord(true).build()))
+            .insert_int(1, Some(StrValidator::new().regex(true).build()))
+            .build();
+        let reading = MapValidator::new()
+            .req_add("at", TimeValidator::new().query(true).ord(true).build())
+            .req_add(
+                "data",
+                BinValidator::new()
+                    .query(true)
+                    .bit(true)
+                    .ord(true)
+                    .size(true)
+                    .build(),
+            )
+            .opt_add("flag", BoolValidator::new().query(true).build())
+            .opt_add("kind", kind)
+            .opt_add("ratio", Validator::F32(F32Validator::new().query(true)))
+            .opt_add("weight", Validator::F64(F64Validator::new().ord(true)))
+            .map_ok(true)
+            .build();
+        let schema = SchemaBuilder::new(Validator::Null)
+            .type_add("tag", tag)
+            .type_add("post", post)
+            .entry_add("post", Validator::new_ref("post"), None)
+            .entry_add("count", IntValidator::new().query(true).build(), None)
+            .entry_signer_ok("count", true)
+            .entry_add("reading", reading, None)
+            .build()
+            .unwrap();
+        let schema = Schema::from_doc(&schema).unwrap();
+        let fields = schema.queryable_fields();
+        let names: Vec<(&str, &str)> = fields
+            .iter()
+            .map(|f| (f.entry.as_str(), f.path.as_str()))
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                ("count", ""),
+                ("post", ""),
+                ("post", "extra"),
+                ("post", "extra.*"),
+                ("post", "parent"),
+                ("post", "score"),
+                ("post", "tags"),
+                ("post", "tags[*]"),
+                ("post", "title"),
+                ("reading", ""),
+                ("reading", "at"),
+                ("reading", "data"),
+                ("reading", "flag"),
+                ("reading", "kind"),
+                ("reading", "kind.Num"),
+                ("reading", "kind[1]"),
+                ("reading", "ratio"),
+                ("reading", "weight"),
+            ]
+        );
+        let caps = |entry: &str, path: &str| {
+            fields
+                .iter()
+                .find(|f| f.entry == entry && f.path == path)
+                .unwrap()
+        };
+        let count = caps("count", "");
+        assert!(count.signer && count.caps.allowed && count.caps.in_nin && !count.caps.ord);
+        let post = caps("post", "");
+        assert!(!post.signer && post.caps.map);
+        let title = &caps("post", "title").caps;
+        assert!(title.in_nin && title.regex && title.size && !title.ord);
+        assert_eq!(title, &caps("post", "tags[*]").caps);
+        let score = &caps("post", "score").caps;
+        assert!(score.ord && score.bit && !score.in_nin);
+        let tags = &caps("post", "tags").caps;
+        assert!(tags.array && tags.contains && tags.unique && !tags.size);
+        let extra = &caps("post", "extra").caps;
+        assert!(extra.map && extra.match_keys && extra.len_keys && !extra.unique);
+        let parent = &caps("post", "parent").caps;
+        assert!(parent.link && parent.schema && !parent.in_nin);
+
+        let at = &caps("reading", "at").caps;
+        assert!(at.in_nin && at.ord && !at.bit);
+        let data = &caps("reading", "data").caps;
+        assert!(data.in_nin && data.bit && data.ord && data.size && !data.regex);
+        let flag = &caps("reading", "flag").caps;
+        assert!(flag.in_nin && !flag.ord);
+        let ratio = &caps("reading", "ratio").caps;
+        assert!(ratio.in_nin && !ratio.ord);
+        let weight = &caps("reading", "weight").caps;
+        assert!(weight.ord && !weight.in_nin);
+        let kind = &caps("reading", "kind").caps;
+        assert!(kind.allowed && !kind.in_nin);
+        let num = &caps("reading", "kind.Num").caps;
+        assert!(num.ord && !num.in_nin);
+        let tagged = &caps("reading", "kind[1]").caps;
+        assert!(tagged.regex && !tagged.size);
+    }
+
+    #[test]
+    fn query_regex_permission() {
+        use crate::validator::MultiValidator;
+        use regex::Regex;
+        let schema = |title: Validator| {
+            let post = MapValidator::new()
+                .req_add("title", title)
+                .map_ok(true)
+                .build();
+            let schema = SchemaBuilder::new(Validator::Null)
+                .entry_add("post", post, None)
+                .max_regex(1)
+                .build()
+                .unwrap();
+            Schema::from_doc(&schema).unwrap()
+        };
+        let query = |title: Validator| {
+            NewQuery::new("post", MapValidator::new().req_add("title", title).build())
+        };
+        let ranged = || StrValidator::new().max_len(10).build();
+        let pattern = || {
+            StrValidator::new()
+                .matches(Regex::new("^a").unwrap())
+                .build()
+        };
+
+        // Range queries are allowed separately from regex queries
+        let title = StrValidator::new().size(true).build();
+        assert!(schema(title.clone()).encode_query(query(ranged())).is_ok());
+        let err = schema(title).encode_query(query(pattern())).unwrap_err();
+        let err = err.validate_error().unwrap();
+        assert_eq!(err.code, ValidateCode::QueryNotAllowed);
+        assert_eq!(err.path.to_string(), "title");
+        assert!(err.message.contains("`regex`"), "{}", err.message);
+        let title = StrValidator::new().regex(true).build();
+        assert!(schema(title).encode_query(query(pattern())).is_ok());
+
+        // With a Multi in the schema, any branch may allow the regex
+        let title = MultiValidator::new()
+            .push(StrValidator::new().size(true).build())
+            .push(StrValidator::new().regex(true).build())
+            .build();
+        assert!(schema(title.clone()).encode_query(query(pattern())).is_ok());
+        assert!(schema(title).encode_query(query(ranged())).is_ok());
+        let title = MultiValidator::new()
+            .push(StrValidator::new().size(true).build())
+            .push(IntValidator::new().build())
+            .build();
+        let err = schema(title).encode_query(query(pattern())).unwrap_err();
+        assert_eq!(err.validate_error().unwrap().path.to_string(), "title");
+
+        // Other rejections don't blame a regex
+        let title = StrValidator::new().regex(true).build();
+        let err = schema(title).encode_query(query(ranged())).unwrap_err();
+        assert!(err.validate_error().unwrap().path.segments().is_empty());
+    }
+
+    #[test]
+    fn query_order() {
+        let post = MapValidator::new()
+            .req_add("time", IntValidator::new().ord(true).build())
+            .req_add("title", StrValidator::new().build())
+            .map_ok(true)
+            .build();
+        let schema = SchemaBuilder::new(Validator::Null)
+            .entry_add("post", post, None)
+            .build()
+            .unwrap();
+        let schema = Schema::from_doc(&schema).unwrap();
+        let query = || NewQuery::new("post", MapValidator::new().build());
+
+        // Sorting on an ord-permitted field works, and survives a round trip
+        let encoded = schema
+            .encode_query(
+                query()
+                    .limit(50)
+                    .order_by("time", OrderDirection::Descending),
+            )
+            .unwrap();
+        let decoded = schema.decode_query(encoded).unwrap();
+        assert_eq!(decoded.max_results(), Some(50));
+        let order = decoded.order().unwrap();
+        assert_eq!(order.path(), "time");
+        assert_eq!(order.direction(), OrderDirection::Descending);
+
+        // Without ordering or limits, neither shows up
+        let decoded = schema
+            .decode_query(schema.encode_query(query()).unwrap())
+            .unwrap();
+        assert_eq!(decoded.max_results(), None);
+        assert!(decoded.order().is_none());
+
+        // Sorting on other fields is rejected
+        for path in ["title", "body"].iter() {
+            let err = schema
+                .encode_query(query().order_by(path, OrderDirection::Ascending))
+                .unwrap_err();
+            let err = err.validate_error().unwrap();
+            assert_eq!(err.code, ValidateCode::QueryNotAllowed);
+            assert_eq!(err.path.to_string(), *path);
+            assert!(err.message.contains("`ord`"), "{}", err.message);
+        }
+        assert!(schema
+            .encode_query(query().order_by("time[", OrderDirection::Ascending))
+            .is_err());
+    }
+
+    #[test]
+    fn query_cursor() {
+        use crate::query::QueryCursor;
+        let post = MapValidator::new()
+            .req_add("time", IntValidator::new().ord(true).build())
+            .req_add("title", StrValidator::new().build())
+            .map_ok(true)
+            .build();
+        let schema = SchemaBuilder::new(Validator::Null)
+            .entry_add("post", post, None)
+            .build()
+            .unwrap();
+        let schema = Schema::from_doc(&schema).unwrap();
+        let parent = Hash::new(b"Pretend I am a parent document");
+        let entry = |time: i64, title: &str| {
+            let mut data = BTreeMap::new();
+            data.insert("time".to_string(), Value::from(time));
+            data.insert("title".to_string(), Value::from(title));
+            let entry = NewEntry::new(data, "post", &parent).unwrap();
+            let (_, encoded) = schema.encode_new_entry(entry).unwrap().complete().unwrap();
+            schema
+                .decode_entry(encoded, "post", &parent)
+                .unwrap()
+                .complete()
+                .unwrap()
+        };
+        // Two entries share a time, so they're ordered by hash
+        let mut entries = vec![
+            entry(5, "e"),
+            entry(1, "a"),
+            entry(3, "c"),
+            entry(3, "d"),
+            entry(2, "b"),
+        ];
+        entries.sort_by(|a, b| {
+            entry_time(b)
+                .cmp(&entry_time(a))
+                .then_with(|| a.hash().cmp(&b.hash()))
+        });
+        let query = || {
+            NewQuery::new("post", MapValidator::new().map_ok(true).build())
+                .order_by("time", OrderDirection::Descending)
+                .limit(2)
+        };
+
+        // Page through the results two at a time, resuming from a cursor after each page
+        let mut cursor: Option<QueryCursor> = None;
+        let mut seen = Vec::new();
+        loop {
+            let new_query = match cursor.clone() {
+                Some(cursor) => query().after(cursor),
+                None => query(),
+            };
+            let decoded = schema
+                .decode_query(schema.encode_query(new_query.clone()).unwrap())
+                .unwrap();
+            let page: Vec<&Entry> = entries
+                .iter()
+                .filter(|e| decoded.past_cursor(e).unwrap())
+                .take(decoded.max_results().unwrap() as usize)
+                .collect();
+            let last = match page.last() {
+                Some(last) => *last,
+                None => break,
+            };
+            // Both sides derive the same cursor
+            let next = new_query.cursor(last).unwrap();
+            assert_eq!(next, decoded.cursor(last).unwrap());
+            assert_eq!(next.entry_hash(), &last.hash());
+            assert_eq!(next.key(), Some(&Value::from(entry_time(last))));
+            seen.extend(page.into_iter().map(|e| e.hash()));
+            cursor = Some(next);
+        }
+        let expected: Vec<Hash> = entries.iter().map(|e| e.hash()).collect();
+        assert_eq!(seen, expected);
+
+        // Cursors round-trip as fog-pack values
+        let cursor = cursor.unwrap();
+        let mut ser = FogSerializer::default();
+        cursor.serialize(&mut ser).unwrap();
+        let encoded = ser.finish();
+        let decoded = QueryCursor::deserialize(&mut FogDeserializer::new(&encoded)).unwrap();
+        assert_eq!(decoded, cursor);
+        assert_eq!(decoded.query_hash(), cursor.query_hash());
+
+        // A cursor can't be used with a different query
+        let other = NewQuery::new("post", MapValidator::new().map_ok(true).build())
+            .order_by("time", OrderDirection::Ascending)
+            .after(cursor);
+        let err = schema
+            .decode_query(schema.encode_query(other).unwrap())
+            .unwrap_err();
+        assert_eq!(
+            err.validate_error().unwrap().code,
+            ValidateCode::QueryNotAllowed
+        );
+    }
+
+    fn entry_time(entry: &Entry) -> i64 {
+        entry.deserialize::<BTreeMap<String, Value>>().unwrap()["time"]
+            .as_i64()
+            .unwrap()
+    }
+
+    #[test]
+    fn pretty_print() {
+        use crate::validator::{ArrayValidator, BoolValidator, HashValidator, MultiValidator};
+        let doc = MapValidator::new()
+            .comment("A blog post")
+            .req_add(
+                "title",
+                StrValidator::new().comment("The post's title").build(),
+            )
+            .req_add(
+                "tags",
+                ArrayValidator::new()
+                    .items(Validator::new_ref("tag"))
+                    .build(),
+            )
+            .opt_add(
+                "meta",
+                MapValidator::new()
+                    .opt_add("draft", BoolValidator::new().default_value(true).build())
+                    .opt_add("reply-to", HashValidator::new().build())
+                    .build(),
+            )
+            .opt_add(
+                "score",
+                MultiValidator::new()
+                    .push(IntValidator::new().build())
+                    .push(Validator::Null)
+                    .build(),
+            )
+            .build();
+        let schema = SchemaBuilder::new(doc)
+            .name("blog")
+            .version(2)
+            .description("Posts and their comments")
+            .entry_add(
+                "comment",
+                MapValidator::new()
+                    .values(StrValidator::new().build())
+                    .build(),
+                None,
+            )
+            .type_add("tag", StrValidator::new().max_len(16).build())
+            .build()
+            .unwrap();
+        let schema = Schema::from_doc(&schema).unwrap();
+        let expected = r#"// Posts and their comments
+schema "blog" v2
+
+// A blog post
+doc: {
+  tags: Array<tag>;
+  // The post's title
+  title: Str;
+  meta?: {
+    draft?: Bool = true;
+    "reply-to"?: Hash;
+  };
+  score?: Int | null;
+};
+
+entry "comment": Map<Str>;
+
+type tag = Str;
+"#;
+        assert_eq!(schema.pretty_print(), expected);
+    }
+
+    #[test]
+    fn query_limits() {
+        use crate::query::QueryLimits;
+        use crate::validator::AllValidator;
+        let schema = SchemaBuilder::new(Validator::Null)
+            .entry_add("num", IntValidator::new().query(true).build(), None)
+            .build()
+            .unwrap();
+        let schema = Schema::from_doc(&schema).unwrap();
+        let encode = |query: NewQuery| schema.encode_query(query).unwrap();
+        let decode = |query: Validator| schema.decode_query(encode(NewQuery::new("num", query)));
+        let exceeded = |result: Result<Query>, what: &str| match result {
+            Err(Error::FailValidate(err)) => {
+                assert_eq!(err.code, ValidateCode::QueryNotAllowed);
+                assert!(err.message.contains(what), "unexpected error: {}", err);
+            }
+            other => panic!(
+                "expected the {} limit to be exceeded, got {:?}",
+                what, other
+            ),
+        };
+        let limits = QueryLimits::default();
+
+        // An All validator holding `n - 1` integer validators
+        let many = |n: usize| {
+            (1..n)
+                .fold(AllValidator::new(), |all, _| {
+                    all.push(IntValidator::new().build())
+                })
+                .build()
+        };
+        assert!(decode(many(limits.max_validators)).is_ok());
+        exceeded(decode(many(limits.max_validators + 1)), "validators");
+
+        // An integer validator with an `n` long `in` list
+        let list = |n: usize| {
+            (0..n)
+                .fold(IntValidator::new(), |int, i| int.in_add(i as u64))
+                .build()
+        };
+        assert!(decode(list(limits.max_list_entries)).is_ok());
+        exceeded(
+            decode(list(limits.max_list_entries + 1)),
+            "`in`/`nin` values",
+        );
+
+        // `n` levels of nested All validators
+        let nested = |n: usize| {
+            (1..n).fold(IntValidator::new().build(), |inner, _| {
+                AllValidator::new().push(inner).build()
+            })
+        };
+        assert!(decode(nested(limits.max_depth)).is_ok());
+        exceeded(decode(nested(limits.max_depth + 1)), "nesting");
+
+        // Limits are set by the caller, and cover every clause
+        let strict = QueryLimits::new().max_validators(3);
+        let query = NewQuery::new("num", many(2));
+        let encoded = encode(query.clone());
+        assert!(schema.decode_query_with_limits(encoded, &strict).is_ok());
+        let encoded = encode(query.clause_add("num", many(2)));
+        exceeded(
+            schema.decode_query_with_limits(encoded, &strict),
+            "validators",
+        );
+
+        // Oversized queries are rejected before being parsed
+        assert!(matches!(
+            schema.decode_query(vec![0; MAX_QUERY_SIZE + 1]),
+            Err(Error::LengthTooLong { .. })
+        ));
+    }
+
+    #[test]
+    fn query_clauses() {
+        use crate::query::QueryCombinator;
+        let post = MapValidator::new()
+            .req_add("title", StrValidator::new().query(true).build())
+            .map_ok(true)
+            .build();
+        let tag = StrValidator::new().query(true).build();
+        let schema = SchemaBuilder::new(Validator::Null)
+            .entry_add("post", post, None)
+            .entry_add("tag", tag, None)
+            .entry_add("note", StrValidator::new().build(), None)
+            .build()
+            .unwrap();
+        let schema = Schema::from_doc(&schema).unwrap();
+        let title = MapValidator::new()
+            .req_add("title", StrValidator::new().in_add("fog").build())
+            .build();
+        let tag = || StrValidator::new().in_add("rust").build();
+
+        // Every clause has to be allowed, even in an AND
+        let query = NewQuery::new("post", title.clone()).clause_add("tag", tag());
+        assert!(schema.encode_query(query).is_ok());
+        let query = NewQuery::new("post", title.clone()).clause_add("note", tag());
+        assert!(schema.encode_query(query).is_err());
+        let query = NewQuery::new("post", title.clone()).clause_add("reply", tag());
+        assert!(schema.encode_query(query).is_err());
+
+        let query = NewQuery::new("post", title.clone())
+            .clause_add("tag", tag())
+            .clause_add("tag", StrValidator::new().in_add("pack").build())
+            .combinator(QueryCombinator::Or);
+        let encoded = schema.encode_query(query.clone()).unwrap();
+        let decoded = schema.decode_query(encoded).unwrap();
+        assert_eq!(decoded.combine(), QueryCombinator::Or);
+        let clauses: Vec<(&str, &Validator)> = decoded.clauses().collect();
+        assert_eq!(clauses, query.clauses().collect::<Vec<_>>());
+        assert_eq!(clauses.len(), 3);
+        assert_eq!(clauses[0], ("post", &title));
+
+        // Plain queries have a single clause, combined with AND
+        let encoded = schema.encode_query(NewQuery::new("tag", tag())).unwrap();
+        let decoded = schema.decode_query(encoded).unwrap();
+        assert_eq!(decoded.combine(), QueryCombinator::And);
+        assert_eq!(decoded.clauses().count(), 1);
+    }
+
+    #[test]
+    fn query_clause_matching() {
+        use crate::query::QueryCombinator;
+        let schema = SchemaBuilder::new(Validator::Null)
+            .entry_add("post", StrValidator::new().query(true).build(), None)
+            .entry_add("tag", StrValidator::new().query(true).build(), None)
+            .build()
+            .unwrap();
+        let schema = Schema::from_doc(&schema).unwrap();
+        let parent = Hash::new(b"Pretend I am a parent document");
+        let entry = |key: &str, text: &str| {
+            let entry = NewEntry::new(text, key, &parent).unwrap();
+            let (_, encoded) = schema.encode_new_entry(entry).unwrap().complete().unwrap();
+            schema
+                .decode_entry(encoded, key, &parent)
+                .unwrap()
+                .complete()
+                .unwrap()
+        };
+        let tag = |text: &str| StrValidator::new().in_add(text).build();
+        let decode = |query: NewQuery| {
+            schema
+                .decode_query(schema.encode_query(query).unwrap())
+                .unwrap()
+        };
+        let check = |query: &Query, entry: &Entry| {
+            let expected = query.matches(entry).unwrap().is_match();
+            assert_eq!(query.compile().matches(entry).unwrap().is_match(), expected);
+            expected
+        };
+
+        // Every clause for the entry's key is used, combined with OR
+        let query = decode(
+            NewQuery::new("tag", tag("rust"))
+                .clause_add("tag", tag("pack"))
+                .combinator(QueryCombinator::Or),
+        );
+        assert!(check(&query, &entry("tag", "rust")));
+        assert!(check(&query, &entry("tag", "pack")));
+        assert!(!check(&query, &entry("tag", "fog")));
+
+        // ...or combined with AND
+        let query = decode(
+            NewQuery::new("tag", StrValidator::new().nin_add("rust").build())
+                .clause_add("tag", StrValidator::new().nin_add("pack").build()),
+        );
+        assert!(!check(&query, &entry("tag", "rust")));
+        assert!(!check(&query, &entry("tag", "pack")));
+        assert!(check(&query, &entry("tag", "fog")));
+
+        // Entries under a key without a clause never match
+        let query = decode(NewQuery::new("tag", StrValidator::new().build()));
+        assert!(check(&query, &entry("tag", "rust")));
+        assert!(!check(&query, &entry("post", "rust")));
+        let query = decode(
+            NewQuery::new("tag", tag("rust"))
+                .clause_add("tag", tag("pack"))
+                .combinator(QueryCombinator::Or),
+        );
+        assert!(!check(&query, &entry("post", "rust")));
+        let entries = [entry("tag", "pack"), entry("post", "pack")];
+        let found = query.filter(entries.iter()).unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].key(), "tag");
+    }
+
+    #[test]
+    fn query_matches() {
+        use crate::query::OrderDirection;
+        let post = MapValidator::new()
+            .req_add("title", StrValidator::new().query(true).build())
+            .opt_add("score", IntValidator::new().ord(true).build())
+            .map_ok(true)
+            .build();
+        let schema = SchemaBuilder::new(Validator::Null)
+            .entry_add("post", post, None)
+            .build()
+            .unwrap();
+        let schema = Schema::from_doc(&schema).unwrap();
+        let validator = MapValidator::new()
+            .req_add("title", StrValidator::new().in_add("fog").build())
+            .build();
+        let query = NewQuery::new("post", validator.clone())
+            .limit(10)
+            .order_by("score", OrderDirection::Ascending);
+        let query = schema
+            .decode_query(schema.encode_query(query).unwrap())
+            .unwrap();
+        assert_eq!(query.key(), "post");
+        assert_eq!(query.validator(), &validator);
+        assert_eq!(query.max_results(), Some(10));
+        assert_eq!(query.order().unwrap().path(), "score");
+
+        let parent = Hash::new(b"Pretend I am a parent document");
+        let entry = |title: &str| {
+            let mut data = BTreeMap::new();
+            data.insert("title".to_string(), Value::from(title));
+            let entry = NewEntry::new(data, "post", &parent).unwrap();
+            let (_, encoded) = schema.encode_new_entry(entry).unwrap().complete().unwrap();
+            schema
+                .decode_entry(encoded, "post", &parent)
+                .unwrap()
+                .complete()
+                .unwrap()
+        };
+        assert!(query.matches(&entry("fog")).unwrap().is_match());
+        assert!(!query.matches(&entry("pack")).unwrap().is_match());
+
+        // Malformed and disallowed queries are rejected when decoding
+        assert!(matches!(
+            schema.decode_query(vec![0xff, 0x00]),
+            Err(Error::BadEncode(_)) | Err(Error::SerdeFail(_))
+        ));
+        let other = SchemaBuilder::new(Validator::Null)
+            .entry_add("post", StrValidator::new().query(true).build(), None)
+            .build()
+            .unwrap();
+        let other = Schema::from_doc(&other).unwrap();
+        let encoded = other
+            .encode_query(NewQuery::new(
+                "post",
+                StrValidator::new().in_add("fog").build(),
+            ))
+            .unwrap();
+        let err = schema.decode_query(encoded).unwrap_err();
+        assert_eq!(
+            err.validate_error().unwrap().code,
+            ValidateCode::QueryNotAllowed
+        );
+    }
+
+    #[test]
+    fn query_exclude() {
+        use crate::validator::{HashValidator, NotValidator};
+        let link = MapValidator::new()
+            .req_add("kind", StrValidator::new().query(true).build())
+            .map_ok(true)
+            .build();
+        let post = MapValidator::new()
+            .req_add("author", StrValidator::new().query(true).build())
+            .req_add("title", StrValidator::new().build())
+            .opt_add(
+                "cover",
+                HashValidator::new().link(link).link_ok(true).build(),
+            )
+            .map_ok(true)
+            .build();
+        let schema = SchemaBuilder::new(Validator::Null)
+            .entry_add("post", post, None)
+            .build()
+            .unwrap();
+        let schema = Schema::from_doc(&schema).unwrap();
+        // Match one field, letting the others through. Map validators fail on keys they don't
+        // have a validator for, so every other field in the Entry must be listed.
+        let field = |key: &str, validator: Validator| {
+            ["author", "title", "cover"]
+                .iter()
+                .filter(|other| **other != key)
+                .fold(MapValidator::new(), |map, other| {
+                    map.opt_add(*other, Validator::Any)
+                })
+                .req_add(key, validator)
+                .build()
+        };
+        let author = |name: &str| field("author", StrValidator::new().in_add(name).build());
+        let title = |name: &str| field("title", StrValidator::new().in_add(name).build());
+        let denied = |query: NewQuery| {
+            let err = schema.encode_query(query).unwrap_err();
+            assert_eq!(
+                err.validate_error().unwrap().code,
+                ValidateCode::QueryNotAllowed
+            );
+        };
+
+        // Exclusions need the same permissions as the equivalent positive query
+        let query = NewQuery::new("post", Validator::Any).exclude(author("spammer"));
+        let query = schema
+            .decode_query(schema.encode_query(query).unwrap())
+            .unwrap();
+        denied(NewQuery::new("post", Validator::Any).exclude(title("secret")));
+        denied(NewQuery::new("post", author("alice")).exclude(title("secret")));
+
+        // So do doubly negated ones
+        let double = NotValidator::new(author("alice")).build();
+        assert!(schema
+            .encode_query(NewQuery::new("post", Validator::Any).exclude(double))
+            .is_ok());
+        let double = NotValidator::new(title("secret")).build();
+        denied(NewQuery::new("post", Validator::Any).exclude(double));
+
+        // Link requirements can't be negated, even where they're allowed
+        let kind = MapValidator::new()
+            .req_add("kind", StrValidator::new().in_add("image").build())
+            .build();
+        let cover = field("cover", HashValidator::new().link(kind.clone()).build());
+        assert!(schema
+            .encode_query(NewQuery::new("post", cover.clone()))
+            .is_ok());
+        denied(NewQuery::new("post", Validator::Any).exclude(cover));
+
+        let parent = Hash::new(b"Pretend I am a parent document");
+        let entry = |author: &str| {
+            let mut data = BTreeMap::new();
+            data.insert("author".to_string(), Value::from(author));
+            data.insert("title".to_string(), Value::from("Hello"));
+            let entry = NewEntry::new(data, "post", &parent).unwrap();
+            let (_, encoded) = schema.encode_new_entry(entry).unwrap().complete().unwrap();
+            schema
+                .decode_entry(encoded, "post", &parent)
+                .unwrap()
+                .complete()
+                .unwrap()
+        };
+        assert!(query.matches(&entry("alice")).unwrap().is_match());
+        assert!(!query.matches(&entry("spammer")).unwrap().is_match());
+
+        // Further exclusions are added to the same All validator
+        let query = NewQuery::new("post", Validator::Any)
+            .exclude(author("spammer"))
+            .exclude(author("troll"));
+        match query.validator() {
+            Validator::All(all) => assert_eq!(all.iter().count(), 3),
+            other => panic!("expected an All validator, got {:?}", other),
+        }
+        let query = schema
+            .decode_query(schema.encode_query(query).unwrap())
+            .unwrap();
+        assert!(query.matches(&entry("alice")).unwrap().is_match());
+        assert!(!query.matches(&entry("troll")).unwrap().is_match());
+    }
+
+    #[test]
+    fn query_linked() {
+        use crate::query::QueryMatch;
+        use crate::validator::HashValidator;
+        use std::collections::HashMap;
+        let attachment = MapValidator::new()
+            .req_add("kind", StrValidator::new().build())
+            .build();
+        let attach_schema = SchemaBuilder::new(attachment).build().unwrap();
+        let attach_schema = Schema::from_doc(&attach_schema).unwrap();
+        let entry_validator = |link_ok: bool| {
+            let link = MapValidator::new()
+                .req_add("kind", StrValidator::new().query(true).build())
+                .map_ok(true)
+                .build();
+            let hash = HashValidator::new()
+                .link(link)
+                .link_ok(link_ok)
+                .schema_ok(true)
+                .build();
+            MapValidator::new()
+                .req_add("attachment", hash)
+                .map_ok(true)
+                .build()
+        };
+        let schema = SchemaBuilder::new(Validator::Null)
+            .entry_add("post", entry_validator(true), None)
+            .build()
+            .unwrap();
+        let schema = Schema::from_doc(&schema).unwrap();
+
+        // An in-memory store of every attachment the executor holds
+        let kind = |kind: &str| {
+            let mut data = BTreeMap::new();
+            data.insert("kind".to_string(), Value::from(kind));
+            data
+        };
+        let image = attach_schema
+            .validate_new_doc(NewDocument::new(kind("image"), Some(attach_schema.hash())).unwrap())
+            .unwrap();
+        let text = attach_schema
+            .validate_new_doc(NewDocument::new(kind("text"), Some(attach_schema.hash())).unwrap())
+            .unwrap();
+        let loose =
+            NoSchema::validate_new_doc(NewDocument::new(kind("image"), None).unwrap()).unwrap();
+        let store: HashMap<Hash, Document> = vec![image.clone(), text.clone(), loose.clone()]
+            .into_iter()
+            .map(|doc| (doc.hash(), doc))
+            .collect();
+
+        let parent = Hash::new(b"Pretend I am a parent document");
+        let entry = |attachment: &Document| {
+            let mut data = BTreeMap::new();
+            data.insert("attachment".to_string(), Value::from(attachment.hash()));
+            let entry = NewEntry::new(data, "post", &parent).unwrap();
+            let mut checklist = schema.encode_new_entry(entry).unwrap();
+            checklist.check(attachment).unwrap();
+            let (_, encoded) = checklist.complete().unwrap();
+            let mut checklist = schema.decode_entry(encoded, "post", &parent).unwrap();
+            checklist.check(attachment).unwrap();
+            checklist.complete().unwrap()
+        };
+
+        // Entries whose attachment is an image under the attachment schema
+        let link = MapValidator::new()
+            .req_add("kind", StrValidator::new().in_add("image").build())
+            .build();
+        let query = MapValidator::new()
+            .req_add(
+                "attachment",
+                HashValidator::new()
+                    .link(link)
+                    .schema_add(attach_schema.hash().clone())
+                    .build(),
+            )
+            .build();
+        let query = schema
+            .decode_query(
+                schema
+                    .encode_query(NewQuery::new("post", query.clone()))
+                    .unwrap(),
+            )
+            .unwrap();
+
+        // Execute the query, looking up linked documents until the match is settled
+        let execute = |entry: &Entry| {
+            let mut result = query.matches(entry).unwrap();
+            while let QueryMatch::Pending(pending) = result {
+                let hash = pending.pending_hashes().next().unwrap().clone();
+                result = pending.resolve(&store[&hash]).unwrap();
+            }
+            result.is_match()
+        };
+        assert!(execute(&entry(&image)));
+        assert!(!execute(&entry(&text)));
+        assert!(!execute(&entry(&loose)));
+
+        // Only the pending documents can be fed in
+        match query.matches(&entry(&image)).unwrap() {
+            QueryMatch::Pending(pending) => {
+                assert_eq!(pending.pending_hashes().count(), 1);
+                assert!(pending.resolve(&text).is_err());
+            }
+            other => panic!("expected a pending match, got {:?}", other),
+        }
+
+        // Link constraints need the schema's permission
+        let strict = SchemaBuilder::new(Validator::Null)
+            .entry_add("post", entry_validator(false), None)
+            .build()
+            .unwrap();
+        let strict = Schema::from_doc(&strict).unwrap();
+        let link_query = MapValidator::new()
+            .req_add(
+                "attachment",
+                HashValidator::new()
+                    .link(
+                        MapValidator::new()
+                            .req_add("kind", StrValidator::new().in_add("image").build())
+                            .build(),
+                    )
+                    .build(),
+            )
+            .build();
+        assert!(strict
+            .encode_query(NewQuery::new("post", link_query.clone()))
+            .is_err());
+        assert!(schema
+            .encode_query(NewQuery::new("post", link_query))
+            .is_ok());
+    }
+
+    #[test]
+    fn query_doc() {
+        let doc = MapValidator::new()
+            .req_add("tag", StrValidator::new().query(true).build())
+            .req_add("title", StrValidator::new().build())
+            .opt_add("score", IntValidator::new().ord(true).build())
+            .map_ok(true)
+            .build();
+        let schema = SchemaBuilder::new(doc)
+            .entry_add("post", IntValidator::new().build(), None)
+            .build()
+            .unwrap();
+        let schema = Schema::from_doc(&schema).unwrap();
+        let field = |key: &str, validator: Validator| {
+            ["tag", "title", "score"]
+                .iter()
+                .filter(|other| **other != key)
+                .fold(MapValidator::new(), |map, other| {
+                    map.opt_add(*other, Validator::Any)
+                })
+                .req_add(key, validator)
+                .build()
+        };
+        let denied = |query: NewQuery| {
+            let err = schema.encode_query(query).unwrap_err();
+            assert_eq!(
+                err.validate_error().unwrap().code,
+                ValidateCode::QueryNotAllowed
+            );
+        };
+
+        // Root-level constraints need the document validator's permissions
+        let tag = field("tag", StrValidator::new().in_add("news").build());
+        let query = schema
+            .decode_query(schema.encode_query(NewQuery::doc(tag.clone())).unwrap())
+            .unwrap();
+        assert!(query.is_doc());
+        assert_eq!(query.key(), "");
+        denied(NewQuery::doc(field(
+            "title",
+            StrValidator::new().in_add("Hello").build(),
+        )));
+        schema
+            .encode_query(
+                NewQuery::doc(Validator::Any).order_by("score", OrderDirection::Ascending),
+            )
+            .unwrap();
+        denied(NewQuery::doc(Validator::Any).order_by("title", OrderDirection::Ascending));
+        denied(NewQuery::doc(tag.clone()).clause_add("post", Validator::Any));
+        // Entry validators don't grant document query permissions
+        denied(NewQuery::new("post", tag));
+
+        let make = |tag: &str| {
+            let mut data = BTreeMap::new();
+            data.insert("tag".to_string(), Value::from(tag));
+            data.insert("title".to_string(), Value::from("Hello"));
+            schema
+                .validate_new_doc(NewDocument::new(data, Some(schema.hash())).unwrap())
+                .unwrap()
+        };
+        assert!(query.matches_doc(&make("news")).unwrap().is_match());
+        assert!(!query.matches_doc(&make("sports")).unwrap().is_match());
+
+        // Document and Entry queries can't be run against the other target
+        let parent = Hash::new(b"Pretend I am a parent document");
+        let entry = NewEntry::new(1u8, "post", &parent).unwrap();
+        let (_, encoded) = schema.encode_new_entry(entry).unwrap().complete().unwrap();
+        let entry = schema
+            .decode_entry(encoded, "post", &parent)
+            .unwrap()
+            .complete()
+            .unwrap();
+        assert!(query.matches(&entry).is_err());
+        let entry_query = schema
+            .decode_query(
+                schema
+                    .encode_query(NewQuery::new("post", Validator::Any))
+                    .unwrap(),
+            )
+            .unwrap();
+        assert!(entry_query.matches_doc(&make("news")).is_err());
+    }
+
+    #[test]
+    fn query_filter() {
+        use fog_crypto::identity::IdentityKey;
+        let post = MapValidator::new()
+            .req_add("time", IntValidator::new().ord(true).build())
+            .req_add("tag", StrValidator::new().query(true).build())
+            .map_ok(true)
+            .build();
+        let schema = SchemaBuilder::new(Validator::Null)
+            .entry_add("post", post, None)
+            .entry_signer_ok("post", true)
+            .build()
+            .unwrap();
+        let schema = Schema::from_doc(&schema).unwrap();
+        let keys: Vec<IdentityKey> = (0..2)
+            .map(|_| IdentityKey::new_temp(&mut rand::rngs::OsRng))
+            .collect();
+        let parent = Hash::new(b"Pretend I am a parent document");
+        let entry = |time: i64, tag: &str, key: usize| {
+            let mut data = BTreeMap::new();
+            data.insert("time".to_string(), Value::from(time));
+            data.insert("tag".to_string(), Value::from(tag));
+            let entry = NewEntry::new(data, "post", &parent)
+                .unwrap()
+                .sign(&keys[key])
+                .unwrap();
+            let (_, encoded) = schema.encode_new_entry(entry).unwrap().complete().unwrap();
+            schema
+                .decode_entry(encoded, "post", &parent)
+                .unwrap()
+                .complete()
+                .unwrap()
+        };
+        // (time, tag, signer)
+        let corpus = [
+            (4, "news", 0),
+            (9, "news", 0),
+            (1, "news", 1),
+            (7, "sports", 0),
+            (2, "news", 0),
+            (6, "news", 0),
+            (8, "news", 1),
+            (3, "news", 0),
+        ];
+        let entries: Vec<Entry> = corpus.iter().map(|(t, g, k)| entry(*t, g, *k)).collect();
+        let times =
+            |results: &[&Entry]| -> Vec<i64> { results.iter().map(|e| entry_time(e)).collect() };
+
+        let tag = MapValidator::new()
+            .req_add("tag", StrValidator::new().in_add("news").build())
+            .opt_add("time", Validator::Any)
+            .build();
+        let query = || {
+            NewQuery::new("post", tag.clone())
+                .signed_by(vec![keys[0].id().clone()])
+                .order_by("time", OrderDirection::Descending)
+                .limit(2)
+        };
+        let decode = |query: NewQuery| {
+            schema
+                .decode_query(schema.encode_query(query).unwrap())
+                .unwrap()
+        };
+
+        // News posts signed by the first key, newest first: 9, 6, 4, 3, 2
+        let first = decode(query());
+        let page = first.filter(entries.iter()).unwrap();
+        assert_eq!(times(&page), vec![9, 6]);
+        let second = decode(query().after(first.cursor(page[1]).unwrap()));
+        let page = second.filter(entries.iter()).unwrap();
+        assert_eq!(times(&page), vec![4, 3]);
+        let third = decode(query().after(second.cursor(page[1]).unwrap()));
+        let page = third.filter(entries.iter()).unwrap();
+        assert_eq!(times(&page), vec![2]);
+        let last = decode(query().after(third.cursor(page[0]).unwrap()));
+        assert!(last.filter(entries.iter()).unwrap().is_empty());
+
+        // Without ordering or a limit, every match is returned in Entry hash order
+        let all = decode(NewQuery::new("post", tag.clone()));
+        let results = all.filter(entries.iter()).unwrap();
+        let mut expected: Vec<&Entry> = entries
+            .iter()
+            .filter(|e| {
+                e.deserialize::<BTreeMap<String, Value>>().unwrap()["tag"].as_str() == Some("news")
+            })
+            .collect();
+        expected.sort_by_key(|e| e.hash());
+        assert_eq!(results.len(), 7);
+        assert!(results
+            .iter()
+            .zip(expected.iter())
+            .all(|(a, b)| a.hash() == b.hash()));
+
+        // Ascending order
+        let asc = decode(
+            NewQuery::new("post", tag.clone())
+                .order_by("time", OrderDirection::Ascending)
+                .limit(3),
+        );
+        assert_eq!(times(&asc.filter(entries.iter()).unwrap()), vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn query_signers() {
+        use fog_crypto::identity::IdentityKey;
+        let schema = |signer_ok: bool| {
+            let schema = SchemaBuilder::new(Validator::Null)
+                .entry_add("post", IntValidator::new().build(), None)
+                .entry_signer_ok("post", signer_ok)
+                .build()
+                .unwrap();
+            Schema::from_doc(&schema).unwrap()
+        };
+        let keys: Vec<IdentityKey> = (0..3)
+            .map(|_| IdentityKey::new_temp(&mut rand::rngs::OsRng))
+            .collect();
+        let query = || {
+            NewQuery::new("post", IntValidator::new().build())
+                .signed_by(keys[..2].iter().map(|k| k.id().clone()).collect())
+        };
+
+        assert!(schema(false).encode_query(query()).is_err());
+        let schema = schema(true);
+        let encoded = schema.encode_query(query()).unwrap();
+        let query = schema.decode_query(encoded).unwrap();
+        assert_eq!(query.signers().len(), 2);
+
+        let parent = Hash::new(b"Pretend I am a parent document");
+        let entry = |key: Option<&IdentityKey>| {
+            let mut entry = NewEntry::new(1u8, "post", &parent).unwrap();
+            if let Some(key) = key {
+                entry = entry.sign(key).unwrap();
+            }
+            let (_, encoded) = schema.encode_new_entry(entry).unwrap().complete().unwrap();
+            schema
+                .decode_entry(encoded, "post", &parent)
+                .unwrap()
+                .complete()
+                .unwrap()
+        };
+        assert!(query.matches(&entry(Some(&keys[0]))).unwrap().is_match());
+        assert!(query.matches(&entry(Some(&keys[1]))).unwrap().is_match());
+        assert!(!query.matches(&entry(Some(&keys[2]))).unwrap().is_match());
+        assert!(!query.matches(&entry(None)).unwrap().is_match());
+
+        // Re-signing replaces the signer
+        let resigned = NewEntry::new(1u8, "post", &parent)
+            .unwrap()
+            .sign(&keys[0])
+            .unwrap()
+            .sign(&keys[2])
+            .unwrap();
+        let (_, encoded) = schema
+            .encode_new_entry(resigned)
+            .unwrap()
+            .complete()
+            .unwrap();
+        let resigned = schema
+            .decode_entry(encoded, "post", &parent)
+            .unwrap()
+            .complete()
+            .unwrap();
+        assert!(!query.matches(&resigned).unwrap().is_match());
+
+        // Signer queries are opt-in
+        let plain = SchemaBuilder::new(Validator::Null)
+            .entry_add("post", IntValidator::new().build(), None)
+            .build()
+            .unwrap();
+        let plain = Schema::from_doc(&plain).unwrap();
+        assert!(plain
+            .encode_query(
+                NewQuery::new("post", IntValidator::new().build())
+                    .signed_by(vec![keys[0].id().clone()])
+            )
+            .is_err());
+        assert!(plain
+            .encode_query(NewQuery::new("post", IntValidator::new().build()))
+            .is_ok());
+    }
+
+    #[test]
+    fn from_doc_with_types() {
+        let mut base = BTreeMap::new();
+        base.insert("title".to_string(), StrValidator::new().max_len(8).build());
+        let doc = MapValidator::new()
+            .req_add("title", Validator::new_ref("title"))
+            .build();
+
+        // The derived schema can't be used without the base types
+        assert!(SchemaBuilder::new(doc.clone()).build().is_err());
+        let schema_doc = SchemaBuilder::new(doc).build_with_types(&base).unwrap();
+        assert!(Schema::from_doc(&schema_doc).is_err());
+        let schema = Schema::from_doc_with_types(&schema_doc, base.clone()).unwrap();
+        assert_eq!(schema.named_types().count(), 1);
+
+        let mut value = BTreeMap::new();
+        value.insert("title".to_string(), Value::from("short"));
+        let doc = NewDocument::new(&value, Some(schema.hash())).unwrap();
+        assert!(schema.validate_new_doc(doc).is_ok());
+        value.insert("title".to_string(), Value::from("much too long"));
+        let doc = NewDocument::new(&value, Some(schema.hash())).unwrap();
+        assert!(schema.validate_new_doc(doc).is_err());
+
+        // Schemas may repeat a supplied type, but can't redefine it
+        let same = SchemaBuilder::new(Validator::new_ref("title"))
+            .type_add("title", StrValidator::new().max_len(8).build())
+            .build()
+            .unwrap();
+        assert!(Schema::from_doc_with_types(&same, base.clone()).is_ok());
+        let different = SchemaBuilder::new(Validator::new_ref("title"))
+            .type_add("title", StrValidator::new().build())
+            .build()
+            .unwrap();
+        assert!(Schema::from_doc_with_types(&different, base).is_err());
+    }
+
+    #[test]
+    fn parse_mode() {
+        let doc = MapValidator::new()
+            .req_add("title", StrValidator::new().max_len(8).build())
+            .build();
+        let schema_doc = SchemaBuilder::new(doc)
+            .type_add("id", IntValidator::new().build())
+            .build()
+            .unwrap();
+
+        // Add fields from a hypothetical newer version, at the top level and inside validators
+        let mut value: Value = schema_doc.deserialize().unwrap();
+        let extra = ["doc.Map.req.title.Str.new_field", "types.id.Int.new_field"];
+        for path in extra.iter() {
+            let path = ValuePath::parse(path).unwrap();
+            let (last, parent) = path.segments().split_last().unwrap();
+            let parent = ValuePath::new(parent.to_vec()).get_mut(&mut value).unwrap();
+            if let PathSegment::Key(key) = last {
+                parent
+                    .as_map_mut()
+                    .unwrap()
+                    .insert(key.clone(), Value::from(1u8));
+            }
+        }
+        value
+            .as_map_mut()
+            .unwrap()
+            .insert("new_field".to_string(), Value::from(true));
+        let schema_doc =
+            NoSchema::validate_new_doc(NewDocument::new(&value, None).unwrap()).unwrap();
+
+        // Strict mode rejects it, lenient mode loads it and reports the unknown fields
+        assert!(Schema::from_doc(&schema_doc).is_err());
+        let schema =
+            Schema::from_doc_with_mode(&schema_doc, SchemaParseMode::IgnoreUnknown).unwrap();
+        assert_eq!(schema.hash(), &schema_doc.hash());
+        let mut unknown: Vec<String> = schema
+            .unknown_fields()
+            .iter()
+            .map(|p| p.to_string())
+            .collect();
+        unknown.sort();
+        assert_eq!(
+            unknown,
+            vec![
+                "doc.Map.req.title.Str.new_field",
+                "new_field",
+                "types.id.Int.new_field"
+            ]
+        );
+
+        // The known parts of the schema still work
+        let mut doc = BTreeMap::new();
+        doc.insert("title".to_string(), Value::from("short"));
+        let new_doc = NewDocument::new(&doc, Some(schema.hash())).unwrap();
+        assert!(schema.validate_new_doc(new_doc).is_ok());
+        doc.insert("title".to_string(), Value::from("much too long"));
+        let new_doc = NewDocument::new(&doc, Some(schema.hash())).unwrap();
+        assert!(schema.validate_new_doc(new_doc).is_err());
+
+        // A schema with nothing unknown loads the same either way
+        let plain = SchemaBuilder::new(Validator::Any).build().unwrap();
+        let schema = Schema::from_doc_with_mode(&plain, SchemaParseMode::IgnoreUnknown).unwrap();
+        assert!(schema.unknown_fields().is_empty());
+    }
+
+    #[test]
+    fn named_types() {
+        let post = MapValidator::new()
+            .req_add("title", StrValidator::new().build())
+            .build();
+        let schema = SchemaBuilder::new(Validator::new_ref("post"))
+            .type_add("post", post.clone())
+            .type_add("count", IntValidator::new().build())
+            .entry_add("reply", Validator::new_ref("post"), None)
+            .build()
+            .unwrap();
+        let schema = Schema::from_doc(&schema).unwrap();
+        let types: Vec<(&str, &Validator)> = schema.named_types().collect();
+        assert_eq!(types.len(), 2);
+        assert_eq!(types[0].0, "count");
+        assert_eq!(types[1], ("post", &post));
+
+        // Refs to missing types are caught up front, wherever they are
+        assert!(SchemaBuilder::new(Validator::new_ref("missing"))
+            .build()
+            .is_err());
+        assert!(SchemaBuilder::new(Validator::Null)
+            .entry_add(
+                "e",
+                MapValidator::new()
+                    .opt_add("a", Validator::new_ref("missing"))
+                    .build(),
+                None
+            )
+            .build()
+            .is_err());
+    }
+
+    #[test]
+    fn unused_types() {
+        use crate::validator::{AllValidator, ArrayValidator, EnumValidator, MultiValidator};
+        // "used" is only reachable through "direct", which is reached through nested validators.
+        // "unused" refers to "also_unused", but nothing refers to "unused".
+        let doc = MapValidator::new()
+            .opt_add(
+                "a",
+                ArrayValidator::new()
+                    .items(
+                        MultiValidator::new()
+                            .push(Validator::Null)
+                            .push(
+                                AllValidator::new()
+                                    .push(Validator::new_ref("direct"))
+                                    .build(),
+                            )
+                            .build(),
+                    )
+                    .build(),
+            )
+            .build();
+        let builder = SchemaBuilder::new(doc)
+            .type_add(
+                "direct",
+                EnumValidator::new()
+                    .insert("Used", Some(Validator::new_ref("used")))
+                    .build(),
+            )
+            .type_add("used", IntValidator::new().build())
+            .type_add("unused", Validator::new_ref("also_unused"))
+            .type_add("also_unused", StrValidator::new().build());
+        assert_eq!(builder.unused_types(), vec!["also_unused", "unused"]);
+
+        // Entry validators count as uses too
+        let with_entry = builder
+            .clone()
+            .entry_add("e", Validator::new_ref("unused"), None);
+        assert!(with_entry.unused_types().is_empty());
+
+        let kept = Schema::from_doc(&builder.clone().build().unwrap()).unwrap();
+        assert_eq!(kept.named_types().count(), 4);
+        let pruned = builder.prune_unused(true).build().unwrap();
+        let pruned = Schema::from_doc(&pruned).unwrap();
+        let names: Vec<&str> = pruned.named_types().map(|(name, _)| name).collect();
+        assert_eq!(names, vec!["direct", "used"]);
+    }
+}
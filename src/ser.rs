@@ -9,7 +9,7 @@
 
 use fog_crypto::serde::FOG_TYPE_ENUM;
 use serde::ser::*;
-use std::{collections::BTreeMap, convert::TryFrom, mem};
+use std::{collections::BTreeMap, convert::TryFrom, fmt, mem};
 
 use crate::marker::ExtType;
 use crate::{element::*, MAX_DOC_SIZE};
@@ -18,6 +18,39 @@ use crate::error::{Error, Result};
 
 use crate::depth_tracking::DepthTracker;
 
+/// Prefix a serialization error from a nested value with its location, so failures deep inside
+/// arrays and maps report a full path like `.entries[2].time: map key must be a string`. The path
+/// is built up as the error returns through each containing value, so successful serialization
+/// doesn't pay for tracking it.
+fn nest_error(err: Error, segment: impl fmt::Display) -> Error {
+    match err {
+        Error::SerdeFail(msg) => {
+            if msg.starts_with('[') || msg.starts_with('.') {
+                Error::SerdeFail(format!("{}{}", segment, msg))
+            } else {
+                Error::SerdeFail(format!("{}: {}", segment, msg))
+            }
+        }
+        err => err,
+    }
+}
+
+struct Index(usize);
+
+impl fmt::Display for Index {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "[{}]", self.0)
+    }
+}
+
+struct Key<'a>(&'a str);
+
+impl fmt::Display for Key<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, ".{}", self.0)
+    }
+}
+
 #[derive(Clone, Debug)]
 pub(crate) struct FogSerializer {
     must_be_ordered: bool,
@@ -78,10 +111,10 @@ impl<'a> Serializer for &'a mut FogSerializer {
     type SerializeSeq = SeqSerializer<'a>;
     type SerializeTuple = TupleSerializer<'a>;
     type SerializeTupleStruct = TupleSerializer<'a>;
-    type SerializeTupleVariant = TupleSerializer<'a>;
+    type SerializeTupleVariant = VariantSerializer<TupleSerializer<'a>>;
     type SerializeMap = MapSerializer<'a>;
     type SerializeStruct = StructSerializer<'a>;
-    type SerializeStructVariant = StructSerializer<'a>;
+    type SerializeStructVariant = VariantSerializer<StructSerializer<'a>>;
 
     fn is_human_readable(&self) -> bool {
         false
@@ -193,7 +226,9 @@ impl<'a> Serializer for &'a mut FogSerializer {
         } else {
             self.encode_element(Element::Map(1))?;
             self.encode_element(Element::Str(variant))?;
-            value.serialize(self)
+            value
+                .serialize(self)
+                .map_err(|e| nest_error(e, Key(variant)))
         }
     }
 
@@ -226,7 +261,7 @@ impl<'a> Serializer for &'a mut FogSerializer {
         self.encode_element(Element::Map(1))?;
         self.encode_element(Element::Str(variant))?;
         self.encode_element(Element::Array(len))?;
-        Ok(TupleSerializer::new(self))
+        Ok(VariantSerializer::new(TupleSerializer::new(self), variant))
     }
 
     fn serialize_map(self, len: Option<usize>) -> Result<Self::SerializeMap> {
@@ -248,7 +283,7 @@ impl<'a> Serializer for &'a mut FogSerializer {
         self.encode_element(Element::Map(1))?;
         self.encode_element(Element::Str(variant))?;
         self.encode_element(Element::Map(len))?;
-        Ok(StructSerializer::new(self))
+        Ok(VariantSerializer::new(StructSerializer::new(self), variant))
     }
 
     fn collect_seq<I>(self, iter: I) -> Result<()>
@@ -310,7 +345,8 @@ impl<'a> Serializer for &'a mut FogSerializer {
                     } else {
                         last_key = Some(mem::take(&mut new_key));
                     }
-                    v.serialize(&mut *self)?;
+                    v.serialize(&mut *self)
+                        .map_err(|e| nest_error(e, Key(last_key.as_deref().unwrap_or_default())))?;
                 }
             } else {
                 // Sized & Unordered
@@ -326,7 +362,7 @@ impl<'a> Serializer for &'a mut FogSerializer {
                 // Serialize in order
                 for (k, v) in map.iter() {
                     self.encode_element(Element::Str(k))?;
-                    v.serialize(&mut *self)?;
+                    v.serialize(&mut *self).map_err(|e| nest_error(e, Key(k)))?;
                 }
             }
         } else if self.must_be_ordered {
@@ -348,7 +384,7 @@ impl<'a> Serializer for &'a mut FogSerializer {
             self.encode_element(Element::Map(map.len()))?;
             for (k, v) in map.iter() {
                 self.encode_element(Element::Str(k))?;
-                v.serialize(&mut *self)?;
+                v.serialize(&mut *self).map_err(|e| nest_error(e, Key(k)))?;
             }
         } else {
             // Unsized & Unordered
@@ -365,7 +401,7 @@ impl<'a> Serializer for &'a mut FogSerializer {
             self.encode_element(Element::Map(map.len()))?;
             for (k, v) in map.iter() {
                 self.encode_element(Element::Str(k))?;
-                v.serialize(&mut *self)?;
+                v.serialize(&mut *self).map_err(|e| nest_error(e, Key(k)))?;
             }
         }
         Ok(())
@@ -390,6 +426,7 @@ impl<'a> Serializer for &'a mut FogSerializer {
 /// instead, in which case we can avoid temporarily encoding to a buffer.
 pub(crate) struct SeqSerializer<'a> {
     se: &'a mut FogSerializer,
+    index: usize,
     unknown_len: Option<(usize, Vec<u8>)>,
 }
 
@@ -399,6 +436,7 @@ impl<'a> SeqSerializer<'a> {
             se.encode_element(Element::Array(len))?;
             Ok(Self {
                 se,
+                index: 0,
                 unknown_len: None,
             })
         } else {
@@ -407,6 +445,7 @@ impl<'a> SeqSerializer<'a> {
             let enc = mem::take(&mut se.buf);
             Ok(Self {
                 se,
+                index: 0,
                 unknown_len: Some((0, enc)),
             })
         }
@@ -427,7 +466,11 @@ impl<'a> SerializeSeq for SeqSerializer<'a> {
                 )));
             }
         }
-        value.serialize(&mut *self.se)
+        let index = self.index;
+        self.index += 1;
+        value
+            .serialize(&mut *self.se)
+            .map_err(|e| nest_error(e, Index(index)))
     }
 
     fn end(self) -> Result<()> {
@@ -445,11 +488,20 @@ impl<'a> SerializeSeq for SeqSerializer<'a> {
 
 pub(crate) struct TupleSerializer<'a> {
     se: &'a mut FogSerializer,
+    index: usize,
 }
 
 impl<'a> TupleSerializer<'a> {
     fn new(se: &'a mut FogSerializer) -> Self {
-        Self { se }
+        Self { se, index: 0 }
+    }
+
+    fn serialize_inner<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        let index = self.index;
+        self.index += 1;
+        value
+            .serialize(&mut *self.se)
+            .map_err(|e| nest_error(e, Index(index)))
     }
 }
 
@@ -458,7 +510,7 @@ impl<'a> SerializeTuple for TupleSerializer<'a> {
     type Error = Error;
 
     fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
-        value.serialize(&mut *self.se)
+        self.serialize_inner(value)
     }
 
     fn end(self) -> Result<()> {
@@ -471,7 +523,7 @@ impl<'a> SerializeTupleStruct for TupleSerializer<'a> {
     type Error = Error;
 
     fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
-        value.serialize(&mut *self.se)
+        self.serialize_inner(value)
     }
 
     fn end(self) -> Result<()> {
@@ -479,12 +531,27 @@ impl<'a> SerializeTupleStruct for TupleSerializer<'a> {
     }
 }
 
-impl<'a> SerializeTupleVariant for TupleSerializer<'a> {
+/// Wraps the serializer for an enum variant's contents, so errors within the contents report the
+/// variant name as part of their path.
+pub(crate) struct VariantSerializer<S> {
+    inner: S,
+    variant: &'static str,
+}
+
+impl<S> VariantSerializer<S> {
+    fn new(inner: S, variant: &'static str) -> Self {
+        Self { inner, variant }
+    }
+}
+
+impl<'a> SerializeTupleVariant for VariantSerializer<TupleSerializer<'a>> {
     type Ok = ();
     type Error = Error;
 
     fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
-        value.serialize(&mut *self.se)
+        self.inner
+            .serialize_inner(value)
+            .map_err(|e| nest_error(e, Key(self.variant)))
     }
 
     fn end(self) -> Result<()> {
@@ -625,8 +692,10 @@ impl<'a> SerializeMap for MapSerializer<'a> {
 
     fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
         match self {
-            MapSerializer::SizedOrdered { se, .. } => {
-                value.serialize(&mut **se)?;
+            MapSerializer::SizedOrdered { se, last_key, .. } => {
+                value
+                    .serialize(&mut **se)
+                    .map_err(|e| nest_error(e, Key(last_key.as_deref().unwrap_or_default())))?;
             }
             MapSerializer::SizedUnordered {
                 se,
@@ -637,7 +706,9 @@ impl<'a> SerializeMap for MapSerializer<'a> {
                 // off for later reordering
                 let buf = mem::take(&mut se.buf);
                 se.encode_element(Element::Str(pending_key))?;
-                value.serialize(&mut **se)?;
+                value
+                    .serialize(&mut **se)
+                    .map_err(|e| nest_error(e, Key(pending_key)))?;
                 // Replace buffers & store off in BTreeMap
                 let buf = mem::replace(&mut se.buf, buf);
                 let key = mem::take(pending_key);
@@ -645,8 +716,10 @@ impl<'a> SerializeMap for MapSerializer<'a> {
                     return Err(Error::SerdeFail("map has repeated keys".into()));
                 }
             }
-            MapSerializer::UnsizedOrdered { se, .. } => {
-                value.serialize(&mut **se)?;
+            MapSerializer::UnsizedOrdered { se, last_key, .. } => {
+                value
+                    .serialize(&mut **se)
+                    .map_err(|e| nest_error(e, Key(last_key.as_deref().unwrap_or_default())))?;
             }
             MapSerializer::UnsizedUnordered {
                 se,
@@ -657,7 +730,9 @@ impl<'a> SerializeMap for MapSerializer<'a> {
                 // off for later reordering
                 let buf = mem::take(&mut se.buf);
                 se.encode_element(Element::Str(pending_key))?;
-                value.serialize(&mut **se)?;
+                value
+                    .serialize(&mut **se)
+                    .map_err(|e| nest_error(e, Key(pending_key)))?;
                 // Replace buffers & store off in BTreeMap
                 let buf = mem::replace(&mut se.buf, buf);
                 let key = mem::take(pending_key);
@@ -748,14 +823,18 @@ impl<'a> StructSerializer<'a> {
                     *last_key = Some(field);
                 }
                 se.encode_element(Element::Str(field))?;
-                value.serialize(&mut **se)?;
+                value
+                    .serialize(&mut **se)
+                    .map_err(|e| nest_error(e, Key(field)))?;
             }
             StructSerializer::Unordered { se, map } => {
                 // Slot in buffer, fill it like we're writing to the actual buffer, then store it
                 // off for later reordering
                 let buf = mem::take(&mut se.buf);
                 se.encode_element(Element::Str(field))?;
-                value.serialize(&mut **se)?;
+                value
+                    .serialize(&mut **se)
+                    .map_err(|e| nest_error(e, Key(field)))?;
                 // Replace buffers & store off in BTreeMap
                 let buf = mem::replace(&mut se.buf, buf);
                 map.insert(field, buf); // Structs should never have repeated fields, so don't check for them
@@ -794,7 +873,7 @@ impl<'a> SerializeStruct for StructSerializer<'a> {
     }
 }
 
-impl<'a> SerializeStructVariant for StructSerializer<'a> {
+impl<'a> SerializeStructVariant for VariantSerializer<StructSerializer<'a>> {
     type Ok = ();
     type Error = Error;
 
@@ -803,11 +882,13 @@ impl<'a> SerializeStructVariant for StructSerializer<'a> {
         field: &'static str,
         value: &T,
     ) -> Result<()> {
-        self.serialize_field_inner(field, value)
+        self.inner
+            .serialize_field_inner(field, value)
+            .map_err(|e| nest_error(e, Key(self.variant)))
     }
 
     fn end(self) -> Result<()> {
-        self.end_inner();
+        self.inner.end_inner();
         Ok(())
     }
 }
@@ -1922,4 +2003,57 @@ mod test {
             assert_eq!(ser.buf, enc);
         }
     }
+
+    #[test]
+    fn error_path() {
+        #[derive(Serialize)]
+        struct Entry {
+            time: BTreeMap<i8, u8>,
+        }
+        #[derive(Serialize)]
+        enum Wrapper {
+            Tuple(u8, Vec<Entry>),
+        }
+        #[derive(Serialize)]
+        struct Log {
+            entries: Vec<Entry>,
+            wrapped: Option<Wrapper>,
+        }
+
+        let bad = || {
+            let mut time = BTreeMap::new();
+            time.insert(1i8, 1u8);
+            Entry { time }
+        };
+        let ok = || Entry {
+            time: BTreeMap::new(),
+        };
+        let error_msg = |log: Log, ordered: bool| {
+            let mut ser = FogSerializer::with_params(ordered);
+            match log.serialize(&mut ser) {
+                Err(Error::SerdeFail(msg)) => msg,
+                other => panic!("expected serialization failure, got {:?}", other),
+            }
+        };
+
+        for &ordered in [false, true].iter() {
+            let log = Log {
+                entries: vec![ok(), ok(), bad()],
+                wrapped: None,
+            };
+            let msg = error_msg(log, ordered);
+            assert!(msg.starts_with(".entries[2].time: "), "got {}", msg);
+
+            let log = Log {
+                entries: Vec::new(),
+                wrapped: Some(Wrapper::Tuple(0, vec![bad()])),
+            };
+            let msg = error_msg(log, ordered);
+            assert!(
+                msg.starts_with(".wrapped.Tuple[1][0].time: "),
+                "got {}",
+                msg
+            );
+        }
+    }
 }
@@ -0,0 +1,353 @@
+//! Random generators for property testing with validators.
+//!
+//! This module is only available with the `testing` feature enabled. It provides two generators:
+//!
+//! - [`Validator::arbitrary`], which makes a random validator tree
+//! - [`generate_conforming`], which makes a random value that passes a given validator
+//!
+//! Together, they can be used to fuzz code that handles schema'd data, by generating a random
+//! schema and then random documents that adhere to it. Both take any [`rand::Rng`], so they can be
+//! driven by a seeded RNG for reproducible tests, or by the RNG of a property testing framework.
+//!
+//! Generated validators cover the Null, Bool, Int, F32, F64, Bin, Str, Time, Array, Map, Enum,
+//! and Multi validators, with length limits, ranges, `in`/`nin` lists, and array `prefix`/`items`
+//! combinations. Constraints that are hard to satisfy at random, like regular expressions or
+//! bit fields, are never generated, so every generated validator has values that pass it.
+//!
+//! ```
+//! # use fog_pack::{testing::generate_conforming, validator::Validator};
+//! # use rand::SeedableRng;
+//! let mut rng = rand::rngs::StdRng::seed_from_u64(1);
+//! let validator = Validator::arbitrary(&mut rng, 3);
+//! let value = generate_conforming(&validator, &mut rng);
+//! ```
+
+use crate::{integer::Integer, timestamp::Timestamp, validator::*, value::Value};
+use rand::{seq::SliceRandom, Rng};
+use std::collections::BTreeMap;
+
+/// Largest number of items generated for arrays, maps, and lists.
+const MAX_ITEMS: usize = 4;
+
+/// Characters used in generated strings. Multi-byte characters are included so that byte and
+/// character lengths differ.
+const CHARS: &[char] = &['a', 'b', 'c', 'x', 'y', 'z', '0', '9', ' ', 'é', 'λ', '中'];
+
+fn random_string<R: Rng + ?Sized>(rng: &mut R, min_len: usize, max_len: usize) -> String {
+    let mut s = String::new();
+    let target = rng.gen_range(min_len, max_len + 1);
+    while s.len() < target {
+        let c = *CHARS.choose(rng).unwrap();
+        if s.len() + c.len_utf8() > max_len {
+            s.push('a');
+        } else {
+            s.push(c);
+        }
+    }
+    s
+}
+
+fn random_bytes<R: Rng + ?Sized>(rng: &mut R, min_len: usize, max_len: usize) -> Vec<u8> {
+    let len = rng.gen_range(min_len, max_len + 1);
+    (0..len).map(|_| rng.gen()).collect()
+}
+
+/// Make a random lower and upper bound, as `(min, max)` with `min <= max`.
+fn random_range<R: Rng + ?Sized>(rng: &mut R, low: i64, high: i64) -> (i64, i64) {
+    let a = rng.gen_range(low, high + 1);
+    let b = rng.gen_range(low, high + 1);
+    (a.min(b), a.max(b))
+}
+
+/// Pick a random key for a map, not already in `used`.
+fn random_key<R: Rng + ?Sized>(rng: &mut R, used: &BTreeMap<String, Validator>) -> String {
+    loop {
+        let key = random_string(rng, 1, 6);
+        if !used.contains_key(&key) {
+            return key;
+        }
+    }
+}
+
+impl Validator {
+    /// Make a random validator, nesting arrays, maps, enums, and multi-validators no more than
+    /// `depth` levels deep. A depth of 0 only makes validators for single values. Every validator
+    /// made has values that pass it, which [`generate_conforming`] can make.
+    ///
+    /// Only available with the `testing` feature.
+    pub fn arbitrary<R: Rng + ?Sized>(rng: &mut R, depth: u32) -> Validator {
+        let kinds = if depth == 0 { 8 } else { 12 };
+        match rng.gen_range(0, kinds) {
+            0 => Validator::Null,
+            1 => BoolValidator::new().build(),
+            2 => arbitrary_int(rng),
+            3 => arbitrary_f32(rng),
+            4 => arbitrary_f64(rng),
+            5 => arbitrary_bin(rng),
+            6 => arbitrary_str(rng),
+            7 => arbitrary_time(rng),
+            8 => arbitrary_array(rng, depth - 1),
+            9 => arbitrary_map(rng, depth - 1),
+            10 => arbitrary_enum(rng, depth - 1),
+            _ => arbitrary_multi(rng, depth - 1),
+        }
+    }
+}
+
+fn arbitrary_int<R: Rng + ?Sized>(rng: &mut R) -> Validator {
+    let (min, max) = random_range(rng, -1000, 1000);
+    let mut validator = IntValidator::new().min(min).max(max + 1);
+    if rng.gen_bool(0.3) {
+        for _ in 0..rng.gen_range(1, MAX_ITEMS) {
+            validator = validator.in_add(rng.gen_range(min, max + 1));
+        }
+    } else if rng.gen_bool(0.3) {
+        // The range always has at least two values, so one can be banned
+        validator = validator.nin_add(rng.gen_range(min, max + 2));
+    }
+    validator.build()
+}
+
+fn arbitrary_f32<R: Rng + ?Sized>(rng: &mut R) -> Validator {
+    let (min, max) = random_range(rng, -1000, 1000);
+    let mut validator = F32Validator::new().min(min as f32).max(max as f32);
+    if rng.gen_bool(0.3) {
+        validator = validator.in_add(min as f32).in_add(max as f32);
+    }
+    Validator::F32(validator)
+}
+
+fn arbitrary_f64<R: Rng + ?Sized>(rng: &mut R) -> Validator {
+    let (min, max) = random_range(rng, -1000, 1000);
+    let mut validator = F64Validator::new().min(min as f64).max(max as f64);
+    if rng.gen_bool(0.3) {
+        validator = validator.in_add(min as f64).in_add(max as f64);
+    }
+    Validator::F64(validator)
+}
+
+fn arbitrary_bin<R: Rng + ?Sized>(rng: &mut R) -> Validator {
+    let (min, max) = random_range(rng, 0, 16);
+    let mut validator = BinValidator::new().min_len(min as u32).max_len(max as u32);
+    if rng.gen_bool(0.3) {
+        for _ in 0..rng.gen_range(1, MAX_ITEMS) {
+            validator = validator.in_add(random_bytes(rng, min as usize, max as usize));
+        }
+    }
+    validator.build()
+}
+
+fn arbitrary_str<R: Rng + ?Sized>(rng: &mut R) -> Validator {
+    let (min, max) = random_range(rng, 0, 16);
+    let mut validator = StrValidator::new().min_len(min as u32).max_len(max as u32);
+    if rng.gen_bool(0.3) {
+        for _ in 0..rng.gen_range(1, MAX_ITEMS) {
+            validator = validator.in_add(random_string(rng, min as usize, max as usize));
+        }
+    } else if rng.gen_bool(0.2) {
+        // Leave room for more than one string, so one can be banned
+        validator = StrValidator::new()
+            .min_len(min as u32)
+            .max_len(max as u32 + 1)
+            .nin_add(random_string(rng, min as usize, max as usize));
+    }
+    validator.build()
+}
+
+fn arbitrary_time<R: Rng + ?Sized>(rng: &mut R) -> Validator {
+    let (min, max) = random_range(rng, 0, 4_000_000_000);
+    let mut validator = TimeValidator::new()
+        .min(Timestamp::from_sec(min))
+        .max(Timestamp::from_sec(max));
+    if rng.gen_bool(0.3) {
+        validator = validator.in_add(Timestamp::from_sec(rng.gen_range(min, max + 1)));
+    }
+    validator.build()
+}
+
+fn arbitrary_array<R: Rng + ?Sized>(rng: &mut R, depth: u32) -> Validator {
+    let prefix_len = rng.gen_range(0, 3);
+    let mut validator = ArrayValidator::new();
+    for _ in 0..prefix_len {
+        validator = validator.prefix_add(Validator::arbitrary(rng, depth));
+    }
+    let (min, max) = random_range(rng, 0, MAX_ITEMS as i64);
+    validator
+        .items(Validator::arbitrary(rng, depth))
+        .min_len(min as u32)
+        .max_len(max as u32)
+        .build()
+}
+
+fn arbitrary_map<R: Rng + ?Sized>(rng: &mut R, depth: u32) -> Validator {
+    let mut map = MapValidator::new();
+    for _ in 0..rng.gen_range(0, MAX_ITEMS) {
+        let key = random_key(rng, &map.req);
+        map.req.insert(key, Validator::arbitrary(rng, depth));
+    }
+    for _ in 0..rng.gen_range(0, MAX_ITEMS) {
+        let key = random_key(rng, &map.req);
+        map.opt
+            .entry(key)
+            .or_insert_with(|| Validator::arbitrary(rng, depth));
+    }
+    map.build()
+}
+
+fn arbitrary_enum<R: Rng + ?Sized>(rng: &mut R, depth: u32) -> Validator {
+    let mut validator = EnumValidator::new();
+    for i in 0..rng.gen_range(1, MAX_ITEMS) {
+        let content = if rng.gen_bool(0.5) {
+            Some(Validator::arbitrary(rng, depth))
+        } else {
+            None
+        };
+        validator = validator.insert(format!("var{}", i), content);
+    }
+    validator.build()
+}
+
+fn arbitrary_multi<R: Rng + ?Sized>(rng: &mut R, depth: u32) -> Validator {
+    let mut validator = MultiValidator::new();
+    for _ in 0..rng.gen_range(1, MAX_ITEMS) {
+        // Nested Multi validators are skipped during validation, so don't put any in
+        let branch = loop {
+            let branch = Validator::arbitrary(rng, depth);
+            if !matches!(branch, Validator::Multi(_)) {
+                break branch;
+            }
+        };
+        validator = validator.push(branch);
+    }
+    validator.build()
+}
+
+/// Make a random value that passes `validator`.
+///
+/// This supports every validator that [`Validator::arbitrary`] can make. Validators with
+/// constraints it doesn't know how to satisfy, like regular expressions, lockboxes, or
+/// references to named types, cause a panic.
+///
+/// Only available with the `testing` feature.
+pub fn generate_conforming<R: Rng + ?Sized>(validator: &Validator, rng: &mut R) -> Value {
+    match validator {
+        Validator::Null => Value::Null,
+        Validator::Bool(_) => Value::Bool(rng.gen()),
+        Validator::Int(v) => {
+            if let Some(int) = v.in_list.choose(rng) {
+                return Value::Int(*int);
+            }
+            let min = v.min.as_i64().expect("int minimum out of range");
+            let max = v.max.as_i64().expect("int maximum out of range");
+            loop {
+                let int = Integer::from(rng.gen_range(min, max + 1));
+                if !v.nin_list.contains(&int) {
+                    return Value::Int(int);
+                }
+            }
+        }
+        Validator::F32(v) => match v.in_list.choose(rng) {
+            Some(f) => Value::F32(*f),
+            None => Value::F32(rng.gen_range(v.min, v.max + 1.0).min(v.max)),
+        },
+        Validator::F64(v) => match v.in_list.choose(rng) {
+            Some(f) => Value::F64(*f),
+            None => Value::F64(rng.gen_range(v.min, v.max + 1.0).min(v.max)),
+        },
+        Validator::Bin(v) => match v.in_list.choose(rng) {
+            Some(bin) => Value::Bin(bin.to_vec()),
+            None => Value::Bin(random_bytes(rng, v.min_len as usize, v.max_len as usize)),
+        },
+        Validator::Str(v) => {
+            if let Some(s) = v.in_list.choose(rng) {
+                return Value::Str(s.clone());
+            }
+            loop {
+                let s = random_string(rng, v.min_len as usize, v.max_len as usize);
+                if !v.nin_list.contains(&s) {
+                    return Value::Str(s);
+                }
+            }
+        }
+        Validator::Time(v) => match v.in_list.choose(rng) {
+            Some(time) => Value::Timestamp(*time),
+            None => {
+                let sec = rng.gen_range(v.min.timestamp_utc(), v.max.timestamp_utc() + 1);
+                Value::Timestamp(Timestamp::from_sec(sec))
+            }
+        },
+        Validator::Array(v) => {
+            let len = rng.gen_range(v.min_len, v.max_len + 1) as usize;
+            let array = (0..len)
+                .map(|i| generate_conforming(v.prefix.get(i).unwrap_or(&v.items), rng))
+                .collect::<Vec<Value>>();
+            Value::Array(array)
+        }
+        Validator::Map(v) => {
+            let mut map = BTreeMap::new();
+            for (key, validator) in v.req.iter() {
+                map.insert(key.clone(), generate_conforming(validator, rng));
+            }
+            for (key, validator) in v.opt.iter() {
+                if rng.gen_bool(0.5) {
+                    map.insert(key.clone(), generate_conforming(validator, rng));
+                }
+            }
+            Value::Map(map)
+        }
+        Validator::Enum(v) => {
+            let variants: Vec<(&String, &Option<Validator>)> = v.var.iter().collect();
+            let (name, content) = variants.choose(rng).expect("enum has no named variants");
+            match content {
+                Some(content) => {
+                    let mut map = BTreeMap::new();
+                    map.insert((*name).clone(), generate_conforming(content, rng));
+                    Value::Map(map)
+                }
+                None => Value::Str((*name).clone()),
+            }
+        }
+        Validator::Multi(v) => {
+            let branch = v.0.choose(rng).expect("multi-validator has no branches");
+            generate_conforming(branch, rng)
+        }
+        Validator::Const(v) => v.value.clone(),
+        Validator::Any => Value::Null,
+        validator => panic!("can't generate values for validator {:?}", validator),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{element::Parser, ser::FogSerializer};
+    use rand::{rngs::StdRng, SeedableRng};
+    use serde::Serialize;
+
+    #[test]
+    fn values_conform() {
+        let types = BTreeMap::new();
+        let mut rng = StdRng::seed_from_u64(0x5eed);
+        for _ in 0..500 {
+            let mut validator = Validator::arbitrary(&mut rng, 3);
+            validator.check_build().unwrap();
+            validator.build_index();
+            for _ in 0..4 {
+                let value = generate_conforming(&validator, &mut rng);
+                let mut ser = FogSerializer::default();
+                value.serialize(&mut ser).unwrap();
+                let data = ser.finish();
+                let result = validator
+                    .validate(&types, Parser::new(&data), None)
+                    .and_then(|(parser, _)| parser.finish());
+                assert!(
+                    result.is_ok(),
+                    "{:?} failed {:?}: {}",
+                    value,
+                    validator,
+                    result.unwrap_err()
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,259 @@
+use super::*;
+use crate::error::{Error, Result, ValidateCode};
+use serde::{Deserialize, Serialize};
+
+/// "All" validator that checks with several validators at once.
+///
+/// This validator will only accept a value that passes every one of its contained validators.
+/// This can be used like an "all of" operator, or a logical AND of validators. An empty list
+/// accepts any value.
+///
+/// When going through the contained validators, some rules are followed to avoid possible cyclic
+/// references:
+///
+/// - Contained All-validators fail validation
+/// - Contained Ref validators that refer to an All-validator fail validation.
+/// - Contained Ref validators that refer to a Ref validator fail validation.
+///
+/// More succintly, the banned sequences are: All->All, All->Ref->All, All->Ref->Ref. Unlike the
+/// [`Multi`][MultiValidator] validator, banned validators aren't skipped, as skipping them would
+/// make the All-validator accept more than it should.
+///
+/// # Query Checking
+///
+/// The validator for a query must be accepted by every one of the validators in the
+/// All-validator. If a contained validator violates the cyclic reference rules, the query is
+/// rejected.
+///
+/// When a query uses an All-validator, each of its contained validators must be accepted by the
+/// schema's validator.
+///
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct AllValidator(pub Vec<Validator>);
+
+impl AllValidator {
+    /// Make a new validator with the default configuration.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a new validator to the list.
+    pub fn push(mut self, validator: Validator) -> Self {
+        self.0.push(validator);
+        self
+    }
+
+    /// Build this into a [`Validator`] enum.
+    pub fn build(self) -> Validator {
+        Validator::All(self)
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, Validator> {
+        self.0.iter()
+    }
+
+    /// Get the contained validator, following a Ref if need be. Returns `None` if this would
+    /// break the cyclic reference rules.
+    fn resolve<'c>(
+        types: &'c BTreeMap<String, Validator>,
+        validator: &'c Validator,
+    ) -> Option<&'c Validator> {
+        match validator {
+            Validator::Ref(ref_name) => match types.get(ref_name) {
+                Some(Validator::Ref(_)) | Some(Validator::All(_)) | None => None,
+                Some(validator) => Some(validator),
+            },
+            Validator::All(_) => None,
+            validator => Some(validator),
+        }
+    }
+
+    pub(crate) fn validate<'de, 'c>(
+        &'c self,
+        types: &'c BTreeMap<String, Validator>,
+        mut parser: Parser<'de>,
+        mut checklist: Option<Checklist<'c>>,
+    ) -> Result<(Parser<'de>, Option<Checklist<'c>>)> {
+        if self.0.is_empty() {
+            read_any(&mut parser)?;
+            return Ok((parser, checklist));
+        }
+
+        // Every validator sees the same starting parser state. All of them read exactly one
+        // value, so any passing parser state is the one to return. The checklist is threaded
+        // through each validator so every Hash requirement is collected.
+        let mut end_parser = None;
+        let mut failures = Vec::new();
+        for (index, validator) in self.0.iter().enumerate() {
+            let validator = match Self::resolve(types, validator) {
+                Some(validator) => validator,
+                None => {
+                    failures.push(format!("[{}]: banned cyclic validator", index));
+                    continue;
+                }
+            };
+            match validator.validate(types, parser.clone(), checklist.clone()) {
+                Ok((new_parser, new_checklist)) => {
+                    end_parser = Some(new_parser);
+                    checklist = new_checklist;
+                }
+                Err(Error::FailValidate(err)) => failures.push(format!("[{}]: {}", index, err)),
+                Err(err) => return Err(err),
+            }
+        }
+
+        match end_parser {
+            Some(parser) if failures.is_empty() => Ok((parser, checklist)),
+            _ => Err(Error::validate(
+                ValidateCode::NoBranch,
+                format!("validator All failed on branches {}", failures.join("; ")),
+            )),
+        }
+    }
+
+    pub(crate) fn query_check(
+        &self,
+        types: &BTreeMap<String, Validator>,
+        other: &Validator,
+    ) -> bool {
+        self.0
+            .iter()
+            .all(|validator| match Self::resolve(types, validator) {
+                Some(validator) => validator.query_check(types, other),
+                None => false,
+            })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{de::FogDeserializer, element::Parser, ser::FogSerializer, value::Value};
+
+    fn validate(
+        types: &BTreeMap<String, Validator>,
+        validator: &Validator,
+        value: impl Into<Value>,
+    ) -> Result<()> {
+        let mut ser = FogSerializer::default();
+        value.into().serialize(&mut ser).unwrap();
+        let data = ser.finish();
+        let (parser, _) = validator.validate(types, Parser::new(&data), None)?;
+        parser.finish()
+    }
+
+    #[test]
+    fn conjunction() {
+        let mut types = BTreeMap::new();
+        types.insert("name".to_string(), StrValidator::new().min_len(2).build());
+        let validator = AllValidator::new()
+            .push(Validator::new_ref("name"))
+            .push(StrValidator::new().max_len(10).build())
+            .build();
+        assert!(validate(&types, &validator, "fog").is_ok());
+        assert!(validate(&types, &validator, "f").is_err());
+        assert!(validate(&types, &validator, "fog-pack-rocks").is_err());
+        assert!(validate(&types, &validator, 1u8).is_err());
+
+        // Error messages report each failing branch
+        match validate(&types, &validator, 1u8) {
+            Err(Error::FailValidate(err)) => {
+                assert_eq!(err.code, ValidateCode::NoBranch);
+                assert!(err.message.contains("[0]"));
+                assert!(err.message.contains("[1]"));
+            }
+            _ => panic!("expected a validation failure"),
+        }
+
+        // An empty list passes anything
+        assert!(validate(&types, &AllValidator::new().build(), 1u8).is_ok());
+    }
+
+    #[test]
+    fn cyclic() {
+        let mut types = BTreeMap::new();
+        types.insert("all".to_string(), AllValidator::new().build());
+        types.insert("ref".to_string(), Validator::new_ref("all"));
+        for banned in [
+            AllValidator::new().build(),
+            Validator::new_ref("all"),
+            Validator::new_ref("ref"),
+        ]
+        .iter()
+        {
+            let validator = AllValidator::new().push(banned.clone()).build();
+            assert!(validate(&types, &validator, 1u8).is_err());
+        }
+
+        // Multi can't contain an All, so this can't recurse forever
+        types.insert(
+            "loop".to_string(),
+            AllValidator::new()
+                .push(
+                    MultiValidator::new()
+                        .push(Validator::new_ref("loop"))
+                        .build(),
+                )
+                .build(),
+        );
+        assert!(validate(&types, &Validator::new_ref("loop"), 1u8).is_err());
+    }
+
+    #[test]
+    fn nested() {
+        let types = BTreeMap::new();
+        let small = AllValidator::new()
+            .push(IntValidator::new().min(0u8).build())
+            .push(IntValidator::new().max(10u8).build())
+            .build();
+        let array = ArrayValidator::new().items(small.clone()).build();
+        assert!(validate(&types, &array, vec![Value::from(1u8), Value::from(10u8)]).is_ok());
+        assert!(validate(&types, &array, vec![Value::from(1u8), Value::from(11u8)]).is_err());
+
+        let map = MapValidator::new().req_add("n", small).build();
+        let mut value = BTreeMap::new();
+        value.insert("n".to_string(), Value::from(5u8));
+        assert!(validate(&types, &map, value.clone()).is_ok());
+        value.insert("n".to_string(), Value::from(-5i8));
+        assert!(validate(&types, &map, value).is_err());
+    }
+
+    #[test]
+    fn ser_round_trip() {
+        let validator = MapValidator::new()
+            .req_add(
+                "a",
+                AllValidator::new()
+                    .push(StrValidator::new().max_len(10).build())
+                    .push(Validator::new_ref("name"))
+                    .build(),
+            )
+            .build();
+        let mut ser = FogSerializer::default();
+        validator.serialize(&mut ser).unwrap();
+        let encoded = ser.finish();
+        let mut de = FogDeserializer::new(&encoded);
+        let decoded = Validator::deserialize(&mut de).unwrap();
+        assert_eq!(validator, decoded);
+    }
+
+    #[test]
+    fn query() {
+        let types = BTreeMap::new();
+        let schema = AllValidator::new()
+            .push(IntValidator::new().query(true).build())
+            .push(IntValidator::new().query(true).ord(true).build())
+            .build();
+        let query_in = IntValidator::new().in_add(1u8).build();
+        let query_ord = IntValidator::new().max(5u8).build();
+        assert!(schema.query_check(&types, &query_in));
+        assert!(!schema.query_check(&types, &query_ord));
+
+        // All queries need every branch to be allowed
+        let schema = IntValidator::new().query(true).build();
+        let query = AllValidator::new().push(query_in.clone()).build();
+        assert!(schema.query_check(&types, &query));
+        let query = AllValidator::new().push(query_in).push(query_ord).build();
+        assert!(!schema.query_check(&types, &query));
+    }
+}
@@ -0,0 +1,171 @@
+use super::*;
+use crate::element::*;
+use crate::error::Result;
+use serde::{Deserialize, Serialize};
+
+#[inline]
+fn u32_is_max(v: &u32) -> bool {
+    *v == u32::MAX
+}
+
+/// Validator that accepts any value within size limits.
+///
+/// This validator works like [`Validator::Any`], passing any valid fog-pack value, but also puts
+/// limits on how large the value may be. Validation passes if:
+///
+/// - The encoded value is no more than `max_bytes` bytes long.
+/// - Arrays and maps in the value are nested no more than `max_depth` levels deep. A value with
+///   no arrays or maps has a depth of 0, and `[[1]]` has a depth of 2.
+///
+/// This is useful for fields that are meant to be forward-compatible, where any value should be
+/// accepted, but without letting the field grow up to the full document size limit.
+///
+/// # Defaults
+///
+/// Fields that aren't specified for the validator use their defaults instead. The defaults for
+/// each field are:
+///
+/// - comment: ""
+/// - max_bytes: u32::MAX
+/// - max_depth: u32::MAX
+///
+/// # Query Checking
+///
+/// This validator is treated exactly like [`Validator::Any`] when checking queries.
+///
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields, default)]
+pub struct AnyValidator {
+    /// An optional comment explaining the validator.
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub comment: String,
+    /// The maximum number of bytes the encoded value may take up.
+    #[serde(skip_serializing_if = "u32_is_max")]
+    pub max_bytes: u32,
+    /// The maximum nesting depth of arrays and maps in the value.
+    #[serde(skip_serializing_if = "u32_is_max")]
+    pub max_depth: u32,
+}
+
+impl std::default::Default for AnyValidator {
+    fn default() -> Self {
+        Self {
+            comment: String::new(),
+            max_bytes: u32::MAX,
+            max_depth: u32::MAX,
+        }
+    }
+}
+
+impl AnyValidator {
+    /// Make a new validator with the default configuration.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set a comment for the validator.
+    pub fn comment(mut self, comment: impl Into<String>) -> Self {
+        self.comment = comment.into();
+        self
+    }
+
+    /// Set the maximum number of bytes the encoded value may take up.
+    pub fn max_bytes(mut self, max_bytes: u32) -> Self {
+        self.max_bytes = max_bytes;
+        self
+    }
+
+    /// Set the maximum nesting depth of arrays and maps in the value.
+    pub fn max_depth(mut self, max_depth: u32) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    /// Build this into a [`Validator`] enum.
+    pub fn build(self) -> Validator {
+        Validator::AnyBounded(self)
+    }
+
+    pub(crate) fn validate(&self, parser: &mut Parser) -> Result<()> {
+        read_any_bounded(parser, self.max_bytes as usize, self.max_depth)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{de::FogDeserializer, ser::FogSerializer, value::Value};
+
+    fn encode(value: &Value) -> Vec<u8> {
+        let mut ser = FogSerializer::default();
+        value.serialize(&mut ser).unwrap();
+        ser.finish()
+    }
+
+    fn validate(validator: &Validator, value: &Value) -> Result<()> {
+        let types = BTreeMap::new();
+        let data = encode(value);
+        let (parser, _) = validator.validate(&types, Parser::new(&data), None)?;
+        parser.finish()
+    }
+
+    #[test]
+    fn ser_default() {
+        let schema = AnyValidator::default();
+        let mut ser = FogSerializer::default();
+        schema.serialize(&mut ser).unwrap();
+        let expected: Vec<u8> = vec![0x80];
+        assert_eq!(ser.finish(), expected);
+    }
+
+    #[test]
+    fn ser_round_trip() {
+        let validator = AnyValidator::new().max_bytes(100).max_depth(2).build();
+        let mut ser = FogSerializer::default();
+        validator.serialize(&mut ser).unwrap();
+        let encoded = ser.finish();
+        let mut de = FogDeserializer::new(&encoded);
+        assert_eq!(validator, Validator::deserialize(&mut de).unwrap());
+    }
+
+    #[test]
+    fn byte_limit() {
+        let value = Value::from(vec![Value::from("abc"); 10]);
+        let len = encode(&value).len() as u32;
+        let validator = |max| AnyValidator::new().max_bytes(max).build();
+        assert!(validate(&validator(len), &value).is_ok());
+        assert!(validate(&validator(len - 1), &value).is_err());
+
+        // A single large value is caught too
+        let value = Value::from(vec![0u8; 64]);
+        let len = encode(&value).len() as u32;
+        assert!(validate(&validator(len), &value).is_ok());
+        assert!(validate(&validator(len - 1), &value).is_err());
+    }
+
+    #[test]
+    fn depth_limit() {
+        let validator = |max| AnyValidator::new().max_depth(max).build();
+        let scalar = Value::from(1u8);
+        let nested = Value::from(vec![Value::from(vec![Value::from(1u8)])]);
+        let mut map = BTreeMap::new();
+        map.insert("a".to_string(), nested.clone());
+        let map = Value::from(map);
+        assert!(validate(&validator(0), &scalar).is_ok());
+        assert!(validate(&validator(0), &nested).is_err());
+        assert!(validate(&validator(1), &nested).is_err());
+        assert!(validate(&validator(2), &nested).is_ok());
+        assert!(validate(&validator(2), &map).is_err());
+        assert!(validate(&validator(3), &map).is_ok());
+    }
+
+    #[test]
+    fn query_as_any() {
+        let types = BTreeMap::new();
+        let bounded = AnyValidator::new().max_bytes(10).build();
+        let schema = IntValidator::new().build();
+        assert!(schema.query_check(&types, &bounded));
+        assert!(!bounded.query_check(&types, &Validator::Any));
+        assert!(!bounded.query_check(&types, &schema));
+    }
+}
@@ -1,6 +1,11 @@
 use super::*;
-use crate::error::{Error, Result};
-use crate::{de::FogDeserializer, element::*, value::Value, value_ref::ValueRef};
+use crate::error::{Error, Result, ValidateCode};
+use crate::{
+    de::FogDeserializer,
+    element::*,
+    value::{PathSegment, Value},
+    value_ref::ValueRef,
+};
 use serde::{Deserialize, Serialize};
 use std::{default::Default, iter::repeat};
 
@@ -23,6 +28,94 @@ fn u32_is_max(v: &u32) -> bool {
     *v == u32::MAX
 }
 
+/// A path to an array nested inside an array being validated, for use in `same_len`.
+///
+/// The path always starts with an index into the array being validated, optionally followed by
+/// map keys and array indices that descend into that item. A path that is only an index is
+/// encoded as a bare integer; longer paths are encoded as an array of integers (indices) and
+/// strings (map keys).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SameLenPath(pub Vec<PathSegment>);
+
+impl SameLenPath {
+    /// Make a path that refers directly to an item in the array.
+    pub fn index(index: usize) -> Self {
+        Self(vec![PathSegment::Index(index)])
+    }
+
+    /// Extend the path with a map key.
+    pub fn key(mut self, key: impl Into<String>) -> Self {
+        self.0.push(PathSegment::Key(key.into()));
+        self
+    }
+
+    /// Extend the path with an array index.
+    pub fn then_index(mut self, index: usize) -> Self {
+        self.0.push(PathSegment::Index(index));
+        self
+    }
+}
+
+impl std::fmt::Display for SameLenPath {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        self.0
+            .iter()
+            .try_for_each(|segment| write!(f, "{}", segment))
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum SameLenSegment {
+    Index(usize),
+    Key(String),
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum SameLenRepr {
+    Index(usize),
+    Path(Vec<SameLenSegment>),
+}
+
+impl Serialize for SameLenPath {
+    fn serialize<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error> {
+        let repr = match self.0.as_slice() {
+            [PathSegment::Index(index)] => SameLenRepr::Index(*index),
+            path => SameLenRepr::Path(
+                path.iter()
+                    .map(|segment| match segment {
+                        PathSegment::Index(index) => SameLenSegment::Index(*index),
+                        PathSegment::Key(key) => SameLenSegment::Key(key.clone()),
+                    })
+                    .collect(),
+            ),
+        };
+        repr.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for SameLenPath {
+    fn deserialize<D: serde::Deserializer<'de>>(
+        deserializer: D,
+    ) -> std::result::Result<Self, D::Error> {
+        Ok(match SameLenRepr::deserialize(deserializer)? {
+            SameLenRepr::Index(index) => SameLenPath::index(index),
+            SameLenRepr::Path(path) => SameLenPath(
+                path.into_iter()
+                    .map(|segment| match segment {
+                        SameLenSegment::Index(index) => PathSegment::Index(index),
+                        SameLenSegment::Key(key) => PathSegment::Key(key),
+                    })
+                    .collect(),
+            ),
+        })
+    }
+}
+
 /// Validator for arrays.
 ///
 /// This validator type will only pass array values. Validation passes if:
@@ -31,8 +124,15 @@ fn u32_is_max(v: &u32) -> bool {
 /// - The array must not be among the arrays in the `nin` list.
 /// - The arrays's length is less than or equal to the value in `max_len`.
 /// - The arrays's length is greater than or equal to the value in `min_len`.
+/// - The number of items after the `prefix` validators is at least `min_extra`.
+/// - All arrays found at the paths in `same_len` have the same length.
 /// - If `unique` is true, the array items are all unique.
-/// - For each validator in the `contains` list, at least one item in the array passes.
+/// - If `unique_by` is not empty, the array items must all be maps, and the values found at the
+///   `unique_by` keys must be unique across the items. A missing key counts as its own "absent"
+///   value, so two items both missing a key are only distinct if they differ on another key.
+/// - For each validator in the `contains` list, at least one item in the array passes. If the
+///   validator has a matching entry in `contains_count`, the number of passing items must
+///   instead be between the entry's minimum and maximum, inclusive.
 /// - Each item in the array is checked with a validator at the same index in the `prefix` array.
 ///     All validators must pass. If there is no validator at the same index, the validator in
 ///     `items` must pass. If a validator is not used, it passes automatially.
@@ -44,13 +144,17 @@ fn u32_is_max(v: &u32) -> bool {
 ///
 /// - comment: ""
 /// - contains: empty
+/// - contains_count: empty
+/// - same_len: empty
 /// - items: Validator::Any
 /// - prefix: empty
 /// - max_len: u32::MAX
 /// - min_len: u32::MIN
+/// - min_extra: 0
 /// - in_list: empty
 /// - nin_list: empty
 /// - unique: false
+/// - unique_by: empty
 /// - query: false
 /// - array: false
 /// - contains_ok: false
@@ -64,9 +168,9 @@ fn u32_is_max(v: &u32) -> bool {
 ///
 /// - query: `in` and `nin` lists
 /// - array: `prefix` and `items`
-/// - contains_ok: `contains`
-/// - unique_ok: `unique`
-/// - size: `max_len` and `min_len`
+/// - contains_ok: `contains` and `contains_count`
+/// - unique_ok: `unique` and `unique_by`
+/// - size: `max_len`, `min_len`, `min_extra`, and `same_len`
 ///
 /// In addition, sub-validators in the query are matched against the schema's sub-validators:
 ///
@@ -85,6 +189,11 @@ pub struct ArrayValidator {
     /// For each validator in this array, at least one item in the array must pass the validator.
     #[serde(skip_serializing_if = "Vec::is_empty")]
     pub contains: Vec<Validator>,
+    /// The minimum and maximum number of items that must pass each validator in `contains`,
+    /// matched up by index. Validators without a matching entry use `(1, u32::MAX)`. This cannot
+    /// be longer than `contains`.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub contains_count: Vec<(u32, u32)>,
     /// A validator that each item in the array must pass, unless it is instead checked by
     /// `prefix`.
     #[serde(skip_serializing_if = "validator_is_any")]
@@ -100,15 +209,29 @@ pub struct ArrayValidator {
     /// The minimum allowed number of items in the array.
     #[serde(skip_serializing_if = "u32_is_zero")]
     pub min_len: u32,
+    /// The minimum number of items that must follow the ones matched up with `prefix`.
+    #[serde(skip_serializing_if = "u32_is_zero")]
+    pub min_extra: u32,
+    /// A list of paths to arrays nested within this array, which must all have the same length.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub same_len: Vec<SameLenPath>,
     /// A vector of specific allowed values, stored under the `in` field. If empty, this vector is not checked against.
     #[serde(rename = "in", skip_serializing_if = "Vec::is_empty")]
     pub in_list: Vec<Vec<Value>>,
     /// A vector of specific unallowed values, stored under the `nin` field.
     #[serde(rename = "nin", skip_serializing_if = "Vec::is_empty")]
     pub nin_list: Vec<Vec<Value>>,
+    /// Precomputed lookup index for the `in` and `nin` lists, holding the encoded form of each
+    /// array.
+    #[serde(skip)]
+    pub(crate) list_index: ListIndex<Vec<u8>>,
     /// If set, all items in the array must be unique.
     #[serde(skip_serializing_if = "is_false")]
     pub unique: bool,
+    /// If not empty, all items in the array must be maps, and the set of values under these keys
+    /// must be unique across all items.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub unique_by: Vec<String>,
     /// If true, queries against matching spots may have values in the `in` or `nin` lists.
     #[serde(skip_serializing_if = "is_false")]
     pub query: bool,
@@ -131,13 +254,18 @@ impl Default for ArrayValidator {
         Self {
             comment: String::new(),
             contains: Vec::new(),
+            contains_count: Vec::new(),
             items: Box::new(Validator::Any),
             prefix: Vec::new(),
             max_len: u32::MAX,
             min_len: u32::MIN,
+            min_extra: 0,
+            same_len: Vec::new(),
             in_list: Vec::new(),
             nin_list: Vec::new(),
+            list_index: ListIndex::default(),
             unique: false,
+            unique_by: Vec::new(),
             query: false,
             array: false,
             contains_ok: false,
@@ -165,6 +293,16 @@ impl ArrayValidator {
         self
     }
 
+    /// Extend the `contains` list with another validator, which must be passed by between `min`
+    /// and `max` items in the array, inclusive.
+    pub fn contains_count_add(mut self, validator: Validator, min: u32, max: u32) -> Self {
+        self.contains_count
+            .resize(self.contains.len(), (1, u32::MAX));
+        self.contains.push(validator);
+        self.contains_count.push((min, max));
+        self
+    }
+
     /// Set the `items` validator.
     pub fn items(mut self, items: Validator) -> Self {
         self.items = Box::new(items);
@@ -177,6 +315,12 @@ impl ArrayValidator {
         self
     }
 
+    /// Replace the `prefix` list with a new list of validators.
+    pub fn prefix(mut self, prefix: impl IntoIterator<Item = Validator>) -> Self {
+        self.prefix = prefix.into_iter().collect();
+        self
+    }
+
     /// Set the maximum number of allowed bytes.
     pub fn max_len(mut self, max_len: u32) -> Self {
         self.max_len = max_len;
@@ -189,6 +333,18 @@ impl ArrayValidator {
         self
     }
 
+    /// Set the minimum number of items that must follow the `prefix` items.
+    pub fn min_extra(mut self, min_extra: u32) -> Self {
+        self.min_extra = min_extra;
+        self
+    }
+
+    /// Add a path to the `same_len` list.
+    pub fn same_len_add(mut self, path: SameLenPath) -> Self {
+        self.same_len.push(path);
+        self
+    }
+
     /// Add a value to the `in` list.
     pub fn in_add(mut self, add: impl Into<Vec<Value>>) -> Self {
         self.in_list.push(add.into());
@@ -207,6 +363,12 @@ impl ArrayValidator {
         self
     }
 
+    /// Add a key to the `unique_by` list.
+    pub fn unique_by_add(mut self, key: impl Into<String>) -> Self {
+        self.unique_by.push(key.into());
+        self
+    }
+
     /// Set whether or not queries can use the `in` and `nin` lists.
     pub fn query(mut self, query: bool) -> Self {
         self.query = query;
@@ -242,6 +404,156 @@ impl ArrayValidator {
         Validator::Array(self)
     }
 
+    pub(crate) fn check_build(&self) -> Result<()> {
+        if self.contains_count.len() > self.contains.len() {
+            return Err(Error::validate(
+                ValidateCode::BadValidator,
+                format!(
+                "Array validator has {} `contains_count` entries but only {} `contains` validators",
+                self.contains_count.len(),
+                self.contains.len()
+            ),
+            ));
+        }
+        if let Some((index, (min, max))) = self
+            .contains_count
+            .iter()
+            .enumerate()
+            .find(|(_, (min, max))| min > max)
+        {
+            return Err(Error::validate(
+                ValidateCode::BadValidator,
+                format!(
+                    "Array validator `contains_count` entry {} has a minimum of {}, greater than its maximum of {}",
+                    index, min, max
+                ),
+            ));
+        }
+        if let Some(path) = self
+            .same_len
+            .iter()
+            .find(|path| !matches!(path.0.first(), Some(PathSegment::Index(_))))
+        {
+            return Err(Error::validate(
+                ValidateCode::BadValidator,
+                format!(
+                    "Array validator `same_len` path \"{}\" doesn't start with an index",
+                    path
+                ),
+            ));
+        }
+        let min_total = self.prefix.len() as u64 + self.min_extra as u64;
+        if self.min_extra > 0 && min_total > self.max_len as u64 {
+            return Err(Error::validate(ValidateCode::BadValidator, format!(
+                "Array validator needs at least {} items ({} prefix + {} min_extra), but max_len is {}",
+                min_total,
+                self.prefix.len(),
+                self.min_extra,
+                self.max_len
+            )));
+        }
+        Ok(())
+    }
+
+    pub(crate) fn build_index(&mut self) {
+        // Floats compare differently by value than by encoding (NaN never equals itself, and -0.0
+        // equals 0.0), so lists with any floats are always checked by value instead.
+        fn has_float(v: &Value) -> bool {
+            match v {
+                Value::F32(_) | Value::F64(_) => true,
+                Value::Array(a) => a.iter().any(has_float),
+                Value::Map(m) => m.values().any(has_float),
+                _ => false,
+            }
+        }
+        if self
+            .in_list
+            .iter()
+            .chain(self.nin_list.iter())
+            .flatten()
+            .any(has_float)
+        {
+            self.list_index = ListIndex::default();
+            return;
+        }
+        // Arrays that can't be encoded will never match anything, so they're left out of the
+        // index.
+        let encode = |v: &Vec<Value>| -> Option<Vec<u8>> {
+            let mut ser = FogSerializer::default();
+            v.serialize(&mut ser).ok()?;
+            Some(ser.finish())
+        };
+        self.list_index = ListIndex::build(
+            self.in_list.iter().filter_map(encode),
+            self.nin_list.iter().filter_map(encode),
+        );
+    }
+
+    /// Find the length of the array at a `same_len` path, starting from a parser positioned at
+    /// the start of the array being validated. The parser is cloned, so normal validation is
+    /// unaffected.
+    fn same_len_of<'a>(parser: &Parser<'a>, path: &SameLenPath) -> Result<usize> {
+        let not_found = || {
+            Error::validate(
+                ValidateCode::WrongType,
+                format!("Array has no value at `same_len` path {}", path),
+            )
+        };
+        let next = |parser: &mut Parser<'a>| -> Result<Result<Element<'a>>> {
+            parser.next().ok_or_else(not_found)
+        };
+        let mut parser = parser.clone();
+        let mut current = next(&mut parser)??;
+        for segment in path.0.iter() {
+            match (segment, current) {
+                (PathSegment::Index(index), Element::Array(len)) => {
+                    if *index >= len {
+                        return Err(not_found());
+                    }
+                    for _ in 0..*index {
+                        read_any(&mut parser)?;
+                    }
+                }
+                (PathSegment::Key(key), Element::Map(len)) => {
+                    let mut found = false;
+                    for _ in 0..len {
+                        match next(&mut parser)?? {
+                            Element::Str(k) if k == key => {
+                                found = true;
+                                break;
+                            }
+                            Element::Str(_) => read_any(&mut parser)?,
+                            _ => return Err(not_found()),
+                        }
+                    }
+                    if !found {
+                        return Err(not_found());
+                    }
+                }
+                _ => return Err(not_found()),
+            }
+            current = next(&mut parser)??;
+        }
+        match current {
+            Element::Array(len) => Ok(len),
+            elem => Err(Error::validate(
+                ValidateCode::WrongType,
+                format!(
+                    "Array `same_len` path {} is {}, not an array",
+                    path,
+                    elem.name()
+                ),
+            )),
+        }
+    }
+
+    fn contains_bounds(&self, index: usize) -> (u32, u32) {
+        self.contains_count
+            .get(index)
+            .copied()
+            .unwrap_or((1, u32::MAX))
+    }
+
     pub(crate) fn validate<'de, 'c>(
         &'c self,
         types: &'c BTreeMap<String, Validator>,
@@ -251,69 +563,163 @@ impl ArrayValidator {
         let val_parser = parser.clone();
         let elem = parser
             .next()
-            .ok_or_else(|| Error::FailValidate("Expected an array".to_string()))??;
+            .ok_or_else(|| Error::validate(ValidateCode::WrongType, "Expected an array"))??;
         let len = if let Element::Array(len) = elem {
             len
         } else {
-            return Err(Error::FailValidate(format!(
-                "Expected Array, got {}",
-                elem.name()
-            )));
+            return Err(Error::wrong_type("Array", elem.name()));
         };
 
         if (len as u32) > self.max_len {
-            return Err(Error::FailValidate(format!(
-                "Array is {} elements, longer than maximum allowed of {}",
-                len, self.max_len
-            )));
+            return Err(Error::validate(
+                ValidateCode::TooLong,
+                format!(
+                    "Array is {} elements, longer than maximum allowed of {}",
+                    len, self.max_len
+                ),
+            ));
         }
         if (len as u32) < self.min_len {
-            return Err(Error::FailValidate(format!(
-                "Array is {} elements, shorter than minimum allowed of {}",
-                len, self.min_len
-            )));
+            return Err(Error::validate(
+                ValidateCode::TooShort,
+                format!(
+                    "Array is {} elements, shorter than minimum allowed of {}",
+                    len, self.min_len
+                ),
+            ));
+        }
+        let extra = len.saturating_sub(self.prefix.len());
+        if (extra as u64) < (self.min_extra as u64) {
+            return Err(Error::validate(
+                ValidateCode::TooShort,
+                format!(
+                    "Array has {} items after the prefix, fewer than minimum allowed of {}",
+                    extra, self.min_extra
+                ),
+            ));
+        }
+
+        if let Some((first, rest)) = self.same_len.split_first() {
+            let first_len = Self::same_len_of(&val_parser, first)?;
+            for path in rest {
+                let len = Self::same_len_of(&val_parser, path)?;
+                if len != first_len {
+                    return Err(Error::validate(
+                        ValidateCode::WrongType,
+                        format!(
+                            "Array `same_len` mismatch: {} has length {}, but {} has length {}",
+                            first, first_len, path, len
+                        ),
+                    ));
+                }
+            }
+        }
+
+        // With a built index, the `in` and `nin` lists can be checked using the array's encoded
+        // form, which is canonical, instead of comparing against every list entry.
+        let check_lists = !self.in_list.is_empty() || !self.nin_list.is_empty();
+        let indexed = self.list_index.is_built();
+        if check_lists && indexed {
+            let start = val_parser.remaining();
+            let mut skip_parser = val_parser.clone();
+            read_any(&mut skip_parser)?;
+            let encoded = &start[..(start.len() - skip_parser.remaining().len())];
+            if !self.in_list.is_empty() && self.list_index.in_contains(encoded) == Some(false) {
+                return Err(Error::validate(
+                    ValidateCode::NotInList,
+                    "Array is not on `in` list",
+                ));
+            }
+            if self.list_index.nin_contains(encoded) == Some(true) {
+                return Err(Error::validate(
+                    ValidateCode::InNinList,
+                    "Array is on `nin` list",
+                ));
+            }
         }
 
         // Check all the requirements that require parsing the entire array
-        if self.unique || !self.in_list.is_empty() || !self.nin_list.is_empty() {
+        if self.unique || !self.unique_by.is_empty() || (check_lists && !indexed) {
             let mut de = FogDeserializer::from_parser(val_parser);
             let array = Vec::<ValueRef>::deserialize(&mut de)?;
 
-            if !self.in_list.is_empty() && !self.in_list.iter().any(|v| *v == array) {
-                return Err(Error::FailValidate("Array is not on `in` list".to_string()));
+            if !indexed && !self.in_list.is_empty() && !self.in_list.iter().any(|v| *v == array) {
+                return Err(Error::validate(
+                    ValidateCode::NotInList,
+                    "Array is not on `in` list",
+                ));
             }
 
-            if self.nin_list.iter().any(|v| *v == array) {
-                return Err(Error::FailValidate("Array is on `nin` list".to_string()));
+            if !indexed && self.nin_list.iter().any(|v| *v == array) {
+                return Err(Error::validate(
+                    ValidateCode::InNinList,
+                    "Array is on `nin` list",
+                ));
             }
 
             if self.unique
                 && array
                     .iter()
                     .enumerate()
-                    .any(|(index, lhs)| array.iter().skip(index).any(|rhs| lhs == rhs))
+                    .any(|(index, lhs)| array.iter().skip(index + 1).any(|rhs| lhs == rhs))
             {
-                return Err(Error::FailValidate(
+                return Err(Error::validate(
+                    ValidateCode::NotUnique,
                     "Array does not contain unique elements".to_string(),
                 ));
             }
+
+            if !self.unique_by.is_empty() {
+                let keys = array
+                    .iter()
+                    .enumerate()
+                    .map(|(index, item)| match item {
+                        ValueRef::Map(map) => Ok(self
+                            .unique_by
+                            .iter()
+                            .map(|key| map.get(key.as_str()))
+                            .collect::<Vec<Option<&ValueRef>>>()),
+                        _ => Err(Error::validate(
+                            ValidateCode::WrongType,
+                            format!(
+                                "Array item {} is not a map, but `unique_by` requires maps",
+                                index
+                            ),
+                        )),
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+                if keys
+                    .iter()
+                    .enumerate()
+                    .any(|(index, lhs)| keys.iter().skip(index + 1).any(|rhs| lhs == rhs))
+                {
+                    return Err(Error::validate(
+                        ValidateCode::NotUnique,
+                        format!("Array items are not unique by keys {:?}", self.unique_by),
+                    ));
+                }
+            }
         }
 
         // Loop through each item, verifying it with the appropriate validator
-        let mut contains_result = vec![false; self.contains.len()];
+        let mut contains_result = vec![0u32; self.contains.len()];
         let mut validators = self.prefix.iter().chain(repeat(self.items.as_ref()));
-        for _ in 0..len {
+        for index in 0..len {
             // If we have a "contains", check
             if !self.contains.is_empty() {
                 self.contains
                     .iter()
                     .zip(contains_result.iter_mut())
-                    .for_each(|(validator, passed)| {
-                        if !*passed {
+                    .enumerate()
+                    .for_each(|(c_index, (validator, count))| {
+                        // Once the minimum is met, we only need to keep counting if there's a
+                        // maximum to enforce.
+                        let (min, max) = self.contains_bounds(c_index);
+                        if *count < min || max != u32::MAX {
                             let result =
                                 validator.validate(types, parser.clone(), checklist.clone());
                             if let Ok((_, c)) = result {
-                                *passed = true;
+                                *count += 1;
                                 checklist = c;
                             }
                         }
@@ -322,21 +728,34 @@ impl ArrayValidator {
             let (p, c) = validators
                 .next()
                 .unwrap()
-                .validate(types, parser, checklist)?;
+                .validate(types, parser, checklist)
+                .map_err(|e| nest_error(e, PathSegment::Index(index)))?;
             parser = p;
             checklist = c;
         }
 
-        if !contains_result.iter().all(|x| *x) {
+        for (index, count) in contains_result.iter().enumerate() {
+            let (min, max) = self.contains_bounds(index);
+            if index < self.contains_count.len() && (*count < min || *count > max) {
+                return Err(Error::validate(ValidateCode::MissingContains, format!(
+                    "Array had {} items satisfying `contains` validator {}, but needs between {} and {}",
+                    count, index, min, max
+                )));
+            }
+        }
+        let counted = self.contains_count.len().min(contains_result.len());
+        let uncounted = &contains_result[counted..];
+        if uncounted.contains(&0) {
             let mut err_str = String::from("Array was missing items satisfying `contains` list:");
-            let iter = contains_result
+            let iter = uncounted
                 .iter()
                 .enumerate()
-                .filter(|(_, pass)| !**pass)
+                .filter(|(_, count)| **count == 0)
+                .map(|(index, count)| (index + counted, count))
                 .map(|(index, _)| format!(" {},", index));
             err_str.extend(iter);
             err_str.pop(); // Remove the final comma
-            return Err(Error::FailValidate(err_str));
+            return Err(Error::validate(ValidateCode::MissingContains, err_str));
         }
         Ok((parser, checklist))
     }
@@ -348,9 +767,13 @@ impl ArrayValidator {
     ) -> bool {
         let initial_check = (self.query || (other.in_list.is_empty() && other.nin_list.is_empty()))
             && (self.array || (other.prefix.is_empty() && validator_is_any(&other.items)))
-            && (self.contains_ok || other.contains.is_empty())
-            && (self.unique_ok || !other.unique)
-            && (self.size || (u32_is_max(&other.max_len) && u32_is_zero(&other.min_len)));
+            && (self.contains_ok || (other.contains.is_empty() && other.contains_count.is_empty()))
+            && (self.unique_ok || (!other.unique && other.unique_by.is_empty()))
+            && (self.size
+                || (u32_is_max(&other.max_len)
+                    && u32_is_zero(&other.min_len)
+                    && u32_is_zero(&other.min_extra)
+                    && other.same_len.is_empty()));
         if !initial_check {
             return false;
         }
@@ -420,4 +843,479 @@ mod test {
         println!("{}", de.get_debug().unwrap());
         assert_eq!(schema, decoded);
     }
+
+    fn validate_array(validator: &Validator, array: Vec<Value>) -> Result<()> {
+        let mut ser = FogSerializer::default();
+        array.serialize(&mut ser).unwrap();
+        let data = ser.finish();
+        let types = BTreeMap::new();
+        let (parser, _) = validator.validate(&types, Parser::new(&data), None)?;
+        parser.finish()
+    }
+
+    #[test]
+    fn malformed_not_wrong_type() {
+        // A truncated string is a decoding failure, not just the wrong type
+        let mut ser = FogSerializer::default();
+        "hello".serialize(&mut ser).unwrap();
+        let mut data = ser.finish();
+        data.pop();
+        let types = BTreeMap::new();
+        for validator in [ArrayValidator::new().build(), MapValidator::new().build()].iter() {
+            let err = validator
+                .validate(&types, Parser::new(&data), None)
+                .unwrap_err();
+            assert!(matches!(err, Error::LengthTooShort { .. }), "got {:?}", err);
+        }
+    }
+
+    #[test]
+    fn contains_count_overlap() {
+        // Between 1 and 2 strings, and exactly one "primary" string
+        let validator = ArrayValidator::new()
+            .contains_count_add(StrValidator::new().build(), 1, 2)
+            .contains_count_add(StrValidator::new().in_add("primary").build(), 1, 1)
+            .build();
+        let pass = vec![Value::from("primary"), Value::from(1u8)];
+        validate_array(&validator, pass).unwrap();
+        let pass = vec![Value::from("primary"), Value::from("other")];
+        validate_array(&validator, pass).unwrap();
+        let too_many = vec![
+            Value::from("primary"),
+            Value::from("other"),
+            Value::from("third"),
+        ];
+        let err = validate_array(&validator, too_many).unwrap_err();
+        assert!(
+            matches!(err, Error::FailValidate(ref e) if e.code == ValidateCode::MissingContains && e.message.contains("3 items") && e.message.contains("validator 0"))
+        );
+        let two_primary = vec![Value::from("primary"), Value::from("primary")];
+        let err = validate_array(&validator, two_primary).unwrap_err();
+        assert!(
+            matches!(err, Error::FailValidate(ref e) if e.code == ValidateCode::MissingContains && e.message.contains("2 items") && e.message.contains("validator 1"))
+        );
+        let no_primary = vec![Value::from("other")];
+        let err = validate_array(&validator, no_primary).unwrap_err();
+        assert!(
+            matches!(err, Error::FailValidate(ref e) if e.code == ValidateCode::MissingContains && e.message.contains("0 items") && e.message.contains("validator 1"))
+        );
+    }
+
+    #[test]
+    fn contains_count_mixed() {
+        // Counts only cover the first validator; the second uses the plain "at least one" rule.
+        let validator = ArrayValidator {
+            contains: vec![IntValidator::new().build(), StrValidator::new().build()],
+            contains_count: vec![(0, 1)],
+            ..ArrayValidator::default()
+        }
+        .build();
+        validate_array(&validator, vec![Value::from("a")]).unwrap();
+        validate_array(&validator, vec![Value::from("a"), Value::from(1u8)]).unwrap();
+        validate_array(
+            &validator,
+            vec![Value::from("a"), Value::from(1u8), Value::from(2u8)],
+        )
+        .unwrap_err();
+        validate_array(&validator, vec![Value::from(1u8)]).unwrap_err();
+    }
+
+    #[test]
+    fn contains_count_build() {
+        use crate::schema::SchemaBuilder;
+        let validator = ArrayValidator {
+            contains: vec![IntValidator::new().build()],
+            contains_count: vec![(1, 2), (1, 2)],
+            ..ArrayValidator::default()
+        }
+        .build();
+        assert!(SchemaBuilder::new(validator.clone()).build().is_err());
+        assert!(SchemaBuilder::new(Validator::Null)
+            .type_add("bad", validator)
+            .build()
+            .is_err());
+        let validator = ArrayValidator::new()
+            .contains_count_add(IntValidator::new().build(), 1, 2)
+            .build();
+        assert!(SchemaBuilder::new(validator).build().is_ok());
+        // The minimum can't be above the maximum
+        let validator = ArrayValidator::new()
+            .contains_count_add(IntValidator::new().build(), 3, 2)
+            .build();
+        assert!(SchemaBuilder::new(validator).build().is_err());
+        let validator = ArrayValidator::new()
+            .contains_count_add(IntValidator::new().build(), 2, 2)
+            .build();
+        assert!(SchemaBuilder::new(validator).build().is_ok());
+    }
+
+    #[test]
+    fn contains_count_query() {
+        let types = BTreeMap::new();
+        let query = ArrayValidator::new()
+            .contains_count_add(IntValidator::new().build(), 1, 2)
+            .build();
+        let schema = ArrayValidator::new()
+            .items(IntValidator::new().build())
+            .build();
+        assert!(!schema.query_check(&types, &query));
+        let schema = ArrayValidator::new()
+            .items(IntValidator::new().build())
+            .contains_ok(true)
+            .build();
+        assert!(schema.query_check(&types, &query));
+    }
+
+    fn user(id: Option<u8>, name: &str) -> Value {
+        let mut map = BTreeMap::new();
+        if let Some(id) = id {
+            map.insert("id".to_string(), Value::from(id));
+        }
+        map.insert("name".to_string(), Value::from(name));
+        Value::from(map)
+    }
+
+    #[test]
+    fn unique() {
+        let validator = ArrayValidator::new().unique(true).build();
+        validate_array(&validator, vec![]).unwrap();
+        validate_array(&validator, vec![Value::from(1u8), Value::from(2u8)]).unwrap();
+        validate_array(&validator, vec![Value::from(1u8), Value::from(1u8)]).unwrap_err();
+    }
+
+    #[test]
+    fn unique_by() {
+        let validator = ArrayValidator::new().unique_by_add("id").build();
+        // Shared names are fine, shared ids are not
+        validate_array(
+            &validator,
+            vec![user(Some(1), "alice"), user(Some(2), "alice")],
+        )
+        .unwrap();
+        let err = validate_array(
+            &validator,
+            vec![user(Some(1), "alice"), user(Some(1), "bob")],
+        )
+        .unwrap_err();
+        assert!(matches!(err, Error::FailValidate(ref e) if e.code == ValidateCode::NotUnique));
+    }
+
+    #[test]
+    fn unique_by_missing_key() {
+        let validator = ArrayValidator::new().unique_by_add("id").build();
+        // A missing key is distinct from any present value...
+        validate_array(&validator, vec![user(None, "alice"), user(Some(1), "bob")]).unwrap();
+        // ...but two missing keys are the same.
+        validate_array(&validator, vec![user(None, "alice"), user(None, "bob")]).unwrap_err();
+        // With a second key, absent ids can be told apart by the name.
+        let validator = ArrayValidator::new()
+            .unique_by_add("id")
+            .unique_by_add("name")
+            .build();
+        validate_array(&validator, vec![user(None, "alice"), user(None, "bob")]).unwrap();
+        validate_array(&validator, vec![user(None, "alice"), user(None, "alice")]).unwrap_err();
+    }
+
+    #[test]
+    fn unique_by_non_map() {
+        let validator = ArrayValidator::new().unique_by_add("id").build();
+        let err =
+            validate_array(&validator, vec![user(Some(1), "alice"), Value::from(1u8)]).unwrap_err();
+        assert!(
+            matches!(err, Error::FailValidate(ref e) if e.code == ValidateCode::WrongType && e.message.contains("item 1 is not a map"))
+        );
+    }
+
+    #[test]
+    fn unique_by_query() {
+        let types = BTreeMap::new();
+        let query = ArrayValidator::new().unique_by_add("id").build();
+        assert!(!ArrayValidator::new().build().query_check(&types, &query));
+        assert!(ArrayValidator::new()
+            .unique_ok(true)
+            .build()
+            .query_check(&types, &query));
+    }
+
+    #[test]
+    fn unique_by_ser() {
+        let validator = ArrayValidator::new().unique_by_add("id");
+        let mut ser = FogSerializer::default();
+        validator.serialize(&mut ser).unwrap();
+        let actual = ser.finish();
+        let mut expected = vec![0x81];
+        serialize_elem(&mut expected, Element::Str("unique_by"));
+        serialize_elem(&mut expected, Element::Array(1));
+        serialize_elem(&mut expected, Element::Str("id"));
+        assert_eq!(expected, actual);
+        let mut de = FogDeserializer::new(&actual);
+        assert_eq!(validator, ArrayValidator::deserialize(&mut de).unwrap());
+    }
+
+    #[test]
+    fn min_extra() {
+        let validator = ArrayValidator::new()
+            .prefix(vec![
+                StrValidator::new().build(),
+                IntValidator::new().build(),
+                Validator::Any,
+            ])
+            .items(IntValidator::new().build())
+            .min_extra(2)
+            .build();
+        let array = |len: u8| -> Vec<Value> {
+            let mut array = vec![Value::from("a"), Value::from(1u8), Value::Null];
+            array.extend((3..len).map(Value::from));
+            array
+        };
+        let err = validate_array(&validator, array(3)).unwrap_err();
+        assert!(
+            matches!(err, Error::FailValidate(ref e) if e.code == ValidateCode::TooShort && e.message.contains("0 items after"))
+        );
+        let err = validate_array(&validator, array(4)).unwrap_err();
+        assert!(
+            matches!(err, Error::FailValidate(ref e) if e.code == ValidateCode::TooShort && e.message.contains("1 items after"))
+        );
+        validate_array(&validator, array(5)).unwrap();
+    }
+
+    #[test]
+    fn min_extra_build() {
+        use crate::schema::SchemaBuilder;
+        let validator = ArrayValidator::new()
+            .prefix(vec![Validator::Any, Validator::Any, Validator::Any])
+            .min_extra(2)
+            .max_len(4)
+            .build();
+        assert!(SchemaBuilder::new(validator).build().is_err());
+        let validator = ArrayValidator::new()
+            .prefix(vec![Validator::Any, Validator::Any, Validator::Any])
+            .min_extra(2)
+            .max_len(5)
+            .build();
+        assert!(SchemaBuilder::new(validator).build().is_ok());
+    }
+
+    #[test]
+    fn min_extra_query() {
+        let types = BTreeMap::new();
+        let query = ArrayValidator::new().min_extra(1).build();
+        assert!(!ArrayValidator::new().build().query_check(&types, &query));
+        assert!(ArrayValidator::new()
+            .size(true)
+            .build()
+            .query_check(&types, &query));
+    }
+
+    fn fail_msg(validator: &Validator, array: Vec<Value>) -> String {
+        match validate_array(validator, array) {
+            Err(Error::FailValidate(err)) => err.to_string(),
+            other => panic!("expected a validation failure, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn error_index() {
+        let validator = ArrayValidator::new()
+            .prefix_add(StrValidator::new().build())
+            .items(IntValidator::new().build())
+            .build();
+        let msg = fail_msg(&validator, vec![Value::from(1u8)]);
+        assert!(msg.starts_with("[0]: "), "{}", msg);
+        let msg = fail_msg(
+            &validator,
+            vec![Value::from("a"), Value::from(1u8), Value::from("b")],
+        );
+        assert!(msg.starts_with("[2]: Expected Int"), "{}", msg);
+    }
+
+    #[test]
+    fn error_structured() {
+        let inner = MapValidator::new()
+            .req_add("n", IntValidator::new().build())
+            .build();
+        let validator = ArrayValidator::new().items(inner).build();
+        let mut map = BTreeMap::new();
+        map.insert("n".to_string(), Value::from("a"));
+        let err = validate_array(&validator, vec![Value::from(map)]).unwrap_err();
+        let err = err.validate_error().unwrap();
+        assert_eq!(err.code, ValidateCode::WrongType);
+        assert_eq!(
+            err.path.segments(),
+            &[PathSegment::Index(0), PathSegment::Key("n".to_string())]
+        );
+        assert_eq!(err.expected.as_deref(), Some("Int"));
+        assert_eq!(err.actual.as_deref(), Some("Str"));
+        assert_eq!(err.to_string(), "[0].n: Expected Int, got Str");
+    }
+
+    #[test]
+    fn error_nested_path() {
+        use crate::validator::MapValidator;
+        let inner = ArrayValidator::new()
+            .items(StrValidator::new().build())
+            .build();
+        let validator = ArrayValidator::new()
+            .items(MapValidator::new().req_add("tags", inner).build())
+            .build();
+        let good = |tags: Vec<Value>| {
+            let mut map = BTreeMap::new();
+            map.insert("tags".to_string(), Value::from(tags));
+            Value::from(map)
+        };
+        let array = vec![
+            good(vec![Value::from("a")]),
+            good(vec![Value::from("a"), Value::from("b"), Value::from(3u8)]),
+        ];
+        let msg = fail_msg(&validator, array);
+        assert!(msg.starts_with("[1].tags[2]: expected Str"), "{}", msg);
+    }
+
+    #[test]
+    fn indexed_in_list() {
+        let entry = |n: u32| vec![Value::from(n), Value::from(format!("item {}", n))];
+        let mut naive = ArrayValidator::new();
+        for n in 0..5000 {
+            naive = naive.in_add(entry(n * 2));
+        }
+        let naive = naive.nin_add(entry(4)).build();
+        let mut indexed = naive.clone();
+        indexed.build_index();
+
+        let candidates: Vec<Vec<Value>> = (0..200).map(|n| entry(n * 50 + 4)).collect();
+        let run = |validator: &Validator| -> Vec<bool> {
+            candidates
+                .iter()
+                .map(|c| validate_array(validator, c.clone()).is_ok())
+                .collect()
+        };
+        let indexed_results = run(&indexed);
+        assert_eq!(run(&naive), indexed_results);
+        // Even entries are on the `in` list, odd ones aren't, and entry 4 is on the `nin` list
+        assert!(!indexed_results[0]);
+        assert!(indexed_results[1]);
+        assert!(!validate_array(&indexed, entry(3)).is_ok());
+        assert!(validate_array(&indexed, entry(9998)).is_ok());
+    }
+
+    #[test]
+    fn indexed_floats() {
+        let naive = ArrayValidator::new()
+            .in_add(vec![Value::F64(0.0)])
+            .in_add(vec![Value::F64(f64::NAN)])
+            .nin_add(vec![Value::F32(-0.0)])
+            .build();
+        let mut indexed = naive.clone();
+        indexed.build_index();
+        let candidates = [
+            vec![Value::F64(0.0)],
+            vec![Value::F64(-0.0)],
+            vec![Value::F64(f64::NAN)],
+            vec![Value::F32(0.0)],
+        ];
+        for candidate in candidates.iter() {
+            assert_eq!(
+                validate_array(&naive, candidate.clone()).is_ok(),
+                validate_array(&indexed, candidate.clone()).is_ok(),
+                "mismatch on {:?}",
+                candidate
+            );
+        }
+        // -0.0 equals 0.0, but NaN never equals itself
+        assert!(validate_array(&indexed, vec![Value::F64(-0.0)]).is_ok());
+        assert!(validate_array(&indexed, vec![Value::F64(f64::NAN)]).is_err());
+        assert!(validate_array(&indexed, vec![Value::F32(0.0)]).is_err());
+    }
+
+    fn ints(len: u8) -> Value {
+        Value::from((0..len).map(Value::from).collect::<Vec<Value>>())
+    }
+
+    fn column(len: u8) -> Value {
+        let mut map = BTreeMap::new();
+        map.insert("name".to_string(), Value::from("col"));
+        map.insert("values".to_string(), ints(len));
+        Value::from(map)
+    }
+
+    #[test]
+    fn same_len_index() {
+        let validator = ArrayValidator::new()
+            .same_len_add(SameLenPath::index(0))
+            .same_len_add(SameLenPath::index(2))
+            .build();
+        validate_array(&validator, vec![ints(3), Value::Null, ints(3)]).unwrap();
+        let msg = fail_msg(&validator, vec![ints(3), Value::Null, ints(4)]);
+        assert!(
+            msg.contains("[0] has length 3, but [2] has length 4"),
+            "{}",
+            msg
+        );
+        let msg = fail_msg(&validator, vec![ints(3), Value::Null]);
+        assert!(msg.contains("no value at `same_len` path [2]"), "{}", msg);
+        let msg = fail_msg(&validator, vec![ints(3), Value::Null, Value::Null]);
+        assert!(msg.contains("not an array"), "{}", msg);
+    }
+
+    #[test]
+    fn same_len_nested() {
+        let validator = ArrayValidator::new()
+            .items(MapValidator::new().values(Validator::Any).build())
+            .same_len_add(SameLenPath::index(0).key("values"))
+            .same_len_add(SameLenPath::index(1).key("values"))
+            .build();
+        validate_array(&validator, vec![column(4), column(4)]).unwrap();
+        let msg = fail_msg(&validator, vec![column(4), column(5)]);
+        assert!(
+            msg.contains("[0].values has length 4, but [1].values has length 5"),
+            "{}",
+            msg
+        );
+        let msg = fail_msg(&validator, vec![column(4), Value::Null]);
+        assert!(
+            msg.contains("no value at `same_len` path [1].values"),
+            "{}",
+            msg
+        );
+
+        // Descend through an index after a key
+        let validator = ArrayValidator::new()
+            .same_len_add(SameLenPath::index(0))
+            .same_len_add(SameLenPath::index(1).key("values").then_index(1))
+            .build();
+        let mut map = BTreeMap::new();
+        map.insert(
+            "values".to_string(),
+            Value::from(vec![Value::Null, ints(2)]),
+        );
+        validate_array(&validator, vec![ints(2), Value::from(map)]).unwrap();
+    }
+
+    #[test]
+    fn same_len_ser() {
+        // The bare-index form is a plain integer, longer paths are arrays
+        let validator = ArrayValidator::new()
+            .same_len_add(SameLenPath::index(1))
+            .same_len_add(SameLenPath::index(2).key("values"));
+        let mut ser = FogSerializer::default();
+        validator.serialize(&mut ser).unwrap();
+        let actual = ser.finish();
+        let mut expected = vec![0x81];
+        serialize_elem(&mut expected, Element::Str("same_len"));
+        serialize_elem(&mut expected, Element::Array(2));
+        serialize_elem(&mut expected, Element::Int(1u8.into()));
+        serialize_elem(&mut expected, Element::Array(2));
+        serialize_elem(&mut expected, Element::Int(2u8.into()));
+        serialize_elem(&mut expected, Element::Str("values"));
+        assert_eq!(expected, actual);
+        let mut de = FogDeserializer::new(&actual);
+        assert_eq!(validator, ArrayValidator::deserialize(&mut de).unwrap());
+
+        // A path must start with an index
+        let validator = ArrayValidator {
+            same_len: vec![SameLenPath(vec![PathSegment::Key("a".into())])],
+            ..ArrayValidator::default()
+        };
+        assert!(validator.check_build().is_err());
+    }
 }
@@ -1,6 +1,6 @@
 use super::*;
 use crate::element::*;
-use crate::error::{Error, Result};
+use crate::error::{Error, Result, ValidateCode};
 use serde::{Deserialize, Serialize};
 use serde_bytes::ByteBuf;
 use std::default::Default;
@@ -37,6 +37,7 @@ fn u32_is_max(v: &u32) -> bool {
 /// - The value is greater than the minimum in `min`, or equal to it if `ex_min` is not set to true.
 /// - The value's length in bytes is less than or equal to the value in `max_len`.
 /// - The value's length in bytes is greater than or equal to the value in `min_len`.
+/// - If `exact_len` is set, the value's length in bytes is equal to it.
 /// - If the `in` list is not empty, the value must be among the values in the list.
 /// - The value must not be among the values in the `nin` list.
 ///
@@ -46,6 +47,7 @@ fn u32_is_max(v: &u32) -> bool {
 /// each field are:
 ///
 /// - comment: ""
+/// - default: None
 /// - bits_clr: empty
 /// - bits_set: empty
 /// - max: empty
@@ -54,6 +56,7 @@ fn u32_is_max(v: &u32) -> bool {
 /// - ex_min: false
 /// - max_len: u32::MAX
 /// - min_len: 0
+/// - exact_len: None
 /// - in_list: empty
 /// - nin_list: empty
 /// - query: false
@@ -61,12 +64,26 @@ fn u32_is_max(v: &u32) -> bool {
 /// - ord: false
 /// - size: false
 ///
+/// # Query Checking
+///
+/// Queries for byte sequences are only allowed to use non-default values for each field if the
+/// corresponding query permission is set in the schema's validator:
+///
+/// - query: `in` and `nin` lists
+/// - bit: `bits_clr` and `bits_set`
+/// - ord: `max`, `min`, `ex_max`, and `ex_min`
+/// - size: `max_len`, `min_len`, and `exact_len`
+///
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 #[serde(deny_unknown_fields, default)]
 pub struct BinValidator {
     /// An optional comment explaining the validator.
     #[serde(skip_serializing_if = "String::is_empty")]
     pub comment: String,
+    /// An optional default value, used when filling in new values from a schema. It is not
+    /// checked during validation.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub default: Option<ByteBuf>,
     /// A byte sequence used as a bit field. Any bits set in it must be cleared in an allowed
     /// value.
     #[serde(skip_serializing_if = "bytes_empty")]
@@ -94,12 +111,19 @@ pub struct BinValidator {
     /// Set the minimum allowed number of bytes.
     #[serde(skip_serializing_if = "u32_is_zero")]
     pub min_len: u32,
+    /// Set the exact number of allowed bytes. Equivalent to setting `max_len` and `min_len` to the
+    /// same value.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exact_len: Option<u32>,
     /// A vector of specific allowed values, stored under the `in` field. If empty, this vector is not checked against.
     #[serde(rename = "in", skip_serializing_if = "Vec::is_empty")]
     pub in_list: Vec<ByteBuf>,
     /// A vector of specific unallowed values, stored under the `nin` field.
     #[serde(rename = "nin", skip_serializing_if = "Vec::is_empty")]
     pub nin_list: Vec<ByteBuf>,
+    /// Precomputed lookup index for the `in` and `nin` lists.
+    #[serde(skip)]
+    pub(crate) list_index: ListIndex<Vec<u8>>,
     /// If true, queries against matching spots may have values in the `in` or `nin` lists.
     #[serde(skip_serializing_if = "is_false")]
     pub query: bool,
@@ -111,8 +135,8 @@ pub struct BinValidator {
     /// values to non-defaults.
     #[serde(skip_serializing_if = "is_false")]
     pub ord: bool,
-    /// If true, queries against matching spots may set the `min_len` and `max_len` values to
-    /// non-defaults.
+    /// If true, queries against matching spots may set the `min_len`, `max_len`, and `exact_len`
+    /// values to non-defaults.
     #[serde(skip_serializing_if = "is_false")]
     pub size: bool,
 }
@@ -121,6 +145,7 @@ impl Default for BinValidator {
     fn default() -> Self {
         Self {
             comment: String::new(),
+            default: None,
             bits_clr: ByteBuf::new(),
             bits_set: ByteBuf::new(),
             ex_max: false,
@@ -129,8 +154,10 @@ impl Default for BinValidator {
             min: ByteBuf::new(),
             max_len: u32::MAX,
             min_len: u32::MIN,
+            exact_len: None,
             in_list: Vec::new(),
             nin_list: Vec::new(),
+            list_index: ListIndex::default(),
             query: false,
             bit: false,
             ord: false,
@@ -151,6 +178,12 @@ impl BinValidator {
         self
     }
 
+    /// Set the default value.
+    pub fn default_value(mut self, default: impl Into<Vec<u8>>) -> Self {
+        self.default = Some(ByteBuf::from(default.into()));
+        self
+    }
+
     /// Choose which bits must be set.
     pub fn bits_set(mut self, bits_set: impl Into<Vec<u8>>) -> Self {
         self.bits_set = ByteBuf::from(bits_set);
@@ -199,6 +232,12 @@ impl BinValidator {
         self
     }
 
+    /// Set the exact number of allowed bytes.
+    pub fn exact_len(mut self, exact_len: u32) -> Self {
+        self.exact_len = Some(exact_len);
+        self
+    }
+
     /// Add a value to the `in` list.
     pub fn in_add(mut self, add: impl Into<Vec<u8>>) -> Self {
         self.in_list.push(ByteBuf::from(add));
@@ -229,7 +268,7 @@ impl BinValidator {
         self
     }
 
-    /// Set whether or not queries can use the `max_len` and `min_len` values.
+    /// Set whether or not queries can use the `max_len`, `min_len`, and `exact_len` values.
     pub fn size(mut self, size: bool) -> Self {
         self.size = size;
         self
@@ -240,33 +279,50 @@ impl BinValidator {
         Validator::Bin(self)
     }
 
+    pub(crate) fn build_index(&mut self) {
+        self.list_index = ListIndex::build(
+            self.in_list.iter().map(|v| v.to_vec()),
+            self.nin_list.iter().map(|v| v.to_vec()),
+        );
+    }
+
     pub(crate) fn validate(&self, parser: &mut Parser) -> Result<()> {
         use std::iter::repeat;
 
         // Get element
         let elem = parser
             .next()
-            .ok_or_else(|| Error::FailValidate("expected binary data".to_string()))??;
+            .ok_or_else(|| Error::validate(ValidateCode::WrongType, "expected binary data"))??;
         let val = if let Element::Bin(v) = elem {
             v
         } else {
-            return Err(Error::FailValidate(format!(
-                "expected Bin, got {}",
-                elem.name()
-            )));
+            return Err(Error::validate(
+                ValidateCode::WrongType,
+                format!("expected Bin, got {}", elem.name()),
+            ));
         };
 
         // Length checks
         if (val.len() as u32) > self.max_len {
-            return Err(Error::FailValidate(
+            return Err(Error::validate(
+                ValidateCode::TooLong,
                 "Bin is longer than max_len".to_string(),
             ));
         }
         if (val.len() as u32) < self.min_len {
-            return Err(Error::FailValidate(
+            return Err(Error::validate(
+                ValidateCode::TooShort,
                 "Bin is shorter than min_len".to_string(),
             ));
         }
+        if let Some(exact_len) = self.exact_len {
+            if (val.len() as u32) != exact_len {
+                return Err(Error::validate(
+                    ValidateCode::OutOfRange,
+                    format!("Bin is {} bytes, but exact_len is {}", val.len(), exact_len),
+                ));
+            }
+        }
 
         // Bit checks
         if self
@@ -275,7 +331,8 @@ impl BinValidator {
             .zip(val.iter().chain(repeat(&0u8)))
             .any(|(bit, val)| (bit & val) != *bit)
         {
-            return Err(Error::FailValidate(
+            return Err(Error::validate(
+                ValidateCode::BitMismatch,
                 "Bin does not have all required bits set".to_string(),
             ));
         }
@@ -285,7 +342,8 @@ impl BinValidator {
             .zip(val.iter().chain(repeat(&0u8)))
             .any(|(bit, val)| (bit & val) != 0)
         {
-            return Err(Error::FailValidate(
+            return Err(Error::validate(
+                ValidateCode::BitMismatch,
                 "Bin does not have all required bits cleared".to_string(),
             ));
         }
@@ -320,23 +378,40 @@ impl BinValidator {
             };
 
             if !max_pass {
-                return Err(Error::FailValidate(
+                return Err(Error::validate(
+                    ValidateCode::OutOfRange,
                     "Bin greater than maximum allowed".to_string(),
                 ));
             }
             if !min_pass {
-                return Err(Error::FailValidate(
+                return Err(Error::validate(
+                    ValidateCode::OutOfRange,
                     "Bin less than minimum allowed".to_string(),
                 ));
             }
         }
 
         // in/nin checks
-        if !self.in_list.is_empty() && !self.in_list.iter().any(|v| *v == val) {
-            return Err(Error::FailValidate("Bin is not on `in` list".to_string()));
+        if !self.in_list.is_empty()
+            && !self
+                .list_index
+                .in_contains(val)
+                .unwrap_or_else(|| self.in_list.iter().any(|v| *v == val))
+        {
+            return Err(Error::validate(
+                ValidateCode::NotInList,
+                "Bin is not on `in` list",
+            ));
         }
-        if self.nin_list.iter().any(|v| *v == val) {
-            return Err(Error::FailValidate("Bin is on `nin` list".to_string()));
+        if self
+            .list_index
+            .nin_contains(val)
+            .unwrap_or_else(|| self.nin_list.iter().any(|v| *v == val))
+        {
+            return Err(Error::validate(
+                ValidateCode::InNinList,
+                "Bin is on `nin` list",
+            ));
         }
 
         Ok(())
@@ -347,7 +422,10 @@ impl BinValidator {
             && (self.bit || (other.bits_set.is_empty() && other.bits_clr.is_empty()))
             && (self.ord
                 || (!other.ex_min && !other.ex_max && other.min.is_empty() && other.max.is_empty()))
-            && (self.size || (u32_is_max(&other.max_len) && u32_is_zero(&other.min_len)))
+            && (self.size
+                || (u32_is_max(&other.max_len)
+                    && u32_is_zero(&other.min_len)
+                    && other.exact_len.is_none()))
     }
 
     pub(crate) fn query_check(&self, other: &Validator) -> bool {
@@ -362,3 +440,66 @@ impl BinValidator {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{de::FogDeserializer, query::NewQuery, schema::*, ser::FogSerializer};
+
+    #[test]
+    fn ser_default() {
+        // Should be an empty map if we use the defaults
+        let schema = BinValidator::default();
+        let mut ser = FogSerializer::default();
+        schema.serialize(&mut ser).unwrap();
+        let expected: Vec<u8> = vec![0x80];
+        let actual = ser.finish();
+        assert_eq!(expected, actual);
+
+        let mut de = FogDeserializer::new(&actual);
+        let decoded = BinValidator::deserialize(&mut de).unwrap();
+        assert_eq!(schema, decoded);
+    }
+
+    #[test]
+    fn exact_len() {
+        let validator = BinValidator::new().exact_len(32);
+        let mut ser = FogSerializer::default();
+        validator.serialize(&mut ser).unwrap();
+        let mut expected: Vec<u8> = vec![0x81];
+        serialize_elem(&mut expected, Element::Str("exact_len"));
+        serialize_elem(&mut expected, Element::Int(32u32.into()));
+        assert_eq!(expected, ser.finish());
+
+        let run = |len: usize| {
+            let mut ser = FogSerializer::default();
+            serde_bytes::Bytes::new(&vec![0u8; len])
+                .serialize(&mut ser)
+                .unwrap();
+            let data = ser.finish();
+            let mut parser = Parser::new(&data);
+            validator.validate(&mut parser)
+        };
+        assert!(run(31).is_err());
+        assert!(run(32).is_ok());
+        assert!(run(33).is_err());
+    }
+
+    #[test]
+    fn exact_len_query() {
+        let schema = |size: bool| {
+            let doc = SchemaBuilder::new(Validator::Null)
+                .entry_add(
+                    "id",
+                    BinValidator::new().exact_len(32).size(size).build(),
+                    None,
+                )
+                .build()
+                .unwrap();
+            Schema::from_doc(&doc).unwrap()
+        };
+        let query = || NewQuery::new("id", BinValidator::new().exact_len(32).build());
+        assert!(schema(false).encode_query(query()).is_err());
+        assert!(schema(true).encode_query(query()).is_ok());
+    }
+}
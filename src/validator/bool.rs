@@ -1,6 +1,6 @@
 use super::*;
 use crate::element::*;
-use crate::error::{Error, Result};
+use crate::error::{Error, Result, ValidateCode};
 use serde::{Deserialize, Serialize};
 
 #[inline]
@@ -18,6 +18,7 @@ fn is_false(v: &bool) -> bool {
 /// Fields that aren't specified for the validator use their defaults instead. The defaults for
 /// each field are:
 /// - comment: ""
+/// - default: None
 /// - in_list: empty
 /// - nin_list: empty
 /// - query: false
@@ -28,6 +29,10 @@ pub struct BoolValidator {
     /// An optional comment explaining the validator.
     #[serde(skip_serializing_if = "String::is_empty")]
     pub comment: String,
+    /// An optional default value, used when filling in new values from a schema. It is not
+    /// checked during validation.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub default: Option<bool>,
     /// A vector of specific allowed values, stored under the `in` field. If empty, this vector is not checked against.
     #[serde(rename = "in", skip_serializing_if = "Vec::is_empty")]
     pub in_list: Vec<bool>,
@@ -43,6 +48,7 @@ impl std::default::Default for BoolValidator {
     fn default() -> Self {
         Self {
             comment: String::new(),
+            default: None,
             in_list: Vec::new(),
             nin_list: Vec::new(),
             query: false,
@@ -62,6 +68,12 @@ impl BoolValidator {
         self
     }
 
+    /// Set the default value.
+    pub fn default_value(mut self, default: bool) -> Self {
+        self.default = Some(default);
+        self
+    }
+
     /// Add a value to the `in` list.
     pub fn in_add(mut self, add: bool) -> Self {
         self.in_list.push(add);
@@ -88,22 +100,23 @@ impl BoolValidator {
     pub(crate) fn validate(&self, parser: &mut Parser) -> Result<()> {
         let elem = parser
             .next()
-            .ok_or_else(|| Error::FailValidate("Expected a boolean".to_string()))??;
+            .ok_or_else(|| Error::validate(ValidateCode::WrongType, "Expected a boolean"))??;
         let elem = if let Element::Bool(v) = elem {
             v
         } else {
-            return Err(Error::FailValidate(format!(
-                "Expected Bool, got {}",
-                elem.name()
-            )));
+            return Err(Error::wrong_type("Bool", elem.name()));
         };
         if !self.in_list.is_empty() && !self.in_list.iter().any(|v| *v == elem) {
-            return Err(Error::FailValidate(
+            return Err(Error::validate(
+                ValidateCode::NotInList,
                 "Boolean is not on `in` list".to_string(),
             ));
         }
         if self.nin_list.iter().any(|v| *v == elem) {
-            return Err(Error::FailValidate("Boolean is on `nin` list".to_string()));
+            return Err(Error::validate(
+                ValidateCode::InNinList,
+                "Boolean is on `nin` list",
+            ));
         }
         Ok(())
     }
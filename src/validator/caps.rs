@@ -0,0 +1,687 @@
+use super::*;
+use crate::value::{PathSegment, ValuePath};
+use std::collections::BTreeSet;
+
+/// A summary of which constraints a query may use against a validator.
+///
+/// Each field corresponds to one of the query permission flags on the validators, like `query`
+/// or `ord`, and is true if a query validator may set the constraints that flag controls. This
+/// can be used to see what a query is allowed to do before building one, instead of building a
+/// query and seeing if the schema rejects it.
+///
+/// A validator that doesn't have a given permission flag (like `regex` on an integer validator)
+/// always reports it as false.
+///
+/// For [`Multi`][MultiValidator] validators, a permission is reported if any of the contained
+/// validators allow it, as a query only needs to be accepted by one of them. For
+/// [`All`][AllValidator] validators, a permission is reported only if every contained validator
+/// allows it.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct QueryCaps {
+    /// A query validator may be used here at all. This is false only for validators that reject
+    /// every query, like Any.
+    pub allowed: bool,
+    /// The query may use the `in` and `nin` lists (the `query` flag).
+    pub in_nin: bool,
+    /// The query may use ordering constraints like `min` and `max` (the `ord` flag).
+    pub ord: bool,
+    /// The query may use `bits_set` and `bits_clr` (the `bit` flag).
+    pub bit: bool,
+    /// The query may use length constraints, like `max_len` and `min_len` (the `size` flag).
+    pub size: bool,
+    /// The query may use `matches` on a string (the `regex` flag).
+    pub regex: bool,
+    /// The query may use `items` and `prefix` on an array (the `array` flag).
+    pub array: bool,
+    /// The query may use `contains` on an array (the `contains_ok` flag).
+    pub contains: bool,
+    /// The query may use `unique` on an array, or `values_unique` on a map (the `unique_ok`
+    /// flag).
+    pub unique: bool,
+    /// The query may use `req`, `opt`, `ban`, `strict`, and `values` on a map (the `map_ok`
+    /// flag).
+    pub map: bool,
+    /// The query may use `matches` in a map's key validator (the `match_keys` flag).
+    pub match_keys: bool,
+    /// The query may use `max_len` and `min_len` in a map's key validator (the `len_keys` flag).
+    pub len_keys: bool,
+    /// The query may use `link` on a hash (the `link_ok` flag).
+    pub link: bool,
+    /// The query may use `schema` on a hash (the `schema_ok` flag).
+    pub schema: bool,
+    /// The query may use `encoding`, `decoded_max_len`, and `decoded_min_len` on a string (the
+    /// `encoding_ok` flag).
+    pub encoding: bool,
+}
+
+impl QueryCaps {
+    /// Capabilities with every permission set, the starting point for intersections.
+    fn any() -> Self {
+        Self {
+            allowed: true,
+            in_nin: true,
+            ord: true,
+            bit: true,
+            size: true,
+            regex: true,
+            array: true,
+            contains: true,
+            unique: true,
+            map: true,
+            match_keys: true,
+            len_keys: true,
+            link: true,
+            schema: true,
+            encoding: true,
+        }
+    }
+
+    fn union(self, other: Self) -> Self {
+        Self {
+            allowed: self.allowed || other.allowed,
+            in_nin: self.in_nin || other.in_nin,
+            ord: self.ord || other.ord,
+            bit: self.bit || other.bit,
+            size: self.size || other.size,
+            regex: self.regex || other.regex,
+            array: self.array || other.array,
+            contains: self.contains || other.contains,
+            unique: self.unique || other.unique,
+            map: self.map || other.map,
+            match_keys: self.match_keys || other.match_keys,
+            len_keys: self.len_keys || other.len_keys,
+            link: self.link || other.link,
+            schema: self.schema || other.schema,
+            encoding: self.encoding || other.encoding,
+        }
+    }
+
+    fn intersect(self, other: Self) -> Self {
+        Self {
+            allowed: self.allowed && other.allowed,
+            in_nin: self.in_nin && other.in_nin,
+            ord: self.ord && other.ord,
+            bit: self.bit && other.bit,
+            size: self.size && other.size,
+            regex: self.regex && other.regex,
+            array: self.array && other.array,
+            contains: self.contains && other.contains,
+            unique: self.unique && other.unique,
+            map: self.map && other.map,
+            match_keys: self.match_keys && other.match_keys,
+            len_keys: self.len_keys && other.len_keys,
+            link: self.link && other.link,
+            schema: self.schema && other.schema,
+            encoding: self.encoding && other.encoding,
+        }
+    }
+}
+
+/// One step along a path through a validator tree. Besides the steps a [`ValuePath`] can take,
+/// this can step to the `items` validator of an array or the `values` validator of a map, which
+/// cover any index or key not otherwise named.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum Step {
+    Key(String),
+    Index(usize),
+    Items,
+    Values,
+}
+
+impl From<&PathSegment> for Step {
+    fn from(segment: &PathSegment) -> Self {
+        match segment {
+            PathSegment::Key(key) => Step::Key(key.clone()),
+            PathSegment::Index(index) => Step::Index(*index),
+        }
+    }
+}
+
+/// Write out a path in the [`ValuePath`] syntax, using `[*]` for an `items` step and `*` for a
+/// `values` step.
+fn format_steps(steps: &[Step]) -> String {
+    let mut out = String::new();
+    for step in steps {
+        match step {
+            Step::Key(key) if out.is_empty() => out.push_str(key),
+            Step::Key(key) => {
+                out.push('.');
+                out.push_str(key);
+            }
+            Step::Index(index) => out.push_str(&format!("[{}]", index)),
+            Step::Items => out.push_str("[*]"),
+            Step::Values if out.is_empty() => out.push('*'),
+            Step::Values => out.push_str(".*"),
+        }
+    }
+    out
+}
+
+/// Resolve a validator contained in a Multi, following the same rules used for validation.
+/// Returns `None` for validators the Multi would skip.
+fn multi_branch<'a>(
+    types: &'a BTreeMap<String, Validator>,
+    validator: &'a Validator,
+) -> Option<&'a Validator> {
+    match validator {
+        Validator::Ref(ref_name) => match types.get(ref_name)? {
+            Validator::Ref(_) | Validator::Multi(_) | Validator::All(_) => None,
+            validator => Some(validator),
+        },
+        Validator::Multi(_) | Validator::All(_) => None,
+        validator => Some(validator),
+    }
+}
+
+/// Resolve a validator contained in an All, following the same rules used for validation.
+/// Returns `None` for validators the All would reject outright.
+fn all_branch<'a>(
+    types: &'a BTreeMap<String, Validator>,
+    validator: &'a Validator,
+) -> Option<&'a Validator> {
+    match validator {
+        Validator::Ref(ref_name) => match types.get(ref_name)? {
+            Validator::Ref(_) | Validator::All(_) => None,
+            validator => Some(validator),
+        },
+        Validator::All(_) => None,
+        validator => Some(validator),
+    }
+}
+
+impl Validator {
+    /// Summarize which constraints a query may use against this validator. Named types in `types`
+    /// are used to resolve any [`Ref`][Validator::Ref] validators.
+    ///
+    /// This only looks at this validator, not at any validators nested inside it. Use
+    /// [`query_capabilities_at`][Self::query_capabilities_at] to look at nested validators.
+    pub fn query_capabilities(&self, types: &BTreeMap<String, Validator>) -> QueryCaps {
+        let allowed = QueryCaps {
+            allowed: true,
+            ..QueryCaps::default()
+        };
+        match self {
+            Validator::Null | Validator::Const(_) | Validator::Enum(_) => allowed,
+            Validator::Bool(v) => QueryCaps {
+                in_nin: v.query,
+                ..allowed
+            },
+            Validator::Int(v) => QueryCaps {
+                in_nin: v.query,
+                bit: v.bit,
+                ord: v.ord,
+                ..allowed
+            },
+            Validator::F32(v) => QueryCaps {
+                in_nin: v.query,
+                ord: v.ord,
+                ..allowed
+            },
+            Validator::F64(v) => QueryCaps {
+                in_nin: v.query,
+                ord: v.ord,
+                ..allowed
+            },
+            Validator::Bin(v) => QueryCaps {
+                in_nin: v.query,
+                bit: v.bit,
+                ord: v.ord,
+                size: v.size,
+                ..allowed
+            },
+            Validator::Str(v) => QueryCaps {
+                in_nin: v.query,
+                regex: v.regex,
+                size: v.size,
+                encoding: v.encoding_ok,
+                ..allowed
+            },
+            Validator::Time(v) => QueryCaps {
+                in_nin: v.query,
+                ord: v.ord,
+                ..allowed
+            },
+            Validator::Array(v) => QueryCaps {
+                in_nin: v.query,
+                array: v.array,
+                contains: v.contains_ok,
+                unique: v.unique_ok,
+                size: v.size,
+                ..allowed
+            },
+            Validator::Map(v) => QueryCaps {
+                in_nin: v.query,
+                size: v.size,
+                map: v.map_ok,
+                unique: v.unique_ok,
+                match_keys: v.match_keys,
+                len_keys: v.len_keys,
+                ..allowed
+            },
+            Validator::Hash(v) => QueryCaps {
+                in_nin: v.query,
+                link: v.link_ok,
+                schema: v.schema_ok,
+                ..allowed
+            },
+            Validator::Identity(v) => QueryCaps {
+                in_nin: v.query,
+                ..allowed
+            },
+            Validator::StreamId(v) => QueryCaps {
+                in_nin: v.query,
+                ..allowed
+            },
+            Validator::LockId(v) => QueryCaps {
+                in_nin: v.query,
+                ..allowed
+            },
+            Validator::DataLockbox(v) => QueryCaps {
+                size: v.size,
+                ..allowed
+            },
+            Validator::IdentityLockbox(v) => QueryCaps {
+                size: v.size,
+                ..allowed
+            },
+            Validator::StreamLockbox(v) => QueryCaps {
+                size: v.size,
+                ..allowed
+            },
+            Validator::LockLockbox(v) => QueryCaps {
+                size: v.size,
+                ..allowed
+            },
+            Validator::Ref(ref_name) => match types.get(ref_name) {
+                None | Some(Validator::Ref(_)) => QueryCaps::default(),
+                Some(validator) => validator.query_capabilities(types),
+            },
+            Validator::Multi(list) => list
+                .iter()
+                .filter_map(|v| multi_branch(types, v))
+                .map(|v| v.query_capabilities(types))
+                .fold(QueryCaps::default(), QueryCaps::union),
+            Validator::All(list) => list
+                .iter()
+                .map(|v| match all_branch(types, v) {
+                    Some(v) => v.query_capabilities(types),
+                    None => QueryCaps::default(),
+                })
+                .fold(QueryCaps::any(), QueryCaps::intersect),
+            Validator::Not(_) | Validator::Any | Validator::AnyBounded(_) => QueryCaps::default(),
+        }
+    }
+
+    /// Summarize which constraints a query may use at a path inside this validator. Named types
+    /// in `types` are used to resolve any [`Ref`][Validator::Ref] validators.
+    ///
+    /// Map keys in the path select the matching `req` or `opt` validator, falling back to
+    /// `values`. Array indices select the matching `prefix` validator, falling back to `items`.
+    /// For enums, a key selects a named variant and an index selects an integer-tagged variant,
+    /// falling back to `other`.
+    ///
+    /// A query can only reach into a map or array if it's allowed to use `map_ok` or `array`
+    /// constraints, so nested validators reached without those permissions report no
+    /// capabilities. Returns `None` if the path doesn't lead to any validator.
+    pub fn query_capabilities_at(
+        &self,
+        types: &BTreeMap<String, Validator>,
+        path: &ValuePath,
+    ) -> Option<QueryCaps> {
+        let steps: Vec<Step> = path.segments().iter().map(Step::from).collect();
+        self.caps_at(types, &steps)
+    }
+
+    /// List every path inside this validator that a query can use, along with the constraints
+    /// allowed there, sorted by path. Paths are written in the [`ValuePath`] syntax, except that
+    /// `[*]` stands for any array index checked by `items`, and `*` stands for any map key
+    /// checked by `values`. The validator itself is at the empty path. Map keys and enum variant
+    /// names are written without escaping, so one containing `.` or `[`, or one that is `*`,
+    /// gives a path that can't be told apart from a different one.
+    ///
+    /// Paths are found by following map `req`, `opt`, and `values` validators, array `prefix`
+    /// and `items` validators, enum variants, and the branches of Multi and All validators,
+    /// resolving Ref validators with `types`. Each path's capabilities are found as in
+    /// [`query_capabilities_at`][Self::query_capabilities_at], and paths that don't allow any
+    /// query are left out.
+    pub fn queryable_paths(&self, types: &BTreeMap<String, Validator>) -> Vec<(String, QueryCaps)> {
+        let mut paths = BTreeSet::new();
+        self.collect_paths(types, &mut Vec::new(), &mut Vec::new(), &mut paths);
+        let mut found: Vec<(String, QueryCaps)> = paths
+            .iter()
+            .filter_map(|steps| {
+                let caps = self.caps_at(types, steps).filter(|caps| caps.allowed)?;
+                Some((format_steps(steps), caps))
+            })
+            .collect();
+        found.sort_by(|a, b| a.0.cmp(&b.0));
+        found
+    }
+
+    /// Gather every path reachable inside this validator. Named types already being followed
+    /// aren't followed again, so recursive types end.
+    fn collect_paths<'a>(
+        &'a self,
+        types: &'a BTreeMap<String, Validator>,
+        path: &mut Vec<Step>,
+        refs: &mut Vec<&'a str>,
+        out: &mut BTreeSet<Vec<Step>>,
+    ) {
+        out.insert(path.clone());
+        let mut nested = |validator: &'a Validator, step: Step, refs: &mut Vec<&'a str>| {
+            path.push(step);
+            validator.collect_paths(types, path, refs, out);
+            path.pop();
+        };
+        match self {
+            Validator::Ref(name) => {
+                if let Some(validator) = types.get(name) {
+                    if !refs.contains(&name.as_str()) {
+                        refs.push(name);
+                        validator.collect_paths(types, path, refs, out);
+                        refs.pop();
+                    }
+                }
+            }
+            Validator::Multi(list) => list
+                .iter()
+                .for_each(|v| v.collect_paths(types, path, refs, out)),
+            Validator::All(list) => list
+                .iter()
+                .for_each(|v| v.collect_paths(types, path, refs, out)),
+            Validator::Map(v) => {
+                for (key, validator) in v.req.iter().chain(v.opt.iter()) {
+                    nested(validator, Step::Key(key.clone()), refs);
+                }
+                if let Some(values) = &v.values {
+                    nested(values, Step::Values, refs);
+                }
+            }
+            Validator::Array(v) => {
+                for (index, validator) in v.prefix.iter().enumerate() {
+                    nested(validator, Step::Index(index), refs);
+                }
+                nested(&v.items, Step::Items, refs);
+            }
+            Validator::Enum(v) => {
+                for (name, validator) in v.var.iter() {
+                    if let Some(validator) = validator {
+                        nested(validator, Step::Key(name.clone()), refs);
+                    }
+                }
+                for (tag, validator) in v.int_var.iter() {
+                    if let (true, Some(validator)) = (*tag >= 0, validator) {
+                        nested(validator, Step::Index(*tag as usize), refs);
+                    }
+                }
+            }
+            _ => (),
+        }
+    }
+
+    fn caps_at(&self, types: &BTreeMap<String, Validator>, path: &[Step]) -> Option<QueryCaps> {
+        let (segment, rest) = match path.split_first() {
+            Some(split) => split,
+            None => return Some(self.query_capabilities(types)),
+        };
+        let (next, reachable) = match (self, segment) {
+            (Validator::Ref(ref_name), _) => {
+                return match types.get(ref_name)? {
+                    Validator::Ref(_) => None,
+                    validator => validator.caps_at(types, path),
+                }
+            }
+            (Validator::Multi(list), _) => {
+                return list
+                    .iter()
+                    .filter_map(|v| multi_branch(types, v))
+                    .filter_map(|v| v.caps_at(types, path))
+                    .reduce(QueryCaps::union);
+            }
+            (Validator::All(list), _) => {
+                let branches: Vec<Option<QueryCaps>> = list
+                    .iter()
+                    .map(|v| all_branch(types, v).and_then(|v| v.caps_at(types, path)))
+                    .collect();
+                if branches.iter().all(Option::is_none) {
+                    return None;
+                }
+                return Some(
+                    branches
+                        .into_iter()
+                        .map(Option::unwrap_or_default)
+                        .fold(QueryCaps::any(), QueryCaps::intersect),
+                );
+            }
+            (Validator::Map(v), Step::Key(key)) => (v.field_validator(key)?, v.map_ok),
+            (Validator::Map(v), Step::Values) => (v.values.as_deref()?, v.map_ok),
+            (Validator::Array(v), Step::Index(index)) => {
+                (v.prefix.get(*index).unwrap_or(&v.items), v.array)
+            }
+            (Validator::Array(v), Step::Items) => (v.items.as_ref(), v.array),
+            (Validator::Enum(v), segment) => {
+                let variant = match segment {
+                    Step::Key(key) => v.get_var(key),
+                    Step::Index(index) => v.int_var.get(&(*index as i64)),
+                    Step::Items | Step::Values => return None,
+                };
+                let next = match variant {
+                    Some(variant) => variant.as_ref()?,
+                    None => v.other.as_ref()?.as_deref()?,
+                };
+                (next, true)
+            }
+            _ => return None,
+        };
+        let caps = next.caps_at(types, rest)?;
+        Some(if reachable {
+            caps
+        } else {
+            QueryCaps::default()
+        })
+    }
+
+    /// Find a pattern-matching constraint in `query` that this validator doesn't permit,
+    /// returning where it is in the query and the name of the permission it needs. This is used to
+    /// explain why a query was rejected; it doesn't find any other kind of denied constraint.
+    pub(crate) fn denied_pattern(
+        &self,
+        types: &BTreeMap<String, Validator>,
+        query: &Validator,
+    ) -> Option<(ValuePath, &'static str)> {
+        self.find_denied_pattern(types, query, &mut Vec::new())
+    }
+
+    fn find_denied_pattern(
+        &self,
+        types: &BTreeMap<String, Validator>,
+        query: &Validator,
+        path: &mut Vec<PathSegment>,
+    ) -> Option<(ValuePath, &'static str)> {
+        // Spots the query can't reach at all are rejected for some other reason
+        let steps: Vec<Step> = path.iter().map(Step::from).collect();
+        let caps = self.caps_at(types, &steps).filter(|caps| caps.allowed)?;
+        let mut nested = |query: &Validator, segment: PathSegment| {
+            path.push(segment);
+            let denied = self.find_denied_pattern(types, query, path);
+            path.pop();
+            denied
+        };
+        match query {
+            Validator::Str(q) if q.matches.is_some() && !caps.regex => {
+                Some((ValuePath::new(path.clone()), "regex"))
+            }
+            Validator::Map(q) if q.keys.matches.is_some() && !caps.match_keys => {
+                Some((ValuePath::new(path.clone()), "match_keys"))
+            }
+            Validator::Map(q) => q
+                .req
+                .iter()
+                .chain(q.opt.iter())
+                .find_map(|(key, v)| nested(v, PathSegment::Key(key.clone()))),
+            Validator::Array(q) => q
+                .prefix
+                .iter()
+                .chain(std::iter::once(q.items.as_ref()))
+                .enumerate()
+                .find_map(|(index, v)| nested(v, PathSegment::Index(index))),
+            Validator::Multi(MultiValidator(list)) | Validator::All(AllValidator(list)) => list
+                .iter()
+                .find_map(|v| self.find_denied_pattern(types, v, path)),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn path(path: &str) -> ValuePath {
+        ValuePath::parse(path).unwrap()
+    }
+
+    #[test]
+    fn flags() {
+        let types = BTreeMap::new();
+        let caps = IntValidator::new().query(true).ord(true).build();
+        let caps = caps.query_capabilities(&types);
+        assert!(caps.allowed && caps.in_nin && caps.ord);
+        assert!(!caps.bit && !caps.regex && !caps.size);
+
+        let caps = StrValidator::new().regex(true).build();
+        let caps = caps.query_capabilities(&types);
+        assert!(caps.allowed && caps.regex && !caps.in_nin);
+
+        assert_eq!(
+            Validator::Any.query_capabilities(&types),
+            QueryCaps::default()
+        );
+        assert!(Validator::Null.query_capabilities(&types).allowed);
+    }
+
+    #[test]
+    fn array_and_map() {
+        let mut types = BTreeMap::new();
+        types.insert("score".to_string(), IntValidator::new().ord(true).build());
+        let tags = ArrayValidator::new()
+            .prefix_add(StrValidator::new().regex(true).build())
+            .items(StrValidator::new().query(true).build())
+            .array(true)
+            .contains_ok(true)
+            .build();
+        let doc = MapValidator::new()
+            .req_add("score", Validator::new_ref("score"))
+            .opt_add("tags", tags)
+            .values(BoolValidator::new().query(true).build())
+            .map_ok(true)
+            .build();
+
+        let caps = doc.query_capabilities_at(&types, &path("")).unwrap();
+        assert!(caps.map && !caps.size);
+        let caps = doc.query_capabilities_at(&types, &path("score")).unwrap();
+        assert!(caps.ord && !caps.in_nin);
+        let caps = doc.query_capabilities_at(&types, &path("tags")).unwrap();
+        assert!(caps.array && caps.contains && !caps.unique);
+        let caps = doc.query_capabilities_at(&types, &path("tags[0]")).unwrap();
+        assert!(caps.regex && !caps.in_nin);
+        let caps = doc.query_capabilities_at(&types, &path("tags[5]")).unwrap();
+        assert!(caps.in_nin && !caps.regex);
+        let caps = doc.query_capabilities_at(&types, &path("other")).unwrap();
+        assert!(caps.in_nin);
+
+        // Paths that don't match the validator's shape don't lead anywhere
+        assert!(doc.query_capabilities_at(&types, &path("[0]")).is_none());
+        assert!(doc
+            .query_capabilities_at(&types, &path("score.a"))
+            .is_none());
+
+        // Without `map_ok`, nothing inside the map can be queried
+        let closed = MapValidator::new()
+            .req_add("score", Validator::new_ref("score"))
+            .build();
+        let caps = closed
+            .query_capabilities_at(&types, &path("score"))
+            .unwrap();
+        assert_eq!(caps, QueryCaps::default());
+    }
+
+    #[test]
+    fn enums() {
+        let types = BTreeMap::new();
+        let validator = EnumValidator::new()
+            .insert("Empty", None)
+            .insert("Int", Some(IntValidator::new().bit(true).build()))
+            .alias("Integer", "Int")
+            .build();
+        let caps = validator.query_capabilities(&types);
+        assert!(caps.allowed && !caps.in_nin);
+        let caps = validator
+            .query_capabilities_at(&types, &path("Int"))
+            .unwrap();
+        assert!(caps.bit);
+        let caps = validator
+            .query_capabilities_at(&types, &path("Integer"))
+            .unwrap();
+        assert!(caps.bit);
+        assert!(validator
+            .query_capabilities_at(&types, &path("Empty"))
+            .is_none());
+        assert!(validator
+            .query_capabilities_at(&types, &path("Missing"))
+            .is_none());
+    }
+
+    #[test]
+    fn multi_and_all() {
+        let mut types = BTreeMap::new();
+        types.insert(
+            "multi".to_string(),
+            MultiValidator::new()
+                .push(IntValidator::new().bit(true).build())
+                .build(),
+        );
+        let int_ord = IntValidator::new().ord(true).query(true).build();
+        let int_in = IntValidator::new().query(true).build();
+
+        let multi = MultiValidator::new()
+            .push(int_ord.clone())
+            .push(StrValidator::new().size(true).build())
+            .push(Validator::new_ref("multi"))
+            .build();
+        let caps = multi.query_capabilities(&types);
+        assert!(caps.ord && caps.in_nin && caps.size);
+        // The Multi behind a Ref is skipped
+        assert!(!caps.bit);
+
+        let all = AllValidator::new().push(int_ord).push(int_in).build();
+        let caps = all.query_capabilities(&types);
+        assert!(caps.allowed && caps.in_nin && !caps.ord);
+        let caps = AllValidator::new().build().query_capabilities(&types);
+        assert_eq!(caps, QueryCaps::any());
+
+        // Paths go through every branch
+        let multi = MultiValidator::new()
+            .push(
+                MapValidator::new()
+                    .req_add("a", StrValidator::new().regex(true).build())
+                    .map_ok(true)
+                    .build(),
+            )
+            .push(
+                MapValidator::new()
+                    .req_add("a", IntValidator::new().ord(true).build())
+                    .map_ok(true)
+                    .build(),
+            )
+            .build();
+        let caps = multi.query_capabilities_at(&types, &path("a")).unwrap();
+        assert!(caps.regex && caps.ord);
+        let all = AllValidator::new()
+            .push(multi)
+            .push(MapValidator::new().map_ok(true).build())
+            .build();
+        let caps = all.query_capabilities_at(&types, &path("a")).unwrap();
+        assert_eq!(caps, QueryCaps::default());
+    }
+}
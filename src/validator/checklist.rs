@@ -4,7 +4,7 @@ use super::*;
 use crate::Hash;
 use crate::{
     document::Document,
-    error::{Error, Result},
+    error::{Error, Result, ValidateCode},
 };
 
 /// An item in a Checklist. To complete it, find a document whose hash matches the one that was
@@ -19,14 +19,32 @@ pub struct ListItem<'a> {
 }
 
 impl<'a> ListItem<'a> {
+    /// Get the schemas a Document must use to pass this item, or `None` if any schema (or no
+    /// schema at all) is acceptable. An empty list means no Document can pass.
+    pub fn allowed_schemas(&self) -> Option<Vec<&'a Hash>> {
+        let schema = self.schema;
+        let mut lists = self.inner.schema.iter().map(|list| {
+            list.iter()
+                .map(move |s| s.as_ref().unwrap_or(schema))
+                .collect::<Vec<&Hash>>()
+        });
+        let first = lists.next()?;
+        Some(lists.fold(first, |allowed, list| {
+            allowed.into_iter().filter(|s| list.contains(s)).collect()
+        }))
+    }
+
+    /// Check a Document against this item. Fails if the Document doesn't use an allowed schema,
+    /// or if its data doesn't pass every `link` validator.
     pub fn check(self, doc: &Document) -> Result<()> {
         // Check that the Document meets all the `schema` requirements from each Hash validator
         if !self.inner.schema.is_empty() {
             let doc_schema = match doc.schema_hash() {
                 Some(schema) => schema,
                 None => {
-                    return Err(Error::FailValidate(
-                        "Document has no schema, but must pass `schema` validation".into(),
+                    return Err(Error::validate(
+                        ValidateCode::BadLink,
+                        "Document has no schema, but must pass `schema` validation",
                     ))
                 }
             };
@@ -37,8 +55,9 @@ impl<'a> ListItem<'a> {
                 })
             });
             if !all_schema_pass {
-                return Err(Error::FailValidate(
-                    "Document schema didn't satisfy all `schema` requirements".into(),
+                return Err(Error::validate(
+                    ValidateCode::BadLink,
+                    "Document schema didn't satisfy all `schema` requirements",
                 ));
             }
         }
@@ -48,15 +67,16 @@ impl<'a> ListItem<'a> {
         // We don't need to `finish()` the parser after each validation because that's to
         // catch sitautions where the inner data contains more than one fog-pack value in sequence.
         // Because we already have a Document, that check was already performed.
-        let parser = Parser::new(doc.data());
+        let parser = Parser::new(doc.data()).with_signer(doc.signer());
         let all_link_pass = self
             .inner
             .link
             .iter()
             .all(|validator| validator.validate(self.types, parser.clone(), None).is_ok());
         if !all_link_pass {
-            return Err(Error::FailValidate(
-                "Document schema didn't satisfy all `link` requirements".into(),
+            return Err(Error::validate(
+                ValidateCode::BadLink,
+                "Document schema didn't satisfy all `link` requirements",
             ));
         }
         Ok(())
@@ -78,6 +98,14 @@ impl<'a> InnerListItem<'a> {
     }
 }
 
+/// Data awaiting validation against other Documents.
+///
+/// [`Hash`][crate::validator::HashValidator] validators with a `link` or `schema` requirement
+/// can't finish validating on their own: the Documents the hashes refer to must be checked too.
+/// Each of those Documents should be found and passed through one of [`check`][Self::check] or
+/// [`iter`][Self::iter]. Once every item is checked, [`complete`][Self::complete] returns the
+/// validated data. If some Documents aren't available yet, [`defer`][Self::defer] gives up the
+/// data along with the items still left to check.
 #[derive(Clone, Debug)]
 pub struct DataChecklist<'a, T> {
     list: Checklist<'a>,
@@ -96,14 +124,58 @@ impl<'a, T> DataChecklist<'a, T> {
         self.list.iter()
     }
 
+    /// Check a Document against the checklist item with the same hash. Fails if there is no such
+    /// item, or if the Document doesn't meet the item's requirements.
     pub fn check(&mut self, doc: &Document) -> Result<()> {
         self.list.check(doc)
     }
 
+    /// Get the number of items that still need to be checked.
+    pub fn pending(&self) -> usize {
+        self.list.list.len()
+    }
+
+    /// Iterate over the hashes of the Documents that still need to be checked, without removing
+    /// them from the checklist.
+    pub fn pending_hashes(&self) -> impl Iterator<Item = &Hash> {
+        self.list.list.keys()
+    }
+
+    /// Check if every item has been checked, such that [`complete`][Self::complete] will succeed.
+    pub fn is_complete(&self) -> bool {
+        self.list.list.is_empty()
+    }
+
+    /// Finish the checklist, returning the validated data. Fails if any items weren't checked.
     pub fn complete(self) -> Result<T> {
         self.list.complete()?;
         Ok(self.data)
     }
+
+    /// Finish the checklist without checking the remaining items, returning the data along with
+    /// the items that still need to be checked. The data must not be treated as validated until
+    /// every returned item has passed its [`check`][ListItem::check].
+    pub fn defer(self) -> (T, Vec<(Hash, ListItem<'a>)>) {
+        let Checklist {
+            list,
+            types,
+            schema,
+        } = self.list;
+        let items = list
+            .into_iter()
+            .map(|(hash, inner)| {
+                (
+                    hash,
+                    ListItem {
+                        inner,
+                        schema,
+                        types,
+                    },
+                )
+            })
+            .collect();
+        (self.data, items)
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -157,7 +229,12 @@ impl<'a> Checklist<'a> {
     fn check(&mut self, doc: &Document) -> Result<()> {
         self.list
             .remove(&doc.hash())
-            .ok_or_else(|| Error::FailValidate("provided document wasn't in checlist".into()))
+            .ok_or_else(|| {
+                Error::validate(
+                    ValidateCode::BadLink,
+                    "provided document wasn't in checlist",
+                )
+            })
             .and_then(|inner| {
                 let item = ListItem {
                     inner,
@@ -172,8 +249,9 @@ impl<'a> Checklist<'a> {
         if self.list.is_empty() {
             Ok(())
         } else {
-            Err(Error::FailValidate(
-                "Not all verification checklist items were completed".into(),
+            Err(Error::validate(
+                ValidateCode::Incomplete,
+                "Not all verification checklist items were completed",
             ))
         }
     }
@@ -181,7 +259,7 @@ impl<'a> Checklist<'a> {
 
 #[cfg(test)]
 mod test {
-    use crate::{document::NewDocument, schema::*, types::Integer};
+    use crate::{document::NewDocument, entry::NewEntry, schema::*, types::Integer};
 
     use super::*;
 
@@ -234,10 +312,144 @@ mod test {
             .try_for_each(|(hash, item)| {
                 let doc = map
                     .get(&hash)
-                    .ok_or_else(|| Error::FailValidate("".into()))?;
+                    .ok_or_else(|| Error::validate(ValidateCode::BadLink, ""))?;
                 item.check(doc)
             })
             .unwrap();
         checklist.complete().unwrap();
     }
+
+    #[test]
+    fn link_schema() {
+        let schema1 = SchemaBuilder::new(Validator::Int(IntValidator::default()))
+            .build()
+            .unwrap();
+        let schema1 = Schema::from_doc(&schema1).unwrap();
+        let schema2 = SchemaBuilder::new(Validator::Null).build().unwrap();
+        let schema2 = Schema::from_doc(&schema2).unwrap();
+        let good = schema1
+            .validate_new_doc(NewDocument::new(1u8, Some(schema1.hash())).unwrap())
+            .unwrap();
+        let wrong = schema2
+            .validate_new_doc(NewDocument::new((), Some(schema2.hash())).unwrap())
+            .unwrap();
+
+        let types = BTreeMap::new();
+        let allowed = [Some(schema1.hash().clone())];
+        let new_list = |docs: &[&Document]| {
+            let mut checklist = Checklist::new(schema1.hash(), &types);
+            for doc in docs {
+                checklist.insert(doc.hash(), Some(&allowed), None);
+            }
+            DataChecklist::from_checklist(checklist, 7u8)
+        };
+
+        // Satisfied link
+        let mut checklist = new_list(&[&good]);
+        checklist.check(&good).unwrap();
+        assert_eq!(checklist.complete().unwrap(), 7u8);
+
+        // Linked document uses the wrong schema
+        let mut checklist = new_list(&[&wrong]);
+        assert!(checklist.check(&wrong).is_err());
+
+        // Unchecked items block completion, but can be deferred
+        let checklist = new_list(&[&good, &wrong]);
+        assert_eq!(checklist.pending(), 2);
+        assert!(checklist.clone().complete().is_err());
+        let (data, items) = checklist.defer();
+        assert_eq!(data, 7u8);
+        assert_eq!(items.len(), 2);
+        for (hash, item) in items {
+            if hash == good.hash() {
+                item.check(&good).unwrap();
+            } else {
+                assert!(item.check(&wrong).is_err());
+            }
+        }
+    }
+
+    #[test]
+    fn resolve_links() {
+        let item_schema = SchemaBuilder::new(Validator::Str(StrValidator::default()))
+            .build()
+            .unwrap();
+        let item_schema = Schema::from_doc(&item_schema).unwrap();
+        let entry = MapValidator::new()
+            .req_add(
+                "item",
+                HashValidator::new()
+                    .schema_add(item_schema.hash().clone())
+                    .build(),
+            )
+            .req_add("parent", HashValidator::new().schema_self().build())
+            .build();
+        let schema = SchemaBuilder::new(Validator::Null)
+            .entry_add("link", entry, None)
+            .build()
+            .unwrap();
+        let schema = Schema::from_doc(&schema).unwrap();
+
+        // A simple document store
+        let item = item_schema
+            .validate_new_doc(NewDocument::new("item", Some(item_schema.hash())).unwrap())
+            .unwrap();
+        let parent = schema
+            .validate_new_doc(NewDocument::new((), Some(schema.hash())).unwrap())
+            .unwrap();
+        let mut store = HashMap::new();
+        store.insert(item.hash(), item.clone());
+        store.insert(parent.hash(), parent.clone());
+
+        let mut data = BTreeMap::new();
+        data.insert("item", item.hash());
+        data.insert("parent", parent.hash());
+        let entry = NewEntry::new(&data, "link", &parent.hash()).unwrap();
+        let mut checklist = schema.encode_new_entry(entry).unwrap();
+        assert_eq!(checklist.pending(), 2);
+        assert!(!checklist.is_complete());
+
+        let hashes: Vec<Hash> = checklist.pending_hashes().cloned().collect();
+        assert_eq!(hashes.len(), 2);
+        assert!(hashes.contains(&item.hash()));
+        assert!(hashes.contains(&parent.hash()));
+
+        for hash in hashes {
+            checklist.check(store.get(&hash).unwrap()).unwrap();
+        }
+        assert!(checklist.is_complete());
+        checklist.complete().unwrap();
+
+        // Linking the documents the wrong way around fails
+        let mut data = BTreeMap::new();
+        data.insert("item", parent.hash());
+        data.insert("parent", item.hash());
+        let entry = NewEntry::new(&data, "link", &parent.hash()).unwrap();
+        let mut checklist = schema.encode_new_entry(entry).unwrap();
+        let results: Vec<Result<()>> = checklist
+            .iter()
+            .map(|(hash, item)| item.check(store.get(&hash).unwrap()))
+            .collect();
+        assert!(results.iter().all(|r| r.is_err()));
+    }
+
+    #[test]
+    fn allowed_schemas() {
+        let (a, b, c) = (Hash::new(b"a"), Hash::new(b"b"), Hash::new(b"c"));
+        let types = BTreeMap::new();
+        let first = [Some(a.clone()), Some(b.clone()), None];
+        let second = [Some(b.clone()), Some(c.clone())];
+        let mut checklist = Checklist::new(&c, &types);
+        checklist.insert(Hash::new(b"1"), Some(&first), None);
+        checklist.insert(Hash::new(b"1"), Some(&second), None);
+        checklist.insert(Hash::new(b"2"), None, Some(&Validator::Null));
+        let mut checklist = DataChecklist::from_checklist(checklist, ());
+        for (hash, item) in checklist.iter() {
+            if hash == Hash::new(b"1") {
+                assert_eq!(item.allowed_schemas(), Some(vec![&b, &c]));
+            } else {
+                assert_eq!(item.allowed_schemas(), None);
+            }
+        }
+    }
 }
@@ -0,0 +1,88 @@
+use super::*;
+use crate::error::{Error, Result, ValidateCode};
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+
+/// Validator that only passes a value exactly equal to a constant.
+///
+/// Equality is checked on the encoded form, so floating-point values must match bit-for-bit (a
+/// NaN constant will match the identical NaN). The constant is encoded once when a Schema or
+/// Query is loaded, and is never serialized as anything but the constant itself.
+///
+/// # Query Checking
+///
+/// A Const validator in a schema only allows queries that use the Any validator, or a Const
+/// validator with the same constant.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct ConstValidator {
+    /// The constant value.
+    pub value: Value,
+    /// Precomputed encoding of the constant.
+    #[serde(skip)]
+    pub(crate) encoded: Option<Vec<u8>>,
+}
+
+impl ConstValidator {
+    /// Make a new validator that only passes `value`.
+    pub fn new(value: impl Into<Value>) -> Self {
+        Self {
+            value: value.into(),
+            encoded: None,
+        }
+    }
+
+    /// Build this into a [`Validator`] enum.
+    pub fn build(self) -> Validator {
+        Validator::Const(self)
+    }
+
+    pub(crate) fn build_index(&mut self) {
+        self.encoded = encode_const(&self.value).ok();
+    }
+
+    /// Get the encoded constant, encoding it now if the index hasn't been built.
+    fn encoded(&self) -> Result<Cow<'_, [u8]>> {
+        match &self.encoded {
+            Some(encoded) => Ok(Cow::Borrowed(encoded)),
+            None => Ok(Cow::Owned(encode_const(&self.value)?)),
+        }
+    }
+
+    pub(crate) fn validate(&self, parser: &mut Parser) -> Result<()> {
+        let start = parser.remaining();
+        read_any(parser)?;
+        let found = &start[..(start.len() - parser.remaining().len())];
+        if found == self.encoded()?.as_ref() {
+            Ok(())
+        } else {
+            Err(Error::validate(
+                ValidateCode::NoMatch,
+                "value doesn't match Const value".to_string(),
+            ))
+        }
+    }
+
+    pub(crate) fn query_check(&self, other: &Validator) -> bool {
+        match other {
+            Validator::Const(other) => match (self.encoded(), other.encoded()) {
+                (Ok(value), Ok(other)) => value == other,
+                _ => false,
+            },
+            Validator::Any => true,
+            _ => false,
+        }
+    }
+}
+
+impl PartialEq for ConstValidator {
+    fn eq(&self, other: &Self) -> bool {
+        self.value == other.value
+    }
+}
+
+impl std::fmt::Debug for ConstValidator {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_tuple("ConstValidator").field(&self.value).finish()
+    }
+}
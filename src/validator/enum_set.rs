@@ -1,7 +1,18 @@
 use super::*;
-use crate::error::{Error, Result};
-use serde::{Deserialize, Serialize};
+use crate::error::{Error, Result, ValidateCode};
+use crate::integer::Integer;
+use serde::de::{Error as _, IgnoredAny, MapAccess, SeqAccess, Visitor};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::default::Default;
+use std::fmt;
+
+fn get_other<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<Option<Box<Validator>>>, D::Error> {
+    // If this function is called, the field was present, so a `null` here means "accept unknown
+    // unit variants only", not "no catch-all".
+    Ok(Some(Option::<Box<Validator>>::deserialize(deserializer)?))
+}
 
 /// "Enum" validator that selects a validator based on the value's enum variant.
 ///
@@ -13,12 +24,32 @@ use std::default::Default;
 /// For unit variants, there is no validator, and they pass as long as their name is a key in the
 /// `BTreeMap`.
 ///
+/// Variants may instead be identified by an integer tag, using the `int_var` map. An integer-tagged
+/// unit variant is a single integer, and an integer-tagged variant with associated data is a
+/// 2-element array of the tag followed by the data. fog-pack maps can only have string keys, so
+/// the single-pair map form isn't used for integer tags. A value using the wrong kind of tag never
+/// matches, even if a string variant's name happens to be the tag written out.
+///
+/// Named variants can also be given aliases through the `alias` map, which maps an old variant
+/// name to the current one. If a name isn't found in `var`, it's looked up in `alias`, and the
+/// validator for the target variant is used instead. This lets a schema keep accepting documents
+/// written before a variant was renamed. Every alias must point to a variant in `var`.
+///
+/// Unknown variants normally fail validation. If `other` is set, they are accepted instead, like
+/// Rust's `#[serde(other)]`, which lets an older schema accept documents made with newer versions
+/// of an enum. Unknown unit variants always pass, and unknown variants with associated data pass
+/// if `other` holds a validator that the data passes. Setting `other` to `Some(None)` only
+/// accepts unknown unit variants.
+///
 /// # Query Checking
 ///
-/// The query validator must be an Any or an Enum validator, and the maps are directly checked against
-/// each other. The query validator may use a subset of the enum list. For unit variants, both the
-/// query validator and schema validator must have `None` instead of a validator. As an example,
-/// see the following:
+/// The query validator must be an Any or an Enum validator, and the maps are directly checked
+/// against each other, with `var` checked against `var` and `int_var` checked against `int_var`.
+/// An alias in the schema validator is treated the same as its target variant. Query variants not
+/// found in the schema validator are checked against the schema's `other` setting, and a query may
+/// only set `other` if the schema validator does as well. The query validator may use a subset of
+/// the enum list. For unit variants, both the query validator and schema validator must have
+/// `None` instead of a validator. As an example, see the following:
 ///
 /// ```
 /// # use fog_pack::{
@@ -74,15 +105,170 @@ use std::default::Default;
 /// # }
 /// ```
 ///
-#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
-pub struct EnumValidator(pub BTreeMap<String, Option<Validator>>);
+/// # Encoding
+///
+/// If only named variants are used, the validator is encoded as a map from variant name to
+/// validator, the same as in earlier versions of fog-pack. Once any integer-tagged variants,
+/// aliases, or a catch-all are set, it's instead encoded as a single-element array holding a map
+/// with `var`, `int_var`, `alias`, and `other` fields. Since the two forms are told apart by
+/// being a map or an array, any variant name can be used in either one.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct EnumValidator {
+    /// Variants identified by their name.
+    pub var: BTreeMap<String, Option<Validator>>,
+    /// Variants identified by an integer tag.
+    pub int_var: BTreeMap<i64, Option<Validator>>,
+    /// Old variant names, mapped to the name of the variant in `var` they now refer to.
+    pub alias: BTreeMap<String, String>,
+    /// The catch-all for unknown variants. If `None`, unknown variants fail validation.
+    /// Otherwise, unknown unit variants pass, and unknown variants with data must pass the
+    /// contained validator, if there is one.
+    pub other: Option<Option<Box<Validator>>>,
+}
+
+/// The full encoding of an [`EnumValidator`], used when it has more than named variants. It's
+/// always wrapped in a single-element array, so it can't be mistaken for a map of variants.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields, default)]
+pub(crate) struct EnumFields {
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    var: BTreeMap<String, Option<Validator>>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty", with = "int_var_serde")]
+    int_var: BTreeMap<i64, Option<Validator>>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    alias: BTreeMap<String, String>,
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        deserialize_with = "get_other"
+    )]
+    other: Option<Option<Box<Validator>>>,
+}
+
+impl Serialize for EnumValidator {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if self.int_var.is_empty() && self.alias.is_empty() && self.other.is_none() {
+            return self.var.serialize(serializer);
+        }
+        let fields = EnumFields {
+            var: self.var.clone(),
+            int_var: self.int_var.clone(),
+            alias: self.alias.clone(),
+            other: self.other.clone(),
+        };
+        serializer.collect_seq(std::iter::once(fields))
+    }
+}
 
-impl Default for EnumValidator {
-    fn default() -> Self {
-        Self(BTreeMap::new())
+impl<'de> Deserialize<'de> for EnumValidator {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(EnumVisitor)
     }
 }
 
+struct EnumVisitor;
+
+impl<'de> Visitor<'de> for EnumVisitor {
+    type Value = EnumValidator;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("an Enum validator map")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, map: A) -> Result<Self::Value, A::Error> {
+        let var = BTreeMap::deserialize(serde::de::value::MapAccessDeserializer::new(map))?;
+        Ok(EnumValidator {
+            var,
+            ..EnumValidator::default()
+        })
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let full: EnumFields = seq
+            .next_element()?
+            .ok_or_else(|| A::Error::invalid_length(0, &self))?;
+        if seq.next_element::<IgnoredAny>()?.is_some() {
+            return Err(A::Error::custom(
+                "Enum validator array must have exactly one element",
+            ));
+        }
+        Ok(EnumValidator {
+            var: full.var,
+            int_var: full.int_var,
+            alias: full.alias,
+            other: full.other,
+        })
+    }
+}
+
+/// fog-pack map keys must be strings, so the integer-tagged variants are stored as an array of
+/// `[tag, validator]` pairs instead.
+mod int_var_serde {
+    use super::Validator;
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+    use std::collections::BTreeMap;
+
+    pub fn serialize<S: Serializer>(
+        map: &BTreeMap<i64, Option<Validator>>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(map.iter())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<BTreeMap<i64, Option<Validator>>, D::Error> {
+        let pairs = Vec::<(i64, Option<Validator>)>::deserialize(deserializer)?;
+        let len = pairs.len();
+        let map: BTreeMap<_, _> = pairs.into_iter().collect();
+        if map.len() != len {
+            return Err(D::Error::custom("int_var has repeated tags"));
+        }
+        Ok(map)
+    }
+}
+
+/// How [`EnumValidator::merge`] handles a variant that is in both validators with different
+/// settings.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConflictPolicy {
+    /// Fail the merge.
+    Error,
+    /// Keep the variant from the validator being merged into.
+    KeepSelf,
+    /// Keep the variant from the validator being merged in.
+    KeepOther,
+}
+
+/// Merge `other` into `map` according to `policy`. `name` is used to describe a conflict.
+fn merge_map<K: Ord + Clone + fmt::Debug, V: PartialEq>(
+    map: &mut BTreeMap<K, V>,
+    other: BTreeMap<K, V>,
+    policy: ConflictPolicy,
+    name: &str,
+) -> Result<()> {
+    for (k, v) in other {
+        match map.get(&k) {
+            Some(ours) if *ours == v => (),
+            Some(_) => match policy {
+                ConflictPolicy::Error => {
+                    return Err(Error::validate(
+                        ValidateCode::BadValidator,
+                        format!("Enum {} {:?} conflicts while merging", name, k),
+                    ))
+                }
+                ConflictPolicy::KeepSelf => (),
+                ConflictPolicy::KeepOther => {
+                    map.insert(k, v);
+                }
+            },
+            None => {
+                map.insert(k, v);
+            }
+        }
+    }
+    Ok(())
+}
+
 impl EnumValidator {
     /// Make a new validator with the default configuration.
     pub fn new() -> Self {
@@ -91,21 +277,126 @@ impl EnumValidator {
 
     /// Add a new enum to the set.
     pub fn insert(mut self, variant: impl Into<String>, validator: Option<Validator>) -> Self {
-        self.0.insert(variant.into(), validator);
+        self.var.insert(variant.into(), validator);
+        self
+    }
+
+    /// Add a new integer-tagged enum to the set.
+    pub fn insert_int(mut self, tag: i64, validator: Option<Validator>) -> Self {
+        self.int_var.insert(tag, validator);
+        self
+    }
+
+    /// Add an alias, so that a variant named `old` is validated as the variant `variant`.
+    pub fn alias(mut self, old: impl Into<String>, variant: impl Into<String>) -> Self {
+        self.alias.insert(old.into(), variant.into());
+        self
+    }
+
+    /// Set the catch-all for unknown variants.
+    pub fn other(mut self, other: Option<Validator>) -> Self {
+        self.other = Some(other.map(Box::new));
         self
     }
 
+    /// Remove a named variant from the set, returning its validator setting if it was present.
+    pub fn remove(&mut self, variant: &str) -> Option<Option<Validator>> {
+        self.var.remove(variant)
+    }
+
+    /// Get mutable access to a named variant's validator setting.
+    pub fn get_mut(&mut self, variant: &str) -> Option<&mut Option<Validator>> {
+        self.var.get_mut(variant)
+    }
+
+    /// Get the number of variants, both named and integer-tagged.
+    pub fn len(&self) -> usize {
+        self.var.len() + self.int_var.len()
+    }
+
+    /// Check if there are no variants, named or integer-tagged.
+    pub fn is_empty(&self) -> bool {
+        self.var.is_empty() && self.int_var.is_empty()
+    }
+
+    /// Merge another validator's variants, aliases, and catch-all into this one. A variant that
+    /// is in both validators with different settings is resolved using `on_conflict`; identical
+    /// variants never conflict.
+    pub fn merge(mut self, other: EnumValidator, on_conflict: ConflictPolicy) -> Result<Self> {
+        merge_map(&mut self.var, other.var, on_conflict, "variant")?;
+        merge_map(&mut self.int_var, other.int_var, on_conflict, "variant")?;
+        merge_map(&mut self.alias, other.alias, on_conflict, "alias")?;
+        match (&self.other, other.other) {
+            (_, None) => (),
+            (None, theirs) => self.other = theirs,
+            (Some(ours), Some(theirs)) if *ours == theirs => (),
+            (Some(_), theirs) => match on_conflict {
+                ConflictPolicy::Error => {
+                    return Err(Error::validate(
+                        ValidateCode::BadValidator,
+                        "Enum catch-all conflicts while merging".to_string(),
+                    ))
+                }
+                ConflictPolicy::KeepSelf => (),
+                ConflictPolicy::KeepOther => self.other = theirs,
+            },
+        }
+        Ok(self)
+    }
+
     /// Build this into a [`Validator`] enum.
     pub fn build(self) -> Validator {
         Validator::Enum(self)
     }
 
     pub fn iter(&self) -> std::collections::btree_map::Iter<String, Option<Validator>> {
-        self.0.iter()
+        self.var.iter()
     }
 
     pub fn values(&self) -> std::collections::btree_map::Values<String, Option<Validator>> {
-        self.0.values()
+        self.var.values()
+    }
+
+    /// Iterate over the validators of all variants, both named and integer-tagged, along with the
+    /// catch-all for unknown variants.
+    pub(crate) fn validators(&self) -> impl Iterator<Item = &Validator> {
+        self.var
+            .values()
+            .chain(self.int_var.values())
+            .flatten()
+            .chain(self.other.iter().flatten().map(|v| v.as_ref()))
+    }
+
+    /// Iterate mutably over the validators of all variants, both named and integer-tagged, along
+    /// with the catch-all for unknown variants.
+    pub(crate) fn validators_mut(&mut self) -> impl Iterator<Item = &mut Validator> {
+        self.var
+            .values_mut()
+            .chain(self.int_var.values_mut())
+            .flatten()
+            .chain(self.other.iter_mut().flatten().map(|v| v.as_mut()))
+    }
+
+    /// Look up a named variant, following an alias if there's no variant by that name.
+    pub(crate) fn get_var(&self, name: &str) -> Option<&Option<Validator>> {
+        self.var
+            .get(name)
+            .or_else(|| self.alias.get(name).and_then(|target| self.var.get(target)))
+    }
+
+    pub(crate) fn check_build(&self) -> Result<()> {
+        for (old, target) in self.alias.iter() {
+            if !self.var.contains_key(target) {
+                return Err(Error::validate(
+                    ValidateCode::BadValidator,
+                    format!(
+                        "Enum alias {:?} points to nonexistent variant {:?}",
+                        old, target
+                    ),
+                ));
+            }
+        }
+        Ok(())
     }
 
     pub(crate) fn validate<'de, 'c>(
@@ -114,44 +405,84 @@ impl EnumValidator {
         mut parser: Parser<'de>,
         checklist: Option<Checklist<'c>>,
     ) -> Result<(Parser<'de>, Option<Checklist<'c>>)> {
-        // Get the enum itself, which should be a map with 1 key-value pair or a string.
+        // Get the enum itself, which should be a map with 1 key-value pair or a string. Integer
+        // tags are either an integer or a 2-element array of the tag and the value.
         let elem = parser
             .next()
-            .ok_or_else(|| Error::FailValidate("expected a enum".to_string()))??;
-        let (key, has_value) = match elem {
-            Element::Str(v) => (v, false),
+            .ok_or_else(|| Error::validate(ValidateCode::WrongType, "expected a enum"))??;
+        let (validator, has_value, name) = match elem {
+            Element::Str(v) => (self.get_var(v), false, v.to_string()),
             Element::Map(1) => {
-                let key = parser
-                    .next()
-                    .ok_or_else(|| Error::FailValidate("expected a string".to_string()))??;
+                let key = parser.next().ok_or_else(|| {
+                    Error::validate(ValidateCode::WrongType, "expected a string")
+                })??;
                 if let Element::Str(key) = key {
-                    (key, true)
+                    (self.get_var(key), true, key.to_string())
                 } else {
-                    return Err(Error::FailValidate("expected a string".to_string()));
+                    return Err(Error::validate(
+                        ValidateCode::WrongType,
+                        "expected a string",
+                    ));
                 }
             }
-            _ => return Err(Error::FailValidate("expected an enum".to_string())),
+            Element::Int(v) => (self.get_int(&v), false, v.to_string()),
+            Element::Array(2) => {
+                let tag = parser.next().ok_or_else(|| {
+                    Error::validate(ValidateCode::WrongType, "expected an integer")
+                })??;
+                if let Element::Int(tag) = tag {
+                    (self.get_int(&tag), true, tag.to_string())
+                } else {
+                    return Err(Error::validate(
+                        ValidateCode::WrongType,
+                        "expected an integer",
+                    ));
+                }
+            }
+            _ => return Err(Error::validate(ValidateCode::WrongType, "expected an enum")),
         };
 
-        // Find the matching validator and verify the (possible) content against it
-        let validator = self
-            .0
-            .get(key)
-            .ok_or_else(|| Error::FailValidate(format!("{} is not in enum list", key)))?;
+        // Verify the (possible) content against the matching validator
+        let validator = match (validator, &self.other) {
+            (Some(validator), _) => validator,
+            (None, Some(other)) => {
+                return match (other, has_value) {
+                    (_, false) => Ok((parser, checklist)),
+                    (None, true) => Err(Error::validate(
+                        ValidateCode::UnknownVariant,
+                        format!(
+                        "enum {} is not in enum list, and only unknown unit variants are allowed",
+                        name
+                    ),
+                    )),
+                    (Some(validator), true) => validator.validate(types, parser, checklist),
+                };
+            }
+            (None, None) => {
+                return Err(Error::validate(
+                    ValidateCode::UnknownVariant,
+                    format!("{} is not in enum list", name),
+                ))
+            }
+        };
         match (validator, has_value) {
             (None, false) => Ok((parser, checklist)),
-            (None, true) => Err(Error::FailValidate(format!(
-                "enum {} shouldn't have any associated value",
-                key
-            ))),
-            (Some(_), false) => Err(Error::FailValidate(format!(
-                "enum {} should have an associated value",
-                key
-            ))),
+            (None, true) => Err(Error::validate(
+                ValidateCode::UnknownVariant,
+                format!("enum {} shouldn't have any associated value", name),
+            )),
+            (Some(_), false) => Err(Error::validate(
+                ValidateCode::UnknownVariant,
+                format!("enum {} should have an associated value", name),
+            )),
             (Some(validator), true) => validator.validate(types, parser, checklist),
         }
     }
 
+    fn get_int(&self, tag: &Integer) -> Option<&Option<Validator>> {
+        tag.as_i64().and_then(|tag| self.int_var.get(&tag))
+    }
+
     pub(crate) fn query_check(
         &self,
         types: &BTreeMap<String, Validator>,
@@ -164,16 +495,33 @@ impl EnumValidator {
                 // 2. That our enum's matching validator would allow the query's validator
                 //    for that enum.
                 // 3. If both have a "None" instead of a validator, that's also OK
+                // Variants not in our enum (and the query's own catch-all) must instead be allowed
+                // by our catch-all.
+                let check_other = |theirs: Option<&Validator>| match (&self.other, theirs) {
+                    (Some(_), None) => true,
+                    (Some(Some(validator)), Some(other_v)) => validator.query_check(types, other_v),
+                    _ => false,
+                };
+                let check = |ours: Option<&Option<Validator>>, theirs: &Option<Validator>| match (
+                    ours, theirs,
+                ) {
+                    (Some(Some(validator)), Some(other_v)) => validator.query_check(types, other_v),
+                    (Some(None), None) => true,
+                    (None, theirs) => check_other(theirs.as_ref()),
+                    _ => false,
+                };
                 other
-                    .0
+                    .var
                     .iter()
-                    .all(|(other_k, other_v)| match (self.0.get(other_k), other_v) {
-                        (Some(Some(validator)), Some(other_v)) => {
-                            validator.query_check(types, other_v)
-                        }
-                        (Some(None), None) => true,
-                        _ => false,
-                    })
+                    .all(|(other_k, other_v)| check(self.get_var(other_k), other_v))
+                    && other
+                        .int_var
+                        .iter()
+                        .all(|(other_k, other_v)| check(self.int_var.get(other_k), other_v))
+                    && match &other.other {
+                        Some(theirs) => check_other(theirs.as_deref()),
+                        None => true,
+                    }
             }
             Validator::Any => true,
             _ => false,
@@ -200,4 +548,324 @@ mod test {
             .unwrap();
         Schema::from_doc(&schema_doc).unwrap();
     }
+
+    fn validate_enum(validator: &Validator, value: impl Serialize) -> Result<()> {
+        use crate::{element::Parser, ser::FogSerializer};
+        let mut ser = FogSerializer::default();
+        value.serialize(&mut ser).unwrap();
+        let data = ser.finish();
+        let types = BTreeMap::new();
+        let (parser, _) = validator.validate(&types, Parser::new(&data), None)?;
+        parser.finish()
+    }
+
+    fn mixed() -> Validator {
+        EnumValidator::new()
+            .insert("Empty", None)
+            .insert("Text", Some(StrValidator::new().build()))
+            .insert_int(0, None)
+            .insert_int(7, Some(IntValidator::new().build()))
+            .build()
+    }
+
+    #[test]
+    fn int_tags() {
+        let validator = mixed();
+        validate_enum(&validator, "Empty").unwrap();
+        let mut text = BTreeMap::new();
+        text.insert("Text", "hi");
+        validate_enum(&validator, &text).unwrap();
+        validate_enum(&validator, 0u8).unwrap();
+        validate_enum(&validator, (7u8, 3u8)).unwrap();
+
+        // Unit variant given data, data variant without it, and unknown tags
+        assert!(validate_enum(&validator, (0u8, 3u8)).is_err());
+        assert!(validate_enum(&validator, 7u8).is_err());
+        assert!(validate_enum(&validator, 1u8).is_err());
+        assert!(validate_enum(&validator, (7u8, "x")).is_err());
+    }
+
+    #[test]
+    fn wrong_tag_type() {
+        let validator = mixed();
+        assert!(validate_enum(&validator, "0").is_err());
+        assert!(validate_enum(&validator, ("Text", "hi")).is_err());
+        let mut text = BTreeMap::new();
+        text.insert("7", 3u8);
+        assert!(validate_enum(&validator, &text).is_err());
+    }
+
+    #[test]
+    fn int_tags_ser() {
+        use crate::{de::FogDeserializer, ser::FogSerializer};
+        let validator = mixed();
+        let mut ser = FogSerializer::default();
+        validator.serialize(&mut ser).unwrap();
+        let data = ser.finish();
+        let mut de = FogDeserializer::new(&data);
+        let decoded = Validator::deserialize(&mut de).unwrap();
+        assert_eq!(validator, decoded);
+    }
+
+    #[test]
+    fn named_only_ser() {
+        use crate::{de::FogDeserializer, element::serialize_elem, ser::FogSerializer};
+        // Validators with only named variants are encoded as a bare map of variants
+        let validator = EnumValidator::new()
+            .insert("A", None)
+            .insert("B", Some(Validator::Null));
+        let mut ser = FogSerializer::default();
+        validator.serialize(&mut ser).unwrap();
+        let actual = ser.finish();
+        let mut expected = vec![0x82];
+        serialize_elem(&mut expected, Element::Str("A"));
+        serialize_elem(&mut expected, Element::Null);
+        serialize_elem(&mut expected, Element::Str("B"));
+        serialize_elem(&mut expected, Element::Str("Null"));
+        assert_eq!(expected, actual);
+        let mut de = FogDeserializer::new(&actual);
+        assert_eq!(validator, EnumValidator::deserialize(&mut de).unwrap());
+
+        // Variants that share a field name of the full encoding are still read as variants
+        let mut expected = vec![0x82];
+        serialize_elem(&mut expected, Element::Str("int_var"));
+        serialize_elem(&mut expected, Element::Null);
+        serialize_elem(&mut expected, Element::Str("other"));
+        serialize_elem(&mut expected, Element::Null);
+        let validator = EnumValidator::new()
+            .insert("int_var", None)
+            .insert("other", None);
+        let mut ser = FogSerializer::default();
+        validator.serialize(&mut ser).unwrap();
+        assert_eq!(expected, ser.finish());
+        let mut de = FogDeserializer::new(&expected);
+        let decoded = EnumValidator::deserialize(&mut de).unwrap();
+        assert_eq!(validator, decoded);
+        assert!(decoded.other.is_none());
+        assert!(validator.check_build().is_ok());
+        let other = validator.build();
+        assert!(validate_enum(&other, "other").is_ok());
+        assert!(validate_enum(&other, "unknown").is_err());
+    }
+
+    #[test]
+    fn full_ser() {
+        use crate::{de::FogDeserializer, element::serialize_elem, ser::FogSerializer};
+        // Anything beyond named variants is wrapped in an array
+        let validator = EnumValidator::new().insert("other", None).other(None);
+        let mut ser = FogSerializer::default();
+        validator.serialize(&mut ser).unwrap();
+        let actual = ser.finish();
+        let mut expected = Vec::new();
+        serialize_elem(&mut expected, Element::Array(1));
+        serialize_elem(&mut expected, Element::Map(2));
+        serialize_elem(&mut expected, Element::Str("other"));
+        serialize_elem(&mut expected, Element::Null);
+        serialize_elem(&mut expected, Element::Str("var"));
+        serialize_elem(&mut expected, Element::Map(1));
+        serialize_elem(&mut expected, Element::Str("other"));
+        serialize_elem(&mut expected, Element::Null);
+        assert_eq!(expected, actual);
+        let mut de = FogDeserializer::new(&actual);
+        assert_eq!(validator, EnumValidator::deserialize(&mut de).unwrap());
+
+        // The array must hold exactly one element
+        let mut bad = Vec::new();
+        serialize_elem(&mut bad, Element::Array(0));
+        assert!(EnumValidator::deserialize(&mut FogDeserializer::new(&bad)).is_err());
+        let mut bad = Vec::new();
+        serialize_elem(&mut bad, Element::Array(2));
+        bad.extend_from_slice(&actual[1..]);
+        bad.extend_from_slice(&actual[1..]);
+        assert!(EnumValidator::deserialize(&mut FogDeserializer::new(&bad)).is_err());
+    }
+
+    #[test]
+    fn int_tags_query() {
+        let types = BTreeMap::new();
+        let schema = mixed();
+        let query = EnumValidator::new()
+            .insert_int(7, Some(IntValidator::new().build()))
+            .build();
+        assert!(schema.query_check(&types, &query));
+        let query = EnumValidator::new().insert_int(7, None).build();
+        assert!(!schema.query_check(&types, &query));
+        let query = EnumValidator::new().insert_int(1, None).build();
+        assert!(!schema.query_check(&types, &query));
+    }
+
+    fn renamed() -> Validator {
+        EnumValidator::new()
+            .insert("Message", Some(StrValidator::new().build()))
+            .insert("Ping", None)
+            .alias("Msg", "Message")
+            .alias("Heartbeat", "Ping")
+            .build()
+    }
+
+    #[test]
+    fn aliases() {
+        let validator = renamed();
+        let mut old = BTreeMap::new();
+        old.insert("Msg", "hi");
+        validate_enum(&validator, &old).unwrap();
+        validate_enum(&validator, "Heartbeat").unwrap();
+        validate_enum(&validator, "Ping").unwrap();
+        let mut bad = BTreeMap::new();
+        bad.insert("Msg", 1u8);
+        assert!(validate_enum(&validator, &bad).is_err());
+        assert!(validate_enum(&validator, "Msg").is_err());
+    }
+
+    #[test]
+    fn alias_bad_target() {
+        use crate::schema::SchemaBuilder;
+        let validator = EnumValidator::new()
+            .insert("Message", None)
+            .alias("Msg", "Mesage")
+            .build();
+        assert!(SchemaBuilder::new(validator).build().is_err());
+        assert!(SchemaBuilder::new(renamed()).build().is_ok());
+    }
+
+    #[test]
+    fn alias_ser() {
+        use crate::{de::FogDeserializer, ser::FogSerializer};
+        let validator = renamed();
+        let mut ser = FogSerializer::default();
+        validator.serialize(&mut ser).unwrap();
+        let data = ser.finish();
+        let mut de = FogDeserializer::new(&data);
+        assert_eq!(validator, Validator::deserialize(&mut de).unwrap());
+    }
+
+    #[test]
+    fn alias_query() {
+        let types = BTreeMap::new();
+        let query = EnumValidator::new()
+            .insert("Msg", Some(StrValidator::new().build()))
+            .insert("Heartbeat", None)
+            .build();
+        assert!(renamed().query_check(&types, &query));
+        let query = EnumValidator::new().insert("Msg", None).build();
+        assert!(!renamed().query_check(&types, &query));
+    }
+
+    fn extensible() -> Validator {
+        EnumValidator::new()
+            .insert("Known", None)
+            .other(Some(IntValidator::new().max(10u8).build()))
+            .build()
+    }
+
+    #[test]
+    fn other_variants() {
+        let validator = extensible();
+        validate_enum(&validator, "Known").unwrap();
+        validate_enum(&validator, "Unknown").unwrap();
+        validate_enum(&validator, 5u8).unwrap();
+        let mut data = BTreeMap::new();
+        data.insert("Unknown", 3u8);
+        validate_enum(&validator, &data).unwrap();
+        data.insert("Unknown", 30u8);
+        assert!(validate_enum(&validator, &data).is_err());
+        // A known unit variant still can't carry data
+        let mut data = BTreeMap::new();
+        data.insert("Known", 3u8);
+        assert!(validate_enum(&validator, &data).is_err());
+
+        let unit_only = EnumValidator::new().other(None).build();
+        validate_enum(&unit_only, "Unknown").unwrap();
+        let mut data = BTreeMap::new();
+        data.insert("Unknown", 3u8);
+        assert!(validate_enum(&unit_only, &data).is_err());
+
+        let closed = EnumValidator::new().insert("Known", None).build();
+        assert!(validate_enum(&closed, "Unknown").is_err());
+    }
+
+    #[test]
+    fn other_ser() {
+        use crate::{de::FogDeserializer, ser::FogSerializer};
+        for validator in [extensible(), EnumValidator::new().other(None).build()].iter() {
+            let mut ser = FogSerializer::default();
+            validator.serialize(&mut ser).unwrap();
+            let data = ser.finish();
+            let mut de = FogDeserializer::new(&data);
+            assert_eq!(validator, &Validator::deserialize(&mut de).unwrap());
+        }
+    }
+
+    #[test]
+    fn other_query() {
+        let types = BTreeMap::new();
+        let query = EnumValidator::new()
+            .insert("Unknown", Some(IntValidator::new().build()))
+            .build();
+        assert!(extensible().query_check(&types, &query));
+        let closed = EnumValidator::new().insert("Known", None).build();
+        assert!(!closed.query_check(&types, &query));
+        let query = EnumValidator::new().other(None).build();
+        assert!(extensible().query_check(&types, &query));
+        assert!(!closed.query_check(&types, &query));
+    }
+
+    #[test]
+    fn merge_halves() {
+        let base = EnumValidator::new()
+            .insert("Empty", None)
+            .insert("Text", Some(StrValidator::new().build()));
+        let extra = EnumValidator::new()
+            .insert("Text", Some(StrValidator::new().build()))
+            .insert_int(3, Some(IntValidator::new().build()))
+            .alias("Txt", "Text");
+        let merged = base.merge(extra, ConflictPolicy::Error).unwrap();
+        assert_eq!(merged.len(), 3);
+        let by_hand = EnumValidator::new()
+            .insert("Empty", None)
+            .insert("Text", Some(StrValidator::new().build()))
+            .insert_int(3, Some(IntValidator::new().build()))
+            .alias("Txt", "Text");
+        assert_eq!(merged, by_hand);
+
+        let (merged, by_hand) = (merged.build(), by_hand.build());
+        let mut txt = BTreeMap::new();
+        txt.insert("Txt", "hi");
+        for validator in [&merged, &by_hand].iter() {
+            validate_enum(validator, "Empty").unwrap();
+            validate_enum(validator, &txt).unwrap();
+            validate_enum(validator, (3u8, 1u8)).unwrap();
+            assert!(validate_enum(validator, "Other").is_err());
+        }
+    }
+
+    #[test]
+    fn merge_conflicts() {
+        let ours = || EnumValidator::new().insert("A", None);
+        let theirs = || EnumValidator::new().insert("A", Some(Validator::Null));
+        assert!(ours().merge(theirs(), ConflictPolicy::Error).is_err());
+        let kept = ours().merge(theirs(), ConflictPolicy::KeepSelf).unwrap();
+        assert_eq!(kept.var.get("A"), Some(&None));
+        let kept = ours().merge(theirs(), ConflictPolicy::KeepOther).unwrap();
+        assert_eq!(kept.var.get("A"), Some(&Some(Validator::Null)));
+    }
+
+    #[test]
+    fn edit_variants() {
+        let mut validator = EnumValidator::new()
+            .insert("A", None)
+            .insert("B", Some(IntValidator::new().build()));
+        assert!(!validator.is_empty());
+        *validator.get_mut("B").unwrap() = Some(StrValidator::new().build());
+        assert!(validator.get_mut("C").is_none());
+        assert_eq!(validator.remove("A"), Some(None));
+        assert_eq!(validator.remove("A"), None);
+        assert_eq!(validator.len(), 1);
+        let validator = validator.build();
+        let mut data = BTreeMap::new();
+        data.insert("B", "text");
+        validate_enum(&validator, &data).unwrap();
+        assert!(validate_enum(&validator, "A").is_err());
+        assert!(EnumValidator::new().is_empty());
+    }
 }
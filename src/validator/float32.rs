@@ -1,6 +1,6 @@
 use super::*;
 use crate::element::*;
-use crate::error::{Error, Result};
+use crate::error::{Error, Result, ValidateCode};
 use serde::{Deserialize, Serialize};
 
 #[inline]
@@ -31,6 +31,7 @@ fn is_nan(v: &f32) -> bool {
 /// each field are:
 ///
 /// - comment: ""
+/// - default: None
 /// - max: NaN
 /// - min: NaN
 /// - ex_max: false
@@ -46,6 +47,10 @@ pub struct F32Validator {
     /// An optional comment explaining the validator.
     #[serde(skip_serializing_if = "String::is_empty")]
     pub comment: String,
+    /// An optional default value, used when filling in new values from a schema. It is not
+    /// checked during validation.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub default: Option<f32>,
     /// The maximum allowed f32 value. If NaN, it is ignored.
     #[serde(skip_serializing_if = "is_nan")]
     pub max: f32,
@@ -77,6 +82,7 @@ impl std::default::Default for F32Validator {
     fn default() -> Self {
         Self {
             comment: String::new(),
+            default: None,
             max: f32::NAN,
             min: f32::NAN,
             ex_max: false,
@@ -101,6 +107,12 @@ impl F32Validator {
         self
     }
 
+    /// Set the default value.
+    pub fn default_value(mut self, default: f32) -> Self {
+        self.default = Some(default);
+        self
+    }
+
     /// Set the maximum allowed value.
     pub fn max(mut self, max: f32) -> Self {
         self.max = max;
@@ -152,29 +164,34 @@ impl F32Validator {
     pub(crate) fn validate(&self, parser: &mut Parser) -> Result<()> {
         let elem = parser
             .next()
-            .ok_or_else(|| Error::FailValidate("Expected a f32".to_string()))??;
+            .ok_or_else(|| Error::validate(ValidateCode::WrongType, "Expected a f32"))??;
         let elem = if let Element::F32(v) = elem {
             v
         } else {
-            return Err(Error::FailValidate(format!(
-                "Expected F32, got {}",
-                elem.name()
-            )));
+            return Err(Error::wrong_type("F32", elem.name()));
         };
         let bytes = elem.to_ne_bytes();
         if !self.in_list.is_empty() && !self.in_list.iter().any(|v| v.to_ne_bytes() == bytes) {
-            return Err(Error::FailValidate("F32 is not on `in` list".to_string()));
+            return Err(Error::validate(
+                ValidateCode::NotInList,
+                "F32 is not on `in` list",
+            ));
         }
         if self.nin_list.iter().any(|v| v.to_ne_bytes() == bytes) {
-            return Err(Error::FailValidate("F32 is on `nin` list".to_string()));
+            return Err(Error::validate(
+                ValidateCode::InNinList,
+                "F32 is on `nin` list",
+            ));
         }
         if !self.max.is_nan() && ((self.ex_max && elem >= self.max) || (elem > self.max)) {
-            return Err(Error::FailValidate(
+            return Err(Error::validate(
+                ValidateCode::OutOfRange,
                 "F32 greater than maximum allowed".to_string(),
             ));
         }
         if !self.min.is_nan() && ((self.ex_min && elem <= self.min) || (elem < self.min)) {
-            return Err(Error::FailValidate(
+            return Err(Error::validate(
+                ValidateCode::OutOfRange,
                 "F32 less than maximum allowed".to_string(),
             ));
         }
@@ -1,6 +1,6 @@
 use super::*;
 use crate::element::*;
-use crate::error::{Error, Result};
+use crate::error::{Error, Result, ValidateCode};
 use serde::{Deserialize, Serialize};
 
 #[inline]
@@ -31,6 +31,7 @@ fn is_nan(v: &f64) -> bool {
 /// each field are:
 ///
 /// - comment: ""
+/// - default: None
 /// - max: NaN
 /// - min: NaN
 /// - ex_max: false
@@ -46,6 +47,10 @@ pub struct F64Validator {
     /// An optional comment explaining the validator.
     #[serde(skip_serializing_if = "String::is_empty")]
     pub comment: String,
+    /// An optional default value, used when filling in new values from a schema. It is not
+    /// checked during validation.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub default: Option<f64>,
     /// The maximum allowed f64 value. If NaN, it is ignored.
     #[serde(skip_serializing_if = "is_nan")]
     pub max: f64,
@@ -77,6 +82,7 @@ impl std::default::Default for F64Validator {
     fn default() -> Self {
         Self {
             comment: String::new(),
+            default: None,
             max: f64::NAN,
             min: f64::NAN,
             ex_max: false,
@@ -101,6 +107,12 @@ impl F64Validator {
         self
     }
 
+    /// Set the default value.
+    pub fn default_value(mut self, default: f64) -> Self {
+        self.default = Some(default);
+        self
+    }
+
     /// Set the maximum allowed value.
     pub fn max(mut self, max: f64) -> Self {
         self.max = max;
@@ -152,29 +164,34 @@ impl F64Validator {
     pub(crate) fn validate(&self, parser: &mut Parser) -> Result<()> {
         let elem = parser
             .next()
-            .ok_or_else(|| Error::FailValidate("Expected a f64".to_string()))??;
+            .ok_or_else(|| Error::validate(ValidateCode::WrongType, "Expected a f64"))??;
         let elem = if let Element::F64(v) = elem {
             v
         } else {
-            return Err(Error::FailValidate(format!(
-                "Expected F64, got {}",
-                elem.name()
-            )));
+            return Err(Error::wrong_type("F64", elem.name()));
         };
         let bytes = elem.to_ne_bytes();
         if !self.in_list.is_empty() && !self.in_list.iter().any(|v| v.to_ne_bytes() == bytes) {
-            return Err(Error::FailValidate("F64 is not on `in` list".to_string()));
+            return Err(Error::validate(
+                ValidateCode::NotInList,
+                "F64 is not on `in` list",
+            ));
         }
         if self.nin_list.iter().any(|v| v.to_ne_bytes() == bytes) {
-            return Err(Error::FailValidate("F64 is on `nin` list".to_string()));
+            return Err(Error::validate(
+                ValidateCode::InNinList,
+                "F64 is on `nin` list",
+            ));
         }
         if !self.max.is_nan() && ((self.ex_max && elem >= self.max) || (elem > self.max)) {
-            return Err(Error::FailValidate(
+            return Err(Error::validate(
+                ValidateCode::OutOfRange,
                 "F64 greater than maximum allowed".to_string(),
             ));
         }
         if !self.min.is_nan() && ((self.ex_min && elem <= self.min) || (elem < self.min)) {
-            return Err(Error::FailValidate(
+            return Err(Error::validate(
+                ValidateCode::OutOfRange,
                 "F64 less than maximum allowed".to_string(),
             ));
         }
@@ -1,6 +1,6 @@
 use super::*;
 use crate::element::*;
-use crate::error::{Error, Result};
+use crate::error::{Error, Result, ValidateCode};
 use crate::Hash;
 use serde::{Deserialize, Deserializer, Serialize};
 use std::default::Default;
@@ -180,25 +180,24 @@ impl HashValidator {
     ) -> Result<()> {
         let elem = parser
             .next()
-            .ok_or_else(|| Error::FailValidate("Expected a hash".to_string()))??;
+            .ok_or_else(|| Error::validate(ValidateCode::WrongType, "Expected a hash"))??;
         let val = if let Element::Hash(v) = elem {
             v
         } else {
-            return Err(Error::FailValidate(format!(
-                "Expected Hash, got {}",
-                elem.name()
-            )));
+            return Err(Error::wrong_type("Hash", elem.name()));
         };
 
         // in/nin checks
         if !self.in_list.is_empty() && !self.in_list.iter().any(|v| *v == val) {
-            return Err(Error::FailValidate(
-                "Timestamp is not on `in` list".to_string(),
+            return Err(Error::validate(
+                ValidateCode::NotInList,
+                "Hash is not on `in` list",
             ));
         }
         if self.nin_list.iter().any(|v| *v == val) {
-            return Err(Error::FailValidate(
-                "Timestamp is on `nin` list".to_string(),
+            return Err(Error::validate(
+                ValidateCode::InNinList,
+                "Hash is on `nin` list",
             ));
         }
 
@@ -297,4 +296,74 @@ mod test {
             .validate(&mut parser, &mut checklist)
             .expect("should succeed as a validator");
     }
+
+    fn validate_hash(validator: &HashValidator, hash: &Hash) -> Result<()> {
+        let mut ser = FogSerializer::default();
+        hash.serialize(&mut ser).unwrap();
+        let encoded = ser.finish();
+        let mut parser = Parser::new(&encoded);
+        validator.validate(&mut parser, &mut None)?;
+        parser.finish()
+    }
+
+    #[test]
+    fn in_nin_lists() {
+        let a = Hash::new(b"a");
+        let b = Hash::new(b"b");
+        let c = Hash::new(b"c");
+        let validator = HashValidator::new()
+            .in_add(a.clone())
+            .in_add(b.clone())
+            .nin_add(b.clone());
+        assert!(validate_hash(&validator, &a).is_ok());
+        assert!(validate_hash(&validator, &b).is_err());
+        assert!(validate_hash(&validator, &c).is_err());
+
+        let validator = HashValidator::new().nin_add(a.clone());
+        assert!(validate_hash(&validator, &a).is_err());
+        assert!(validate_hash(&validator, &c).is_ok());
+    }
+
+    #[test]
+    fn query_permissions() {
+        let types = BTreeMap::new();
+        let query_in = HashValidator::new().in_add(Hash::new(b"a")).build();
+        let query_nin = HashValidator::new().nin_add(Hash::new(b"a")).build();
+        let query_link = HashValidator::new().link(Validator::Null).build();
+        let query_schema = HashValidator::new().schema_add(Hash::new(b"s")).build();
+
+        let schema = HashValidator::new();
+        assert!(schema.query_check(&types, &HashValidator::new().build()));
+        assert!(!schema.query_check(&types, &query_in));
+        assert!(!schema.query_check(&types, &query_nin));
+        assert!(!schema.query_check(&types, &query_link));
+        assert!(!schema.query_check(&types, &query_schema));
+
+        let schema = HashValidator::new().query(true);
+        assert!(schema.query_check(&types, &query_in));
+        assert!(schema.query_check(&types, &query_nin));
+        assert!(!schema.query_check(&types, &query_link));
+
+        let schema = HashValidator::new().link_ok(true).link(Validator::Null);
+        assert!(schema.query_check(&types, &query_link));
+        let schema = HashValidator::new()
+            .link_ok(true)
+            .link(Validator::Bool(BoolValidator::new()));
+        assert!(!schema.query_check(&types, &query_link));
+
+        let schema = HashValidator::new().schema_ok(true);
+        assert!(schema.query_check(&types, &query_schema));
+
+        let schema = HashValidator::new().query(true);
+        assert!(schema.query_check(
+            &types,
+            &Validator::Multi(MultiValidator(vec![query_in.clone(), query_nin]))
+        ));
+        assert!(!schema.query_check(
+            &types,
+            &Validator::Multi(MultiValidator(vec![query_in, query_link]))
+        ));
+        assert!(schema.query_check(&types, &Validator::Any));
+        assert!(!schema.query_check(&types, &Validator::Null));
+    }
 }
@@ -1,6 +1,6 @@
 use super::*;
 use crate::element::*;
-use crate::error::{Error, Result};
+use crate::error::{Error, Result, ValidateCode};
 use crate::Identity;
 use serde::{Deserialize, Serialize};
 
@@ -15,6 +15,8 @@ fn is_false(v: &bool) -> bool {
 ///
 /// - If the `in` list is not empty, the Identity must be among the ones in the list.
 /// - The Identity must not be among the ones in the `nin` list.
+/// - If `signer` is true, the Identity must be the one that signed the containing Document or
+///   Entry. Unsigned Documents and Entries always fail this check.
 ///
 /// # Defaults
 ///
@@ -24,8 +26,14 @@ fn is_false(v: &bool) -> bool {
 /// - comment: ""
 /// - in_list: empty
 /// - nin_list: empty
+/// - signer: false
 /// - query: false
 ///
+/// # Query Checking
+///
+/// Queries for Identities are only allowed to use non-default values for `in`, `nin`, and
+/// `signer` if `query` is set in the schema's validator.
+///
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 #[serde(deny_unknown_fields, default)]
 pub struct IdentityValidator {
@@ -38,6 +46,9 @@ pub struct IdentityValidator {
     /// A vector of specific unallowed values, stored under the `nin` field.
     #[serde(rename = "nin", skip_serializing_if = "Vec::is_empty")]
     pub nin_list: Vec<Identity>,
+    /// If true, the Identity must be the signer of the containing Document or Entry.
+    #[serde(skip_serializing_if = "is_false")]
+    pub signer: bool,
     /// If true, queries against matching spots may have values in the `in` or `nin` lists.
     #[serde(skip_serializing_if = "is_false")]
     pub query: bool,
@@ -49,6 +60,7 @@ impl std::default::Default for IdentityValidator {
             comment: String::new(),
             in_list: Vec::new(),
             nin_list: Vec::new(),
+            signer: false,
             query: false,
         }
     }
@@ -78,7 +90,13 @@ impl IdentityValidator {
         self
     }
 
-    /// Set whether or not queries can use the `in` and `nin` lists.
+    /// Set whether or not the Identity must be the signer of the containing Document or Entry.
+    pub fn signer(mut self, signer: bool) -> Self {
+        self.signer = signer;
+        self
+    }
+
+    /// Set whether or not queries can use the `in` and `nin` lists, and `signer`.
     pub fn query(mut self, query: bool) -> Self {
         self.query = query;
         self
@@ -92,28 +110,35 @@ impl IdentityValidator {
     pub(crate) fn validate(&self, parser: &mut Parser) -> Result<()> {
         let elem = parser
             .next()
-            .ok_or_else(|| Error::FailValidate("Expected an Identity".to_string()))??;
+            .ok_or_else(|| Error::validate(ValidateCode::WrongType, "Expected an Identity"))??;
         let elem = if let Element::Identity(v) = elem {
             v
         } else {
-            return Err(Error::FailValidate(format!(
-                "Expected Identity, got {}",
-                elem.name()
-            )));
+            return Err(Error::wrong_type("Identity", elem.name()));
         };
         if !self.in_list.is_empty() && !self.in_list.iter().any(|v| v == elem.as_ref()) {
-            return Err(Error::FailValidate(
+            return Err(Error::validate(
+                ValidateCode::NotInList,
                 "Identity is not on `in` list".to_string(),
             ));
         }
         if self.nin_list.iter().any(|v| v == elem.as_ref()) {
-            return Err(Error::FailValidate("Identity is on `nin` list".to_string()));
+            return Err(Error::validate(
+                ValidateCode::InNinList,
+                "Identity is on `nin` list",
+            ));
+        }
+        if self.signer && parser.signer() != Some(elem.as_ref()) {
+            return Err(Error::validate(
+                ValidateCode::NotSigner,
+                "Identity is not the signer of the containing Document or Entry".to_string(),
+            ));
         }
         Ok(())
     }
 
     fn query_check_self(&self, other: &Self) -> bool {
-        self.query || (other.in_list.is_empty() && other.nin_list.is_empty())
+        self.query || (other.in_list.is_empty() && other.nin_list.is_empty() && !other.signer)
     }
 
     pub(crate) fn query_check(&self, other: &Validator) -> bool {
@@ -128,3 +153,48 @@ impl IdentityValidator {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{document::*, schema::*};
+    use fog_crypto::identity::IdentityKey;
+
+    #[test]
+    fn signer() {
+        let key = IdentityKey::new_temp(&mut rand::rngs::OsRng);
+        let other_key = IdentityKey::new_temp(&mut rand::rngs::OsRng);
+        let schema = SchemaBuilder::new(IdentityValidator::new().signer(true).build())
+            .build()
+            .unwrap();
+        let schema = Schema::from_doc(&schema).unwrap();
+        let new_doc = |id: &Identity| NewDocument::new(id, Some(schema.hash())).unwrap();
+
+        // Signed by the contained Identity
+        let doc = new_doc(key.id()).sign(&key).unwrap();
+        let doc = schema.validate_new_doc(doc).unwrap();
+        let (_, encoded) = schema.encode_doc(doc).unwrap();
+        schema.decode_doc(encoded).unwrap();
+
+        // Signed by someone else, or not signed at all
+        let doc = new_doc(key.id()).sign(&other_key).unwrap();
+        assert!(schema.validate_new_doc(doc).is_err());
+        assert!(schema.validate_new_doc(new_doc(key.id())).is_err());
+
+        // Without the signer requirement, any Identity is fine
+        let schema = SchemaBuilder::new(IdentityValidator::new().build())
+            .build()
+            .unwrap();
+        let schema = Schema::from_doc(&schema).unwrap();
+        let doc = NewDocument::new(key.id(), Some(schema.hash())).unwrap();
+        schema.validate_new_doc(doc).unwrap();
+    }
+
+    #[test]
+    fn signer_query() {
+        let query = IdentityValidator::new().signer(true).build();
+        assert!(!IdentityValidator::new().query_check(&query));
+        assert!(IdentityValidator::new().query(true).query_check(&query));
+        assert!(IdentityValidator::new().query_check(&IdentityValidator::new().build()));
+    }
+}
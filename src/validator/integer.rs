@@ -1,6 +1,6 @@
 use super::*;
 use crate::element::*;
-use crate::error::{Error, Result};
+use crate::error::{Error, Result, ValidateCode};
 use crate::*;
 use serde::{Deserialize, Serialize};
 
@@ -32,6 +32,8 @@ fn int_is_min(v: &Integer) -> bool {
 /// - The bits set in `bits_set` are set in the integer
 /// - The integer is less than the maximum in `max`, or equal to it if `ex_max` is not set to true.
 /// - The integer is greater than the minimum in `min`, or equal to it if `ex_min` is not set to true.
+/// - If `bit_width` is set, the integer fits in an integer of that many bits, signed if
+///   `bit_signed` is true and unsigned otherwise.
 /// - If the `in` list is not empty, the integer must be among the integers in it.
 /// - The integer must not be among the integers in the `nin` list.
 ///
@@ -41,12 +43,15 @@ fn int_is_min(v: &Integer) -> bool {
 /// each field are:
 ///
 /// - comment: ""
+/// - default: None
 /// - bits_clr: 0
 /// - bits_set: 0
 /// - max: u64::MAX
 /// - min: i64::MIN
 /// - ex_max: false
 /// - ex_min: false
+/// - bit_width: None
+/// - bit_signed: false
 /// - in_list: empty
 /// - nin_list: empty
 /// - query: false
@@ -59,6 +64,10 @@ pub struct IntValidator {
     /// An optional comment explaining the validator.
     #[serde(skip_serializing_if = "String::is_empty")]
     pub comment: String,
+    /// An optional default value, used when filling in new values from a schema. It is not
+    /// checked during validation.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub default: Option<Integer>,
     /// An unsigned 64-bit integers used as a bit field. Any bits set in it must be cleared in an
     /// allowed value.
     #[serde(skip_serializing_if = "u64_is_zero")]
@@ -79,12 +88,21 @@ pub struct IntValidator {
     /// Changes `min` into an exclusive maximum.
     #[serde(skip_serializing_if = "is_false")]
     pub ex_min: bool,
+    /// Restricts the integer to ones that fit in this many bits. Must be 8, 16, 32, or 64.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bit_width: Option<u8>,
+    /// Makes `bit_width` a signed width instead of an unsigned one.
+    #[serde(skip_serializing_if = "is_false")]
+    pub bit_signed: bool,
     /// A vector of specific allowed values, stored under the `in` field. If empty, this vector is not checked against.
     #[serde(rename = "in", skip_serializing_if = "Vec::is_empty")]
     pub in_list: Vec<Integer>,
     /// A vector of specific unallowed values, stored under the `nin` field.
     #[serde(rename = "nin", skip_serializing_if = "Vec::is_empty")]
     pub nin_list: Vec<Integer>,
+    /// Precomputed lookup index for the `in` and `nin` lists.
+    #[serde(skip)]
+    pub(crate) list_index: ListIndex<Integer>,
     /// If true, queries against matching spots may have values in the `in` or `nin` lists.
     #[serde(skip_serializing_if = "is_false")]
     pub query: bool,
@@ -92,8 +110,8 @@ pub struct IntValidator {
     /// non-zero.
     #[serde(skip_serializing_if = "is_false")]
     pub bit: bool,
-    /// If true, queries against matching spots may set the `max`, `min`, `ex_max`, and `ex_min`
-    /// values to non-defaults.
+    /// If true, queries against matching spots may set the `max`, `min`, `ex_max`, `ex_min`, and
+    /// `bit_width` values to non-defaults.
     #[serde(skip_serializing_if = "is_false")]
     pub ord: bool,
 }
@@ -102,14 +120,18 @@ impl std::default::Default for IntValidator {
     fn default() -> Self {
         Self {
             comment: String::new(),
+            default: None,
             bits_clr: 0,
             bits_set: 0,
             max: Integer::max_value(),
             min: Integer::min_value(),
             ex_max: false,
             ex_min: false,
+            bit_width: None,
+            bit_signed: false,
             in_list: Vec::new(),
             nin_list: Vec::new(),
+            list_index: ListIndex::default(),
             query: false,
             bit: false,
             ord: false,
@@ -129,6 +151,12 @@ impl IntValidator {
         self
     }
 
+    /// Set the default value.
+    pub fn default_value(mut self, default: impl Into<Integer>) -> Self {
+        self.default = Some(default.into());
+        self
+    }
+
     /// Choose which bits must be set.
     pub fn bits_set(mut self, bits_set: u64) -> Self {
         self.bits_set = bits_set;
@@ -165,6 +193,25 @@ impl IntValidator {
         self
     }
 
+    /// Require the integer to fit in a signed or unsigned integer of the given number of bits,
+    /// which should be 8, 16, 32, or 64. This also narrows `min` and `max` to the range of that
+    /// integer type, so `bit_width(8, true)` is equivalent to `min(-128).max(127)`.
+    pub fn bit_width(mut self, bits: u8, signed: bool) -> Self {
+        self.bit_width = Some(bits);
+        self.bit_signed = signed;
+        if let Some((min, max)) = self.bit_range() {
+            if self.min < min {
+                self.min = min;
+                self.ex_min = false;
+            }
+            if self.max > max {
+                self.max = max;
+                self.ex_max = false;
+            }
+        }
+        self
+    }
+
     /// Add a value to the `in` list.
     pub fn in_add(mut self, add: impl Into<Integer>) -> Self {
         self.in_list.push(add.into());
@@ -189,7 +236,8 @@ impl IntValidator {
         self
     }
 
-    /// Set whether or not queries can use the `max`, `min`, `ex_max`, and `ex_min` values.
+    /// Set whether or not queries can use the `max`, `min`, `ex_max`, `ex_min`, and `bit_width`
+    /// values.
     pub fn ord(mut self, ord: bool) -> Self {
         self.ord = ord;
         self
@@ -200,45 +248,89 @@ impl IntValidator {
         Validator::Int(self)
     }
 
+    /// Get the inclusive range of integers allowed by `bit_width`, if it is set to a supported
+    /// width.
+    fn bit_range(&self) -> Option<(Integer, Integer)> {
+        let bits = match self.bit_width? {
+            bits @ 8 | bits @ 16 | bits @ 32 | bits @ 64 => bits as u32,
+            _ => return None,
+        };
+        Some(if self.bit_signed {
+            let max = (u64::MAX >> (65 - bits)) as i64;
+            (Integer::from(-max - 1), Integer::from(max))
+        } else {
+            (Integer::from(0u64), Integer::from(u64::MAX >> (64 - bits)))
+        })
+    }
+
+    pub(crate) fn check_build(&self) -> Result<()> {
+        match self.bit_width {
+            Some(bits) if self.bit_range().is_none() => Err(Error::validate(
+                ValidateCode::BadValidator,
+                format!("Int validator has unsupported bit_width of {}", bits),
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    pub(crate) fn build_index(&mut self) {
+        self.list_index =
+            ListIndex::build(self.in_list.iter().copied(), self.nin_list.iter().copied());
+    }
+
     pub(crate) fn validate(&self, parser: &mut Parser) -> Result<()> {
         let elem = parser
             .next()
-            .ok_or_else(|| Error::FailValidate("Expected a integer".to_string()))??;
+            .ok_or_else(|| Error::validate(ValidateCode::WrongType, "Expected a integer"))??;
         let int = if let Element::Int(v) = elem {
             v
         } else {
-            return Err(Error::FailValidate(format!(
-                "Expected Int, got {}",
-                elem.name()
-            )));
+            return Err(Error::wrong_type("Int", elem.name()));
         };
         let bits = int.as_bits();
-        if !self.in_list.is_empty() && !self.in_list.iter().any(|v| *v == int) {
-            return Err(Error::FailValidate(
+        if !self.in_list.is_empty()
+            && !self
+                .list_index
+                .in_contains(&int)
+                .unwrap_or_else(|| self.in_list.iter().any(|v| *v == int))
+        {
+            return Err(Error::validate(
+                ValidateCode::NotInList,
                 "Integer is not on `in` list".to_string(),
             ));
         }
-        if self.nin_list.iter().any(|v| *v == int) {
-            return Err(Error::FailValidate("Integer is on `nin` list".to_string()));
+        if self
+            .list_index
+            .nin_contains(&int)
+            .unwrap_or_else(|| self.nin_list.iter().any(|v| *v == int))
+        {
+            return Err(Error::validate(
+                ValidateCode::InNinList,
+                "Integer is on `nin` list",
+            ));
         }
         if (bits & self.bits_clr) != 0 {
-            return Err(Error::FailValidate(
+            return Err(Error::validate(
+                ValidateCode::BitMismatch,
                 "Integer does not have all required bits cleared".to_string(),
             ));
         }
         if (bits & self.bits_set) != self.bits_set {
-            return Err(Error::FailValidate(
+            return Err(Error::validate(
+                ValidateCode::BitMismatch,
                 "Integer does not have all required bits set".to_string(),
             ));
         }
         match int.cmp(&self.max) {
             std::cmp::Ordering::Equal if self.ex_max => {
-                return Err(Error::FailValidate(
+                return Err(Error::validate(
+                    ValidateCode::OutOfRange,
                     "Integer greater than maximum allowed".to_string(),
                 ))
             }
             std::cmp::Ordering::Greater => {
-                return Err(Error::FailValidate(
+                return Err(Error::validate(
+                    ValidateCode::OutOfRange,
                     "Integer greater than maximum allowed".to_string(),
                 ))
             }
@@ -246,17 +338,27 @@ impl IntValidator {
         }
         match int.cmp(&self.min) {
             std::cmp::Ordering::Equal if self.ex_min => {
-                return Err(Error::FailValidate(
+                return Err(Error::validate(
+                    ValidateCode::OutOfRange,
                     "Integer less than minimum allowed".to_string(),
                 ))
             }
             std::cmp::Ordering::Less => {
-                return Err(Error::FailValidate(
+                return Err(Error::validate(
+                    ValidateCode::OutOfRange,
                     "Integer less than minimum allowed".to_string(),
                 ))
             }
             _ => (),
         }
+        if let Some((min, max)) = self.bit_range() {
+            if int < min || int > max {
+                return Err(Error::validate(
+                    ValidateCode::OutOfRange,
+                    "Integer does not fit in `bit_width`",
+                ));
+            }
+        }
         Ok(())
     }
 
@@ -266,6 +368,7 @@ impl IntValidator {
             && (self.ord
                 || (!other.ex_min
                     && !other.ex_max
+                    && other.bit_width.is_none()
                     && int_is_max(&other.max)
                     && int_is_min(&other.min)))
     }
@@ -282,3 +385,111 @@ impl IntValidator {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{de::FogDeserializer, ser::FogSerializer};
+    use std::collections::BTreeMap;
+
+    fn validate(validator: &Validator, value: impl Into<Integer>) -> Result<()> {
+        let mut ser = FogSerializer::default();
+        value.into().serialize(&mut ser).unwrap();
+        let data = ser.finish();
+        let types = BTreeMap::new();
+        let (parser, _) = validator.validate(&types, Parser::new(&data), None)?;
+        parser.finish()
+    }
+
+    #[test]
+    fn bit_width() {
+        let i8_val = IntValidator::new().bit_width(8, true);
+        assert_eq!(i8_val.min, Integer::from(-128));
+        assert_eq!(i8_val.max, Integer::from(127));
+        let i8_val = i8_val.build();
+        assert!(validate(&i8_val, -128).is_ok());
+        assert!(validate(&i8_val, 127).is_ok());
+        assert!(validate(&i8_val, -129).is_err());
+        assert!(validate(&i8_val, 128).is_err());
+
+        let u16_val = IntValidator::new().bit_width(16, false).build();
+        assert!(validate(&u16_val, 0).is_ok());
+        assert!(validate(&u16_val, 65535).is_ok());
+        assert!(validate(&u16_val, -1).is_err());
+        assert!(validate(&u16_val, 65536).is_err());
+
+        let i64_val = IntValidator::new().bit_width(64, true).build();
+        assert!(validate(&i64_val, i64::MIN).is_ok());
+        assert!(validate(&i64_val, u64::MAX).is_err());
+        let u64_val = IntValidator::new().bit_width(64, false).build();
+        assert!(validate(&u64_val, u64::MAX).is_ok());
+        assert!(validate(&u64_val, -1).is_err());
+    }
+
+    #[test]
+    fn bit_width_intersection() {
+        // Explicit limits inside the width are kept
+        let validator = IntValidator::new().min(-10).max(10).bit_width(8, false);
+        assert_eq!(validator.min, Integer::from(0));
+        assert_eq!(validator.max, Integer::from(10));
+
+        // The width is enforced even if min and max are set wider afterwards
+        let validator = IntValidator::new()
+            .bit_width(8, false)
+            .min(-1000)
+            .max(1000)
+            .build();
+        assert!(validate(&validator, 255).is_ok());
+        assert!(validate(&validator, 256).is_err());
+        assert!(validate(&validator, -1).is_err());
+    }
+
+    #[test]
+    fn bit_width_ser() {
+        let validator = IntValidator::new().bit_width(32, true).build();
+        let mut ser = FogSerializer::default();
+        validator.serialize(&mut ser).unwrap();
+        let encoded = ser.finish();
+        let mut de = FogDeserializer::new(&encoded);
+        assert_eq!(validator, Validator::deserialize(&mut de).unwrap());
+
+        assert!(validator.check_build().is_ok());
+        let mut bad = IntValidator::new();
+        bad.bit_width = Some(12);
+        assert!(bad.build().check_build().is_err());
+    }
+
+    #[test]
+    fn query_permissions() {
+        let types = BTreeMap::new();
+        let exact = IntValidator::new().in_add(1u8).nin_add(2u8).build();
+        let range = IntValidator::new().min(0u8).max(10u8).ex_max(true).build();
+        let bits = IntValidator::new().bits_set(1).bits_clr(2).build();
+        for flags in 0..8u8 {
+            let (query, ord, bit) = (flags & 1 != 0, flags & 2 != 0, flags & 4 != 0);
+            let schema = IntValidator::new().query(query).ord(ord).bit(bit).build();
+            assert_eq!(schema.query_check(&types, &exact), query);
+            assert_eq!(schema.query_check(&types, &range), ord);
+            assert_eq!(schema.query_check(&types, &bits), bit);
+            assert!(schema.query_check(&types, &IntValidator::new().build()));
+            assert!(schema.query_check(&types, &Validator::Any));
+
+            // Every branch of a Multi query must be allowed
+            let multi = MultiValidator::new()
+                .push(exact.clone())
+                .push(range.clone());
+            assert_eq!(schema.query_check(&types, &multi.build()), query && ord);
+        }
+    }
+
+    #[test]
+    fn bit_width_query() {
+        let types = BTreeMap::new();
+        let query = IntValidator::new().bit_width(8, false).build();
+        assert!(!IntValidator::new().build().query_check(&types, &query));
+        assert!(IntValidator::new()
+            .ord(true)
+            .build()
+            .query_check(&types, &query));
+    }
+}
@@ -0,0 +1,263 @@
+//! Support for loading validators written by a newer version of fog-pack.
+//!
+//! Every validator rejects fields it doesn't know about, so a validator with a field added in a
+//! later version fails to deserialize. The functions here work on the raw value of a validator
+//! instead, removing any fields that wouldn't be recognized, so the result can be deserialized
+//! as normal.
+
+use super::*;
+use crate::value::{PathSegment, ValuePath};
+use serde::de::{self, Deserializer, Visitor};
+
+/// A deserializer that only records the field names a struct asks for.
+struct FieldNames(&'static [&'static str]);
+
+impl<'de> Deserializer<'de> for &mut FieldNames {
+    type Error = de::value::Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, _: V) -> Result<V::Value, Self::Error> {
+        Err(de::Error::custom("only structs have field names"))
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        _visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        self.0 = fields;
+        Err(de::Error::custom("only collecting field names"))
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct newtype_struct seq tuple
+        tuple_struct map enum identifier ignored_any
+    }
+}
+
+/// Get the names of all the fields a struct accepts when deserializing.
+fn field_names<'de, T: Deserialize<'de>>() -> &'static [&'static str] {
+    let mut names = FieldNames(&[]);
+    let _ = T::deserialize(&mut names);
+    names.0
+}
+
+/// Removes unrecognized fields from raw validator values, keeping track of where each one was.
+#[derive(Debug, Default)]
+pub(crate) struct FieldStripper {
+    path: Vec<PathSegment>,
+    found: Vec<ValuePath>,
+}
+
+impl FieldStripper {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    /// Get the paths of every field removed so far.
+    pub(crate) fn finish(self) -> Vec<ValuePath> {
+        self.found
+    }
+
+    /// Remove every field in `value` that isn't one of `T`'s fields. Does nothing if `value`
+    /// isn't a map.
+    pub(crate) fn strip_struct<'de, T: Deserialize<'de>>(&mut self, value: &mut Value) {
+        let map = match value.as_map_mut() {
+            Some(map) => map,
+            None => return,
+        };
+        let fields = field_names::<T>();
+        let Self { path, found } = self;
+        map.retain(|key, _| {
+            if fields.contains(&key.as_str()) {
+                return true;
+            }
+            path.push(PathSegment::Key(key.clone()));
+            found.push(ValuePath::new(path.clone()));
+            path.pop();
+            false
+        });
+    }
+
+    /// Call `f` on the value at `key`, if `value` is a map containing it.
+    pub(crate) fn key(
+        &mut self,
+        value: &mut Value,
+        key: &str,
+        f: impl FnOnce(&mut Self, &mut Value),
+    ) {
+        if let Some(v) = value.as_map_mut().and_then(|map| map.get_mut(key)) {
+            self.path.push(PathSegment::Key(key.to_owned()));
+            f(self, v);
+            self.path.pop();
+        }
+    }
+
+    /// Call `f` on every item in `value`, if it is an array or map.
+    pub(crate) fn items(&mut self, value: &mut Value, mut f: impl FnMut(&mut Self, &mut Value)) {
+        if let Some(array) = value.as_array_mut() {
+            for (index, v) in array.iter_mut().enumerate() {
+                self.path.push(PathSegment::Index(index));
+                f(self, v);
+                self.path.pop();
+            }
+        } else if let Some(map) = value.as_map_mut() {
+            for (key, v) in map.iter_mut() {
+                self.path.push(PathSegment::Key(key.clone()));
+                f(self, v);
+                self.path.pop();
+            }
+        }
+    }
+
+    /// Remove unrecognized fields from a `[key, validator]` pair, like an integer enum variant or
+    /// a map's pattern field.
+    fn pair(&mut self, pair: &mut Value) {
+        if let Some(v) = pair.as_array_mut().and_then(|pair| pair.get_mut(1)) {
+            self.path.push(PathSegment::Index(1));
+            self.validator(v);
+            self.path.pop();
+        }
+    }
+
+    /// Remove unrecognized fields from the raw value of a [`Validator`], recursing through all
+    /// sub-validators.
+    ///
+    /// Only fields are removed: an unknown kind of validator is left alone, and will still fail
+    /// to deserialize.
+    pub(crate) fn validator(&mut self, value: &mut Value) {
+        // Validators with contents are stored as a map with a single key that holds the kind
+        let (kind, inner) = match value.as_map_mut() {
+            Some(map) if map.len() == 1 => {
+                let (kind, inner) = map.iter_mut().next().unwrap();
+                (kind.clone(), inner)
+            }
+            _ => return,
+        };
+        self.path.push(PathSegment::Key(kind.clone()));
+        match kind.as_str() {
+            "Bool" => self.strip_struct::<BoolValidator>(inner),
+            "Int" => self.strip_struct::<IntValidator>(inner),
+            "F32" => self.strip_struct::<F32Validator>(inner),
+            "F64" => self.strip_struct::<F64Validator>(inner),
+            "Bin" => self.strip_struct::<BinValidator>(inner),
+            "Str" => self.strip_struct::<StrValidator>(inner),
+            "Time" => self.strip_struct::<TimeValidator>(inner),
+            "Identity" => self.strip_struct::<IdentityValidator>(inner),
+            "StreamId" => self.strip_struct::<StreamIdValidator>(inner),
+            "LockId" => self.strip_struct::<LockIdValidator>(inner),
+            "DataLockbox" => self.strip_struct::<DataLockboxValidator>(inner),
+            "IdentityLockbox" => self.strip_struct::<IdentityLockboxValidator>(inner),
+            "StreamLockbox" => self.strip_struct::<StreamLockboxValidator>(inner),
+            "LockLockbox" => self.strip_struct::<LockLockboxValidator>(inner),
+            "AnyBounded" => self.strip_struct::<AnyValidator>(inner),
+            "Array" => {
+                self.strip_struct::<ArrayValidator>(inner);
+                self.key(inner, "contains", |s, v| s.items(v, Self::validator));
+                self.key(inner, "prefix", |s, v| s.items(v, Self::validator));
+                self.key(inner, "items", Self::validator);
+            }
+            "Map" => {
+                self.strip_struct::<MapValidator>(inner);
+                self.key(inner, "keys", |s, v| s.strip_struct::<KeyValidator>(v));
+                self.key(inner, "values", Self::validator);
+                self.key(inner, "req", |s, v| s.items(v, Self::validator));
+                self.key(inner, "opt", |s, v| s.items(v, Self::validator));
+                self.key(inner, "pattern_fields", |s, v| s.items(v, Self::pair));
+            }
+            "Hash" => {
+                self.strip_struct::<HashValidator>(inner);
+                self.key(inner, "link", Self::validator);
+            }
+            "Multi" | "All" => self.items(inner, Self::validator),
+            "Not" => self.validator(inner),
+            // Enums with only named variants are a map of them; anything else is a map of fields
+            // inside an array
+            "Enum" if inner.is_map() => self.items(inner, Self::validator),
+            "Enum" => self.items(inner, |s, v| {
+                s.strip_struct::<EnumFields>(v);
+                s.key(v, "var", |s, v| s.items(v, Self::validator));
+                s.key(v, "int_var", |s, v| s.items(v, Self::pair));
+                s.key(v, "other", Self::validator);
+            }),
+            _ => (),
+        }
+        self.path.pop();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{de::FogDeserializer, ser::FogSerializer};
+
+    fn to_value(validator: &Validator) -> Value {
+        let mut ser = FogSerializer::default();
+        validator.serialize(&mut ser).unwrap();
+        let buf = ser.finish();
+        Value::deserialize(&mut FogDeserializer::new(&buf)).unwrap()
+    }
+
+    fn from_value(value: &Value) -> Result<Validator> {
+        let mut ser = FogSerializer::default();
+        value.serialize(&mut ser).unwrap();
+        let buf = ser.finish();
+        Validator::deserialize(&mut FogDeserializer::new(&buf))
+    }
+
+    fn insert_extra(value: &mut Value, path: &str) {
+        let path = ValuePath::parse(path).unwrap();
+        let map = path.get_mut(value).unwrap().as_map_mut().unwrap();
+        map.insert("extra".to_string(), Value::Null);
+    }
+
+    #[test]
+    fn field_names_renamed() {
+        let names = field_names::<IntValidator>();
+        assert!(names.contains(&"in"));
+        assert!(names.contains(&"nin"));
+        assert!(!names.contains(&"in_list"));
+        assert!(!names.contains(&"list_index"));
+    }
+
+    #[test]
+    fn strip_nested() {
+        let validator = ArrayValidator::new()
+            .prefix_add(
+                MapValidator::new()
+                    .keys(KeyValidator::new().max_len(4))
+                    .values(BoolValidator::new().build())
+                    .build(),
+            )
+            .items(
+                EnumValidator::new()
+                    .insert("a", Some(IntValidator::new().build()))
+                    .insert_int(1, Some(StrValidator::new().build()))
+                    .build(),
+            )
+            .build();
+        let mut value = to_value(&validator);
+        let extra = [
+            "Array",
+            "Array.prefix[0].Map.keys",
+            "Array.prefix[0].Map.values.Bool",
+            "Array.items.Enum[0]",
+            "Array.items.Enum[0].var.a.Int",
+            "Array.items.Enum[0].int_var[0][1].Str",
+        ];
+        for path in extra.iter() {
+            insert_extra(&mut value, path);
+        }
+        assert!(from_value(&value).is_err());
+
+        let mut stripper = FieldStripper::new();
+        stripper.validator(&mut value);
+        assert_eq!(from_value(&value).unwrap(), validator);
+        let mut found: Vec<String> = stripper.finish().iter().map(|p| p.to_string()).collect();
+        let mut expected: Vec<String> = extra.iter().map(|p| format!("{}.extra", p)).collect();
+        found.sort();
+        expected.sort();
+        assert_eq!(found, expected);
+    }
+}
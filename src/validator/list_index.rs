@@ -0,0 +1,148 @@
+use std::borrow::Borrow;
+use std::collections::HashSet;
+use std::hash::Hash;
+
+/// A precomputed lookup index for a validator's `in` and `nin` lists.
+///
+/// Validators with large `in`/`nin` lists would otherwise be checked with a linear scan of the
+/// list on every validation. The index is built once, when a Schema or Query is loaded, and is
+/// never serialized. Validators that haven't had their index built fall back to the linear scan,
+/// so results are identical either way. Array validators compare encoded arrays through the
+/// index, so they leave it unbuilt if any list contains a float, as float equality differs from
+/// encoding equality.
+///
+/// Two indexes always compare as equal, so the index never affects validator equality.
+#[derive(Clone)]
+pub(crate) struct ListIndex<T: Hash + Eq>(Option<(HashSet<T>, HashSet<T>)>);
+
+impl<T: Hash + Eq> ListIndex<T> {
+    /// Build the index from the `in` and `nin` lists.
+    pub(crate) fn build(
+        in_list: impl IntoIterator<Item = T>,
+        nin_list: impl IntoIterator<Item = T>,
+    ) -> Self {
+        Self(Some((
+            in_list.into_iter().collect(),
+            nin_list.into_iter().collect(),
+        )))
+    }
+
+    /// Check if the index has been built.
+    pub(crate) fn is_built(&self) -> bool {
+        self.0.is_some()
+    }
+
+    /// Look up a value in the indexed `in` list. Returns `None` if the index hasn't been built.
+    pub(crate) fn in_contains<Q>(&self, val: &Q) -> Option<bool>
+    where
+        T: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.0.as_ref().map(|(in_set, _)| in_set.contains(val))
+    }
+
+    /// Look up a value in the indexed `nin` list. Returns `None` if the index hasn't been built.
+    pub(crate) fn nin_contains<Q>(&self, val: &Q) -> Option<bool>
+    where
+        T: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.0.as_ref().map(|(_, nin_set)| nin_set.contains(val))
+    }
+}
+
+impl<T: Hash + Eq> Default for ListIndex<T> {
+    fn default() -> Self {
+        Self(None)
+    }
+}
+
+impl<T: Hash + Eq> PartialEq for ListIndex<T> {
+    fn eq(&self, _: &Self) -> bool {
+        true
+    }
+}
+
+impl<T: Hash + Eq> std::fmt::Debug for ListIndex<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str(if self.is_built() {
+            "ListIndex(built)"
+        } else {
+            "ListIndex(unbuilt)"
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::element::Parser;
+    use crate::ser::FogSerializer;
+    use crate::validator::*;
+    use serde::Serialize;
+    use std::collections::BTreeMap;
+
+    fn check(validator: &Validator, value: impl Serialize) -> bool {
+        let mut ser = FogSerializer::default();
+        value.serialize(&mut ser).unwrap();
+        let data = ser.finish();
+        let types = BTreeMap::new();
+        validator
+            .validate(&types, Parser::new(&data), None)
+            .and_then(|(parser, _)| parser.finish())
+            .is_ok()
+    }
+
+    fn assert_same(naive: Validator, candidates: &[crate::value::Value]) {
+        let mut indexed = naive.clone();
+        indexed.build_index();
+        for candidate in candidates {
+            assert_eq!(
+                check(&naive, candidate),
+                check(&indexed, candidate),
+                "mismatch on {:?}",
+                candidate
+            );
+        }
+    }
+
+    #[test]
+    fn index_matches_naive() {
+        use crate::value::Value;
+        let int = IntValidator::new()
+            .in_add(1u8)
+            .in_add(-5i8)
+            .nin_add(1u8)
+            .build();
+        assert_same(
+            int,
+            &[Value::from(1u8), Value::from(-5i8), Value::from(2u8)],
+        );
+        let bin = BinValidator::new()
+            .in_add(vec![1u8, 2])
+            .nin_add(vec![3u8])
+            .build();
+        assert_same(
+            bin,
+            &[
+                Value::from(vec![1u8, 2]),
+                Value::from(vec![3u8]),
+                Value::from(vec![4u8]),
+            ],
+        );
+        // "e\u{301}" normalizes to "\u{e9}" under NFC
+        let string = StrValidator::new()
+            .in_add("e\u{301}")
+            .nin_add("x")
+            .normalize(Normalize::NFC)
+            .build();
+        assert_same(
+            string,
+            &[
+                Value::from("\u{e9}"),
+                Value::from("e\u{301}"),
+                Value::from("x"),
+                Value::from("y"),
+            ],
+        );
+    }
+}
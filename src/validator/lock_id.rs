@@ -1,6 +1,6 @@
 use super::*;
 use crate::element::*;
-use crate::error::{Error, Result};
+use crate::error::{Error, Result, ValidateCode};
 use crate::LockId;
 use serde::{Deserialize, Serialize};
 
@@ -92,22 +92,23 @@ impl LockIdValidator {
     pub(crate) fn validate(&self, parser: &mut Parser) -> Result<()> {
         let elem = parser
             .next()
-            .ok_or_else(|| Error::FailValidate("Expected a LockId".to_string()))??;
+            .ok_or_else(|| Error::validate(ValidateCode::WrongType, "Expected a LockId"))??;
         let elem = if let Element::LockId(v) = elem {
             v
         } else {
-            return Err(Error::FailValidate(format!(
-                "Expected LockId, got {}",
-                elem.name()
-            )));
+            return Err(Error::wrong_type("LockId", elem.name()));
         };
         if !self.in_list.is_empty() && !self.in_list.iter().any(|v| v == elem.as_ref()) {
-            return Err(Error::FailValidate(
+            return Err(Error::validate(
+                ValidateCode::NotInList,
                 "LockId is not on `in` list".to_string(),
             ));
         }
         if self.nin_list.iter().any(|v| v == elem.as_ref()) {
-            return Err(Error::FailValidate("LockId is on `nin` list".to_string()));
+            return Err(Error::validate(
+                ValidateCode::InNinList,
+                "LockId is on `nin` list",
+            ));
         }
         Ok(())
     }
@@ -1,6 +1,6 @@
 use super::*;
 use crate::element::*;
-use crate::error::{Error, Result};
+use crate::error::{Error, Result, ValidateCode};
 use serde::{Deserialize, Serialize};
 
 #[inline]
@@ -113,26 +113,19 @@ macro_rules! lockbox_validator {
             pub(crate) fn validate(&self, parser: &mut Parser) -> Result<()> {
                 let elem = parser
                     .next()
-                    .ok_or_else(|| Error::FailValidate(concat!("Expected a ",$name).to_string()))??;
+                    .ok_or_else(|| Error::validate(ValidateCode::WrongType, concat!("Expected a ",$name)))??;
                 let elem = if let Element::$e(v) = elem {
                     v
                 } else {
-                    return Err(Error::FailValidate(format!(
-                                concat!("Expected ", $name, ", got {}"),
-                                elem.name()
-                    )));
+                    return Err(Error::wrong_type($name, elem.name()));
                 };
 
                 let len = elem.as_bytes().len() as u32;
                 if len > self.max_len {
-                    return Err(Error::FailValidate(
-                            concat!($name, " is longer than max_len").to_string()
-                    ));
+                    return Err(Error::validate(ValidateCode::TooLong, concat!($name, " is longer than max_len")));
                 }
                 if len < self.min_len {
-                    return Err(Error::FailValidate(
-                            concat!($name, " is shorter than min_len").to_string()
-                    ));
+                    return Err(Error::validate(ValidateCode::TooShort, concat!($name, " is shorter than min_len")));
                 }
 
                 Ok(())
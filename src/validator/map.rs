@@ -1,5 +1,5 @@
 use super::*;
-use crate::error::{Error, Result};
+use crate::error::{Error, Result, ValidateCode};
 use crate::{de::FogDeserializer, element::*, value::Value, value_ref::ValueRef};
 use regex::Regex;
 use serde::{Deserialize, Deserializer, Serialize};
@@ -107,25 +107,27 @@ impl KeyValidator {
         // Get element
         let elem = parser
             .next()
-            .ok_or_else(|| Error::FailValidate("expected a key string".to_string()))??;
+            .ok_or_else(|| Error::validate(ValidateCode::WrongType, "expected a key string"))??;
         let val = if let Element::Str(v) = elem {
             v
         } else {
-            return Err(Error::FailValidate(format!(
-                "expected Str key, got {}",
-                elem.name()
-            )));
+            return Err(Error::validate(
+                ValidateCode::WrongType,
+                format!("expected Str key, got {}", elem.name()),
+            ));
         };
 
         // Length Checks
         if (val.len() as u32) > self.max_len {
-            return Err(Error::FailValidate(
-                "Key is longer than max_len".to_string(),
+            return Err(Error::validate(
+                ValidateCode::TooLong,
+                format!("Key {:?} is longer than max_len", val),
             ));
         }
         if (val.len() as u32) < self.min_len {
-            return Err(Error::FailValidate(
-                "Key is shorter than min_len".to_string(),
+            return Err(Error::validate(
+                ValidateCode::TooShort,
+                format!("Key {:?} is shorter than min_len", val),
             ));
         }
 
@@ -137,8 +139,9 @@ impl KeyValidator {
             match self.normalize {
                 Normalize::None => {
                     if !regex.is_match(val) {
-                        return Err(Error::FailValidate(
-                            "Key doesn't match regular expression".to_string(),
+                        return Err(Error::validate(
+                            ValidateCode::NoMatch,
+                            format!("Key {:?} doesn't match regular expression", val),
                         ));
                     }
                 }
@@ -152,8 +155,9 @@ impl KeyValidator {
                         }
                     };
                     if !regex.is_match(val) {
-                        return Err(Error::FailValidate(
-                            "Key doesn't match regular expression".to_string(),
+                        return Err(Error::validate(
+                            ValidateCode::NoMatch,
+                            format!("Key {:?} doesn't match regular expression", val),
                         ));
                     }
                 }
@@ -167,8 +171,9 @@ impl KeyValidator {
                         }
                     };
                     if !regex.is_match(val) {
-                        return Err(Error::FailValidate(
-                            "Key doesn't match regular expression".to_string(),
+                        return Err(Error::validate(
+                            ValidateCode::NoMatch,
+                            format!("Key {:?} doesn't match regular expression", val),
                         ));
                     }
                 }
@@ -222,14 +227,19 @@ fn get_validator<'de, D: Deserializer<'de>>(
 /// - The number of key-value pairs in the map is greater than or equal to the value in `min_len`.
 /// - Each key passes the [`KeyValidator`] in `keys`.
 /// - Each key is not among the strings in the `ban` list.
+/// - If `strict` is set, each key must be in `req` or `opt`, or match a pattern in
+///   `pattern_fields`.
+/// - If `values_unique` is set, no two values in the map are equal.
 /// - There must be a matching key-value in the map for each key-validator pair in `req` .
 /// - For each key-value pair in the map:
 ///     1. If the key is in `req`, the corresponding validator is used to validate the value.
 ///     2. If the key is not in `req` but is in `opt`, the corresponding validator is used to
 ///        validate the value.
-///     3. if the key is not in `req` or `opt`, the validator for `values` is used to validate the
-///        value.
-///     4. If there is no validator for `values`, validation does not pass.
+///     3. If the key is not in `req` or `opt`, but matches a regular expression in
+///        `pattern_fields`, the validator paired with the first matching expression is used to
+///        validate the value.
+///     4. Otherwise, the validator for `values` is used to validate the value.
+///     5. If there is no validator for `values`, validation does not pass.
 ///
 /// Note how each key-value pair must be validated, so an unlimited collection of key-value pairs
 /// isn't allowed unless there is a validator present in `values`.
@@ -246,12 +256,16 @@ fn get_validator<'de, D: Deserializer<'de>>(
 /// - values: None
 /// - req: empty
 /// - opt: empty
+/// - pattern_fields: empty
 /// - ban: empty
+/// - strict: false
+/// - values_unique: false
 /// - in_list: empty
 /// - nin_list: empty
 /// - query: false
 /// - size: false
 /// - map_ok: false
+/// - unique_ok: false
 /// - match_keys: false
 /// - len_keys: false
 ///
@@ -262,10 +276,13 @@ fn get_validator<'de, D: Deserializer<'de>>(
 ///
 /// - query: `in` and `nin` lists
 /// - size: `max_len` and `min_len`
-/// - map_ok: `req`, `opt`, `ban`, and `values`
+/// - map_ok: `req`, `opt`, `ban`, `strict`, and `values`
+/// - unique_ok: `values_unique`
 /// - match_keys: `matches` in `KeyValidator`
 /// - len_keys: `max_len` and `min_len` in `KeyValidator`
 ///
+/// Queries may never use `pattern_fields`.
+///
 /// In addition, sub-validators in the query are matched against the schema's sub-validators:
 ///
 /// - The `values` validator is checked against the schema's `values` validator.
@@ -273,6 +290,8 @@ fn get_validator<'de, D: Deserializer<'de>>(
 ///     choosing whichever validator is found first. If no validator is found, the check fails.
 /// - The `opt` validators are checked against the schema's `req`/`opt`/`values` validators,
 ///     choosing whichever validator is found first. If no validator is found, the check fails.
+/// - Keys matching one of the schema's `pattern_fields` are checked against that pattern's
+///   validator, just before falling back to `values`.
 ///
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 #[serde(deny_unknown_fields, default)]
@@ -305,9 +324,23 @@ pub struct MapValidator {
     /// validator in `req`.
     #[serde(skip_serializing_if = "BTreeMap::is_empty")]
     pub opt: BTreeMap<String, Validator>,
+    /// A list of regular expressions and validators. A key that isn't in `req` or `opt` is
+    /// checked against each expression in turn, and the value of the first matching key is
+    /// validated by the paired validator instead of by `values`.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub pattern_fields: Vec<(String, Validator)>,
+    /// The compiled regular expressions from `pattern_fields`.
+    #[serde(skip)]
+    pub(crate) pattern_index: PatternIndex,
     /// A list of keys that may not be present in the map.
     #[serde(skip_serializing_if = "Vec::is_empty")]
     pub ban: Vec<String>,
+    /// If true, keys not in `req` or `opt` fail validation, even if `values` is set.
+    #[serde(skip_serializing_if = "is_false")]
+    pub strict: bool,
+    /// If true, all values in the map must be unique.
+    #[serde(skip_serializing_if = "is_false")]
+    pub values_unique: bool,
     /// A vector of specific allowed values, stored under the `in` field. If empty, this vector is not checked against.
     #[serde(rename = "in", skip_serializing_if = "Vec::is_empty")]
     pub in_list: Vec<BTreeMap<String, Value>>,
@@ -320,9 +353,13 @@ pub struct MapValidator {
     /// If true, queries against matching spots may use `max_len` and `min_len`.
     #[serde(skip_serializing_if = "is_false")]
     pub size: bool,
-    /// If true, queries against matching spots may use `req`, `opt`, `ban`, and `values`.
+    /// If true, queries against matching spots may use `req`, `opt`, `ban`, `strict`, and
+    /// `values`.
     #[serde(skip_serializing_if = "is_false")]
     pub map_ok: bool,
+    /// If true, queries against matching spots may use `values_unique`.
+    #[serde(skip_serializing_if = "is_false")]
+    pub unique_ok: bool,
     /// If true, queries against matching spots may use `matches` in the Key Validator.
     #[serde(skip_serializing_if = "is_false")]
     pub match_keys: bool,
@@ -341,12 +378,17 @@ impl Default for MapValidator {
             values: None,
             req: BTreeMap::new(),
             opt: BTreeMap::new(),
+            pattern_fields: Vec::new(),
+            pattern_index: PatternIndex::default(),
             ban: Vec::new(),
+            strict: false,
+            values_unique: false,
             in_list: Vec::new(),
             nin_list: Vec::new(),
             query: false,
             size: false,
             map_ok: false,
+            unique_ok: false,
             match_keys: false,
             len_keys: false,
         }
@@ -383,6 +425,12 @@ impl MapValidator {
         self
     }
 
+    /// Add a new regular expression and validator to the `pattern_fields` list.
+    pub fn pattern_fields_add(mut self, pattern: impl Into<String>, validator: Validator) -> Self {
+        self.pattern_fields.push((pattern.into(), validator));
+        self
+    }
+
     /// Add a new key to the `ban` list.
     pub fn ban_add(mut self, ban: impl Into<String>) -> Self {
         self.ban.push(ban.into());
@@ -431,12 +479,30 @@ impl MapValidator {
         self
     }
 
-    /// Set whether or not queries can use the `req`, `opt`, `ban`, and `values` values.
+    /// Set whether or not keys not in `req` or `opt` fail validation.
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    /// Set whether the values in the map must be unique.
+    pub fn values_unique(mut self, values_unique: bool) -> Self {
+        self.values_unique = values_unique;
+        self
+    }
+
+    /// Set whether or not queries can use the `req`, `opt`, `ban`, `strict`, and `values` values.
     pub fn map_ok(mut self, map_ok: bool) -> Self {
         self.map_ok = map_ok;
         self
     }
 
+    /// Set whether or not queries can use the `values_unique` setting.
+    pub fn unique_ok(mut self, unique_ok: bool) -> Self {
+        self.unique_ok = unique_ok;
+        self
+    }
+
     /// Set whether or not queries can use the `matches` value for the Key Validator.
     pub fn match_keys(mut self, match_keys: bool) -> Self {
         self.match_keys = match_keys;
@@ -455,6 +521,78 @@ impl MapValidator {
         Validator::Map(self)
     }
 
+    pub(crate) fn build_index(&mut self) {
+        self.pattern_index = PatternIndex(
+            self.pattern_fields
+                .iter()
+                .map(|(pattern, _)| Regex::new(pattern).ok())
+                .collect::<Option<Vec<Regex>>>(),
+        );
+    }
+
+    /// Find the validator for the value at `key`: the `req` or `opt` validator for the key, the
+    /// validator for the first matching pattern in `pattern_fields`, or the `values` validator.
+    pub(crate) fn field_validator(&self, key: &str) -> Option<&Validator> {
+        self.req
+            .get(key)
+            .or(self.opt.get(key))
+            .or_else(|| self.pattern_validator(key))
+            .or(self.values.as_deref())
+    }
+
+    /// Find the validator for the first pattern in `pattern_fields` matching `key`.
+    fn pattern_validator(&self, key: &str) -> Option<&Validator> {
+        if self.pattern_fields.is_empty() {
+            return None;
+        }
+        match &self.pattern_index.0 {
+            Some(regexes) => regexes
+                .iter()
+                .zip(self.pattern_fields.iter())
+                .find(|(regex, _)| regex.is_match(key))
+                .map(|(_, (_, validator))| validator),
+            None => self
+                .pattern_fields
+                .iter()
+                .find(|(pattern, _)| matches!(Regex::new(pattern), Ok(r) if r.is_match(key)))
+                .map(|(_, validator)| validator),
+        }
+    }
+
+    pub(crate) fn check_build(&self) -> Result<()> {
+        if self.min_len > self.max_len {
+            return Err(Error::validate(
+                ValidateCode::BadValidator,
+                format!(
+                    "Map validator min_len of {} is greater than max_len of {}",
+                    self.min_len, self.max_len
+                ),
+            ));
+        }
+        if let Some(Err(e)) = self
+            .pattern_fields
+            .iter()
+            .map(|(pattern, _)| Regex::new(pattern))
+            .find(|r| r.is_err())
+        {
+            return Err(Error::validate(
+                ValidateCode::BadValidator,
+                format!("Map validator has an invalid pattern: {}", e),
+            ));
+        }
+        if (self.req.len() as u64) > (self.max_len as u64) {
+            return Err(Error::validate(
+                ValidateCode::BadValidator,
+                format!(
+                    "Map validator has {} required keys, but max_len is {}",
+                    self.req.len(),
+                    self.max_len
+                ),
+            ));
+        }
+        Ok(())
+    }
+
     pub(crate) fn validate<'de, 'c>(
         &'c self,
         types: &'c BTreeMap<String, Validator>,
@@ -464,31 +602,34 @@ impl MapValidator {
         let val_parser = parser.clone();
         let elem = parser
             .next()
-            .ok_or_else(|| Error::FailValidate("Expected a map".to_string()))??;
+            .ok_or_else(|| Error::validate(ValidateCode::WrongType, "Expected a map"))??;
         let len = if let Element::Map(len) = elem {
             len
         } else {
-            return Err(Error::FailValidate(format!(
-                "Expected Map, got {}",
-                elem.name()
-            )));
+            return Err(Error::wrong_type("Map", elem.name()));
         };
 
         if (len as u32) > self.max_len {
-            return Err(Error::FailValidate(format!(
-                "Map is {} pairs, longer than maximum allowed of {}",
-                len, self.max_len
-            )));
+            return Err(Error::validate(
+                ValidateCode::TooLong,
+                format!(
+                    "Map is {} pairs, longer than maximum allowed of {}",
+                    len, self.max_len
+                ),
+            ));
         }
         if (len as u32) < self.min_len {
-            return Err(Error::FailValidate(format!(
-                "Map is {} pairs, shorter than minimum allowed of {}",
-                len, self.min_len
-            )));
+            return Err(Error::validate(
+                ValidateCode::TooShort,
+                format!(
+                    "Map is {} pairs, shorter than minimum allowed of {}",
+                    len, self.min_len
+                ),
+            ));
         }
 
         // Check the requirements that require parsing the entire array
-        if !self.in_list.is_empty() || !self.nin_list.is_empty() {
+        if self.values_unique || !self.in_list.is_empty() || !self.nin_list.is_empty() {
             let mut de = FogDeserializer::from_parser(val_parser);
             let map = BTreeMap::<&str, ValueRef>::deserialize(&mut de)?;
 
@@ -500,7 +641,10 @@ impl MapValidator {
                             .all(|((ks, vs), (ko, vo))| (ks == ko) && (vs == vo))
                 });
                 if !in_pass {
-                    return Err(Error::FailValidate("Map is not on `in` list".to_string()));
+                    return Err(Error::validate(
+                        ValidateCode::NotInList,
+                        "Map is not on `in` list",
+                    ));
                 }
             }
 
@@ -511,46 +655,81 @@ impl MapValidator {
                         .all(|((ks, vs), (ko, vo))| (ks == ko) && (vs == vo))
             });
             if !nin_pass {
-                return Err(Error::FailValidate("Map is on `nin` list".to_string()));
+                return Err(Error::validate(
+                    ValidateCode::InNinList,
+                    "Map is on `nin` list",
+                ));
+            }
+
+            if self.values_unique {
+                let dup = map.iter().enumerate().find_map(|(index, (lk, lv))| {
+                    map.iter()
+                        .skip(index + 1)
+                        .find(|(_, rv)| lv == *rv)
+                        .map(|(rk, _)| (lk, rk))
+                });
+                if let Some((lk, rk)) = dup {
+                    return Err(Error::validate(
+                        ValidateCode::NotUnique,
+                        format!(
+                            "Map values are not unique: keys {:?} and {:?} have the same value",
+                            lk, rk
+                        ),
+                    ));
+                }
             }
         }
 
         // Loop through each item, verifying it with the appropriate validator
-        let mut reqs_found = 0;
+        let mut reqs_found = Vec::new();
         for _ in 0..len {
             let key = self.keys.validate(&mut parser)?;
             if self.ban.iter().any(|k| k == key) {
-                return Err(Error::FailValidate(format!(
-                    "Map key {:?} is on the ban list",
-                    key
-                )));
+                return Err(Error::validate(
+                    ValidateCode::BadKey,
+                    format!("Map key {:?} is banned", key),
+                ));
             }
-            let (p, c) = if let Some(validator) = self
-                .req
-                .get(key)
-                .map(|v| {
-                    reqs_found += 1;
-                    v
-                })
-                .or_else(|| self.opt.get(key))
-                .or_else(|| self.values.as_deref())
+            if self.strict
+                && !self.req.contains_key(key)
+                && !self.opt.contains_key(key)
+                && self.pattern_validator(key).is_none()
             {
-                validator.validate(types, parser, checklist)?
+                return Err(Error::validate(
+                    ValidateCode::BadKey,
+                    format!("Map key {:?} is unknown, and the map is strict", key),
+                ));
+            }
+            if self.req.contains_key(key) {
+                reqs_found.push(key);
+            }
+            let (p, c) = if let Some(validator) = self.field_validator(key) {
+                validator
+                    .validate(types, parser, checklist)
+                    .map_err(|e| nest_error(e, PathSegment::Key(key.to_string())))?
             } else {
-                return Err(Error::FailValidate(format!(
-                    "Map key {:?} has no corresponding validator",
-                    key
-                )));
+                return Err(Error::validate(
+                    ValidateCode::BadKey,
+                    format!("Map key {:?} has no corresponding validator", key),
+                ));
             };
             parser = p;
             checklist = c;
         }
 
-        if reqs_found != self.req.len() {
-            return Err(Error::FailValidate(format!(
-                "Map did not have all required key-value pairs (missing {})",
-                reqs_found
-            )));
+        if reqs_found.len() != self.req.len() {
+            let missing = self
+                .req
+                .keys()
+                .filter(|k| !reqs_found.contains(&k.as_str()))
+                .collect::<Vec<_>>();
+            return Err(Error::validate(
+                ValidateCode::MissingKey,
+                format!(
+                    "Map did not have all required key-value pairs (missing {:?})",
+                    missing
+                ),
+            ));
         }
 
         Ok((parser, checklist))
@@ -563,7 +742,10 @@ impl MapValidator {
                 || (other.req.is_empty()
                     && other.opt.is_empty()
                     && other.ban.is_empty()
+                    && !other.strict
                     && other.values.is_none()))
+            && other.pattern_fields.is_empty()
+            && (self.unique_ok || !other.values_unique)
             && (self.match_keys || other.keys.matches.is_none())
             && (self.len_keys
                 || (u32_is_max(&other.keys.max_len) && u32_is_zero(&other.keys.min_len)));
@@ -582,10 +764,7 @@ impl MapValidator {
                 return false;
             }
             let req_ok = other.req.iter().all(|(ko, kv)| {
-                self.req
-                    .get(ko)
-                    .or_else(|| self.opt.get(ko))
-                    .or_else(|| self.values.as_deref())
+                self.field_validator(ko)
                     .map(|v| v.query_check(types, kv))
                     .unwrap_or(false)
             });
@@ -593,10 +772,7 @@ impl MapValidator {
                 return false;
             }
             let opt_ok = other.opt.iter().all(|(ko, kv)| {
-                self.req
-                    .get(ko)
-                    .or_else(|| self.opt.get(ko))
-                    .or_else(|| self.values.as_deref())
+                self.field_validator(ko)
                     .map(|v| v.query_check(types, kv))
                     .unwrap_or(false)
             });
@@ -623,6 +799,25 @@ impl MapValidator {
     }
 }
 
+/// Precompiled regular expressions for a Map validator's `pattern_fields`. Validators that
+/// haven't had their index built compile each expression as it's needed instead.
+///
+/// Two indexes always compare as equal, so the index never affects validator equality.
+#[derive(Clone, Default)]
+pub(crate) struct PatternIndex(Option<Vec<Regex>>);
+
+impl PartialEq for PatternIndex {
+    fn eq(&self, _: &Self) -> bool {
+        true
+    }
+}
+
+impl std::fmt::Debug for PatternIndex {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("PatternIndex")
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -645,4 +840,301 @@ mod test {
         println!("{}", de.get_debug().unwrap());
         assert_eq!(schema, decoded);
     }
+
+    fn validate_map(validator: &Validator, map: BTreeMap<String, Value>) -> Result<()> {
+        let mut ser = FogSerializer::default();
+        map.serialize(&mut ser).unwrap();
+        let data = ser.finish();
+        let types = BTreeMap::new();
+        let (parser, _) = validator.validate(&types, Parser::new(&data), None)?;
+        parser.finish()
+    }
+
+    fn user_map() -> Validator {
+        MapValidator::new()
+            .keys(
+                KeyValidator::new()
+                    .matches(Regex::new("^[a-z0-9_]+$").unwrap())
+                    .max_len(16),
+            )
+            .req_add("id", IntValidator::new().build())
+            .req_add("name", StrValidator::new().build())
+            .opt_add("email", StrValidator::new().build())
+            .values(BoolValidator::new().build())
+            .build()
+    }
+
+    fn fail_msg(validator: &Validator, map: BTreeMap<String, Value>) -> String {
+        match validate_map(validator, map) {
+            Err(Error::FailValidate(err)) => err.to_string(),
+            other => panic!("expected a validation failure, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn missing_required_key() {
+        let mut map = BTreeMap::new();
+        map.insert("id".to_string(), Value::from(1u8));
+        let msg = fail_msg(&user_map(), map.clone());
+        assert!(msg.contains("missing [\"name\"]"), "{}", msg);
+        map.insert("name".to_string(), Value::from("a"));
+        validate_map(&user_map(), map).unwrap();
+    }
+
+    #[test]
+    fn bad_key_format() {
+        let mut map = BTreeMap::new();
+        map.insert("id".to_string(), Value::from(1u8));
+        map.insert("name".to_string(), Value::from("a"));
+        let mut bad_char = map.clone();
+        bad_char.insert("Bad-Key".to_string(), Value::from(true));
+        let msg = fail_msg(&user_map(), bad_char);
+        assert!(msg.contains("\"Bad-Key\" doesn't match"), "{}", msg);
+        let mut too_long = map;
+        too_long.insert("a_very_long_key_name".to_string(), Value::from(true));
+        let msg = fail_msg(&user_map(), too_long);
+        assert!(
+            msg.contains("\"a_very_long_key_name\" is longer"),
+            "{}",
+            msg
+        );
+    }
+
+    #[test]
+    fn unknown_key_uses_values() {
+        let mut map = BTreeMap::new();
+        map.insert("id".to_string(), Value::from(1u8));
+        map.insert("name".to_string(), Value::from("a"));
+        map.insert("email".to_string(), Value::from("a@example.com"));
+        map.insert("flag".to_string(), Value::from(true));
+        validate_map(&user_map(), map.clone()).unwrap();
+        map.insert("flag".to_string(), Value::from("yes"));
+        let msg = fail_msg(&user_map(), map);
+        assert!(msg.starts_with(".flag: "), "{}", msg);
+    }
+
+    fn int_map(len: u8) -> BTreeMap<String, Value> {
+        (0..len)
+            .map(|n| (format!("k{}", n), Value::from(n)))
+            .collect()
+    }
+
+    #[test]
+    fn size_bounds() {
+        let validator = MapValidator::new()
+            .values(IntValidator::new().build())
+            .min_len(1)
+            .max_len(3)
+            .build();
+        let msg = fail_msg(&validator, int_map(0));
+        assert!(msg.contains("shorter than minimum"), "{}", msg);
+        validate_map(&validator, int_map(1)).unwrap();
+        validate_map(&validator, int_map(3)).unwrap();
+        let msg = fail_msg(&validator, int_map(4));
+        assert!(msg.contains("longer than maximum"), "{}", msg);
+
+        let empty_only = MapValidator::new().max_len(0).build();
+        validate_map(&empty_only, int_map(0)).unwrap();
+        validate_map(&empty_only, int_map(1)).unwrap_err();
+    }
+
+    #[test]
+    fn size_build() {
+        use crate::schema::SchemaBuilder;
+        let validator = MapValidator::new()
+            .req_add("a", Validator::Any)
+            .req_add("b", Validator::Any)
+            .max_len(1)
+            .build();
+        assert!(SchemaBuilder::new(validator).build().is_err());
+        let validator = MapValidator::new().min_len(2).max_len(1).build();
+        assert!(SchemaBuilder::new(validator).build().is_err());
+        let validator = MapValidator::new()
+            .req_add("a", Validator::Any)
+            .req_add("b", Validator::Any)
+            .max_len(2)
+            .build();
+        assert!(SchemaBuilder::new(validator).build().is_ok());
+    }
+
+    #[test]
+    fn size_query() {
+        let types = BTreeMap::new();
+        let query = MapValidator::new().max_len(10).build();
+        assert!(!MapValidator::new().build().query_check(&types, &query));
+        assert!(MapValidator::new()
+            .size(true)
+            .build()
+            .query_check(&types, &query));
+    }
+
+    #[test]
+    fn banned_key() {
+        let validator = MapValidator::new()
+            .values(Validator::Any)
+            .ban_add("__proto__")
+            .build();
+        let mut map = int_map(2);
+        validate_map(&validator, map.clone()).unwrap();
+        map.insert("__proto__".to_string(), Value::from(1u8));
+        let msg = fail_msg(&validator, map);
+        assert!(msg.contains("\"__proto__\" is banned"), "{}", msg);
+    }
+
+    #[test]
+    fn strict_keys() {
+        let validator = MapValidator::new()
+            .req_add("id", IntValidator::new().build())
+            .opt_add("name", StrValidator::new().build())
+            .values(Validator::Any)
+            .strict(true)
+            .build();
+        let mut map = BTreeMap::new();
+        map.insert("id".to_string(), Value::from(1u8));
+        validate_map(&validator, map.clone()).unwrap();
+        map.insert("name".to_string(), Value::from("a"));
+        validate_map(&validator, map.clone()).unwrap();
+        map.insert("extra".to_string(), Value::from(true));
+        let msg = fail_msg(&validator, map);
+        assert!(msg.contains("\"extra\" is unknown"), "{}", msg);
+    }
+
+    #[test]
+    fn strict_query() {
+        let types = BTreeMap::new();
+        let query = MapValidator::new().strict(true).build();
+        assert!(!MapValidator::new().build().query_check(&types, &query));
+        assert!(MapValidator::new()
+            .map_ok(true)
+            .build()
+            .query_check(&types, &query));
+        let query = MapValidator::new().ban_add("x").build();
+        assert!(!MapValidator::new().build().query_check(&types, &query));
+    }
+
+    #[test]
+    fn values_unique() {
+        let validator = MapValidator::new()
+            .values(Validator::Any)
+            .values_unique(true)
+            .build();
+        validate_map(&validator, int_map(0)).unwrap();
+        validate_map(&validator, int_map(3)).unwrap();
+
+        let nested = |n: u8| {
+            let mut inner = BTreeMap::new();
+            inner.insert("url".to_string(), Value::from(vec![Value::from(n)]));
+            Value::from(inner)
+        };
+        let mut map = BTreeMap::new();
+        map.insert("de".to_string(), nested(1));
+        map.insert("en".to_string(), nested(2));
+        validate_map(&validator, map.clone()).unwrap();
+        map.insert("fr".to_string(), nested(1));
+        let msg = fail_msg(&validator, map);
+        assert!(msg.contains("\"de\" and \"fr\""), "{}", msg);
+    }
+
+    #[test]
+    fn values_unique_query() {
+        let types = BTreeMap::new();
+        let query = MapValidator::new().values_unique(true).build();
+        assert!(!MapValidator::new().build().query_check(&types, &query));
+        assert!(MapValidator::new()
+            .unique_ok(true)
+            .build()
+            .query_check(&types, &query));
+    }
+
+    fn pattern_map() -> MapValidator {
+        MapValidator::new()
+            .opt_add("x_count", StrValidator::new().build())
+            .pattern_fields_add("^x_", IntValidator::new().build())
+            .pattern_fields_add("_at$", TimeValidator::new().build())
+            .values(BoolValidator::new().build())
+    }
+
+    #[test]
+    fn pattern_fields() {
+        for built in [false, true].iter() {
+            let mut validator = pattern_map().build();
+            if *built {
+                validator.build_index();
+            }
+            let mut map = BTreeMap::new();
+            map.insert("x_id".to_string(), Value::from(1u8));
+            map.insert("x_count".to_string(), Value::from("opt first"));
+            map.insert("flag".to_string(), Value::from(true));
+            validate_map(&validator, map.clone()).unwrap();
+
+            // The first matching pattern is used, and values only for keys matching none
+            let mut bad = map.clone();
+            bad.insert(
+                "x_at".to_string(),
+                Value::from(crate::timestamp::Timestamp::from_sec(0)),
+            );
+            let msg = fail_msg(&validator, bad);
+            assert!(msg.starts_with(".x_at: "), "{}", msg);
+            let mut bad = map;
+            bad.insert("flag".to_string(), Value::from(1u8));
+            assert!(validate_map(&validator, bad).is_err());
+        }
+    }
+
+    #[test]
+    fn pattern_fields_strict() {
+        let validator = pattern_map().strict(true).build();
+        let mut map = BTreeMap::new();
+        map.insert("x_id".to_string(), Value::from(1u8));
+        validate_map(&validator, map.clone()).unwrap();
+        map.insert("flag".to_string(), Value::from(true));
+        assert!(validate_map(&validator, map).is_err());
+    }
+
+    #[test]
+    fn pattern_fields_build() {
+        assert!(pattern_map().build().check_build().is_ok());
+        let validator = pattern_map()
+            .pattern_fields_add("(", Validator::Null)
+            .build();
+        let err = validator.check_build().unwrap_err();
+        assert_eq!(
+            err.validate_error().unwrap().code,
+            ValidateCode::BadValidator
+        );
+    }
+
+    #[test]
+    fn pattern_fields_ser() {
+        let validator = MapValidator::new().pattern_fields_add("^x_", Validator::Null);
+        let mut ser = FogSerializer::default();
+        validator.serialize(&mut ser).unwrap();
+        let encoded = ser.finish();
+        let value = Value::deserialize(&mut FogDeserializer::new(&encoded)).unwrap();
+        let expected = Value::from(vec![Value::from(vec![
+            Value::from("^x_"),
+            Value::from("Null"),
+        ])]);
+        assert_eq!(value["pattern_fields"], expected);
+        let decoded = MapValidator::deserialize(&mut FogDeserializer::new(&encoded)).unwrap();
+        assert_eq!(validator, decoded);
+    }
+
+    #[test]
+    fn pattern_fields_query() {
+        let types = BTreeMap::new();
+        let schema = pattern_map().map_ok(true).build();
+        let query = MapValidator::new()
+            .opt_add("x_id", IntValidator::new().build())
+            .build();
+        assert!(schema.query_check(&types, &query));
+        let query = MapValidator::new()
+            .opt_add("x_id", StrValidator::new().build())
+            .build();
+        assert!(!schema.query_check(&types, &query));
+        let query = MapValidator::new()
+            .pattern_fields_add("^x_", IntValidator::new().build())
+            .build();
+        assert!(!schema.query_check(&types, &query));
+    }
 }
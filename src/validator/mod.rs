@@ -37,44 +37,62 @@
 //! # }
 //! ```
 
+mod all;
+mod any;
 mod array;
 mod bin;
 mod bool;
+mod caps;
 mod checklist;
+mod constant;
 mod enum_set;
 mod float32;
 mod float64;
 mod hash;
 mod identity;
 mod integer;
+mod lenient;
+mod list_index;
 mod lock_id;
 mod lockbox;
 mod map;
 mod multi;
+mod not;
+mod pretty;
 mod serde_regex;
 mod str;
 mod stream_id;
 mod time;
 
+pub use self::all::*;
+pub use self::any::*;
 pub use self::array::*;
 pub use self::bin::*;
 pub use self::bool::*;
+pub use self::caps::*;
 pub use self::checklist::*;
+pub use self::constant::*;
 pub use self::enum_set::*;
 pub use self::float32::*;
 pub use self::float64::*;
 pub use self::hash::*;
 pub use self::identity::*;
 pub use self::integer::*;
+pub(crate) use self::lenient::FieldStripper;
+use self::list_index::ListIndex;
 pub use self::lock_id::*;
 pub use self::lockbox::*;
 pub use self::map::*;
 pub use self::multi::*;
+pub use self::not::*;
+pub(crate) use self::pretty::PrettyPrinter;
 pub use self::str::*;
 pub use self::stream_id::*;
 pub use self::time::*;
 use crate::element::*;
-use crate::error::{Error, Result};
+use crate::error::{Error, Result, ValidateCode};
+use crate::ser::FogSerializer;
+use crate::value::{PathSegment, Value};
 
 use serde::{Deserialize, Serialize};
 use std::collections::BTreeMap;
@@ -118,8 +136,12 @@ pub enum Validator {
     LockLockbox(LockLockboxValidator),
     Ref(String),
     Multi(MultiValidator),
+    All(AllValidator),
+    Not(NotValidator),
     Enum(EnumValidator),
+    Const(ConstValidator),
     Any,
+    AnyBounded(AnyValidator),
 }
 
 impl Validator {
@@ -135,6 +157,12 @@ impl Validator {
         Self::Any
     }
 
+    /// Create a validator that only passes a value exactly equal to the provided one. See
+    /// [`ConstValidator`] for details.
+    pub fn new_const(value: impl Into<Value>) -> Self {
+        Self::Const(ConstValidator::new(value))
+    }
+
     pub(crate) fn validate<'de, 'c>(
         &'c self,
         types: &'c BTreeMap<String, Validator>,
@@ -145,11 +173,11 @@ impl Validator {
             Validator::Null => {
                 let elem = parser
                     .next()
-                    .ok_or_else(|| Error::FailValidate("expected null".to_string()))??;
+                    .ok_or_else(|| Error::validate(ValidateCode::WrongType, "expected null"))??;
                 if let Element::Null = elem {
                     Ok((parser, checklist))
                 } else {
-                    Err(Error::FailValidate("expected null".to_string()))
+                    Err(Error::validate(ValidateCode::WrongType, "expected null"))
                 }
             }
             Validator::Bool(validator) => {
@@ -216,26 +244,375 @@ impl Validator {
             }
             Validator::Ref(ref_name) => {
                 // Fail if cyclic validation is possible, by banning Ref->Ref.
-                // Ref->Multi->... checks are in the Multi validator code further down.
+                // Ref->Multi->... and Ref->All->... checks are in the Multi and All validator
+                // code.
                 // All other validators pull at least one element, ensuring infinite
                 // recursion/cycling is impossible.
                 let validator = types.get(ref_name).ok_or_else(|| {
-                    Error::FailValidate(format!("validator Ref({}) not in list of types", ref_name))
+                    Error::validate(
+                        ValidateCode::BadValidator,
+                        format!("validator Ref({}) not in list of types", ref_name),
+                    )
                 })?;
                 match validator {
-                    Validator::Ref(_) => Err(Error::FailValidate(format!(
-                        "validator Ref({}) is itself a Ref",
-                        ref_name
-                    ))),
+                    Validator::Ref(_) => Err(Error::validate(
+                        ValidateCode::BadValidator,
+                        format!("validator Ref({}) is itself a Ref", ref_name),
+                    )),
                     _ => validator.validate(types, parser, checklist),
                 }
             }
             Validator::Multi(validator) => validator.validate(types, parser, checklist),
+            Validator::All(validator) => validator.validate(types, parser, checklist),
+            Validator::Not(validator) => validator.validate(types, parser, checklist),
             Validator::Enum(validator) => validator.validate(types, parser, checklist),
+            Validator::Const(validator) => {
+                validator.validate(&mut parser)?;
+                Ok((parser, checklist))
+            }
             Validator::Any => {
                 read_any(&mut parser)?;
                 Ok((parser, checklist))
             }
+            Validator::AnyBounded(validator) => {
+                validator.validate(&mut parser)?;
+                Ok((parser, checklist))
+            }
+        }
+    }
+
+    /// Construct a value populated with the defaults declared in this validator.
+    ///
+    /// Scalar validators return their `default` value, if one is set. A `Const` validator returns
+    /// its constant. A `Map` validator returns a map containing every required and optional
+    /// field that has a default, or `None` if no field has one. An `Array` validator returns an
+    /// empty array. All other validators, including `Ref`, return `None`.
+    pub fn apply_default(&self) -> Option<Value> {
+        match self {
+            Validator::Bool(v) => v.default.map(Value::Bool),
+            Validator::Int(v) => v.default.map(Value::Int),
+            Validator::F32(v) => v.default.map(Value::F32),
+            Validator::F64(v) => v.default.map(Value::F64),
+            Validator::Bin(v) => v.default.as_ref().map(|v| Value::Bin(v.to_vec())),
+            Validator::Str(v) => v.default.as_ref().map(|v| Value::Str(v.clone())),
+            Validator::Time(v) => v.default.map(Value::Timestamp),
+            Validator::Const(v) => Some(v.value.clone()),
+            Validator::Map(v) => {
+                let map: BTreeMap<String, Value> = v
+                    .req
+                    .iter()
+                    .chain(v.opt.iter())
+                    .filter_map(|(key, v)| v.apply_default().map(|v| (key.clone(), v)))
+                    .collect();
+                if map.is_empty() {
+                    None
+                } else {
+                    Some(Value::Map(map))
+                }
+            }
+            Validator::Array(_) => Some(Value::Array(Vec::new())),
+            _ => None,
+        }
+    }
+
+    /// Fill in missing map fields that have a default, recursing into nested maps that are
+    /// present. Only fields whose validator is a scalar validator with its `default` set are
+    /// filled. Arrays are left alone, so defaults for maps inside arrays are not applied.
+    pub(crate) fn fill_defaults(&self, types: &BTreeMap<String, Validator>, value: &mut Value) {
+        let (validator, map) = match (self, value) {
+            (Validator::Ref(name), value) => {
+                if let Some(validator) = types.get(name) {
+                    // A Ref pointing to another Ref would be rejected on validation anyway
+                    if !matches!(validator, Validator::Ref(_)) {
+                        validator.fill_defaults(types, value);
+                    }
+                }
+                return;
+            }
+            (Validator::Map(validator), Value::Map(map)) => (validator, map),
+            _ => return,
+        };
+        for (key, field) in validator.req.iter().chain(validator.opt.iter()) {
+            match map.get_mut(key) {
+                Some(value) => field.fill_defaults(types, value),
+                None => {
+                    let default = match field {
+                        Validator::Bool(_)
+                        | Validator::Int(_)
+                        | Validator::F32(_)
+                        | Validator::F64(_)
+                        | Validator::Bin(_)
+                        | Validator::Str(_)
+                        | Validator::Time(_) => field.apply_default(),
+                        _ => None,
+                    };
+                    if let Some(default) = default {
+                        map.insert(key.clone(), default);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Make a simpler validator that passes and fails exactly the same values as this one. This
+    /// helps shrink programmatically composed schemas. The rewrites are:
+    ///
+    /// - Multi branches that are always skipped (see [`MultiValidator`]) are removed.
+    /// - Repeated branches in a Multi or All validator are removed.
+    /// - A Multi validator with a single branch becomes that branch, as long as the branch isn't
+    ///   a Ref, Multi, or All validator.
+    /// - An All validator with a single branch becomes that branch, as long as the branch isn't a
+    ///   Ref or All validator, and the All validator isn't inside a Multi or All validator.
+    /// - Array `prefix` validators at the end of the list are dropped if they equal the `items`
+    ///   validator and `min_extra` is 0.
+    ///
+    /// If the validator is meant to be used as a named type, don't simplify it directly: a Multi
+    /// or All validator referring to it could otherwise change behavior. Use
+    /// [`SchemaBuilder::simplify`][crate::schema::SchemaBuilder::simplify] instead.
+    pub fn simplify(&self, types: &BTreeMap<String, Validator>) -> Validator {
+        let mut validator = self.clone();
+        validator.simplify_in(types, SimplifyContext::Other);
+        validator
+    }
+
+    /// Simplify a validator in place. Rewrites that change the top-level form of the validator
+    /// are only made where they can't be seen by a containing Multi or All validator.
+    pub(crate) fn simplify_in(
+        &mut self,
+        types: &BTreeMap<String, Validator>,
+        context: SimplifyContext,
+    ) {
+        use SimplifyContext::*;
+        match self {
+            Validator::Array(validator) => {
+                validator
+                    .contains
+                    .iter_mut()
+                    .chain(validator.prefix.iter_mut())
+                    .chain(std::iter::once(validator.items.as_mut()))
+                    .for_each(|v| v.simplify_in(types, Other));
+                if validator.min_extra == 0 {
+                    while validator.prefix.last() == Some(validator.items.as_ref()) {
+                        validator.prefix.pop();
+                    }
+                }
+            }
+            Validator::Map(validator) => validator
+                .values
+                .iter_mut()
+                .map(|v| v.as_mut())
+                .chain(validator.req.values_mut())
+                .chain(validator.opt.values_mut())
+                .chain(validator.pattern_fields.iter_mut().map(|(_, v)| v))
+                .for_each(|v| v.simplify_in(types, Other)),
+            Validator::Hash(validator) => {
+                if let Some(link) = &mut validator.link {
+                    link.simplify_in(types, Other);
+                }
+            }
+            Validator::Enum(validator) => validator
+                .validators_mut()
+                .for_each(|v| v.simplify_in(types, Other)),
+            Validator::Not(validator) => validator.0.simplify_in(types, Other),
+            Validator::Multi(validator) => {
+                validator.0.retain(|v| !multi_skips(types, v));
+                validator
+                    .0
+                    .iter_mut()
+                    .for_each(|v| v.simplify_in(types, InMulti));
+                dedup(&mut validator.0);
+                if matches!(context, Other | InAll)
+                    && validator.0.len() == 1
+                    && !matches!(
+                        validator.0[0],
+                        Validator::Ref(_) | Validator::Multi(_) | Validator::All(_)
+                    )
+                {
+                    *self = validator.0.pop().unwrap();
+                }
+            }
+            Validator::All(validator) => {
+                validator
+                    .0
+                    .iter_mut()
+                    .for_each(|v| v.simplify_in(types, InAll));
+                dedup(&mut validator.0);
+                if context == Other
+                    && validator.0.len() == 1
+                    && !matches!(validator.0[0], Validator::Ref(_) | Validator::All(_))
+                {
+                    *self = validator.0.pop().unwrap();
+                }
+            }
+            _ => (),
+        }
+    }
+
+    /// Build the lookup indexes for `in` and `nin` lists, recursing through all sub-validators.
+    /// This is run when a schema or query is loaded.
+    pub(crate) fn build_index(&mut self) {
+        match self {
+            Validator::Int(validator) => validator.build_index(),
+            Validator::Bin(validator) => validator.build_index(),
+            Validator::Str(validator) => validator.build_index(),
+            Validator::Array(validator) => {
+                validator.build_index();
+                validator
+                    .contains
+                    .iter_mut()
+                    .chain(validator.prefix.iter_mut())
+                    .chain(std::iter::once(validator.items.as_mut()))
+                    .for_each(|v| v.build_index())
+            }
+            Validator::Map(validator) => {
+                validator.build_index();
+                if let Some(values) = &mut validator.values {
+                    values.build_index();
+                }
+                validator
+                    .req
+                    .values_mut()
+                    .chain(validator.opt.values_mut())
+                    .chain(validator.pattern_fields.iter_mut().map(|(_, v)| v))
+                    .for_each(|v| v.build_index())
+            }
+            Validator::Hash(validator) => {
+                if let Some(link) = &mut validator.link {
+                    link.build_index();
+                }
+            }
+            Validator::Multi(validator) => validator.0.iter_mut().for_each(|v| v.build_index()),
+            Validator::All(validator) => validator.0.iter_mut().for_each(|v| v.build_index()),
+            Validator::Not(validator) => validator.0.build_index(),
+            Validator::Enum(validator) => validator.validators_mut().for_each(|v| v.build_index()),
+            Validator::Const(validator) => validator.build_index(),
+            _ => (),
+        }
+    }
+
+    /// Check that the validator's own configuration is consistent, recursing through all
+    /// sub-validators. This is run when a schema is built or loaded, so that misconfigured
+    /// validators are caught up front instead of failing on every validation.
+    pub(crate) fn check_build(&self) -> Result<()> {
+        match self {
+            Validator::Array(validator) => {
+                validator.check_build()?;
+                validator
+                    .contains
+                    .iter()
+                    .chain(validator.prefix.iter())
+                    .chain(std::iter::once(validator.items.as_ref()))
+                    .try_for_each(|v| v.check_build())
+            }
+            Validator::Map(validator) => {
+                validator.check_build()?;
+                if let Some(values) = &validator.values {
+                    values.check_build()?;
+                }
+                validator
+                    .req
+                    .values()
+                    .chain(validator.opt.values())
+                    .chain(validator.pattern_fields.iter().map(|(_, v)| v))
+                    .try_for_each(|v| v.check_build())
+            }
+            Validator::Int(validator) => validator.check_build(),
+            Validator::Str(validator) => validator.check_build(),
+            Validator::Hash(validator) => match &validator.link {
+                Some(link) => link.check_build(),
+                None => Ok(()),
+            },
+            Validator::Multi(validator) => validator.iter().try_for_each(|v| v.check_build()),
+            Validator::All(validator) => validator.iter().try_for_each(|v| v.check_build()),
+            Validator::Not(validator) => validator.0.check_build(),
+            Validator::Enum(validator) => {
+                validator.check_build()?;
+                validator.validators().try_for_each(|v| v.check_build())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Call `f` with the name of every Ref validator, recursing through all sub-validators.
+    pub(crate) fn for_each_ref<'a>(&'a self, f: &mut impl FnMut(&'a str)) {
+        match self {
+            Validator::Ref(name) => f(name),
+            Validator::Array(validator) => validator
+                .contains
+                .iter()
+                .chain(validator.prefix.iter())
+                .chain(std::iter::once(validator.items.as_ref()))
+                .for_each(|v| v.for_each_ref(f)),
+            Validator::Map(validator) => validator
+                .values
+                .iter()
+                .map(|v| v.as_ref())
+                .chain(validator.req.values())
+                .chain(validator.opt.values())
+                .chain(validator.pattern_fields.iter().map(|(_, v)| v))
+                .for_each(|v| v.for_each_ref(f)),
+            Validator::Hash(validator) => {
+                if let Some(link) = &validator.link {
+                    link.for_each_ref(f);
+                }
+            }
+            Validator::Multi(validator) => validator.iter().for_each(|v| v.for_each_ref(f)),
+            Validator::All(validator) => validator.iter().for_each(|v| v.for_each_ref(f)),
+            Validator::Not(validator) => validator.0.for_each_ref(f),
+            Validator::Enum(validator) => validator.validators().for_each(|v| v.for_each_ref(f)),
+            _ => (),
+        }
+    }
+
+    /// Measure how large this validator is, recursing through all sub-validators. Ref validators
+    /// are counted but not followed.
+    pub(crate) fn complexity(&self) -> Complexity {
+        let mut complexity = Complexity::default();
+        self.measure(1, &mut complexity);
+        complexity
+    }
+
+    fn measure(&self, depth: usize, complexity: &mut Complexity) {
+        complexity.validators += 1;
+        complexity.depth = complexity.depth.max(depth);
+        complexity.list_entries += match self {
+            Validator::Bool(v) => v.in_list.len() + v.nin_list.len(),
+            Validator::Int(v) => v.in_list.len() + v.nin_list.len(),
+            Validator::F32(v) => v.in_list.len() + v.nin_list.len(),
+            Validator::F64(v) => v.in_list.len() + v.nin_list.len(),
+            Validator::Bin(v) => v.in_list.len() + v.nin_list.len(),
+            Validator::Str(v) => v.in_list.len() + v.nin_list.len(),
+            Validator::Array(v) => v.in_list.len() + v.nin_list.len(),
+            Validator::Map(v) => v.in_list.len() + v.nin_list.len(),
+            Validator::Time(v) => v.in_list.len() + v.nin_list.len(),
+            Validator::Hash(v) => v.in_list.len() + v.nin_list.len(),
+            Validator::Identity(v) => v.in_list.len() + v.nin_list.len(),
+            Validator::StreamId(v) => v.in_list.len() + v.nin_list.len(),
+            Validator::LockId(v) => v.in_list.len() + v.nin_list.len(),
+            _ => 0,
+        };
+        let mut sub = |v: &Validator| v.measure(depth + 1, complexity);
+        match self {
+            Validator::Array(validator) => validator
+                .contains
+                .iter()
+                .chain(validator.prefix.iter())
+                .chain(std::iter::once(validator.items.as_ref()))
+                .for_each(sub),
+            Validator::Map(validator) => validator
+                .values
+                .iter()
+                .map(|v| v.as_ref())
+                .chain(validator.req.values())
+                .chain(validator.opt.values())
+                .chain(validator.pattern_fields.iter().map(|(_, v)| v))
+                .for_each(sub),
+            Validator::Hash(validator) => validator.link.iter().map(|v| v.as_ref()).for_each(sub),
+            Validator::Multi(validator) => validator.iter().for_each(sub),
+            Validator::All(validator) => validator.iter().for_each(sub),
+            Validator::Not(validator) => sub(&validator.0),
+            Validator::Enum(validator) => validator.validators().for_each(sub),
+            _ => (),
         }
     }
 
@@ -244,6 +621,19 @@ impl Validator {
         types: &BTreeMap<String, Validator>,
         other: &Validator,
     ) -> bool {
+        // An All query is allowed if each of its validators would be allowed on its own
+        if let Validator::All(list) = other {
+            return list.iter().all(|other| self.query_check(types, other));
+        }
+        // A Not query is allowed if its validator would be allowed on its own, as long as it has
+        // no link or schema requirements to invert
+        if let Validator::Not(not) = other {
+            return !not.has_links() && self.query_check(types, not.validator());
+        }
+        // A bounded Any query is treated like an Any query
+        if let Validator::AnyBounded(_) = other {
+            return self.query_check(types, &Validator::Any);
+        }
         match self {
             Validator::Null => matches!(other, Validator::Null | Validator::Any),
             Validator::Bool(validator) => validator.query_check(other),
@@ -274,48 +664,587 @@ impl Validator {
                 }
             },
             Validator::Multi(validator) => validator.query_check(types, other),
+            Validator::All(validator) => validator.query_check(types, other),
+            Validator::Not(validator) => validator.query_check(other),
             Validator::Enum(validator) => validator.query_check(types, other),
-            Validator::Any => false,
+            Validator::Const(validator) => validator.query_check(other),
+            Validator::Any | Validator::AnyBounded(_) => false,
         }
     }
 }
 
-fn read_any(parser: &mut Parser) -> Result<()> {
+/// Where a validator being simplified is used. See [`Validator::simplify`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum SimplifyContext {
+    /// Directly inside a Multi validator.
+    InMulti,
+    /// Directly inside an All validator.
+    InAll,
+    /// A named type, which Multi and All validators can refer to.
+    Named,
+    /// Anywhere else.
+    Other,
+}
+
+/// How large a validator is, as measured by [`Validator::complexity`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(crate) struct Complexity {
+    /// The number of validators, including every sub-validator.
+    pub validators: usize,
+    /// The total number of values in every `in` and `nin` list.
+    pub list_entries: usize,
+    /// How deeply the validators nest. A validator without sub-validators has a depth of 1.
+    pub depth: usize,
+}
+
+/// Check if a Multi validator would always skip this branch.
+fn multi_skips(types: &BTreeMap<String, Validator>, validator: &Validator) -> bool {
+    match validator {
+        Validator::Multi(_) | Validator::All(_) => true,
+        Validator::Ref(name) => matches!(
+            types.get(name),
+            None | Some(Validator::Ref(_)) | Some(Validator::Multi(_)) | Some(Validator::All(_))
+        ),
+        _ => false,
+    }
+}
+
+/// Remove repeated validators, keeping the first of each.
+fn dedup(list: &mut Vec<Validator>) {
+    let mut index = 0;
+    while index < list.len() {
+        if list[..index].contains(&list[index]) {
+            list.remove(index);
+        } else {
+            index += 1;
+        }
+    }
+}
+
+/// Check that no named type can reach itself without first reading a value. Ref, Multi, All, and
+/// Not validators don't read anything on their own, so a cycle made only of these would recurse
+/// forever. Cycles through any other validator, like a tree type that refers to itself inside an
+/// array, are allowed: each step reads a value, so recursion is bounded by the maximum nesting
+/// depth of the data.
+pub(crate) fn check_type_cycles(types: &BTreeMap<String, Validator>) -> Result<()> {
+    // Collect the named types a validator can reach without reading a value
+    fn direct_refs<'a>(validator: &'a Validator, refs: &mut Vec<&'a str>) {
+        match validator {
+            Validator::Ref(name) => refs.push(name),
+            Validator::Multi(validator) => validator.iter().for_each(|v| direct_refs(v, refs)),
+            Validator::All(validator) => validator.iter().for_each(|v| direct_refs(v, refs)),
+            Validator::Not(validator) => direct_refs(&validator.0, refs),
+            _ => (),
+        }
+    }
+
+    fn visit<'a>(
+        types: &'a BTreeMap<String, Validator>,
+        name: &'a str,
+        path: &mut Vec<&'a str>,
+        done: &mut std::collections::HashSet<&'a str>,
+    ) -> Result<()> {
+        if done.contains(name) {
+            return Ok(());
+        }
+        if let Some(start) = path.iter().position(|n| *n == name) {
+            let mut cycle = path[start..].to_vec();
+            cycle.push(name);
+            return Err(Error::validate(
+                ValidateCode::BadValidator,
+                format!(
+                    "named types form a cycle that never reads a value: {}",
+                    cycle.join(" -> ")
+                ),
+            ));
+        }
+        if let Some(validator) = types.get(name) {
+            let mut refs = Vec::new();
+            direct_refs(validator, &mut refs);
+            path.push(name);
+            for next in refs {
+                visit(types, next, path, done)?;
+            }
+            path.pop();
+        }
+        done.insert(name);
+        Ok(())
+    }
+
+    let mut done = std::collections::HashSet::new();
+    types
+        .keys()
+        .try_for_each(|name| visit(types, name, &mut Vec::new(), &mut done))
+}
+
+/// Add the location of a nested value to a validation error, so failures deep inside arrays and
+/// maps report a full path like `[42].tags[3]: Expected Str, got Int`.
+pub(crate) fn nest_error(mut err: Error, segment: PathSegment) -> Error {
+    if let Error::FailValidate(ref mut e) = err {
+        e.nest(segment);
+    }
+    err
+}
+
+fn encode_const(value: &Value) -> Result<Vec<u8>> {
+    let mut ser = FogSerializer::default();
+    value.serialize(&mut ser)?;
+    Ok(ser.finish())
+}
+
+pub(crate) fn read_any(parser: &mut Parser) -> Result<()> {
+    read_any_bounded(parser, usize::MAX, u32::MAX)
+}
+
+/// Read any single value, failing if it encodes to more than `max_bytes`, or if it has arrays or
+/// maps nested more than `max_depth` deep.
+pub(crate) fn read_any_bounded(
+    parser: &mut Parser,
+    max_bytes: usize,
+    max_depth: u32,
+) -> Result<()> {
     fn get_elem<'a>(parser: &mut Parser<'a>) -> Result<Element<'a>> {
         parser
             .next()
-            .ok_or_else(|| Error::FailValidate("expected another value".to_string()))?
-    }
-    let elem = get_elem(parser)?;
-    match elem {
-        Element::Map(len) => {
-            let mut last_key = None;
-            for _ in 0..len {
-                if let Element::Str(key) = get_elem(parser)? {
-                    if let Some(last_key) = last_key {
-                        if key <= last_key {
-                            return Err(Error::FailValidate(format!(
-                                "map keys are unordered: {} follows {}",
-                                key, last_key
-                            )));
+            .ok_or_else(|| Error::validate(ValidateCode::WrongType, "expected another value"))?
+    }
+    fn read<'a>(
+        parser: &mut Parser<'a>,
+        min_left: usize,
+        max_bytes: usize,
+        depth: u32,
+    ) -> Result<()> {
+        let elem = get_elem(parser)?;
+        if parser.remaining().len() < min_left {
+            return Err(Error::validate(
+                ValidateCode::TooLong,
+                format!("value is longer than the maximum of {} bytes", max_bytes),
+            ));
+        }
+        let depth = match elem {
+            Element::Map(_) | Element::Array(_) => depth.checked_sub(1).ok_or_else(|| {
+                Error::validate(ValidateCode::TooDeep, "value is nested deeper than allowed")
+            })?,
+            _ => return Ok(()),
+        };
+        match elem {
+            Element::Map(len) => {
+                let mut last_key = None;
+                for _ in 0..len {
+                    if let Element::Str(key) = get_elem(parser)? {
+                        if let Some(last_key) = last_key {
+                            if key <= last_key {
+                                return Err(Error::validate(
+                                    ValidateCode::BadEncoding,
+                                    format!("map keys are unordered: {} follows {}", key, last_key),
+                                ));
+                            }
                         }
+                        last_key = Some(key);
+                    } else {
+                        return Err(Error::validate(
+                            ValidateCode::WrongType,
+                            "expected string for map key".to_string(),
+                        ));
                     }
-                    last_key = Some(key);
-                } else {
-                    return Err(Error::FailValidate(
-                        "expected string for map key".to_string(),
-                    ));
+                    read(parser, min_left, max_bytes, depth)?;
                 }
-                read_any(parser)?;
+                Ok(())
             }
-            Ok(())
-        }
-        Element::Array(len) => {
-            for _ in 0..len {
-                read_any(parser)?;
+            Element::Array(len) => {
+                for _ in 0..len {
+                    read(parser, min_left, max_bytes, depth)?;
+                }
+                Ok(())
             }
-            Ok(())
+            _ => Ok(()),
+        }
+    }
+    let min_left = parser.remaining().len().saturating_sub(max_bytes);
+    read(parser, min_left, max_bytes, max_depth)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::value::Value;
+
+    fn encode(value: impl Into<Value>) -> Vec<u8> {
+        encode_const(&value.into()).unwrap()
+    }
+
+    fn validate_const(validator: &Validator, data: &[u8]) -> Result<()> {
+        let types = BTreeMap::new();
+        let (parser, _) = validator.validate(&types, Parser::new(data), None)?;
+        parser.finish()
+    }
+
+    #[test]
+    fn const_scalar() {
+        let validator = Validator::new_const(42u8);
+        assert!(validate_const(&validator, &encode(42u8)).is_ok());
+        assert!(validate_const(&validator, &encode(43u8)).is_err());
+        assert!(validate_const(&validator, &encode(-42i8)).is_err());
+        assert!(validate_const(&validator, &encode("42")).is_err());
+        assert!(validate_const(&validator, &encode(())).is_err());
+    }
+
+    #[test]
+    fn const_array() {
+        let validator = Validator::new_const(vec![
+            Value::from(1u8),
+            Value::from("two"),
+            Value::from(vec![Value::from(true)]),
+        ]);
+        let pass = vec![
+            Value::from(1u8),
+            Value::from("two"),
+            Value::from(vec![Value::from(true)]),
+        ];
+        let nested_fail = vec![
+            Value::from(1u8),
+            Value::from("two"),
+            Value::from(vec![Value::from(false)]),
+        ];
+        let short = vec![Value::from(1u8), Value::from("two")];
+        assert!(validate_const(&validator, &encode(pass)).is_ok());
+        assert!(validate_const(&validator, &encode(nested_fail)).is_err());
+        assert!(validate_const(&validator, &encode(short)).is_err());
+    }
+
+    #[test]
+    fn const_map() {
+        let mut map = BTreeMap::new();
+        map.insert("a".to_string(), Value::from(1u8));
+        map.insert("b".to_string(), Value::from(vec![Value::from("c")]));
+        let validator = Validator::new_const(map.clone());
+        assert!(validate_const(&validator, &encode(map.clone())).is_ok());
+        let mut extra = map.clone();
+        extra.insert("d".to_string(), Value::Null);
+        assert!(validate_const(&validator, &encode(extra)).is_err());
+        let mut changed = map;
+        changed.insert("b".to_string(), Value::from(vec![Value::from("d")]));
+        assert!(validate_const(&validator, &encode(changed)).is_err());
+    }
+
+    #[test]
+    fn const_nan() {
+        let validator = Validator::new_const(f64::NAN);
+        assert!(validate_const(&validator, &encode(f64::NAN)).is_ok());
+        assert!(validate_const(&validator, &encode(-f64::NAN)).is_err());
+        assert!(validate_const(&validator, &encode(0.0f64)).is_err());
+        let validator = Validator::new_const(0.0f64);
+        assert!(validate_const(&validator, &encode(-0.0f64)).is_err());
+        assert!(validate_const(&validator, &encode(0.0f32)).is_err());
+    }
+
+    #[test]
+    fn const_query_check() {
+        let types = BTreeMap::new();
+        let schema = Validator::new_const(f32::NAN);
+        assert!(schema.query_check(&types, &Validator::new_const(f32::NAN)));
+        assert!(schema.query_check(&types, &Validator::Any));
+        assert!(!schema.query_check(&types, &Validator::new_const(1.0f32)));
+        assert!(!schema.query_check(&types, &Validator::F32(F32Validator::new())));
+        let schema = Validator::F32(F32Validator::new());
+        assert!(!schema.query_check(&types, &Validator::new_const(1.0f32)));
+    }
+
+    #[test]
+    fn const_ser() {
+        let validator = Validator::new_const(vec![Value::from(1u8), Value::from("a")]);
+        let mut ser = FogSerializer::default();
+        validator.serialize(&mut ser).unwrap();
+        let data = ser.finish();
+        let mut de = crate::de::FogDeserializer::new(&data);
+        let decoded = Validator::deserialize(&mut de).unwrap();
+        assert_eq!(validator, decoded);
+        // The constant is stored directly, without any wrapping struct
+        let mut de = crate::de::FogDeserializer::new(&data);
+        let raw = Value::deserialize(&mut de).unwrap();
+        let mut expected = BTreeMap::new();
+        expected.insert(
+            "Const".to_string(),
+            Value::from(vec![Value::from(1u8), Value::from("a")]),
+        );
+        assert_eq!(raw, Value::Map(expected));
+    }
+
+    #[test]
+    fn const_indexed() {
+        let mut validator = Validator::new_const(vec![Value::from(1u8), Value::from(0.5f32)]);
+        let unindexed = validator.clone();
+        validator.build_index();
+        match &validator {
+            Validator::Const(v) => assert!(v.encoded.is_some()),
+            _ => unreachable!(),
+        }
+        assert_eq!(validator, unindexed);
+        let pass = encode(vec![Value::from(1u8), Value::from(0.5f32)]);
+        let fail = encode(vec![Value::from(1u8), Value::from(1.0f32)]);
+        for v in [&validator, &unindexed].iter() {
+            assert!(validate_const(v, &pass).is_ok());
+            assert!(validate_const(v, &fail).is_err());
+        }
+        let types = BTreeMap::new();
+        assert!(validator.query_check(&types, &unindexed));
+        assert!(unindexed.query_check(&types, &validator));
+    }
+
+    #[test]
+    fn apply_default() {
+        assert_eq!(Validator::Null.apply_default(), None);
+        assert_eq!(IntValidator::new().build().apply_default(), None);
+        assert_eq!(
+            IntValidator::new()
+                .default_value(3u8)
+                .build()
+                .apply_default(),
+            Some(Value::from(3u8))
+        );
+        assert_eq!(
+            ArrayValidator::new().build().apply_default(),
+            Some(Value::Array(Vec::new()))
+        );
+        let validator = MapValidator::new()
+            .req_add("name", StrValidator::new().default_value("anon").build())
+            .req_add("id", IntValidator::new().build())
+            .opt_add("admin", BoolValidator::new().default_value(false).build())
+            .opt_add(
+                "settings",
+                MapValidator::new()
+                    .opt_add("theme", StrValidator::new().default_value("dark").build())
+                    .build(),
+            )
+            .build();
+        let mut settings = BTreeMap::new();
+        settings.insert("theme".to_string(), Value::from("dark"));
+        let mut expected = BTreeMap::new();
+        expected.insert("name".to_string(), Value::from("anon"));
+        expected.insert("admin".to_string(), Value::from(false));
+        expected.insert("settings".to_string(), Value::from(settings));
+        assert_eq!(validator.apply_default(), Some(Value::from(expected)));
+
+        // Maps without any defaults, including in nested maps, have no default
+        let validator = MapValidator::new()
+            .req_add("id", IntValidator::new().build())
+            .opt_add("settings", MapValidator::new().build())
+            .build();
+        assert_eq!(validator.apply_default(), None);
+    }
+
+    #[test]
+    fn default_ser() {
+        let validator = StrValidator::new().default_value("a");
+        let mut ser = FogSerializer::default();
+        validator.serialize(&mut ser).unwrap();
+        let actual = ser.finish();
+        let mut expected = vec![0x81];
+        serialize_elem(&mut expected, Element::Str("default"));
+        serialize_elem(&mut expected, Element::Str("a"));
+        assert_eq!(expected, actual);
+        let mut de = crate::de::FogDeserializer::new(&actual);
+        assert_eq!(validator, StrValidator::deserialize(&mut de).unwrap());
+    }
+
+    fn simplify_corpus() -> Vec<Value> {
+        let mut map = BTreeMap::new();
+        map.insert("a".to_string(), Value::from(1u8));
+        let mut nested = BTreeMap::new();
+        nested.insert("a".to_string(), Value::from(vec![Value::from("x")]));
+        vec![
+            Value::Null,
+            Value::from(true),
+            Value::from(0u8),
+            Value::from(5u8),
+            Value::from(-100i8),
+            Value::from(""),
+            Value::from("hello"),
+            Value::from(1.5f64),
+            Value::from(Vec::<Value>::new()),
+            Value::from(vec![Value::from(1u8), Value::from(2u8)]),
+            Value::from(vec![Value::from(1u8), Value::from("b"), Value::Null]),
+            Value::from(map),
+            Value::from(nested),
+        ]
+    }
+
+    fn check_simplify(types: &BTreeMap<String, Validator>, validator: &Validator) -> Validator {
+        let simple = validator.simplify(types);
+        for value in simplify_corpus() {
+            let data = encode(value.clone());
+            let run = |v: &Validator| {
+                v.validate(types, Parser::new(&data), None)
+                    .and_then(|(parser, _)| parser.finish())
+                    .is_ok()
+            };
+            assert_eq!(
+                run(validator),
+                run(&simple),
+                "{:?} and {:?} disagree on {:?}",
+                validator,
+                simple,
+                value
+            );
+        }
+        let queries = [
+            Validator::Any,
+            Validator::Null,
+            IntValidator::new().build(),
+            IntValidator::new().min(0u8).build(),
+            StrValidator::new().build(),
+            ArrayValidator::new().build(),
+            ArrayValidator::new()
+                .items(IntValidator::new().build())
+                .build(),
+            MapValidator::new().build(),
+            AllValidator::new().build(),
+        ];
+        for query in queries.iter() {
+            assert_eq!(
+                validator.query_check(types, query),
+                simple.query_check(types, query),
+                "{:?} and {:?} disagree on query {:?}",
+                validator,
+                simple,
+                query
+            );
+        }
+        simple
+    }
+
+    #[test]
+    fn simplify_preserves_validation() {
+        let mut types = BTreeMap::new();
+        types.insert("int".to_string(), IntValidator::new().build());
+        types.insert("multi".to_string(), MultiValidator::new().build());
+        types.insert("ref".to_string(), Validator::new_ref("int"));
+        let int = IntValidator::new().min(0u8).build();
+        let string = StrValidator::new().max_len(3).build();
+
+        // Single-branch Multi collapses
+        let v = MultiValidator::new().push(int.clone()).build();
+        assert_eq!(check_simplify(&types, &v), int);
+
+        // Skipped and repeated Multi branches are dropped
+        let v = MultiValidator::new()
+            .push(MultiValidator::new().push(string.clone()).build())
+            .push(Validator::new_ref("multi"))
+            .push(Validator::new_ref("ref"))
+            .push(Validator::new_ref("missing"))
+            .push(int.clone())
+            .push(string.clone())
+            .push(int.clone())
+            .build();
+        let expected = MultiValidator::new()
+            .push(int.clone())
+            .push(string.clone())
+            .build();
+        assert_eq!(check_simplify(&types, &v), expected);
+
+        // Any inside All, and an empty All, would change what queries are allowed
+        let v = AllValidator::new()
+            .push(Validator::Any)
+            .push(int.clone())
+            .build();
+        assert_eq!(check_simplify(&types, &v), v);
+        let v = AllValidator::new().build();
+        assert_eq!(check_simplify(&types, &v), v);
+
+        // Single-branch All collapses
+        let v = AllValidator::new().push(int.clone()).build();
+        assert_eq!(check_simplify(&types, &v), int);
+
+        // Nothing that a containing Multi/All would skip or reject gets collapsed
+        for inner in [
+            AllValidator::new().push(int.clone()).build(),
+            AllValidator::new().build(),
+        ]
+        .iter()
+        {
+            let v = MultiValidator::new()
+                .push(inner.clone())
+                .push(string.clone())
+                .build();
+            check_simplify(&types, &v);
+            let v = AllValidator::new()
+                .push(inner.clone())
+                .push(Validator::Any)
+                .build();
+            check_simplify(&types, &v);
+        }
+        let v = AllValidator::new()
+            .push(MultiValidator::new().push(int.clone()).build())
+            .push(Validator::new_ref("int"))
+            .build();
+        check_simplify(&types, &v);
+
+        // Array prefixes matching the items validator
+        let v = ArrayValidator::new()
+            .prefix(vec![string.clone(), int.clone(), int.clone()])
+            .items(int.clone())
+            .build();
+        let expected = ArrayValidator::new()
+            .prefix(vec![string.clone()])
+            .items(int.clone())
+            .build();
+        assert_eq!(check_simplify(&types, &v), expected);
+        let v = ArrayValidator::new()
+            .prefix(vec![int.clone()])
+            .items(int.clone())
+            .min_extra(1)
+            .build();
+        assert_eq!(check_simplify(&types, &v), v);
+
+        // Rewrites reach into nested validators
+        let v = MapValidator::new()
+            .opt_add("a", MultiValidator::new().push(int.clone()).build())
+            .values(
+                ArrayValidator::new()
+                    .items(MultiValidator::new().push(string.clone()).build())
+                    .build(),
+            )
+            .build();
+        let expected = MapValidator::new()
+            .opt_add("a", int.clone())
+            .values(ArrayValidator::new().items(string.clone()).build())
+            .build();
+        assert_eq!(check_simplify(&types, &v), expected);
+    }
+
+    #[test]
+    fn simplify_schema() {
+        use crate::document::NewDocument;
+        use crate::schema::{Schema, SchemaBuilder};
+        let int = IntValidator::new().build();
+        // "single" must stay a Multi, or the doc's Multi would stop skipping it
+        let doc = MultiValidator::new()
+            .push(Validator::new_ref("single"))
+            .push(StrValidator::new().build())
+            .build();
+        let builder = SchemaBuilder::new(doc)
+            .type_add("single", MultiValidator::new().push(int.clone()).build())
+            .type_add("unused", MultiValidator::new().push(int.clone()).build())
+            .entry_add(
+                "e",
+                AllValidator::new()
+                    .push(MultiValidator::new().push(int).build())
+                    .build(),
+                None,
+            );
+        let plain = builder.clone().build().unwrap();
+        let simple = builder.simplify(true).build().unwrap();
+        assert!(simple.data().len() < plain.data().len());
+        let plain = Schema::from_doc(&plain).unwrap();
+        let simple = Schema::from_doc(&simple).unwrap();
+        for value in simplify_corpus() {
+            let doc = |schema: &Schema| NewDocument::new(&value, Some(schema.hash())).unwrap();
+            assert_eq!(
+                plain.validate_new_doc(doc(&plain)).is_ok(),
+                simple.validate_new_doc(doc(&simple)).is_ok(),
+                "schemas disagree on {:?}",
+                value
+            );
         }
-        _ => Ok(()),
     }
 }
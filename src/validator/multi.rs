@@ -1,5 +1,5 @@
 use super::*;
-use crate::error::{Error, Result};
+use crate::error::{Error, Result, ValidateCode};
 use serde::{Deserialize, Serialize};
 use std::default::Default;
 
@@ -14,8 +14,10 @@ use std::default::Default;
 /// - Contained Multi-validators are skipped
 /// - Contained Ref validators that refer to a Multi-validator are skipped.
 /// - Contained Ref validators that refer to a Ref validator are skipped.
+/// - Contained All-validators, and Ref validators that refer to one, are skipped.
 ///
-/// More succintly, the banned sequences are: Multi->Multi, Multi->Ref->Multi, Multi->Ref->Ref.
+/// More succintly, the banned sequences are: Multi->Multi, Multi->Ref->Multi, Multi->Ref->Ref,
+/// Multi->All, and Multi->Ref->All.
 ///
 /// # Query Checking
 ///
@@ -60,7 +62,8 @@ impl MultiValidator {
         checklist: Option<Checklist<'c>>,
     ) -> Result<(Parser<'de>, Option<Checklist<'c>>)> {
         // Iterate through Multi list, but skip any validators that could potentially be
-        // cyclic. Banned: Multi->Multi, Multi->Ref->Multi, Multi->Ref->Ref.
+        // cyclic. Banned: Multi->Multi, Multi->Ref->Multi, Multi->Ref->Ref, Multi->All,
+        // Multi->Ref->All.
         for validator in self.0.iter() {
             let new_parser = parser.clone();
             let new_checklist = checklist.clone();
@@ -70,10 +73,11 @@ impl MultiValidator {
                     Some(validator) => match validator {
                         Validator::Ref(_) => continue,
                         Validator::Multi(_) => continue,
+                        Validator::All(_) => continue,
                         _ => validator.validate(types, new_parser, new_checklist),
                     },
                 },
-                Validator::Multi(_) => {
+                Validator::Multi(_) | Validator::All(_) => {
                     continue;
                 }
                 _ => validator.validate(types, new_parser, new_checklist),
@@ -84,7 +88,8 @@ impl MultiValidator {
                 return new_result;
             }
         }
-        Err(Error::FailValidate(
+        Err(Error::validate(
+            ValidateCode::NoBranch,
             "validator Multi had no passing validators".to_string(),
         ))
     }
@@ -100,10 +105,11 @@ impl MultiValidator {
                 Some(validator) => match validator {
                     Validator::Ref(_) => false,
                     Validator::Multi(_) => false,
+                    Validator::All(_) => false,
                     _ => validator.query_check(types, other),
                 },
             },
-            Validator::Multi(_) => false,
+            Validator::Multi(_) | Validator::All(_) => false,
             _ => validator.query_check(types, other),
         })
     }
@@ -0,0 +1,191 @@
+use super::*;
+use crate::error::{Error, Result, ValidateCode};
+use serde::{Deserialize, Serialize};
+
+/// "Not" validator that inverts another validator.
+///
+/// This validator will accept any single value that its contained validator rejects. This can be
+/// used like a logical NOT of a validator, such as a string that isn't one of several reserved
+/// words, or an Entry that doesn't have a particular tag. The value must still be well-formed
+/// fog-pack.
+///
+/// The contained validator is run without collecting any Hash requirements, so the `link` and
+/// `schema` parts of a Hash validator inside a Not are never checked, and a hash passes them
+/// unconditionally. A contained validator that can't run at all, like a Ref to a missing type or
+/// a Time validator with a relative bound and no current time to check against, fails the Not
+/// validator as well. So does a value that isn't in canonical form. Only failures caused by the
+/// value not meeting the contained validator's requirements are inverted.
+///
+/// Like the Ref, Multi, and All validators, a Not validator doesn't read a value on its own, so
+/// it counts when checking named types for cycles.
+///
+/// # Query Checking
+///
+/// A Not validator in a schema only allows queries that use the Any validator, as it never grants
+/// permission to query the values it rejects.
+///
+/// When a query uses a Not validator, it's allowed exactly when the contained validator would be
+/// allowed on its own. The contained validator can't have any Hash validators with a `link` or
+/// `schema` requirement, as inverting those would require fetching the linked Documents.
+///
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct NotValidator(pub Box<Validator>);
+
+/// Check if a validation failure means the validator couldn't be run, or the value was
+/// malformed, rather than the value failing the validator's requirements.
+fn is_fatal(code: ValidateCode) -> bool {
+    matches!(
+        code,
+        ValidateCode::BadValidator | ValidateCode::NoTime | ValidateCode::BadEncoding
+    )
+}
+
+impl NotValidator {
+    /// Make a new validator that accepts values `validator` rejects.
+    pub fn new(validator: Validator) -> Self {
+        Self(Box::new(validator))
+    }
+
+    /// Build this into a [`Validator`] enum.
+    pub fn build(self) -> Validator {
+        Validator::Not(self)
+    }
+
+    /// Get the contained validator.
+    pub fn validator(&self) -> &Validator {
+        &self.0
+    }
+
+    pub(crate) fn validate<'de, 'c>(
+        &'c self,
+        types: &'c BTreeMap<String, Validator>,
+        mut parser: Parser<'de>,
+        checklist: Option<Checklist<'c>>,
+    ) -> Result<(Parser<'de>, Option<Checklist<'c>>)> {
+        match self.0.validate(types, parser.clone(), None) {
+            Ok(_) => Err(Error::validate(
+                ValidateCode::NoBranch,
+                "value passed the validator inside Not",
+            )),
+            Err(Error::FailValidate(err)) if !is_fatal(err.code) => {
+                read_any(&mut parser)?;
+                Ok((parser, checklist))
+            }
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Check if the contained validator has any Hash validators with a `link` or `schema`
+    /// requirement. Ref validators aren't followed.
+    pub(crate) fn has_links(&self) -> bool {
+        fn check(validator: &Validator) -> bool {
+            match validator {
+                Validator::Hash(v) => v.link.is_some() || !v.schema.is_empty(),
+                Validator::Array(v) => v
+                    .contains
+                    .iter()
+                    .chain(v.prefix.iter())
+                    .chain(std::iter::once(v.items.as_ref()))
+                    .any(check),
+                Validator::Map(v) => v
+                    .values
+                    .iter()
+                    .map(|v| v.as_ref())
+                    .chain(v.req.values())
+                    .chain(v.opt.values())
+                    .chain(v.pattern_fields.iter().map(|(_, v)| v))
+                    .any(check),
+                Validator::Multi(v) => v.iter().any(check),
+                Validator::All(v) => v.iter().any(check),
+                Validator::Enum(v) => v.validators().any(check),
+                Validator::Not(v) => check(&v.0),
+                _ => false,
+            }
+        }
+        check(&self.0)
+    }
+
+    pub(crate) fn query_check(&self, other: &Validator) -> bool {
+        matches!(other, Validator::Any)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{de::FogDeserializer, element::Parser, ser::FogSerializer, value::Value};
+
+    fn validate(validator: &Validator, value: impl Into<Value>) -> Result<()> {
+        let mut ser = FogSerializer::default();
+        value.into().serialize(&mut ser).unwrap();
+        let data = ser.finish();
+        let types = BTreeMap::new();
+        let (parser, _) = validator.validate(&types, Parser::new(&data), None)?;
+        parser.finish()
+    }
+
+    #[test]
+    fn inverts() {
+        let reserved = StrValidator::new().in_add("admin").in_add("root").build();
+        let validator = NotValidator::new(reserved).build();
+        assert!(validate(&validator, "alice").is_ok());
+        assert!(validate(&validator, 3u8).is_ok());
+        assert!(validate(&validator, "root").is_err());
+        assert!(validate(&validator, "admin").is_err());
+    }
+
+    #[test]
+    fn double_negation() {
+        let int = IntValidator::new().min(0).build();
+        let validator = NotValidator::new(NotValidator::new(int).build()).build();
+        assert!(validate(&validator, 0u8).is_ok());
+        assert!(validate(&validator, 7u8).is_ok());
+        assert!(validate(&validator, -1i8).is_err());
+        assert!(validate(&validator, "0").is_err());
+    }
+
+    #[test]
+    fn bad_inner_fails() {
+        let validator = NotValidator::new(Validator::new_ref("missing")).build();
+        assert!(validate(&validator, 1u8).is_err());
+    }
+
+    #[test]
+    fn environment_errors_pass_through() {
+        // The system clock is always available in tests, so only the classification is checked
+        assert!(is_fatal(ValidateCode::NoTime));
+        assert!(is_fatal(ValidateCode::BadValidator));
+        assert!(!is_fatal(ValidateCode::OutOfRange));
+        assert!(!is_fatal(ValidateCode::NoMatch));
+    }
+
+    #[test]
+    fn malformed_fails() {
+        use crate::element::{serialize_elem, Element};
+        let mut data = Vec::new();
+        serialize_elem(&mut data, Element::Map(2));
+        serialize_elem(&mut data, Element::Str("b"));
+        serialize_elem(&mut data, Element::Null);
+        serialize_elem(&mut data, Element::Str("a"));
+        serialize_elem(&mut data, Element::Null);
+        let validator = NotValidator::new(AnyValidator::new().max_depth(4).build()).build();
+        let types = BTreeMap::new();
+        let err = validator
+            .validate(&types, Parser::new(&data), None)
+            .unwrap_err();
+        assert_eq!(
+            err.validate_error().unwrap().code,
+            ValidateCode::BadEncoding
+        );
+    }
+
+    #[test]
+    fn ser_round_trip() {
+        let validator = NotValidator::new(BoolValidator::new().build()).build();
+        let mut ser = FogSerializer::default();
+        validator.serialize(&mut ser).unwrap();
+        let data = ser.finish();
+        let decoded = Validator::deserialize(&mut FogDeserializer::new(&data)).unwrap();
+        assert_eq!(decoded, validator);
+    }
+}
@@ -0,0 +1,256 @@
+//! Human-readable rendering of validators, loosely modeled on TypeScript type declarations.
+//!
+//! Map validators are written out as indented blocks with one field per line, while everything
+//! else is written inline. Comments are written on the line above the field or declaration they
+//! belong to.
+
+use super::*;
+
+/// Builds up the pretty-printed text of one or more validators.
+#[derive(Debug, Default)]
+pub(crate) struct PrettyPrinter {
+    out: String,
+    indent: usize,
+}
+
+/// Get the comment of a validator, if it has one.
+fn comment(validator: &Validator) -> &str {
+    match validator {
+        Validator::Bool(v) => &v.comment,
+        Validator::Int(v) => &v.comment,
+        Validator::F32(v) => &v.comment,
+        Validator::F64(v) => &v.comment,
+        Validator::Bin(v) => &v.comment,
+        Validator::Str(v) => &v.comment,
+        Validator::Array(v) => &v.comment,
+        Validator::Map(v) => &v.comment,
+        Validator::Time(v) => &v.comment,
+        Validator::Hash(v) => &v.comment,
+        Validator::Identity(v) => &v.comment,
+        Validator::StreamId(v) => &v.comment,
+        Validator::LockId(v) => &v.comment,
+        Validator::DataLockbox(v) => &v.comment,
+        Validator::IdentityLockbox(v) => &v.comment,
+        Validator::StreamLockbox(v) => &v.comment,
+        Validator::LockLockbox(v) => &v.comment,
+        Validator::AnyBounded(v) => &v.comment,
+        _ => "",
+    }
+}
+
+/// Get the default value of a scalar validator as text, if it has one.
+fn default_text(validator: &Validator) -> Option<String> {
+    match validator {
+        Validator::Bool(v) => v.default.map(|d| d.to_string()),
+        Validator::Int(v) => v.default.map(|d| d.to_string()),
+        Validator::F32(v) => v.default.map(|d| d.to_string()),
+        Validator::F64(v) => v.default.map(|d| d.to_string()),
+        Validator::Bin(v) => v.default.as_ref().map(|d| format!("{:?}", d.as_slice())),
+        Validator::Str(v) => v.default.as_ref().map(|d| format!("{:?}", d)),
+        Validator::Time(v) => v.default.map(|d| d.to_string()),
+        _ => None,
+    }
+}
+
+/// Get the text for a map key, quoting it unless it's a plain identifier.
+fn key_text(key: &str) -> String {
+    let plain = matches!(key.chars().next(), Some(c) if !c.is_ascii_digit())
+        && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if plain {
+        key.to_owned()
+    } else {
+        format!("{:?}", key)
+    }
+}
+
+impl PrettyPrinter {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    /// Get the text written so far.
+    pub(crate) fn finish(self) -> String {
+        self.out
+    }
+
+    /// Write a line of text at the current indentation.
+    pub(crate) fn line(&mut self, text: &str) {
+        self.start_line();
+        self.out.push_str(text);
+        self.out.push('\n');
+    }
+
+    /// Write each line of a comment. Writes nothing if the comment is empty.
+    pub(crate) fn comment(&mut self, comment: &str) {
+        for line in comment.lines() {
+            self.start_line();
+            self.out.push_str("// ");
+            self.out.push_str(line);
+            self.out.push('\n');
+        }
+    }
+
+    /// Write a declaration of `name`, followed by `separator` and the validator, along with the
+    /// validator's comment and default value.
+    pub(crate) fn item(&mut self, name: &str, separator: &str, validator: &Validator) {
+        self.comment(comment(validator));
+        self.start_line();
+        self.out.push_str(name);
+        self.out.push_str(separator);
+        self.validator(validator);
+        if let Some(default) = default_text(validator) {
+            self.out.push_str(" = ");
+            self.out.push_str(&default);
+        }
+        self.out.push_str(";\n");
+    }
+
+    fn start_line(&mut self) {
+        for _ in 0..self.indent {
+            self.out.push_str("  ");
+        }
+    }
+
+    /// Write a list of validators, separated by `separator`. Multi and All validators in the list
+    /// are wrapped in parentheses to keep them distinct.
+    fn list<'a>(&mut self, list: impl Iterator<Item = &'a Validator>, separator: &str) {
+        for (index, validator) in list.enumerate() {
+            if index > 0 {
+                self.out.push_str(separator);
+            }
+            if let Validator::Multi(_) | Validator::All(_) = validator {
+                self.out.push('(');
+                self.validator(validator);
+                self.out.push(')');
+            } else {
+                self.validator(validator);
+            }
+        }
+    }
+
+    /// Write a validator inline. Map validators with fields take up multiple lines, ending on the
+    /// line with their closing brace.
+    pub(crate) fn validator(&mut self, validator: &Validator) {
+        match validator {
+            Validator::Null => self.out.push_str("null"),
+            Validator::Any | Validator::AnyBounded(_) => self.out.push_str("any"),
+            Validator::Bool(_) => self.out.push_str("Bool"),
+            Validator::Int(_) => self.out.push_str("Int"),
+            Validator::F32(_) => self.out.push_str("F32"),
+            Validator::F64(_) => self.out.push_str("F64"),
+            Validator::Bin(_) => self.out.push_str("Bin"),
+            Validator::Str(_) => self.out.push_str("Str"),
+            Validator::Time(_) => self.out.push_str("Time"),
+            Validator::Identity(_) => self.out.push_str("Identity"),
+            Validator::StreamId(_) => self.out.push_str("StreamId"),
+            Validator::LockId(_) => self.out.push_str("LockId"),
+            Validator::DataLockbox(_) => self.out.push_str("DataLockbox"),
+            Validator::IdentityLockbox(_) => self.out.push_str("IdentityLockbox"),
+            Validator::StreamLockbox(_) => self.out.push_str("StreamLockbox"),
+            Validator::LockLockbox(_) => self.out.push_str("LockLockbox"),
+            Validator::Ref(name) => self.out.push_str(name),
+            Validator::Const(v) => self.out.push_str(&format!("const {:?}", v.value)),
+            Validator::Hash(v) => {
+                self.out.push_str("Hash");
+                if let Some(link) = &v.link {
+                    self.out.push('<');
+                    self.validator(link);
+                    self.out.push('>');
+                }
+            }
+            Validator::Multi(v) if v.0.is_empty() => self.out.push_str("never"),
+            Validator::Multi(v) => self.list(v.iter(), " | "),
+            Validator::All(v) if v.0.is_empty() => self.out.push_str("any"),
+            Validator::All(v) => self.list(v.iter(), " & "),
+            Validator::Not(v) => {
+                self.out.push_str("Not<");
+                self.validator(&v.0);
+                self.out.push('>');
+            }
+            Validator::Enum(v) => self.enum_validator(v),
+            Validator::Array(v) => self.array(v),
+            Validator::Map(v) => self.map(v),
+        }
+    }
+
+    fn enum_validator(&mut self, validator: &EnumValidator) {
+        let names = validator
+            .var
+            .iter()
+            .map(|(name, v)| (key_text(name), v.as_ref()));
+        let ints = validator
+            .int_var
+            .iter()
+            .map(|(tag, v)| (tag.to_string(), v.as_ref()));
+        let other = validator
+            .other
+            .iter()
+            .map(|v| ("..".to_owned(), v.as_deref()));
+        let variants = names.chain(ints).chain(other);
+        self.out.push_str("enum { ");
+        for (index, (name, v)) in variants.enumerate() {
+            if index > 0 {
+                self.out.push_str(", ");
+            }
+            self.out.push_str(&name);
+            if let Some(v) = v {
+                self.out.push('(');
+                self.validator(v);
+                self.out.push(')');
+            }
+        }
+        self.out.push_str(" }");
+    }
+
+    fn array(&mut self, validator: &ArrayValidator) {
+        if validator.prefix.is_empty() {
+            self.out.push_str("Array<");
+            self.validator(&validator.items);
+            self.out.push('>');
+            return;
+        }
+        self.out.push('[');
+        self.list(validator.prefix.iter(), ", ");
+        // Only show the remaining items if there can be any
+        if validator.max_len as usize > validator.prefix.len() {
+            self.out.push_str(", ...Array<");
+            self.validator(&validator.items);
+            self.out.push('>');
+        }
+        self.out.push(']');
+    }
+
+    fn map(&mut self, validator: &MapValidator) {
+        if validator.req.is_empty()
+            && validator.opt.is_empty()
+            && validator.pattern_fields.is_empty()
+        {
+            match &validator.values {
+                Some(values) => {
+                    self.out.push_str("Map<");
+                    self.validator(values);
+                    self.out.push('>');
+                }
+                None => self.out.push_str("{}"),
+            }
+            return;
+        }
+        self.out.push_str("{\n");
+        self.indent += 1;
+        for (key, v) in validator.req.iter() {
+            self.item(&key_text(key), ": ", v);
+        }
+        for (key, v) in validator.opt.iter() {
+            self.item(&format!("{}?", key_text(key)), ": ", v);
+        }
+        for (pattern, v) in validator.pattern_fields.iter() {
+            self.item(&format!("[key: /{}/]", pattern), ": ", v);
+        }
+        if let Some(values) = &validator.values {
+            self.item("[key: Str]", ": ", values);
+        }
+        self.indent -= 1;
+        self.start_line();
+        self.out.push('}');
+    }
+}
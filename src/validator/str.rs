@@ -1,6 +1,6 @@
 use super::*;
 use crate::element::*;
-use crate::error::{Error, Result};
+use crate::error::{Error, Result, ValidateCode};
 use regex::Regex;
 use serde::{Deserialize, Serialize};
 
@@ -24,6 +24,52 @@ fn normalize_is_none(v: &Normalize) -> bool {
     matches!(v, Normalize::None)
 }
 
+/// A text encoding that a string's contents can be required to follow. See
+/// [`StrValidator::encoding`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum StringEncoding {
+    /// Any UTF-8 string. As all strings are UTF-8, this always passes.
+    Utf8,
+    /// Only ASCII characters.
+    Ascii,
+    /// Standard Base64, using the `+` and `/` characters and padded with `=` to a multiple of 4
+    /// characters.
+    Base64,
+    /// Hexadecimal, with an even number of digits in either upper or lower case.
+    Hex,
+}
+
+impl StringEncoding {
+    /// Get the length in bytes of the string once decoded, or `None` if it isn't valid in this
+    /// encoding.
+    // `usize::is_multiple_of` isn't available on older compilers
+    #[allow(clippy::manual_is_multiple_of)]
+    pub(crate) fn decoded_len(&self, val: &str) -> Option<usize> {
+        let bytes = val.as_bytes();
+        match self {
+            StringEncoding::Utf8 => Some(bytes.len()),
+            StringEncoding::Ascii => val.is_ascii().then_some(bytes.len()),
+            StringEncoding::Base64 => {
+                if bytes.len() % 4 != 0 {
+                    return None;
+                }
+                let data = bytes
+                    .strip_suffix(b"==")
+                    .or_else(|| bytes.strip_suffix(b"="))
+                    .unwrap_or(bytes);
+                let valid = data
+                    .iter()
+                    .all(|b| b.is_ascii_alphanumeric() || *b == b'+' || *b == b'/');
+                valid.then_some(bytes.len() / 4 * 3 - (bytes.len() - data.len()))
+            }
+            StringEncoding::Hex => {
+                let valid = bytes.len() % 2 == 0 && bytes.iter().all(u8::is_ascii_hexdigit);
+                valid.then_some(bytes.len() / 2)
+            }
+        }
+    }
+}
+
 /// Validator for UTF-8 strings.
 ///
 /// This validator type will only pass string values. Validation passes if:
@@ -36,6 +82,9 @@ fn normalize_is_none(v: &Normalize) -> bool {
 ///     against the expression.
 /// - If the `in` list is not empty, the possibly-normalized value must be among the values in the list.
 /// - The possibly-normalized value must not be among the values in the `nin` list.
+/// - If `encoding` is set, the value must be valid in that [encoding][StringEncoding].
+/// - If `encoding` is `Base64` or `Hex`, the value's length in bytes once decoded must be between
+///   `decoded_min_len` and `decoded_max_len`. These can't be set for any other encoding.
 ///
 /// The `normalize` field may be set to `None`, `NFC`, or `NFKC`, corresponding to Unicode
 /// normalization forms. When checked for `in`, `nin`, and `matches`, the value is first put
@@ -48,6 +97,7 @@ fn normalize_is_none(v: &Normalize) -> bool {
 /// each field are:
 ///
 /// - comment: ""
+/// - default: None
 /// - in_list: empty
 /// - nin_list: empty
 /// - matches: None
@@ -56,9 +106,13 @@ fn normalize_is_none(v: &Normalize) -> bool {
 /// - max_char: u32::MAX
 /// - min_char: 0
 /// - normalize: Normalize::None
+/// - encoding: None
+/// - decoded_max_len: u32::MAX
+/// - decoded_min_len: 0
 /// - query: false
 /// - regex: false
 /// - size: false
+/// - encoding_ok: false
 ///
 /// # Regular Expressions
 ///
@@ -99,12 +153,20 @@ pub struct StrValidator {
     /// An optional comment explaining the validator.
     #[serde(skip_serializing_if = "String::is_empty")]
     pub comment: String,
+    /// An optional default value, used when filling in new values from a schema. It is not
+    /// checked during validation.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub default: Option<String>,
     /// A vector of specific allowed values, stored under the `in` field. If empty, this vector is not checked against.
     #[serde(rename = "in", skip_serializing_if = "Vec::is_empty")]
     pub in_list: Vec<String>,
     /// A vector of specific unallowed values, stored under the `nin` field.
     #[serde(rename = "nin", skip_serializing_if = "Vec::is_empty")]
     pub nin_list: Vec<String>,
+    /// Precomputed lookup index for the `in` and `nin` lists, after
+    /// normalization.
+    #[serde(skip)]
+    pub(crate) list_index: ListIndex<String>,
     /// A regular expression that the value must match against.
     #[serde(skip_serializing_if = "Option::is_none", with = "serde_regex")]
     pub matches: Option<Box<Regex>>,
@@ -123,6 +185,15 @@ pub struct StrValidator {
     /// The Unicode normalization setting.
     #[serde(skip_serializing_if = "normalize_is_none")]
     pub normalize: Normalize,
+    /// The encoding the string's contents must follow, if any.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub encoding: Option<StringEncoding>,
+    /// The maximum allowed number of bytes in the decoded value, for `Base64` and `Hex` encodings.
+    #[serde(skip_serializing_if = "u32_is_max")]
+    pub decoded_max_len: u32,
+    /// The minimum allowed number of bytes in the decoded value, for `Base64` and `Hex` encodings.
+    #[serde(skip_serializing_if = "u32_is_zero")]
+    pub decoded_min_len: u32,
     /// If true, queries against matching spots may have values in the `in` or `nin` lists.
     #[serde(skip_serializing_if = "is_false")]
     pub query: bool,
@@ -133,11 +204,16 @@ pub struct StrValidator {
     /// `min_char` values to non-defaults.
     #[serde(skip_serializing_if = "is_false")]
     pub size: bool,
+    /// If true, queries against matching spots may set the `encoding`, `decoded_max_len`, and
+    /// `decoded_min_len` values to non-defaults.
+    #[serde(skip_serializing_if = "is_false")]
+    pub encoding_ok: bool,
 }
 
 impl PartialEq for StrValidator {
     fn eq(&self, rhs: &Self) -> bool {
         (self.comment == rhs.comment)
+            && (self.default == rhs.default)
             && (self.in_list == rhs.in_list)
             && (self.nin_list == rhs.nin_list)
             && (self.max_len == rhs.max_len)
@@ -145,9 +221,13 @@ impl PartialEq for StrValidator {
             && (self.max_char == rhs.max_char)
             && (self.min_char == rhs.min_char)
             && (self.normalize == rhs.normalize)
+            && (self.encoding == rhs.encoding)
+            && (self.decoded_max_len == rhs.decoded_max_len)
+            && (self.decoded_min_len == rhs.decoded_min_len)
             && (self.query == rhs.query)
             && (self.regex == rhs.regex)
             && (self.size == rhs.size)
+            && (self.encoding_ok == rhs.encoding_ok)
             && match (&self.matches, &rhs.matches) {
                 (None, None) => true,
                 (Some(_), None) => false,
@@ -161,17 +241,23 @@ impl std::default::Default for StrValidator {
     fn default() -> Self {
         Self {
             comment: String::new(),
+            default: None,
             in_list: Vec::new(),
             nin_list: Vec::new(),
+            list_index: ListIndex::default(),
             matches: None,
             max_len: u32::MAX,
             min_len: u32::MIN,
             max_char: u32::MAX,
             min_char: u32::MIN,
             normalize: Normalize::None,
+            encoding: None,
+            decoded_max_len: u32::MAX,
+            decoded_min_len: u32::MIN,
             query: false,
             regex: false,
             size: false,
+            encoding_ok: false,
         }
     }
 }
@@ -188,6 +274,12 @@ impl StrValidator {
         self
     }
 
+    /// Set the default value.
+    pub fn default_value(mut self, default: impl Into<String>) -> Self {
+        self.default = Some(default.into());
+        self
+    }
+
     /// Set the maximum number of allowed bytes.
     pub fn max_len(mut self, max_len: u32) -> Self {
         self.max_len = max_len;
@@ -218,6 +310,24 @@ impl StrValidator {
         self
     }
 
+    /// Set the encoding the string's contents must follow.
+    pub fn encoding(mut self, encoding: StringEncoding) -> Self {
+        self.encoding = Some(encoding);
+        self
+    }
+
+    /// Set the maximum number of allowed bytes once decoded, for `Base64` and `Hex` encodings.
+    pub fn decoded_max_len(mut self, decoded_max_len: u32) -> Self {
+        self.decoded_max_len = decoded_max_len;
+        self
+    }
+
+    /// Set the minimum number of allowed bytes once decoded, for `Base64` and `Hex` encodings.
+    pub fn decoded_min_len(mut self, decoded_min_len: u32) -> Self {
+        self.decoded_min_len = decoded_min_len;
+        self
+    }
+
     /// Set the regular expression to check against.
     pub fn matches(mut self, matches: Regex) -> Self {
         self.matches = Some(Box::new(matches));
@@ -255,67 +365,154 @@ impl StrValidator {
         self
     }
 
+    /// Set whether or not queries can use the `encoding`, `decoded_max_len`, and
+    /// `decoded_min_len` values.
+    pub fn encoding_ok(mut self, encoding_ok: bool) -> Self {
+        self.encoding_ok = encoding_ok;
+        self
+    }
+
     /// Build this into a [`Validator`] enum.
     pub fn build(self) -> Validator {
         Validator::Str(self)
     }
 
+    pub(crate) fn build_index(&mut self) {
+        use unicode_normalization::UnicodeNormalization;
+        let normalize = |v: &String| -> String {
+            match self.normalize {
+                Normalize::None => v.clone(),
+                Normalize::NFC => v.nfc().collect(),
+                Normalize::NFKC => v.nfkc().collect(),
+            }
+        };
+        self.list_index = ListIndex::build(
+            self.in_list.iter().map(normalize),
+            self.nin_list.iter().map(normalize),
+        );
+    }
+
+    pub(crate) fn check_build(&self) -> Result<()> {
+        if self.decoded_min_len > self.decoded_max_len {
+            return Err(Error::validate(
+                ValidateCode::BadValidator,
+                format!(
+                    "Str validator decoded_min_len of {} is greater than decoded_max_len of {}",
+                    self.decoded_min_len, self.decoded_max_len
+                ),
+            ));
+        }
+        let decodes = matches!(
+            self.encoding,
+            Some(StringEncoding::Base64) | Some(StringEncoding::Hex)
+        );
+        if !decodes && (!u32_is_zero(&self.decoded_min_len) || !u32_is_max(&self.decoded_max_len)) {
+            return Err(Error::validate(
+                ValidateCode::BadValidator,
+                "Str validator decoded_min_len and decoded_max_len need a Base64 or Hex encoding",
+            ));
+        }
+        Ok(())
+    }
+
     pub(crate) fn validate(&self, parser: &mut Parser) -> Result<()> {
         // Get element
         let elem = parser
             .next()
-            .ok_or_else(|| Error::FailValidate("expected a string".to_string()))??;
+            .ok_or_else(|| Error::validate(ValidateCode::WrongType, "expected a string"))??;
         let val = if let Element::Str(v) = elem {
             v
         } else {
-            return Err(Error::FailValidate(format!(
-                "expected Str, got {}",
-                elem.name()
-            )));
+            return Err(Error::validate(
+                ValidateCode::WrongType,
+                format!("expected Str, got {}", elem.name()),
+            ));
         };
 
         // Length Checks
         if (val.len() as u32) > self.max_len {
-            return Err(Error::FailValidate(
+            return Err(Error::validate(
+                ValidateCode::TooLong,
                 "String is longer than max_len".to_string(),
             ));
         }
         if (val.len() as u32) < self.min_len {
-            return Err(Error::FailValidate(
+            return Err(Error::validate(
+                ValidateCode::TooShort,
                 "String is shorter than min_len".to_string(),
             ));
         }
         if self.max_char < u32::MAX || self.min_char > 0 {
             let len_char = bytecount::num_chars(val.as_bytes()) as u32;
             if len_char > self.max_char {
-                return Err(Error::FailValidate(
+                return Err(Error::validate(
+                    ValidateCode::TooLong,
                     "String is longer than max_len".to_string(),
                 ));
             }
             if len_char < self.min_char {
-                return Err(Error::FailValidate(
+                return Err(Error::validate(
+                    ValidateCode::TooShort,
                     "String is shorter than min_len".to_string(),
                 ));
             }
         }
 
+        // Encoding checks
+        if let Some(encoding) = self.encoding {
+            let len = encoding.decoded_len(val).ok_or_else(|| {
+                Error::validate(
+                    ValidateCode::NoMatch,
+                    format!("String is not valid {:?}", encoding),
+                )
+            })?;
+            if matches!(encoding, StringEncoding::Base64 | StringEncoding::Hex) {
+                if (len as u64) > (self.decoded_max_len as u64) {
+                    return Err(Error::validate(
+                        ValidateCode::TooLong,
+                        "Decoded string is longer than decoded_max_len",
+                    ));
+                }
+                if (len as u64) < (self.decoded_min_len as u64) {
+                    return Err(Error::validate(
+                        ValidateCode::TooShort,
+                        "Decoded string is shorter than decoded_min_len",
+                    ));
+                }
+            }
+        }
+
         // Content checks
         use unicode_normalization::{
             is_nfc_quick, is_nfkc_quick, IsNormalized, UnicodeNormalization,
         };
         match self.normalize {
             Normalize::None => {
-                if !self.in_list.is_empty() && !self.in_list.iter().any(|v| *v == val) {
-                    return Err(Error::FailValidate(
+                if !self.in_list.is_empty()
+                    && !self
+                        .list_index
+                        .in_contains(val)
+                        .unwrap_or_else(|| self.in_list.iter().any(|v| *v == val))
+                {
+                    return Err(Error::validate(
+                        ValidateCode::NotInList,
                         "String is not on `in` list".to_string(),
                     ));
                 }
-                if self.nin_list.iter().any(|v| *v == val) {
-                    return Err(Error::FailValidate("String is on `nin` list".to_string()));
+                if self
+                    .list_index
+                    .nin_contains(val)
+                    .unwrap_or_else(|| self.nin_list.iter().any(|v| *v == val))
+                {
+                    return Err(Error::validate(
+                        ValidateCode::InNinList,
+                        "String is on `nin` list",
+                    ));
                 }
                 if let Some(ref regex) = self.matches {
                     if !regex.is_match(val) {
-                        return Err(Error::FailValidate(
+                        return Err(Error::validate(
+                            ValidateCode::NoMatch,
                             "String doesn't match regular expression".to_string(),
                         ));
                     }
@@ -331,18 +528,31 @@ impl StrValidator {
                     }
                 };
 
-                if !self.in_list.is_empty() && !self.in_list.iter().any(|v| v.nfc().eq(val.chars()))
+                if !self.in_list.is_empty()
+                    && !self
+                        .list_index
+                        .in_contains(val)
+                        .unwrap_or_else(|| self.in_list.iter().any(|v| v.nfc().eq(val.chars())))
                 {
-                    return Err(Error::FailValidate(
+                    return Err(Error::validate(
+                        ValidateCode::NotInList,
                         "String is not on `in` list".to_string(),
                     ));
                 }
-                if self.nin_list.iter().any(|v| v.nfc().eq(val.chars())) {
-                    return Err(Error::FailValidate("String is on `nin` list".to_string()));
+                if self
+                    .list_index
+                    .nin_contains(val)
+                    .unwrap_or_else(|| self.nin_list.iter().any(|v| v.nfc().eq(val.chars())))
+                {
+                    return Err(Error::validate(
+                        ValidateCode::InNinList,
+                        "String is on `nin` list",
+                    ));
                 }
                 if let Some(ref regex) = self.matches {
                     if !regex.is_match(val) {
-                        return Err(Error::FailValidate(
+                        return Err(Error::validate(
+                            ValidateCode::NoMatch,
                             "String doesn't match regular expression".to_string(),
                         ));
                     }
@@ -359,18 +569,30 @@ impl StrValidator {
                 };
 
                 if !self.in_list.is_empty()
-                    && !self.in_list.iter().any(|v| v.nfkc().eq(val.chars()))
+                    && !self
+                        .list_index
+                        .in_contains(val)
+                        .unwrap_or_else(|| self.in_list.iter().any(|v| v.nfkc().eq(val.chars())))
                 {
-                    return Err(Error::FailValidate(
+                    return Err(Error::validate(
+                        ValidateCode::NotInList,
                         "String is not on `in` list".to_string(),
                     ));
                 }
-                if self.nin_list.iter().any(|v| v.nfkc().eq(val.chars())) {
-                    return Err(Error::FailValidate("String is on `nin` list".to_string()));
+                if self
+                    .list_index
+                    .nin_contains(val)
+                    .unwrap_or_else(|| self.nin_list.iter().any(|v| v.nfkc().eq(val.chars())))
+                {
+                    return Err(Error::validate(
+                        ValidateCode::InNinList,
+                        "String is on `nin` list",
+                    ));
                 }
                 if let Some(ref regex) = self.matches {
                     if !regex.is_match(val) {
-                        return Err(Error::FailValidate(
+                        return Err(Error::validate(
+                            ValidateCode::NoMatch,
                             "String doesn't match regular expression".to_string(),
                         ));
                     }
@@ -388,6 +610,10 @@ impl StrValidator {
                     && u32_is_zero(&other.min_len)
                     && u32_is_max(&other.max_char)
                     && u32_is_zero(&other.min_char)))
+            && (self.encoding_ok
+                || (other.encoding.is_none()
+                    && u32_is_max(&other.decoded_max_len)
+                    && u32_is_zero(&other.decoded_min_len)))
     }
 
     pub(crate) fn query_check(&self, other: &Validator) -> bool {
@@ -402,3 +628,135 @@ impl StrValidator {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{de::FogDeserializer, query::NewQuery, schema::*, ser::FogSerializer};
+
+    fn run(validator: &StrValidator, val: &str) -> Result<()> {
+        let mut ser = FogSerializer::default();
+        val.serialize(&mut ser).unwrap();
+        let data = ser.finish();
+        let mut parser = Parser::new(&data);
+        validator.validate(&mut parser)
+    }
+
+    #[test]
+    fn encoding_ser() {
+        let validator = StrValidator::new()
+            .encoding(StringEncoding::Hex)
+            .decoded_max_len(4);
+        let mut ser = FogSerializer::default();
+        validator.serialize(&mut ser).unwrap();
+        let actual = ser.finish();
+        let mut expected: Vec<u8> = vec![0x82];
+        serialize_elem(&mut expected, Element::Str("decoded_max_len"));
+        serialize_elem(&mut expected, Element::Int(4u32.into()));
+        serialize_elem(&mut expected, Element::Str("encoding"));
+        serialize_elem(&mut expected, Element::Str("Hex"));
+        assert_eq!(expected, actual);
+
+        let mut de = FogDeserializer::new(&actual);
+        assert_eq!(validator, StrValidator::deserialize(&mut de).unwrap());
+    }
+
+    #[test]
+    fn encoding() {
+        let validator = |encoding| StrValidator::new().encoding(encoding);
+        assert!(run(&validator(StringEncoding::Utf8), "héllo").is_ok());
+        assert!(run(&validator(StringEncoding::Ascii), "hello").is_ok());
+        assert!(run(&validator(StringEncoding::Ascii), "héllo").is_err());
+
+        let hex = validator(StringEncoding::Hex);
+        assert!(run(&hex, "").is_ok());
+        assert!(run(&hex, "09afAF").is_ok());
+        assert!(run(&hex, "09a").is_err());
+        assert!(run(&hex, "0g").is_err());
+
+        let base64 = validator(StringEncoding::Base64);
+        assert!(run(&base64, "aGk=").is_ok());
+        assert!(run(&base64, "aGk+/w==").is_ok());
+        assert!(run(&base64, "aGk").is_err());
+        assert!(run(&base64, "a=Gk").is_err());
+        assert!(run(&base64, "a===").is_err());
+        assert!(run(&base64, "aG-_").is_err());
+        let err = run(&base64, "aGk").unwrap_err();
+        assert_eq!(err.validate_error().unwrap().code, ValidateCode::NoMatch);
+    }
+
+    #[test]
+    fn decoded_len() {
+        let hex = StrValidator::new()
+            .encoding(StringEncoding::Hex)
+            .decoded_min_len(2)
+            .decoded_max_len(3);
+        assert!(run(&hex, "00").is_err());
+        assert!(run(&hex, "0000").is_ok());
+        assert!(run(&hex, "000000").is_ok());
+        assert!(run(&hex, "00000000").is_err());
+
+        let base64 = StrValidator::new()
+            .encoding(StringEncoding::Base64)
+            .decoded_min_len(2)
+            .decoded_max_len(3);
+        assert!(run(&base64, "AA==").is_err());
+        assert!(run(&base64, "AAA=").is_ok());
+        assert!(run(&base64, "AAAA").is_ok());
+        assert!(run(&base64, "AAAAAA==").is_err());
+    }
+
+    #[test]
+    fn decoded_len_build() {
+        let hex = || StrValidator::new().encoding(StringEncoding::Hex);
+        assert!(hex()
+            .decoded_min_len(2)
+            .decoded_max_len(2)
+            .check_build()
+            .is_ok());
+        assert!(hex()
+            .decoded_min_len(3)
+            .decoded_max_len(2)
+            .check_build()
+            .is_err());
+        // Decoded lengths only make sense for Base64 and Hex
+        let ascii = StrValidator::new().encoding(StringEncoding::Ascii);
+        assert!(ascii.clone().check_build().is_ok());
+        assert!(ascii.decoded_max_len(1).check_build().is_err());
+        assert!(StrValidator::new()
+            .decoded_min_len(1)
+            .check_build()
+            .is_err());
+    }
+
+    #[test]
+    fn encoding_query() {
+        let schema = |encoding_ok: bool| {
+            let doc = SchemaBuilder::new(Validator::Null)
+                .entry_add(
+                    "key",
+                    StrValidator::new().encoding_ok(encoding_ok).build(),
+                    None,
+                )
+                .build()
+                .unwrap();
+            Schema::from_doc(&doc).unwrap()
+        };
+        let encoding = || {
+            NewQuery::new(
+                "key",
+                StrValidator::new().encoding(StringEncoding::Hex).build(),
+            )
+        };
+        let decoded = || {
+            let query = StrValidator::new()
+                .encoding(StringEncoding::Hex)
+                .decoded_min_len(8);
+            NewQuery::new("key", query.build())
+        };
+        assert!(schema(false).encode_query(encoding()).is_err());
+        assert!(schema(false).encode_query(decoded()).is_err());
+        assert!(schema(true).encode_query(encoding()).is_ok());
+        assert!(schema(true).encode_query(decoded()).is_ok());
+    }
+}
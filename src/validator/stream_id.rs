@@ -1,6 +1,6 @@
 use super::*;
 use crate::element::*;
-use crate::error::{Error, Result};
+use crate::error::{Error, Result, ValidateCode};
 use crate::StreamId;
 use serde::{Deserialize, Serialize};
 
@@ -92,22 +92,23 @@ impl StreamIdValidator {
     pub(crate) fn validate(&self, parser: &mut Parser) -> Result<()> {
         let elem = parser
             .next()
-            .ok_or_else(|| Error::FailValidate("Expected a StreamId".to_string()))??;
+            .ok_or_else(|| Error::validate(ValidateCode::WrongType, "Expected a StreamId"))??;
         let elem = if let Element::StreamId(v) = elem {
             v
         } else {
-            return Err(Error::FailValidate(format!(
-                "Expected StreamId, got {}",
-                elem.name()
-            )));
+            return Err(Error::wrong_type("StreamId", elem.name()));
         };
         if !self.in_list.is_empty() && !self.in_list.iter().any(|v| v == elem.as_ref()) {
-            return Err(Error::FailValidate(
+            return Err(Error::validate(
+                ValidateCode::NotInList,
                 "StreamId is not on `in` list".to_string(),
             ));
         }
         if self.nin_list.iter().any(|v| v == elem.as_ref()) {
-            return Err(Error::FailValidate("StreamId is on `nin` list".to_string()));
+            return Err(Error::validate(
+                ValidateCode::InNinList,
+                "StreamId is on `nin` list",
+            ));
         }
         Ok(())
     }
@@ -1,6 +1,6 @@
 use super::*;
 use crate::element::*;
-use crate::error::{Error, Result};
+use crate::error::{Error, Result, ValidateCode};
 use crate::Timestamp;
 use serde::{Deserialize, Serialize};
 use std::default::Default;
@@ -23,6 +23,31 @@ fn time_is_max(v: &Timestamp) -> bool {
     *v == MAX_TIME
 }
 
+/// The precision a timestamp must have, as used by [`TimeValidator`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TimePrecision {
+    /// Whole seconds only.
+    Seconds,
+    /// Whole milliseconds only.
+    Millis,
+    /// Whole microseconds only.
+    Micros,
+    /// Any nanosecond value.
+    Nanos,
+}
+
+impl TimePrecision {
+    /// Get the number of nanoseconds in one unit of this precision.
+    fn unit_nanos(&self) -> u32 {
+        match self {
+            TimePrecision::Seconds => 1_000_000_000,
+            TimePrecision::Millis => 1_000_000,
+            TimePrecision::Micros => 1_000,
+            TimePrecision::Nanos => 1,
+        }
+    }
+}
+
 /// Validator for timestamps.
 ///
 /// This validator will only pass timestamps. Validation passes if:
@@ -31,6 +56,16 @@ fn time_is_max(v: &Timestamp) -> bool {
 /// - The timestamp must not be among the timestamp in the `nin` list.
 /// - The timestamp is less than the maximum in `max`, or equal to it if `ex_max` is not set to true.
 /// - The timestamp is greater than the minimum in `min`, or equal to it if `ex_min` is not set to true.
+/// - If `max_from_now` is set, the timestamp is no later than that many seconds after the time of
+///   validation.
+/// - If `min_from_now` is set, the timestamp is no earlier than that many seconds after the time
+///   of validation. Use a negative value to allow timestamps in the past.
+///
+/// - If `precision` is set, the timestamp's sub-second portion is a whole number of that
+///   precision's units.
+///
+/// The time of validation is the system time, unless a reference time was given as part of the
+/// [`ValidateOptions`][crate::schema::ValidateOptions].
 ///
 /// # Defaults
 ///
@@ -38,21 +73,37 @@ fn time_is_max(v: &Timestamp) -> bool {
 /// each field are:
 ///
 /// - comment: ""
+/// - default: None
 /// - max: maximum possible timestamp
 /// - min: minimum possible timestamp
 /// - ex_max: false
 /// - ex_min: false
+/// - max_from_now: None
+/// - min_from_now: None
+/// - precision: None
 /// - in_list: empty
 /// - nin_list: empty
 /// - query: false
 /// - ord: false
 ///
+/// # Query Checking
+///
+/// Queries for timestamps are only allowed to use non-default values for each field if the
+/// corresponding query permission is set in the schema's validator:
+///
+/// - query: `in` and `nin` lists
+/// - ord: `max`, `min`, `ex_max`, `ex_min`, `max_from_now`, `min_from_now`, and `precision`
+///
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 #[serde(deny_unknown_fields, default)]
 pub struct TimeValidator {
     /// An optional comment explaining the validator.
     #[serde(skip_serializing_if = "String::is_empty")]
     pub comment: String,
+    /// An optional default value, used when filling in new values from a schema. It is not
+    /// checked during validation.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub default: Option<Timestamp>,
     /// The maximum allowed timestamp.
     #[serde(skip_serializing_if = "time_is_max")]
     pub max: Timestamp,
@@ -65,6 +116,15 @@ pub struct TimeValidator {
     /// Changes `min` into an exclusive maximum.
     #[serde(skip_serializing_if = "is_false")]
     pub ex_min: bool,
+    /// The maximum allowed timestamp, in seconds relative to the time of validation.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_from_now: Option<i64>,
+    /// The minimum allowed timestamp, in seconds relative to the time of validation.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_from_now: Option<i64>,
+    /// The required precision of the timestamp.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub precision: Option<TimePrecision>,
     /// A vector of specific allowed values, stored under the `in` field. If empty, this vector is not checked against.
     #[serde(rename = "in", skip_serializing_if = "Vec::is_empty")]
     pub in_list: Vec<Timestamp>,
@@ -74,8 +134,8 @@ pub struct TimeValidator {
     /// If true, queries against matching spots may have values in the `in` or `nin` lists.
     #[serde(skip_serializing_if = "is_false")]
     pub query: bool,
-    /// If true, queries against matching spots may set the `max`, `min`, `ex_max`, and `ex_min`
-    /// values to non-defaults.
+    /// If true, queries against matching spots may set the `max`, `min`, `ex_max`, `ex_min`,
+    /// `max_from_now`, `min_from_now`, and `precision` values to non-defaults.
     #[serde(skip_serializing_if = "is_false")]
     pub ord: bool,
 }
@@ -84,10 +144,14 @@ impl Default for TimeValidator {
     fn default() -> Self {
         Self {
             comment: String::new(),
+            default: None,
             max: MAX_TIME,
             min: MIN_TIME,
             ex_max: false,
             ex_min: false,
+            max_from_now: None,
+            min_from_now: None,
+            precision: None,
             in_list: Vec::new(),
             nin_list: Vec::new(),
             query: false,
@@ -108,6 +172,12 @@ impl TimeValidator {
         self
     }
 
+    /// Set the default value.
+    pub fn default_value(mut self, default: impl Into<Timestamp>) -> Self {
+        self.default = Some(default.into());
+        self
+    }
+
     /// Set the maximum allowed value.
     pub fn max(mut self, max: impl Into<Timestamp>) -> Self {
         self.max = max.into();
@@ -132,6 +202,24 @@ impl TimeValidator {
         self
     }
 
+    /// Set the maximum allowed value, in seconds relative to the time of validation.
+    pub fn max_from_now(mut self, max_from_now: i64) -> Self {
+        self.max_from_now = Some(max_from_now);
+        self
+    }
+
+    /// Set the minimum allowed value, in seconds relative to the time of validation.
+    pub fn min_from_now(mut self, min_from_now: i64) -> Self {
+        self.min_from_now = Some(min_from_now);
+        self
+    }
+
+    /// Set the required precision.
+    pub fn precision(mut self, precision: TimePrecision) -> Self {
+        self.precision = Some(precision);
+        self
+    }
+
     /// Add a value to the `in` list.
     pub fn in_add(mut self, add: impl Into<Timestamp>) -> Self {
         self.in_list.push(add.into());
@@ -150,7 +238,8 @@ impl TimeValidator {
         self
     }
 
-    /// Set whether or not queries can use the `max`, `min`, `ex_max`, and `ex_min` values.
+    /// Set whether or not queries can use the `max`, `min`, `ex_max`, `ex_min`, `max_from_now`,
+    /// `min_from_now`, and `precision` values.
     pub fn ord(mut self, ord: bool) -> Self {
         self.ord = ord;
         self
@@ -164,14 +253,11 @@ impl TimeValidator {
     pub(crate) fn validate(&self, parser: &mut Parser) -> Result<()> {
         let elem = parser
             .next()
-            .ok_or_else(|| Error::FailValidate("Expected a timestamp".to_string()))??;
+            .ok_or_else(|| Error::validate(ValidateCode::WrongType, "Expected a timestamp"))??;
         let val = if let Element::Timestamp(v) = elem {
             v
         } else {
-            return Err(Error::FailValidate(format!(
-                "Expected Time, got {}",
-                elem.name()
-            )));
+            return Err(Error::wrong_type("Time", elem.name()));
         };
 
         // Range checks
@@ -186,24 +272,71 @@ impl TimeValidator {
             val >= self.min
         };
         if !max_pass {
-            return Err(Error::FailValidate(
+            return Err(Error::validate(
+                ValidateCode::OutOfRange,
                 "Timestamp greater than maximum allowed".to_string(),
             ));
         }
         if !min_pass {
-            return Err(Error::FailValidate(
+            return Err(Error::validate(
+                ValidateCode::OutOfRange,
                 "Timestamp less than minimum allowed".to_string(),
             ));
         }
 
+        if let Some(precision) = self.precision {
+            if val.timestamp_subsec_nanos() % precision.unit_nanos() != 0 {
+                return Err(Error::validate(
+                    ValidateCode::TooPrecise,
+                    format!("Timestamp {} is more precise than {:?}", val, precision),
+                ));
+            }
+        }
+
+        // Relative range checks
+        if self.max_from_now.is_some() || self.min_from_now.is_some() {
+            let now = parser.now().ok_or_else(|| {
+                Error::validate(
+                    ValidateCode::NoTime,
+                    "No valid current time to check timestamp against",
+                )
+            })?;
+            // A bound that overflows is beyond any possible timestamp, so it always passes
+            let in_range = |secs: i64| now.timestamp_utc().checked_add(secs).is_some();
+            if let Some(max) = self.max_from_now {
+                if in_range(max) && val > now + max {
+                    return Err(Error::validate(
+                        ValidateCode::OutOfRange,
+                        format!(
+                            "Timestamp is more than {} seconds after the current time {}",
+                            max, now
+                        ),
+                    ));
+                }
+            }
+            if let Some(min) = self.min_from_now {
+                if in_range(min) && val < now + min {
+                    return Err(Error::validate(
+                        ValidateCode::OutOfRange,
+                        format!(
+                            "Timestamp is less than {} seconds after the current time {}",
+                            min, now
+                        ),
+                    ));
+                }
+            }
+        }
+
         // in/nin checks
         if !self.in_list.is_empty() && !self.in_list.iter().any(|v| *v == val) {
-            return Err(Error::FailValidate(
+            return Err(Error::validate(
+                ValidateCode::NotInList,
                 "Timestamp is not on `in` list".to_string(),
             ));
         }
         if self.nin_list.iter().any(|v| *v == val) {
-            return Err(Error::FailValidate(
+            return Err(Error::validate(
+                ValidateCode::InNinList,
                 "Timestamp is on `nin` list".to_string(),
             ));
         }
@@ -217,7 +350,10 @@ impl TimeValidator {
                 || (!other.ex_min
                     && !other.ex_max
                     && time_is_min(&other.min)
-                    && time_is_max(&other.max)))
+                    && time_is_max(&other.max)
+                    && other.max_from_now.is_none()
+                    && other.min_from_now.is_none()
+                    && other.precision.is_none()))
     }
 
     pub(crate) fn query_check(&self, other: &Validator) -> bool {
@@ -259,10 +395,14 @@ mod test {
     fn example_ser() {
         let schema = TimeValidator {
             comment: "The year 2020".to_string(),
+            default: None,
             min: Timestamp::from_utc(1577854800, 0).unwrap(),
             max: Timestamp::from_utc(1609477200, 0).unwrap(),
             ex_min: false,
             ex_max: true,
+            max_from_now: None,
+            min_from_now: None,
+            precision: None,
             in_list: Vec::new(),
             nin_list: Vec::new(),
             query: true,
@@ -304,4 +444,58 @@ mod test {
             }
         }
     }
+
+    #[test]
+    fn from_now_query() {
+        let query = TimeValidator::new().max_from_now(0).build();
+        assert!(!TimeValidator::new().query_check(&query));
+        assert!(TimeValidator::new().ord(true).query_check(&query));
+        let query = TimeValidator::new().min_from_now(-60).build();
+        assert!(!TimeValidator::new().query_check(&query));
+    }
+
+    #[test]
+    fn from_now_overflow() {
+        let validator = TimeValidator::new()
+            .max_from_now(i64::MAX)
+            .min_from_now(i64::MIN)
+            .build();
+        let mut data = Vec::new();
+        serialize_elem(&mut data, Element::Timestamp(Timestamp::from_sec(0)));
+        let types = BTreeMap::new();
+        let parser = Parser::new(&data).with_now(Some(Timestamp::from_sec(10)));
+        let (parser, _) = validator.validate(&types, parser, None).unwrap();
+        parser.finish().unwrap();
+    }
+
+    #[test]
+    fn precision() {
+        let check = |precision: TimePrecision, nano: u32| {
+            let validator = TimeValidator::new().precision(precision).build();
+            let mut data = Vec::new();
+            let time = Timestamp::from_utc(1_600_000_000, nano).unwrap();
+            serialize_elem(&mut data, Element::Timestamp(time));
+            let types = BTreeMap::new();
+            validator
+                .validate(&types, Parser::new(&data), None)
+                .and_then(|(parser, _)| parser.finish())
+                .is_ok()
+        };
+        use TimePrecision::*;
+        assert!(check(Seconds, 0));
+        assert!(!check(Seconds, 1_000_000));
+        assert!(check(Millis, 5_000_000));
+        assert!(!check(Millis, 5_000_001));
+        assert!(!check(Millis, 1_000));
+        assert!(check(Micros, 1_000));
+        assert!(!check(Micros, 1_001));
+        assert!(check(Nanos, 123_456_789));
+        // Leap-second timestamps carry more than a second of nanoseconds
+        assert!(check(Seconds, 1_000_000_000));
+        assert!(!check(Seconds, 1_500_000_000));
+
+        let query = TimeValidator::new().precision(Seconds).build();
+        assert!(!TimeValidator::new().query_check(&query));
+        assert!(TimeValidator::new().ord(true).query_check(&query));
+    }
 }
@@ -1,3 +1,6 @@
+use crate::element::Element;
+use crate::error::{Error, Result};
+use crate::ser::FogSerializer;
 use crate::value_ref::ValueRef;
 use crate::*;
 use std::borrow::Cow;
@@ -38,11 +41,9 @@ impl Value {
             Value::F64(v) => ValueRef::F64(v),
             Value::Bin(ref v) => ValueRef::Bin(v.as_slice()),
             Value::Array(ref v) => ValueRef::Array(v.iter().map(|i| i.as_ref()).collect()),
-            Value::Map(ref v) => ValueRef::Map(
-                v.iter()
-                    .map(|(f, i)| (f.as_ref(), i.as_ref()))
-                    .collect(),
-            ),
+            Value::Map(ref v) => {
+                ValueRef::Map(v.iter().map(|(f, i)| (f.as_ref(), i.as_ref())).collect())
+            }
             Value::Timestamp(v) => ValueRef::Timestamp(v),
             Value::Hash(ref v) => ValueRef::Hash(v.clone()),
             Value::Identity(ref v) => ValueRef::Identity(v.clone()),
@@ -245,6 +246,95 @@ impl Value {
         }
     }
 
+    /// Get the name of this value's type, like `"Str"` or `"Array"`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Value::Null => "Null",
+            Value::Bool(_) => "Bool",
+            Value::Int(_) => "Int",
+            Value::Str(_) => "Str",
+            Value::F32(_) => "F32",
+            Value::F64(_) => "F64",
+            Value::Bin(_) => "Bin",
+            Value::Array(_) => "Array",
+            Value::Map(_) => "Map",
+            Value::Timestamp(_) => "Time",
+            Value::Hash(_) => "Hash",
+            Value::Identity(_) => "Identity",
+            Value::LockId(_) => "LockId",
+            Value::StreamId(_) => "StreamId",
+            Value::DataLockbox(_) => "DataLockbox",
+            Value::IdentityLockbox(_) => "IdentityLockbox",
+            Value::StreamLockbox(_) => "StreamLockbox",
+            Value::LockLockbox(_) => "LockLockbox",
+        }
+    }
+
+    /// Iterate over the items of an array. Non-array values yield nothing.
+    pub fn iter_array(&self) -> std::slice::Iter<'_, Value> {
+        self.as_array().unwrap_or(&[]).iter()
+    }
+
+    /// Iterate over the key-value pairs of a map, in key order. Non-map values yield nothing.
+    pub fn iter_map(&self) -> impl Iterator<Item = (&str, &Value)> {
+        self.as_map()
+            .into_iter()
+            .flatten()
+            .map(|(k, v)| (k.as_str(), v))
+    }
+
+    /// Iterate over the items of an array, failing if this isn't an array.
+    pub fn iter_array_or_err(&self) -> Result<std::slice::Iter<'_, Value>> {
+        match self {
+            Value::Array(array) => Ok(array.iter()),
+            v => Err(Error::wrong_type("Array", v.name())),
+        }
+    }
+
+    /// Iterate over the key-value pairs of a map, in key order, failing if this isn't a map.
+    pub fn iter_map_or_err(&self) -> Result<impl Iterator<Item = (&str, &Value)>> {
+        match self {
+            Value::Map(map) => Ok(map.iter().map(|(k, v)| (k.as_str(), v))),
+            v => Err(Error::wrong_type("Map", v.name())),
+        }
+    }
+
+    /// Convert into the inner array, or return the original value if this isn't an array.
+    #[allow(clippy::result_large_err)]
+    pub fn try_into_array(self) -> Result<Vec<Value>, Value> {
+        match self {
+            Value::Array(array) => Ok(array),
+            v => Err(v),
+        }
+    }
+
+    /// Convert into the inner map, or return the original value if this isn't a map.
+    #[allow(clippy::result_large_err)]
+    pub fn try_into_map(self) -> Result<BTreeMap<String, Value>, Value> {
+        match self {
+            Value::Map(map) => Ok(map),
+            v => Err(v),
+        }
+    }
+
+    /// Return this map with `key` set to `value`, replacing any existing value, or return the
+    /// original value if this isn't a map.
+    #[allow(clippy::result_large_err)]
+    pub fn with_field(self, key: impl Into<String>, value: Value) -> Result<Value, Value> {
+        let mut map = self.try_into_map()?;
+        map.insert(key.into(), value);
+        Ok(Value::Map(map))
+    }
+
+    /// Return this map with `key` removed, if it was present, or return the original value if
+    /// this isn't a map.
+    #[allow(clippy::result_large_err)]
+    pub fn without_field(self, key: &str) -> Result<Value, Value> {
+        let mut map = self.try_into_map()?;
+        map.remove(key);
+        Ok(Value::Map(map))
+    }
+
     pub fn as_timestamp(&self) -> Option<Timestamp> {
         if let Value::Timestamp(time) = *self {
             Some(time)
@@ -326,6 +416,245 @@ impl std::default::Default for Value {
 
 static NULL: Value = Value::Null;
 
+impl Value {
+    /// Look up a nested value using a path like `a.b[2].c`. Returns `None` if the path is
+    /// malformed or doesn't lead to a value. See [`ValuePath`] for the syntax.
+    pub fn get_path(&self, path: &str) -> Option<&Value> {
+        ValuePath::parse(path).ok()?.get(self)
+    }
+
+    /// Encode this value as fog-pack. Fails if the value is nested deeper than
+    /// [`MAX_DEPTH`][crate::MAX_DEPTH], or holds a string, byte array, array, or map larger than
+    /// [`MAX_DOC_SIZE`][crate::MAX_DOC_SIZE].
+    pub fn serialize_to_bytes(&self) -> Result<Vec<u8>> {
+        self.serialize_to_bytes_with_capacity(0)
+    }
+
+    /// Encode this value as fog-pack, starting with a buffer of at least `capacity` bytes. A
+    /// good guess avoids reallocating the buffer as the value is encoded.
+    pub fn serialize_to_bytes_with_capacity(&self, capacity: usize) -> Result<Vec<u8>> {
+        let mut ser = FogSerializer::from_vec(Vec::with_capacity(capacity), false);
+        serde::Serialize::serialize(self, &mut ser)?;
+        Ok(ser.finish())
+    }
+
+    /// Encode this value as fog-pack, writing each element out as soon as it's encoded instead
+    /// of encoding the whole value first. Encoding errors are returned as
+    /// [`InvalidData`][std::io::ErrorKind::InvalidData] errors, and anything encoded before the
+    /// error will already have been written. Many small writes are made, so an unbuffered writer
+    /// should be wrapped in a [`BufWriter`][std::io::BufWriter].
+    pub fn serialize_to_writer<W: std::io::Write>(&self, w: &mut W) -> std::io::Result<()> {
+        self.write_elements(&mut FogSerializer::default(), w)
+    }
+
+    /// Encode this value one element at a time, writing out each one. The serializer only holds
+    /// the element being written, and is kept across calls to track nesting depth.
+    fn write_elements<W: std::io::Write>(
+        &self,
+        ser: &mut FogSerializer,
+        w: &mut W,
+    ) -> std::io::Result<()> {
+        match self {
+            Value::Null => write_elem(ser, w, Element::Null)?,
+            Value::Bool(v) => write_elem(ser, w, Element::Bool(*v))?,
+            Value::Int(v) => write_elem(ser, w, Element::Int(*v))?,
+            Value::Str(v) => write_elem(ser, w, Element::Str(v))?,
+            Value::F32(v) => write_elem(ser, w, Element::F32(*v))?,
+            Value::F64(v) => write_elem(ser, w, Element::F64(*v))?,
+            Value::Bin(v) => write_elem(ser, w, Element::Bin(v))?,
+            Value::Array(v) => {
+                write_elem(ser, w, Element::Array(v.len()))?;
+                for item in v {
+                    item.write_elements(ser, w)?;
+                }
+            }
+            Value::Map(v) => {
+                write_elem(ser, w, Element::Map(v.len()))?;
+                for (key, item) in v {
+                    write_elem(ser, w, Element::Str(key))?;
+                    item.write_elements(ser, w)?;
+                }
+            }
+            Value::Timestamp(v) => write_elem(ser, w, Element::Timestamp(*v))?,
+            Value::Hash(v) => write_elem(ser, w, Element::Hash(v.clone()))?,
+            Value::Identity(v) => write_elem(ser, w, Element::Identity(Box::new(v.clone())))?,
+            Value::LockId(v) => write_elem(ser, w, Element::LockId(Box::new(v.clone())))?,
+            Value::StreamId(v) => write_elem(ser, w, Element::StreamId(Box::new(v.clone())))?,
+            Value::DataLockbox(v) => write_elem(ser, w, Element::DataLockbox(v))?,
+            Value::IdentityLockbox(v) => write_elem(ser, w, Element::IdentityLockbox(v))?,
+            Value::StreamLockbox(v) => write_elem(ser, w, Element::StreamLockbox(v))?,
+            Value::LockLockbox(v) => write_elem(ser, w, Element::LockLockbox(v))?,
+        }
+        Ok(())
+    }
+}
+
+/// Encode a single element and write it out, leaving the serializer's buffer empty.
+fn write_elem<W: std::io::Write>(
+    ser: &mut FogSerializer,
+    w: &mut W,
+    elem: Element,
+) -> std::io::Result<()> {
+    ser.encode_element(elem)
+        .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidData, err))?;
+    w.write_all(&ser.buf)?;
+    ser.buf.clear();
+    Ok(())
+}
+
+/// A single step in a [`ValuePath`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PathSegment {
+    /// Look up a key in a map.
+    Key(String),
+    /// Look up an index in an array.
+    Index(usize),
+}
+
+impl std::fmt::Display for PathSegment {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            PathSegment::Key(key) => write!(f, ".{}", key),
+            PathSegment::Index(index) => write!(f, "[{}]", index),
+        }
+    }
+}
+
+/// A path through nested [`Value`] maps and arrays.
+///
+/// Paths are written as map keys separated by `.`, with array indices written as `[N]`. For
+/// example, `users[0].name` looks up the `users` key in a map, takes the first item of that
+/// array, then looks up its `name` key. An empty path refers to the root value itself.
+///
+/// Map keys in a path cannot contain `.`, `[`, or `]`.
+#[derive(Clone, Debug, PartialEq, Eq, Default)]
+pub struct ValuePath(Vec<PathSegment>);
+
+impl std::fmt::Display for ValuePath {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let mut segments = self.0.iter();
+        if let Some(PathSegment::Key(key)) = self.0.first() {
+            f.write_str(key)?;
+            segments.next();
+        }
+        segments.try_for_each(|segment| write!(f, "{}", segment))
+    }
+}
+
+impl ValuePath {
+    /// Parse a path string. Fails if a key is empty, a bracket is unclosed, or an index isn't a
+    /// valid unsigned integer.
+    pub fn parse(path: &str) -> Result<ValuePath> {
+        let mut segments = Vec::new();
+        let mut rest = path;
+        let mut first = true;
+        while !rest.is_empty() {
+            if let Some(index) = rest.strip_prefix('[') {
+                let end = index
+                    .find(']')
+                    .ok_or_else(|| Error::BadPath(format!("unclosed `[` in \"{}\"", path)))?;
+                let index_str = &index[..end];
+                let index = index_str.parse::<usize>().map_err(|_| {
+                    Error::BadPath(format!(
+                        "\"{}\" is not a valid array index in \"{}\"",
+                        index_str, path
+                    ))
+                })?;
+                segments.push(PathSegment::Index(index));
+                rest = &rest[end + 2..];
+            } else {
+                if !first {
+                    rest = rest.strip_prefix('.').ok_or_else(|| {
+                        Error::BadPath(format!("expected `.` or `[` in \"{}\"", path))
+                    })?;
+                }
+                let end = rest.find(['.', '[', ']']).unwrap_or(rest.len());
+                if end == 0 {
+                    return Err(Error::BadPath(format!("empty key in \"{}\"", path)));
+                }
+                segments.push(PathSegment::Key(rest[..end].to_string()));
+                rest = &rest[end..];
+            }
+            first = false;
+        }
+        Ok(ValuePath(segments))
+    }
+
+    /// Get the segments that make up this path.
+    pub fn segments(&self) -> &[PathSegment] {
+        &self.0
+    }
+
+    /// Make a path from its segments.
+    pub(crate) fn new(segments: Vec<PathSegment>) -> Self {
+        ValuePath(segments)
+    }
+
+    /// Add a segment to the start of the path.
+    pub(crate) fn push_front(&mut self, segment: PathSegment) {
+        self.0.insert(0, segment);
+    }
+
+    /// Follow the path through a value, returning the value at the end of it, if there is one.
+    pub fn get<'a>(&self, value: &'a Value) -> Option<&'a Value> {
+        self.0
+            .iter()
+            .try_fold(value, |value, segment| match segment {
+                PathSegment::Key(key) => value.as_map()?.get(key),
+                PathSegment::Index(index) => value.as_array()?.get(*index),
+            })
+    }
+
+    /// Follow the path through a value, returning a mutable reference to the value at the end of
+    /// it, if there is one.
+    pub fn get_mut<'a>(&self, value: &'a mut Value) -> Option<&'a mut Value> {
+        self.0
+            .iter()
+            .try_fold(value, |value, segment| match segment {
+                PathSegment::Key(key) => value.as_map_mut()?.get_mut(key),
+                PathSegment::Index(index) => value.as_array_mut()?.get_mut(*index),
+            })
+    }
+
+    /// Set the value at the end of the path. The final map key is inserted if it isn't already
+    /// present, but every other step of the path must already exist, and array indices must be
+    /// within the array's current length.
+    pub fn set(mut self, value: &mut Value, new: Value) -> Result<()> {
+        let last = match self.0.pop() {
+            Some(last) => last,
+            None => {
+                *value = new;
+                return Ok(());
+            }
+        };
+        let parent = self
+            .get_mut(value)
+            .ok_or_else(|| Error::BadPath("path doesn't lead to a value".to_string()))?;
+        match last {
+            PathSegment::Key(key) => {
+                let map = parent.as_map_mut().ok_or_else(|| {
+                    Error::BadPath(format!("can't set key \"{}\" on a non-map value", key))
+                })?;
+                map.insert(key, new);
+            }
+            PathSegment::Index(index) => {
+                let array = parent.as_array_mut().ok_or_else(|| {
+                    Error::BadPath(format!("can't set index {} on a non-array value", index))
+                })?;
+                let len = array.len();
+                let item = array.get_mut(index).ok_or_else(|| {
+                    Error::BadPath(format!(
+                        "index {} is out of bounds for array of length {}",
+                        index, len
+                    ))
+                })?;
+                *item = new;
+            }
+        }
+        Ok(())
+    }
+}
+
 impl Index<usize> for Value {
     type Output = Value;
 
@@ -743,52 +1072,62 @@ impl<'de> serde::Deserialize<'de> for Value {
             fn visit_enum<A: EnumAccess<'de>>(self, access: A) -> Result<Self::Value, A::Error> {
                 let (variant, access) = access.variant()?;
                 use fog_crypto::serde::*;
-                use serde_bytes::Bytes;
-                let bytes: &Bytes = access.newtype_variant()?;
+                use serde_bytes::{ByteBuf, Bytes};
+                // Lockboxes are borrowed straight from the input, but the other types may be
+                // handed over as owned bytes.
                 match variant {
                     FOG_TYPE_ENUM_TIME_INDEX => {
+                        let bytes: ByteBuf = access.newtype_variant()?;
                         let val = Timestamp::try_from(bytes.as_ref()).map_err(A::Error::custom)?;
                         Ok(Value::Timestamp(val))
                     }
                     FOG_TYPE_ENUM_HASH_INDEX => {
+                        let bytes: ByteBuf = access.newtype_variant()?;
                         let val = Hash::try_from(bytes.as_ref())
                             .map_err(|e| A::Error::custom(e.serde_err()))?;
                         Ok(Value::Hash(val))
                     }
                     FOG_TYPE_ENUM_IDENTITY_INDEX => {
+                        let bytes: ByteBuf = access.newtype_variant()?;
                         let val = Identity::try_from(bytes.as_ref())
                             .map_err(|e| A::Error::custom(e.serde_err()))?;
                         Ok(Value::Identity(val))
                     }
                     FOG_TYPE_ENUM_LOCK_ID_INDEX => {
+                        let bytes: ByteBuf = access.newtype_variant()?;
                         let val = LockId::try_from(bytes.as_ref())
                             .map_err(|e| A::Error::custom(e.serde_err()))?;
                         Ok(Value::LockId(val))
                     }
                     FOG_TYPE_ENUM_STREAM_ID_INDEX => {
+                        let bytes: ByteBuf = access.newtype_variant()?;
                         let val = StreamId::try_from(bytes.as_ref())
                             .map_err(|e| A::Error::custom(e.serde_err()))?;
                         Ok(Value::StreamId(val))
                     }
                     FOG_TYPE_ENUM_DATA_LOCKBOX_INDEX => {
+                        let bytes: &Bytes = access.newtype_variant()?;
                         let val = DataLockboxRef::from_bytes(bytes)
                             .map_err(|e| A::Error::custom(e.serde_err()))?
                             .to_owned();
                         Ok(Value::DataLockbox(val))
                     }
                     FOG_TYPE_ENUM_IDENTITY_LOCKBOX_INDEX => {
+                        let bytes: &Bytes = access.newtype_variant()?;
                         let val = IdentityLockboxRef::from_bytes(bytes)
                             .map_err(|e| A::Error::custom(e.serde_err()))?
                             .to_owned();
                         Ok(Value::IdentityLockbox(val))
                     }
                     FOG_TYPE_ENUM_STREAM_LOCKBOX_INDEX => {
+                        let bytes: &Bytes = access.newtype_variant()?;
                         let val = StreamLockboxRef::from_bytes(bytes)
                             .map_err(|e| A::Error::custom(e.serde_err()))?
                             .to_owned();
                         Ok(Value::StreamLockbox(val))
                     }
                     FOG_TYPE_ENUM_LOCK_LOCKBOX_INDEX => {
+                        let bytes: &Bytes = access.newtype_variant()?;
                         let val = LockLockboxRef::from_bytes(bytes)
                             .map_err(|e| A::Error::custom(e.serde_err()))?
                             .to_owned();
@@ -802,3 +1141,232 @@ impl<'de> serde::Deserialize<'de> for Value {
         deserializer.deserialize_any(ValueVisitor)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn example() -> Value {
+        let mut user = BTreeMap::new();
+        user.insert("name".to_string(), Value::from("alice"));
+        user.insert(
+            "tags".to_string(),
+            Value::from(vec![Value::from("a"), Value::from("b")]),
+        );
+        let mut root = BTreeMap::new();
+        root.insert("users".to_string(), Value::from(vec![Value::from(user)]));
+        Value::from(root)
+    }
+
+    #[test]
+    fn deserialize_ext_types() {
+        use crate::{de::FogDeserializer, ser::FogSerializer};
+        use serde::{Deserialize, Serialize};
+        let key = fog_crypto::identity::IdentityKey::new_temp(&mut rand::rngs::OsRng);
+        let value = Value::from(vec![
+            Value::from(Timestamp::from_sec(1_600_000_000)),
+            Value::from(Hash::new(b"fog-pack")),
+            Value::from(key.id().clone()),
+        ]);
+        let mut ser = FogSerializer::default();
+        value.serialize(&mut ser).unwrap();
+        let buf = ser.finish();
+        assert_eq!(
+            Value::deserialize(&mut FogDeserializer::new(&buf)).unwrap(),
+            value
+        );
+        let value_ref = ValueRef::deserialize(&mut FogDeserializer::new(&buf)).unwrap();
+        assert_eq!(value_ref.to_owned(), value);
+    }
+
+    #[test]
+    fn serialize_to_bytes() {
+        use crate::de::FogDeserializer;
+        use serde::Deserialize;
+        let value = example();
+        let buf = value.serialize_to_bytes().unwrap();
+        assert_eq!(
+            Value::deserialize(&mut FogDeserializer::new(&buf)).unwrap(),
+            value
+        );
+        let sized = value.serialize_to_bytes_with_capacity(256).unwrap();
+        assert_eq!(sized, buf);
+        assert!(sized.capacity() >= 256);
+        let mut out = Vec::new();
+        value.serialize_to_writer(&mut out).unwrap();
+        assert_eq!(out, buf);
+
+        // The writer gets each element as it's encoded, with the same result
+        struct Counter(Vec<u8>, usize);
+        impl std::io::Write for Counter {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                self.1 += 1;
+                self.0.write(buf)
+            }
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+        let key = fog_crypto::identity::IdentityKey::new_temp(&mut rand::rngs::OsRng);
+        let value = Value::from(vec![
+            example(),
+            Value::from(Timestamp::from_sec(1_600_000_000)),
+            Value::from(Hash::new(b"fog-pack")),
+            Value::from(key.id().clone()),
+            Value::from(vec![0u8, 1, 2]),
+            Value::from(-1.5f32),
+        ]);
+        let mut out = Counter(Vec::new(), 0);
+        value.serialize_to_writer(&mut out).unwrap();
+        assert_eq!(out.0, value.serialize_to_bytes().unwrap());
+        assert!(out.1 > 1);
+
+        // Too deeply nested to encode; the elements before the limit are already written
+        let deep = (0..=MAX_DEPTH).fold(Value::Null, |v, _| Value::from(vec![v]));
+        assert!(deep.serialize_to_bytes().is_err());
+        let mut out = Vec::new();
+        let err = deep.serialize_to_writer(&mut out).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        assert_eq!(out.len(), MAX_DEPTH);
+    }
+
+    #[test]
+    fn iterators() {
+        let value = example();
+        let keys: Vec<&str> = value.iter_map().map(|(k, _)| k).collect();
+        assert_eq!(keys, vec!["users"]);
+        assert_eq!(value.iter_array().count(), 0);
+        assert_eq!(value.iter_map_or_err().unwrap().count(), 1);
+        let err = value.iter_array_or_err().unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Failed validation: Expected Array, got Map"
+        );
+
+        let tags = &value["users"][0]["tags"];
+        let tags: Vec<&Value> = tags.iter_array().collect();
+        assert_eq!(tags, vec![&Value::from("a"), &Value::from("b")]);
+        assert_eq!(value["users"].iter_array_or_err().unwrap().len(), 1);
+        assert_eq!(Value::Null.iter_map().count(), 0);
+        assert!(Value::Null.iter_map_or_err().is_err());
+    }
+
+    #[test]
+    fn try_into_containers() {
+        let array = Value::from(vec![Value::from(1u8)]);
+        assert_eq!(array.clone().try_into_array(), Ok(vec![Value::from(1u8)]));
+        assert_eq!(array.clone().try_into_map(), Err(array));
+        let value = example();
+        let map = value.clone().try_into_map().unwrap();
+        assert!(map.contains_key("users"));
+        assert_eq!(value.clone().try_into_array(), Err(value));
+    }
+
+    #[test]
+    fn with_without_field() {
+        let value = example()
+            .with_field("count", Value::from(2u8))
+            .unwrap()
+            .with_field("count", Value::from(3u8))
+            .unwrap();
+        assert_eq!(value.get_path("count"), Some(&Value::from(3u8)));
+        let value = value.without_field("count").unwrap();
+        assert_eq!(value, example());
+        assert_eq!(value.clone().without_field("missing"), Ok(value));
+
+        let not_map = Value::from(1u8);
+        assert_eq!(
+            not_map.clone().with_field("a", Value::Null),
+            Err(not_map.clone())
+        );
+        assert_eq!(not_map.clone().without_field("a"), Err(not_map));
+    }
+
+    #[test]
+    fn path_parse() {
+        let path = ValuePath::parse("users[0].tags[1]").unwrap();
+        assert_eq!(
+            path.segments(),
+            &[
+                PathSegment::Key("users".to_string()),
+                PathSegment::Index(0),
+                PathSegment::Key("tags".to_string()),
+                PathSegment::Index(1),
+            ]
+        );
+        assert_eq!(
+            ValuePath::parse("[2][3]").unwrap().segments(),
+            &[PathSegment::Index(2), PathSegment::Index(3)]
+        );
+        assert!(ValuePath::parse("").unwrap().segments().is_empty());
+        for good in &["users[0].tags[1]", "[2][3].a", "a", ""] {
+            assert_eq!(ValuePath::parse(good).unwrap().to_string(), *good);
+        }
+        for bad in &[
+            "a..b", "a.", ".a", "a[", "a[x]", "a[-1]", "a]", "a[0]b", "a.[0]",
+        ] {
+            assert!(
+                matches!(ValuePath::parse(bad), Err(Error::BadPath(_))),
+                "path {:?} should fail",
+                bad
+            );
+        }
+    }
+
+    #[test]
+    fn path_get() {
+        let mut value = example();
+        assert_eq!(value.get_path("users[0].name"), Some(&Value::from("alice")));
+        assert_eq!(value.get_path("users[0].tags[1]"), Some(&Value::from("b")));
+        assert_eq!(value.get_path(""), Some(&value));
+        assert_eq!(value.get_path("users[1]"), None);
+        assert_eq!(value.get_path("users.name"), None);
+        assert_eq!(value.get_path("users[0].name[0]"), None);
+        assert_eq!(value.get_path("users[0"), None);
+
+        let path = ValuePath::parse("users[0].name").unwrap();
+        *path.get_mut(&mut value).unwrap() = Value::from("bob");
+        assert_eq!(value.get_path("users[0].name"), Some(&Value::from("bob")));
+    }
+
+    #[test]
+    fn path_set() {
+        let mut value = example();
+        ValuePath::parse("users[0].tags[0]")
+            .unwrap()
+            .set(&mut value, Value::from("z"))
+            .unwrap();
+        assert_eq!(value.get_path("users[0].tags[0]"), Some(&Value::from("z")));
+        ValuePath::parse("users[0].age")
+            .unwrap()
+            .set(&mut value, Value::from(30u8))
+            .unwrap();
+        assert_eq!(value.get_path("users[0].age"), Some(&Value::from(30u8)));
+
+        let fail = |path: &str, value: &mut Value| {
+            ValuePath::parse(path)
+                .unwrap()
+                .set(value, Value::Null)
+                .unwrap_err()
+        };
+        assert!(matches!(
+            fail("users[0].tags[2]", &mut value),
+            Error::BadPath(_)
+        ));
+        assert!(matches!(
+            fail("users[1].name", &mut value),
+            Error::BadPath(_)
+        ));
+        assert!(matches!(fail("users.name", &mut value), Error::BadPath(_)));
+        assert!(matches!(
+            fail("users[0].name[0]", &mut value),
+            Error::BadPath(_)
+        ));
+
+        ValuePath::parse("")
+            .unwrap()
+            .set(&mut value, Value::Null)
+            .unwrap();
+        assert_eq!(value, Value::Null);
+    }
+}
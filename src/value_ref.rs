@@ -704,49 +704,59 @@ impl<'de> serde::Deserialize<'de> for ValueRef<'de> {
             fn visit_enum<A: EnumAccess<'de>>(self, access: A) -> Result<Self::Value, A::Error> {
                 let (variant, access) = access.variant()?;
                 use fog_crypto::serde::*;
-                use serde_bytes::Bytes;
-                let bytes: &Bytes = access.newtype_variant()?;
+                use serde_bytes::{ByteBuf, Bytes};
+                // Lockboxes are borrowed straight from the input, but the other types may be
+                // handed over as owned bytes.
                 match variant {
                     FOG_TYPE_ENUM_TIME_INDEX => {
+                        let bytes: ByteBuf = access.newtype_variant()?;
                         let val = Timestamp::try_from(bytes.as_ref()).map_err(A::Error::custom)?;
                         Ok(ValueRef::Timestamp(val))
                     }
                     FOG_TYPE_ENUM_HASH_INDEX => {
+                        let bytes: ByteBuf = access.newtype_variant()?;
                         let val = Hash::try_from(bytes.as_ref())
                             .map_err(|e| A::Error::custom(e.serde_err()))?;
                         Ok(ValueRef::Hash(val))
                     }
                     FOG_TYPE_ENUM_IDENTITY_INDEX => {
+                        let bytes: ByteBuf = access.newtype_variant()?;
                         let val = Identity::try_from(bytes.as_ref())
                             .map_err(|e| A::Error::custom(e.serde_err()))?;
                         Ok(ValueRef::Identity(val))
                     }
                     FOG_TYPE_ENUM_LOCK_ID_INDEX => {
+                        let bytes: ByteBuf = access.newtype_variant()?;
                         let val = LockId::try_from(bytes.as_ref())
                             .map_err(|e| A::Error::custom(e.serde_err()))?;
                         Ok(ValueRef::LockId(val))
                     }
                     FOG_TYPE_ENUM_STREAM_ID_INDEX => {
+                        let bytes: ByteBuf = access.newtype_variant()?;
                         let val = StreamId::try_from(bytes.as_ref())
                             .map_err(|e| A::Error::custom(e.serde_err()))?;
                         Ok(ValueRef::StreamId(val))
                     }
                     FOG_TYPE_ENUM_DATA_LOCKBOX_INDEX => {
+                        let bytes: &Bytes = access.newtype_variant()?;
                         let val = DataLockboxRef::from_bytes(bytes)
                             .map_err(|e| A::Error::custom(e.serde_err()))?;
                         Ok(ValueRef::DataLockbox(val))
                     }
                     FOG_TYPE_ENUM_IDENTITY_LOCKBOX_INDEX => {
+                        let bytes: &Bytes = access.newtype_variant()?;
                         let val = IdentityLockboxRef::from_bytes(bytes)
                             .map_err(|e| A::Error::custom(e.serde_err()))?;
                         Ok(ValueRef::IdentityLockbox(val))
                     }
                     FOG_TYPE_ENUM_STREAM_LOCKBOX_INDEX => {
+                        let bytes: &Bytes = access.newtype_variant()?;
                         let val = StreamLockboxRef::from_bytes(bytes)
                             .map_err(|e| A::Error::custom(e.serde_err()))?;
                         Ok(ValueRef::StreamLockbox(val))
                     }
                     FOG_TYPE_ENUM_LOCK_LOCKBOX_INDEX => {
+                        let bytes: &Bytes = access.newtype_variant()?;
                         let val = LockLockboxRef::from_bytes(bytes)
                             .map_err(|e| A::Error::custom(e.serde_err()))?;
                         Ok(ValueRef::LockLockbox(val))